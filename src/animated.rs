@@ -0,0 +1,81 @@
+use anyhow::{Context, Result};
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::{AnimationDecoder, ImageFormat};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+/// What to do with animated GIF, PNG or WebP downloads
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AnimatedPolicy {
+    /// Save the file untouched, for setters that play animations (e.g. swww)
+    #[default]
+    Keep,
+    /// Save only the first frame, as a PNG
+    Still,
+    /// Don't save the wallpaper at all
+    Skip,
+}
+
+/// Whether `data`, already detected as `format`, holds more than one frame
+pub fn is_animated(data: &[u8], format: ImageFormat) -> bool {
+    match format {
+        ImageFormat::Gif => GifDecoder::new(Cursor::new(data))
+            .map(|decoder| decoder.into_frames().take(2).count() > 1)
+            .unwrap_or(false),
+        ImageFormat::Png => PngDecoder::new(Cursor::new(data))
+            .and_then(|decoder| decoder.is_apng())
+            .unwrap_or(false),
+        ImageFormat::WebP => WebPDecoder::new(Cursor::new(data))
+            .map(|decoder| decoder.has_animation())
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
+/// The first frame of an animated image, encoded as PNG
+pub fn still_frame(data: &[u8]) -> Result<Vec<u8>> {
+    let img = image::load_from_memory(data).context("Failed to decode animated image")?;
+    let mut png = Cursor::new(Vec::new());
+    img.write_to(&mut png, ImageFormat::Png)
+        .context("Failed to encode still frame")?;
+    Ok(png.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::codecs::gif::GifEncoder;
+    use image::{Delay, Frame, Rgba, RgbaImage};
+
+    fn gif(frames: usize) -> Vec<u8> {
+        let mut data = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut data);
+            for i in 0..frames {
+                let pixel = Rgba([(i * 100) as u8, 0, 0, 255]);
+                encoder
+                    .encode_frame(Frame::from_parts(
+                        RgbaImage::from_pixel(4, 4, pixel),
+                        0,
+                        0,
+                        Delay::from_numer_denom_ms(100, 1),
+                    ))
+                    .unwrap();
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn test_detects_animation_and_extracts_a_frame() {
+        assert!(is_animated(&gif(2), ImageFormat::Gif));
+        assert!(!is_animated(&gif(1), ImageFormat::Gif));
+
+        let still = still_frame(&gif(2)).unwrap();
+        assert_eq!(image::guess_format(&still).unwrap(), ImageFormat::Png);
+        assert!(!is_animated(&still, ImageFormat::Png));
+    }
+}
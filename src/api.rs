@@ -15,7 +15,7 @@ pub struct SearchResponse {
     #[serde(rename = "meta")]
     pub meta: WallpaperMeta,
 }
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 #[serde(rename = "")]
 pub struct Wallpaper {
     pub id: String,
@@ -37,11 +37,57 @@ pub struct Wallpaper {
     pub path: String,
     pub thumbs: Thumbs,
 }
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
 pub struct Thumbs {
-    large: String,
-    original: String,
-    small: String,
+    pub large: String,
+    pub original: String,
+    pub small: String,
+}
+
+impl Thumbs {
+    /// Wallhaven's thumbnail URLs for `id`, for when there's no API response
+    pub fn for_id(id: &str) -> Self {
+        let prefix = id.get(..2).unwrap_or(id);
+        let url = |size: &str| format!("https://th.wallhaven.cc/{}/{}/{}.jpg", size, prefix, id);
+        Thumbs {
+            large: url("lg"),
+            original: url("orig"),
+            small: url("small"),
+        }
+    }
+}
+
+/// Which size of a wallpaper `sync` downloads
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DownloadQuality {
+    /// The full-resolution file
+    #[default]
+    Original,
+    /// Wallhaven's large thumbnail
+    Large,
+    /// Wallhaven's small thumbnail
+    Medium,
+}
+
+impl DownloadQuality {
+    /// The URL to download wallpaper `id` from, given its full-size URL and
+    /// the thumbnails from the API, if known
+    pub fn url(self, id: &str, full: &str, thumbs: Option<&Thumbs>) -> String {
+        let thumb = |pick: fn(&Thumbs) -> &String| {
+            thumbs
+                .map(pick)
+                .filter(|url| !url.is_empty())
+                .cloned()
+                .unwrap_or_else(|| pick(&Thumbs::for_id(id)).clone())
+        };
+        match self {
+            DownloadQuality::Original => full.to_string(),
+            DownloadQuality::Large => thumb(|t| &t.large),
+            DownloadQuality::Medium => thumb(|t| &t.small),
+        }
+    }
 }
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename = "")]
@@ -65,15 +111,29 @@ pub enum MetaQuery {
 #[serde(rename = "")]
 pub struct WallpaperInfoResponse {
     #[serde(rename = "data")]
-    pub data: WallpaperInfo,
+    pub data: WallhavenWallpaper,
 }
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename = "")]
-pub struct WallpaperInfo {
+
+impl WallpaperInfoResponse {
+    /// Parse the body of a `/w/<id>` response, turning an error payload into a typed error
+    pub(crate) fn parse(body: &str) -> crate::Result<WallhavenWallpaper> {
+        if let Ok(r) = serde_json::from_str::<ErrorResponse>(body) {
+            return Err(r.into_error());
+        }
+        Ok(serde_json::from_str::<WallpaperInfoResponse>(body)?.data)
+    }
+}
+
+/// Full metadata of one wallpaper. Fields missing from a response (e.g. an old
+/// cache entry) are left empty rather than failing the whole parse
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename = "", default)]
+pub struct WallhavenWallpaper {
     pub id: String,
     pub url: String,
     pub short_url: String,
-    pub uploader: Uploader,
+    /// Only present on the wallpaper endpoint, not in search results
+    pub uploader: Option<Uploader>,
     pub views: i32,
     pub favorites: i32,
     pub source: String,
@@ -91,13 +151,44 @@ pub struct WallpaperInfo {
     pub thumbs: Thumbs,
     pub tags: Vec<Tag>,
 }
-#[derive(Debug, Serialize, Deserialize, Clone)]
+
+impl From<Wallpaper> for WallhavenWallpaper {
+    /// Search results carry everything but the uploader and tags
+    fn from(w: Wallpaper) -> Self {
+        WallhavenWallpaper {
+            id: w.id,
+            url: w.url,
+            short_url: w.short_url,
+            uploader: None,
+            views: w.views,
+            favorites: w.favorites,
+            source: w.source,
+            purity: w.purity,
+            category: w.category,
+            dimension_x: w.dimension_x,
+            dimension_y: w.dimension_y,
+            resolution: w.resolution,
+            ratio: w.ratio,
+            file_size: w.file_size,
+            file_type: w.file_type,
+            created_at: w.created_at,
+            colors: w.colors,
+            path: w.path,
+            thumbs: w.thumbs,
+            tags: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
 pub struct Uploader {
     pub username: String,
     pub group: String,
     pub avatar: Avatar,
 }
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
 pub struct Avatar {
     #[serde(rename = "200px")]
     pub _200px: String,
@@ -115,8 +206,8 @@ pub struct TagResponse {
     #[serde(rename = "data")]
     pub data: Tag,
 }
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename = "")]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename = "", default)]
 pub struct Tag {
     pub id: i32,
     pub name: String,
@@ -168,19 +259,99 @@ pub struct ErrorResponse {
     pub error: String,
 }
 
+impl ErrorResponse {
+    pub fn into_error(self) -> crate::Error {
+        crate::Error::from_api_message(self.error)
+    }
+}
+
 pub(crate) trait Url {
     fn to_url(&self, base_url: &str) -> String;
 }
 
 use futures::TryFutureExt;
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use std::sync::Arc;
 use std::time::Duration;
-use tokio::fs::OpenOptions;
 use tokio::io::AsyncWriteExt;
 use tokio::time::sleep;
 
 use crate::args::Command;
+use crate::fetch::{HttpFetcher, ReqwestFetcher};
 use crate::helper::get_key_from_config_or_env;
+use crate::output::{say, say_err, styled};
+use crate::search_cache::SearchCache;
+
+/// First wait after Wallhaven answers 429; doubled on every further 429
+const RATE_LIMIT_BACKOFF: Duration = Duration::from_secs(15);
+
+/// Fetch one page of a listing, backing off on rate limits and transient errors
+async fn fetch_page(
+    fetcher: &dyn HttpFetcher,
+    url: &str,
+    api_key: Option<&str>,
+    retry_count: u32,
+) -> crate::Result<SearchResponse> {
+    let mut rate_limit_backoff = RATE_LIMIT_BACKOFF;
+    let mut attempt = 0;
+    let body = loop {
+        attempt += 1;
+        match fetcher.get_text(url, api_key).await {
+            Ok(body) => break body,
+            Err(e @ (crate::Error::NotFound(_) | crate::Error::Unauthorized)) => return Err(e),
+            Err(e) if attempt >= retry_count => return Err(e),
+            Err(crate::Error::RateLimited) => {
+                say_err!(
+                    "   Rate limited by Wallhaven, waiting {}s...",
+                    rate_limit_backoff.as_secs()
+                );
+                sleep(rate_limit_backoff).await;
+                rate_limit_backoff *= 2;
+            }
+            Err(e) => {
+                let delay = 2_u64.pow(attempt - 1);
+                say_err!(
+                    "   Error fetching {}: {}. Retrying in {}s...",
+                    url,
+                    e,
+                    delay
+                );
+                sleep(Duration::from_secs(delay)).await;
+            }
+        }
+    };
+    if let Ok(r) = serde_json::from_str::<ErrorResponse>(&body) {
+        return Err(r.into_error());
+    }
+    Ok(serde_json::from_str(&body)?)
+}
+
+/// Collect the wallpapers of a search or collection listing, following
+/// `meta.last_page` up to `max_pages`. `on_page(page, last_page)` is called
+/// after every page
+pub(crate) async fn fetch_all_pages(
+    fetcher: &dyn HttpFetcher,
+    url: &str,
+    api_key: Option<&str>,
+    retry_count: u32,
+    max_pages: Option<u32>,
+    mut on_page: impl FnMut(u32, u32),
+) -> crate::Result<Vec<Wallpaper>> {
+    let separator = if url.contains('?') { '&' } else { '?' };
+    let mut wallpapers = Vec::new();
+    let mut page = 1;
+    loop {
+        let page_url = format!("{}{}page={}", url, separator, page);
+        let response = fetch_page(fetcher, &page_url, api_key, retry_count).await?;
+        let last_page = response.meta.last_page.max(1) as u32;
+        wallpapers.extend(response.data);
+        on_page(page, last_page);
+        if page >= last_page || max_pages.is_some_and(|max| page >= max) {
+            return Ok(wallpapers);
+        }
+        page += 1;
+    }
+}
 
 #[derive(Debug)]
 pub enum WallhavenClientError {
@@ -188,6 +359,8 @@ pub enum WallhavenClientError {
     DecodeError(String),
     WriteError(String),
     Error(String),
+    /// A typed error from the Wallhaven API, e.g. a rejected key
+    Wallhaven(crate::Error),
 }
 
 impl std::fmt::Display for WallhavenClientError {
@@ -205,28 +378,40 @@ impl std::fmt::Display for WallhavenClientError {
             Self::Error(e) => {
                 write!(f, "Error - {}", e)
             }
+            Self::Wallhaven(e) => e.fmt(f),
         }
     }
 }
 
-impl std::error::Error for WallhavenClientError {}
+impl std::error::Error for WallhavenClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Wallhaven(e) => Some(e),
+            _ => None,
+        }
+    }
+}
 
 pub struct WallhavenClient {
-    http_client: reqwest::Client,
+    fetcher: Arc<dyn HttpFetcher>,
     commands: Command,
     rust_paper: RustPaper,
 }
 
 impl WallhavenClient {
     pub async fn new(commands: Command) -> Result<Self, Error> {
-        let rust_paper = RustPaper::new().await?;
+        Self::with_rust_paper(commands, RustPaper::new().await?)
+    }
+
+    /// Like `new`, but on top of an already built `RustPaper`
+    pub fn with_rust_paper(commands: Command, rust_paper: RustPaper) -> Result<Self, Error> {
         let api_key = get_key_from_config_or_env(rust_paper.config().api_key.as_deref());
         if api_key.is_none() {
-            eprintln!("❌ Error: API key is required for this command.");
-            eprintln!(
+            say_err!("❌ Error: API key is required for this command.");
+            say_err!(
                 "   Please set WALLHAVEN_API_KEY environment variable or add api_key to config."
             );
-            eprintln!("   Example: export WALLHAVEN_API_KEY=\"your_api_key_here\"");
+            say_err!("   Example: export WALLHAVEN_API_KEY=\"your_api_key_here\"");
             std::process::exit(1);
         }
         /* Create http client */
@@ -245,99 +430,195 @@ impl WallhavenClient {
             headers.insert("X-API-KEY", header_api_value);
         }
 
-        let client = reqwest::ClientBuilder::new()
+        let client = crate::helper::client_builder(&rust_paper.config)?
             .default_headers(headers)
-            .timeout(std::time::Duration::from_secs(rust_paper.config.timeout))
+            .timeout(Duration::from_secs(rust_paper.config.metadata_timeout))
             .build()
             .context("Unable to create http client")?;
+        let download_client = crate::helper::create_download_client(&rust_paper.config)?;
 
         Ok(Self {
-            http_client: client,
+            fetcher: Arc::new(ReqwestFetcher::new(client).download_client(download_client)),
             commands,
             rust_paper,
         })
     }
 
+    /// Build a client on top of an existing `RustPaper`, sharing its network layer
+    pub fn from_rust_paper(commands: Command, rust_paper: RustPaper) -> Self {
+        Self {
+            fetcher: rust_paper.fetcher.clone(),
+            commands,
+            rust_paper,
+        }
+    }
+
     pub async fn execute(&mut self) -> Result<String, WallhavenClientError> {
         let resp = match &self.commands {
             Command::Search(s) => {
-                let res = self.request(s.to_url(BASE_URL)).await?;
+                let url = match s.preset.as_deref() {
+                    Some(name) => {
+                        let preset = self.rust_paper.config.preset(name).ok_or_else(|| {
+                            WallhavenClientError::Error(format!("Unknown preset '{}'", name))
+                        })?;
+                        s.with_preset(&preset)
+                            .map_err(|e| {
+                                WallhavenClientError::Error(format!("Preset '{}': {}", name, e))
+                            })?
+                            .to_url(BASE_URL)
+                    }
+                    None => s.to_url(BASE_URL),
+                };
+                let mut exclude = self.rust_paper.config.exclude_tags.clone();
+                exclude.extend(s.exclude.iter().cloned());
+                let mut url = crate::helper::exclude_tags(&url, &exclude);
+                if !url.contains("atleast=") {
+                    if let Some(display) = self.rust_paper.minimum_resolution().await {
+                        url.push_str(&format!("&atleast={}", display));
+                    }
+                }
+                let cache = if s.no_cache || !SearchCache::is_cacheable(&url) {
+                    None
+                } else {
+                    Some(self.search_cache()?)
+                };
+                let cached = match &cache {
+                    Some(cache) => cache.get(&url).await,
+                    None => None,
+                };
+                let fresh = cached.is_none();
+                let res = match cached {
+                    Some(body) => body,
+                    None => self.request(url.clone()).await?,
+                };
 
                 // Check if we got bad status response and return it
                 if let Ok(r) = serde_json::from_str::<ErrorResponse>(&res) {
-                    return Err(WallhavenClientError::RequestError(r.error));
+                    return Err(WallhavenClientError::Wallhaven(r.into_error()));
                 }
 
                 // Check if response has the structure as described in api guide
                 let searchresp: SearchResponse = serde_json::from_str(&res)
                     .map_err(|e| WallhavenClientError::DecodeError(e.to_string()))?;
-                if s.download {
-                    println!("  Found {} wallpaper(s)...", searchresp.data.len());
-                    let max_concurrent = self.rust_paper.config.max_concurrent_downloads as usize;
-                    let m = MultiProgress::new();
-                    let save_location = self.rust_paper.config.save_location.clone();
-                    let integrity = self.rust_paper.config.integrity;
-                    let client = self.http_client.clone();
-                    let mut tasks = stream::iter(searchresp.data.iter())
-                        .map(|w| {
-                            let save_loc = save_location.clone();
-                            let client = client.clone();
-                            let mp = m.clone();
-                            async move {
-                                let res = crate::helper::download_with_progress(
-                                    &w.path,
-                                    &w.id,
-                                    &save_loc,
-                                    &client,
-                                    integrity,
-                                    true,
-                                    Some(mp),
-                                )
-                                .await;
-                                (w, res)
-                            }
-                        })
-                        .buffer_unordered(max_concurrent);
-
-                    let mut lock_updates = Vec::new();
-                    while let Some((w, result)) = tasks.next().await {
-                        match result {
-                            Ok(dl_res) => {
-                                let _ = m.println(format!(
-                                    "  ✓ Downloaded {} - {}",
-                                    w.id, dl_res.file_path
-                                ));
-                                lock_updates.push((w.id.clone(), dl_res.file_path, dl_res.sha256));
-                            }
-                            Err(e) => {
-                                let _ =
-                                    m.println(format!("  ✗ Failed to download {}: {}", w.id, e));
-                            }
-                        }
+                if let Some(cache) = cache.filter(|_| fresh) {
+                    cache.put(&url, &res).await;
+                }
+                if s.interactive {
+                    if searchresp.data.is_empty() {
+                        return Ok(format_search_results(&searchresp));
                     }
-
-                    // Update lock file...
-                    if !lock_updates.is_empty() {
-                        // Now `self` is free to be used here because it wasn't moved into the stream
-                        if let Err(e) = crate::helper::update_wallpapers_list_and_lock(
-                            lock_updates,
-                            &mut self.rust_paper,
-                        )
-                        .await
-                        {
-                            eprintln!("  ⚠ Failed to update lock file: {}", e);
+                    let Some((picked, action)) =
+                        crate::pick::pick(&mut std::io::stdin().lock(), &searchresp.data)
+                    else {
+                        return Ok(String::new());
+                    };
+                    let picked: Vec<Wallpaper> = picked
+                        .into_iter()
+                        .map(|idx| searchresp.data[idx].clone())
+                        .collect();
+                    match action {
+                        crate::pick::PickAction::Add => {
+                            let mut ids = picked.into_iter().map(|w| w.id).collect();
+                            self.rust_paper
+                                .add(&mut ids, &Default::default())
+                                .await
+                                .map_err(|e| WallhavenClientError::Error(e.to_string()))?;
+                            String::new()
+                        }
+                        crate::pick::PickAction::Download => {
+                            self.download_wallpapers(&picked).await;
+                            String::from("\n  ✅ Download complete!")
                         }
                     }
+                } else if s.download {
+                    self.download_wallpapers(&searchresp.data).await;
                     String::from("\n  ✅ Download complete!")
                 } else {
                     format_search_results(&searchresp)
                 }
             }
+            Command::Toplist(t) => {
+                // Wallhaven serves 24 results per page
+                let max_pages = t.count.div_ceil(24);
+                let api_key = get_key_from_config_or_env(self.rust_paper.config.api_key.as_deref());
+                let mut wallpapers = fetch_all_pages(
+                    self.fetcher.as_ref(),
+                    &t.to_url(BASE_URL),
+                    api_key.as_deref(),
+                    self.rust_paper.config.retry_count,
+                    Some(max_pages),
+                    |_, _| {},
+                )
+                .await
+                .map_err(WallhavenClientError::Wallhaven)?;
+                wallpapers.truncate(t.count as usize);
+
+                let output = format_wallpaper_table(&wallpapers, &format!("Toplist ({})", t.range));
+                if t.download {
+                    say!("{}", output);
+                    self.download_wallpapers(&wallpapers).await;
+                    String::from("\n  ✅ Download complete!")
+                } else if t.add {
+                    say!("{}", output);
+                    let mut ids = wallpapers.iter().map(|w| w.id.clone()).collect();
+                    self.rust_paper
+                        .add(&mut ids, &Default::default())
+                        .await
+                        .map_err(|e| WallhavenClientError::Error(e.to_string()))?;
+                    String::new()
+                } else {
+                    output
+                }
+            }
+            Command::Similar(sim) => {
+                let Ok(id) = crate::WallpaperId::parse(&sim.id) else {
+                    return Err(WallhavenClientError::Error(format!(
+                        "'{}' is not a Wallhaven wallpaper",
+                        sim.id
+                    )));
+                };
+                let api_key = get_key_from_config_or_env(self.rust_paper.config.api_key.as_deref());
+                let mut wallpapers = fetch_all_pages(
+                    self.fetcher.as_ref(),
+                    &sim.to_url(BASE_URL),
+                    api_key.as_deref(),
+                    self.rust_paper.config.retry_count,
+                    Some(sim.count.div_ceil(24)),
+                    |_, _| {},
+                )
+                .await
+                .map_err(WallhavenClientError::Wallhaven)?;
+                // Only suggest what isn't tracked or blocked
+                let blocklist = self
+                    .rust_paper
+                    .blocklist()
+                    .await
+                    .map_err(WallhavenClientError::Wallhaven)?;
+                wallpapers.retain(|w| {
+                    w.id != id.as_str()
+                        && !self.rust_paper.wallpapers.contains(&w.id)
+                        && !blocklist.contains(&w.id)
+                });
+                wallpapers.truncate(sim.count as usize);
+
+                let output = format_wallpaper_table(&wallpapers, &format!("Similar to {}", id));
+                if sim.add {
+                    say!("{}", output);
+                    let mut ids = wallpapers.iter().map(|w| w.id.clone()).collect();
+                    self.rust_paper
+                        .add(&mut ids, &Default::default())
+                        .await
+                        .map_err(|e| WallhavenClientError::Error(e.to_string()))?;
+                    String::new()
+                } else {
+                    output
+                }
+            }
             Command::TagInfo(t) => {
                 let res = self.request(t.to_url(BASE_URL)).await?;
 
                 if let Ok(r) = serde_json::from_str::<ErrorResponse>(&res) {
-                    return Err(WallhavenClientError::RequestError(r.error));
+                    return Err(WallhavenClientError::Wallhaven(r.into_error()));
                 }
 
                 let taginfo: TagResponse = serde_json::from_str(&res)
@@ -349,7 +630,7 @@ impl WallhavenClient {
                 let res = self.request(us.to_url(BASE_URL)).await?;
 
                 if let Ok(r) = serde_json::from_str::<ErrorResponse>(&res) {
-                    return Err(WallhavenClientError::RequestError(r.error));
+                    return Err(WallhavenClientError::Wallhaven(r.into_error()));
                 }
 
                 let usersettings: UserSettingsResponse = serde_json::from_str(&res)
@@ -361,7 +642,7 @@ impl WallhavenClient {
                 let res = self.request(uc.to_url(BASE_URL)).await?;
 
                 if let Ok(r) = serde_json::from_str::<ErrorResponse>(&res) {
-                    return Err(WallhavenClientError::RequestError(r.error));
+                    return Err(WallhavenClientError::Wallhaven(r.into_error()));
                 }
 
                 let usercollections: UserCollectionsResponse = serde_json::from_str(&res)
@@ -375,32 +656,142 @@ impl WallhavenClient {
         Ok(resp)
     }
 
+    /// Download search results to the save location and track them
+    async fn download_wallpapers(&mut self, wallpapers: &[Wallpaper]) {
+        say!("  Found {} wallpaper(s)...", wallpapers.len());
+        let blocklist = match self.rust_paper.blocklist().await {
+            Ok(blocklist) => blocklist,
+            Err(e) => {
+                say_err!("  ⚠ {:#}", e);
+                Default::default()
+            }
+        };
+        let wallpapers: Vec<&Wallpaper> = wallpapers
+            .iter()
+            .filter(|w| {
+                let blocked = blocklist.contains(&w.id);
+                if blocked {
+                    say!("  ○ {} is blocked, not downloading it", w.id);
+                }
+                !blocked
+            })
+            .collect();
+        let max_concurrent = self.rust_paper.config.max_concurrent_downloads;
+        let m = MultiProgress::new();
+        let config = Arc::new(self.rust_paper.config.clone());
+        let fetcher = self.fetcher.clone();
+        let decode_pool = self.rust_paper.decode_pool.clone();
+        let mut tasks = stream::iter(wallpapers.iter().copied())
+            .map(|w| {
+                let config = config.clone();
+                let fetcher = fetcher.clone();
+                let mp = m.clone();
+                let decode = decode_pool.clone();
+                async move {
+                    let res = crate::helper::download_with_fallback(
+                        &w.path,
+                        &w.id,
+                        &config,
+                        None,
+                        fetcher.as_ref(),
+                        true,
+                        crate::helper::DownloadProgress {
+                            multi: Some(mp),
+                            ..crate::helper::DownloadProgress::new(decode)
+                        },
+                    )
+                    .await;
+                    (w, res)
+                }
+            })
+            .buffer_unordered(max_concurrent);
+
+        let mut lock_updates = Vec::new();
+        let mut audit_entries = Vec::new();
+        while let Some((w, result)) = tasks.next().await {
+            match result {
+                Ok(dl_res) => {
+                    let _ = m.println(styled!("  ✓ Downloaded {} - {}", w.id, dl_res.file_path));
+                    if self.rust_paper.config.sidecar {
+                        let data = WallhavenWallpaper::from(w.clone());
+                        let sidecar = crate::sidecar::Sidecar::new(&w.id, &w.path, Some(&data));
+                        if let Err(e) = sidecar.write(&dl_res.file_path).await {
+                            let _ = m.println(styled!("  ⚠ {:#}", e));
+                        }
+                    }
+                    if let Err(e) = crate::process::run_steps(
+                        &self.rust_paper.config,
+                        &self.rust_paper.decode_pool,
+                        &dl_res.file_path,
+                    )
+                    .await
+                    {
+                        let _ = m.println(styled!("  ⚠ Processing failed for {}: {:#}", w.id, e));
+                    }
+                    crate::hooks::run(
+                        self.rust_paper.config.hooks.on_download.as_deref(),
+                        "download",
+                        &crate::hooks::wallpaper_env(&w.id, &dl_res.file_path),
+                    )
+                    .await;
+                    audit_entries.push(
+                        crate::audit::AuditEntry::new(crate::audit::AuditEvent::Download, &w.id)
+                            .path(&dl_res.file_path)
+                            .detail(&w.path),
+                    );
+                    lock_updates.push((w.id.clone(), dl_res.file_path, dl_res.hash));
+                }
+                Err(e) if crate::helper::is_skipped(&e) => {
+                    let _ = m.println(styled!("  ○ {}", e));
+                }
+                Err(e) => {
+                    let _ = m.println(styled!("  ✗ Failed to download {}: {}", w.id, e));
+                }
+            }
+        }
+
+        if let Err(e) =
+            crate::audit::append(&self.rust_paper.audit_log_location, &audit_entries).await
+        {
+            say_err!("  ⚠ Failed to update audit log: {}", e);
+        }
+
+        // Update lock file...
+        if !lock_updates.is_empty() {
+            // Now `self` is free to be used here because it wasn't moved into the stream
+            if let Err(e) =
+                crate::helper::update_wallpapers_list_and_lock(lock_updates, &mut self.rust_paper)
+                    .await
+            {
+                say_err!("  ⚠ Failed to update lock file: {}", e);
+            }
+        }
+    }
+
+    /// The cache for search results, expiring after `search_cache_ttl`
+    fn search_cache(&self) -> Result<SearchCache, WallhavenClientError> {
+        let config = &self.rust_paper.config;
+        let ttl = crate::helper::parse_duration(&config.search_cache_ttl)
+            .map_err(|e| WallhavenClientError::Error(format!("search_cache_ttl: {}", e)))?;
+        Ok(SearchCache::new(
+            self.rust_paper.search_cache_location.clone(),
+            ttl,
+            get_key_from_config_or_env(config.api_key.as_deref()),
+        ))
+    }
+
     pub async fn request(&self, url: String) -> Result<String, WallhavenClientError> {
         let max_retry = self.rust_paper.config.retry_count;
         for retry_count in 0..max_retry {
-            let send_result = self.http_client.get(&url).send().await;
-            match send_result {
-                Ok(response) => match response.text().await {
-                    Ok(body) => return Ok(body),
-                    Err(e) if retry_count + 1 < max_retry => {
-                        let delay = 2_u64.pow(retry_count);
-                        eprintln!(
-                            "   Error reading response body (attempt {} of {}): {}. Retrying in {}s...",
-                            retry_count + 1,
-                            max_retry,
-                            e,
-                            delay
-                        );
-                        sleep(Duration::from_secs(delay)).await;
-                        continue;
-                    }
-                    Err(e) => {
-                        return Err(WallhavenClientError::DecodeError(e.to_string()));
-                    }
-                },
+            match self.fetcher.get_text(&url, None).await {
+                Ok(body) => return Ok(body),
+                // Retrying won't fix a bad key or a missing resource
+                Err(e @ (crate::Error::NotFound(_) | crate::Error::Unauthorized)) => {
+                    return Err(WallhavenClientError::Wallhaven(e));
+                }
                 Err(e) if retry_count + 1 < max_retry => {
                     let delay = 2_u64.pow(retry_count);
-                    eprintln!(
+                    say_err!(
                         "   Error fetching content (attempt {} of {}): {}. Retrying in {}s...",
                         retry_count + 1,
                         max_retry,
@@ -410,7 +801,7 @@ impl WallhavenClient {
                     sleep(Duration::from_secs(delay)).await;
                 }
                 Err(e) => {
-                    return Err(WallhavenClientError::RequestError(e.to_string()));
+                    return Err(WallhavenClientError::Wallhaven(e));
                 }
             }
         }
@@ -424,17 +815,16 @@ impl WallhavenClient {
     ) -> Result<(), WallhavenClientError> {
         // Reqwest setup
         let res = self
-            .http_client
-            .get(url)
-            .send()
+            .fetcher
+            .get_stream(url)
             .await
             .map_err(|e| WallhavenClientError::RequestError(e.to_string()))?;
 
         // Get information for bar
         let total_size = res
-            .content_length()
+            .content_length
             .ok_or(format!("Failed to get content length from '{}'", &url))
-            .map_err(|e| WallhavenClientError::RequestError(e))?;
+            .map_err(WallhavenClientError::RequestError)?;
 
         // Indicatif setup
         let pb = ProgressBar::new(total_size);
@@ -446,34 +836,25 @@ impl WallhavenClient {
 
         // Create file path
         let file_path = std::path::Path::new(path);
-        let mut file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(file_path)
+        let mut staged = crate::staging::Staged::create(file_path)
             .await
             .map_err(|e| {
-                WallhavenClientError::WriteError(format!(
-                    "Failed to create file - {}",
-                    e.to_string()
-                ))
+                WallhavenClientError::WriteError(format!("Failed to create file - {}", e))
             })?;
+        let file = staged.file();
 
         // Write file
         let mut downloaded: u64 = 0;
-        let mut stream = res.bytes_stream();
+        let mut stream = res.body;
 
         while let Some(item) = stream.next().await {
-            let chunk = item.or(Err(WallhavenClientError::RequestError(format!(
-                "Error while downloading file"
-            ))))?;
+            let chunk = item.or(Err(WallhavenClientError::RequestError(
+                "Error while downloading file".to_string(),
+            )))?;
 
             file.write_all(&chunk)
                 .map_err(|e| {
-                    WallhavenClientError::WriteError(format!(
-                        "Error while writing to file - {}",
-                        e.to_string()
-                    ))
+                    WallhavenClientError::WriteError(format!("Error while writing to file - {}", e))
                 })
                 .await?;
 
@@ -481,6 +862,9 @@ impl WallhavenClient {
             downloaded = new;
             pb.set_position(new);
         }
+        staged.commit(file_path).await.map_err(|e| {
+            WallhavenClientError::WriteError(format!("Failed to save file - {}", e))
+        })?;
 
         pb.finish_with_message(format!("Downloaded {}", url));
 
@@ -495,16 +879,15 @@ impl WallhavenClient {
     ) -> Result<String, WallhavenClientError> {
         use sha2::{Digest, Sha256};
         let res = self
-            .http_client
-            .get(url)
-            .send()
+            .fetcher
+            .get_stream(url)
             .await
             .map_err(|e| WallhavenClientError::RequestError(e.to_string()))?;
         // Get information for bar
         let total_size = res
-            .content_length()
+            .content_length
             .ok_or(format!("Failed to get content length from '{}'", &url))
-            .map_err(|e| WallhavenClientError::RequestError(e))?;
+            .map_err(WallhavenClientError::RequestError)?;
         // Indicatif setup
         let pb = ProgressBar::new(total_size);
         let style = ProgressStyle::with_template("{msg}\n{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")
@@ -513,38 +896,32 @@ impl WallhavenClient {
         pb.set_style(style);
         pb.set_message(format!("Downloading {}", url));
         let file_path = std::path::Path::new(path);
-        let mut file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(file_path)
+        let mut staged = crate::staging::Staged::create(file_path)
             .await
             .map_err(|e| {
-                WallhavenClientError::WriteError(format!(
-                    "Failed to create file - {}",
-                    e.to_string()
-                ))
+                WallhavenClientError::WriteError(format!("Failed to create file - {}", e))
             })?;
+        let file = staged.file();
         let mut hasher = Sha256::new();
         let mut downloaded: u64 = 0;
-        let mut stream = res.bytes_stream();
+        let mut stream = res.body;
         while let Some(item) = stream.next().await {
-            let chunk = item.or(Err(WallhavenClientError::RequestError(format!(
-                "Error while downloading file"
-            ))))?;
+            let chunk = item.or(Err(WallhavenClientError::RequestError(
+                "Error while downloading file".to_string(),
+            )))?;
             hasher.update(&chunk);
             file.write_all(&chunk)
                 .map_err(|e| {
-                    WallhavenClientError::WriteError(format!(
-                        "Error while writing to file - {}",
-                        e.to_string()
-                    ))
+                    WallhavenClientError::WriteError(format!("Error while writing to file - {}", e))
                 })
                 .await?;
             let new = u64::min(downloaded + (chunk.len() as u64), total_size);
             downloaded = new;
             pb.set_position(new);
         }
+        staged.commit(file_path).await.map_err(|e| {
+            WallhavenClientError::WriteError(format!("Failed to save file - {}", e))
+        })?;
         pb.finish_with_message(format!("Downloaded {}", url));
         let hash = format!("{:x}", hasher.finalize());
         Ok(hash)
@@ -582,26 +959,26 @@ fn format_user_settings(settings: &UserSettings) -> String {
         "  Categories: {}\n",
         settings.categories.join(", ")
     ));
-    if !settings.resolutions.is_empty() && settings.resolutions[0] != "" {
+    if !settings.resolutions.is_empty() && !settings.resolutions[0].is_empty() {
         output.push_str(&format!(
             "  Resolutions: {}\n",
             settings.resolutions.join(", ")
         ));
     }
-    if !settings.aspect_ratios.is_empty() && settings.aspect_ratios[0] != "" {
+    if !settings.aspect_ratios.is_empty() && !settings.aspect_ratios[0].is_empty() {
         output.push_str(&format!(
             "  Aspect Ratios: {}\n",
             settings.aspect_ratios.join(", ")
         ));
     }
     output.push_str(&format!("  Toplist Range: {}\n", settings.toplist_range));
-    if !settings.tag_blacklist.is_empty() && settings.tag_blacklist[0] != "" {
+    if !settings.tag_blacklist.is_empty() && !settings.tag_blacklist[0].is_empty() {
         output.push_str(&format!(
             "  Tag Blacklist: {}\n",
             settings.tag_blacklist.join(", ")
         ));
     }
-    if !settings.user_blacklist.is_empty() && settings.user_blacklist[0] != "" {
+    if !settings.user_blacklist.is_empty() && !settings.user_blacklist[0].is_empty() {
         output.push_str(&format!(
             "  User Blacklist: {}\n",
             settings.user_blacklist.join(", ")
@@ -632,7 +1009,32 @@ fn format_user_collections(collections: &[UserCollections]) -> String {
                 "Private"
             }
         ));
-        output.push_str("\n");
+        output.push('\n');
+    }
+    output
+}
+
+/// Format wallpapers as a ranked table under `title`
+fn format_wallpaper_table(wallpapers: &[Wallpaper], title: &str) -> String {
+    if wallpapers.is_empty() {
+        return format!("  {}: no wallpapers found.\n", title);
+    }
+    let mut output = format!("  {}:\n", title);
+    output.push_str(&format!(
+        "  {:>3}  {:<8}  {:<10}  {:>9}  {:>9}  {:<8}  {}\n",
+        "#", "ID", "RESOLUTION", "FAVORITES", "VIEWS", "CATEGORY", "PURITY"
+    ));
+    for (idx, wallpaper) in wallpapers.iter().enumerate() {
+        output.push_str(&format!(
+            "  {:>3}  {:<8}  {:<10}  {:>9}  {:>9}  {:<8}  {}\n",
+            idx + 1,
+            wallpaper.id,
+            wallpaper.resolution,
+            wallpaper.favorites,
+            wallpaper.views,
+            wallpaper.category,
+            wallpaper.purity
+        ));
     }
     output
 }
@@ -644,7 +1046,7 @@ fn format_search_results(search_resp: &SearchResponse) -> String {
         output.push_str("  No wallpapers found matching your search criteria.\n");
         return output;
     }
-    output.push_str(&format!("  Search Results:\n"));
+    output.push_str("  Search Results:\n");
     output.push_str("  ───────────────\n");
     output.push_str(&format!(
         "  Found: {} wallpaper(s)\n",
@@ -658,7 +1060,7 @@ fn format_search_results(search_resp: &SearchResponse) -> String {
     if let Some(ref seed) = search_resp.meta.seed {
         output.push_str(&format!("  Seed: {}\n", seed));
     }
-    output.push_str("\n");
+    output.push('\n');
     // Display each wallpaper
     for (idx, wallpaper) in search_resp.data.iter().enumerate() {
         output.push_str(&format!(
@@ -685,7 +1087,7 @@ fn format_search_results(search_resp: &SearchResponse) -> String {
             output.push_str(&format!("     Colors: {}\n", wallpaper.colors.join(", ")));
         }
         output.push_str(&format!("     Download: {}\n", wallpaper.path));
-        output.push_str("\n");
+        output.push('\n');
     }
 
     // Add pagination hint if there are more pages
@@ -698,3 +1100,48 @@ fn format_search_results(search_resp: &SearchResponse) -> String {
 
     output
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_wallpaper_response() {
+        let data = WallpaperInfoResponse::parse(
+            r#"{"data":{"id":"7pmgv9","purity":"sfw","path":"https://w.wallhaven.cc/full/7p/wallhaven-7pmgv9.jpg",
+                "uploader":{"username":"artist"},"tags":[{"id":1,"name":"city"}]}}"#,
+        )
+        .unwrap();
+        assert_eq!(data.id, "7pmgv9");
+        assert_eq!(data.uploader.unwrap().username, "artist");
+        assert_eq!(data.tags[0].name, "city");
+        assert!(data.category.is_empty());
+
+        let err = WallpaperInfoResponse::parse(r#"{"error":"Nothing here"}"#).unwrap_err();
+        assert!(matches!(err, crate::Error::ApiError(message) if message == "Nothing here"));
+        let err = WallpaperInfoResponse::parse(r#"{"error":"Unauthorized"}"#).unwrap_err();
+        assert!(matches!(err, crate::Error::Unauthorized));
+    }
+
+    #[test]
+    fn test_download_quality_url() {
+        let full = "https://w.wallhaven.cc/full/7p/wallhaven-7pmgv9.jpg";
+        assert_eq!(DownloadQuality::Original.url("7pmgv9", full, None), full);
+        assert_eq!(
+            DownloadQuality::Medium.url("7pmgv9", full, None),
+            "https://th.wallhaven.cc/small/7p/7pmgv9.jpg"
+        );
+        let thumbs = Thumbs {
+            large: "https://example.com/lg.jpg".to_string(),
+            ..Thumbs::default()
+        };
+        assert_eq!(
+            DownloadQuality::Large.url("7pmgv9", full, Some(&thumbs)),
+            "https://example.com/lg.jpg"
+        );
+        assert_eq!(
+            DownloadQuality::Medium.url("7pmgv9", full, Some(&thumbs)),
+            "https://th.wallhaven.cc/small/7p/7pmgv9.jpg"
+        );
+    }
+}
@@ -1,35 +1,344 @@
+use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::Duration;
 
 use crate::api::Url;
+use crate::config::SearchPreset;
+use crate::run_lock::LockMode;
+use crate::{RustPaper, RustPaperBuilder};
 use clap::{ArgGroup, Args, Parser, Subcommand};
 
 #[derive(Parser)]
 pub struct Cli {
     #[clap(subcommand)]
     pub command: Command,
+
+    /// Work without network access: skip downloads and use cached metadata
+    #[arg(long, global = true)]
+    pub offline: bool,
+
+    /// Wait for another running instance to finish instead of failing
+    #[arg(long, global = true, conflicts_with = "no_lock")]
+    pub wait: bool,
+
+    /// Don't lock the config folder (concurrent runs may corrupt the lock file)
+    #[arg(long, global = true)]
+    pub no_lock: bool,
+
+    /// Use this config file instead of the standard one
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Download to this folder instead of the configured save_location
+    #[arg(long, global = true, value_name = "DIR")]
+    pub save_location: Option<PathBuf>,
+
+    /// Print ASCII instead of Nerd Font glyphs and emoji (automatic in a
+    /// non-UTF-8 locale and on the Linux console)
+    #[arg(long, global = true, alias = "no-emoji")]
+    pub plain: bool,
+}
+
+impl Cli {
+    /// How to handle another instance running on the same config folder.
+    /// `status` only reads the last sync summary, so it never takes the lock
+    pub fn lock_mode(&self) -> LockMode {
+        if self.no_lock || matches!(self.command, Command::Status { .. }) {
+            LockMode::Disabled
+        } else if self.wait {
+            LockMode::Wait
+        } else {
+            LockMode::Fail
+        }
+    }
+
+    /// A builder with the global options applied
+    pub fn builder(&self) -> RustPaperBuilder {
+        // They run until stopped, so they only lock out others while syncing
+        let long_running = matches!(self.command, Command::Watch { .. } | Command::Serve { .. });
+        let mut builder = RustPaper::builder()
+            .lock_mode(self.lock_mode())
+            .lock_per_operation(long_running)
+            // They report, and let you fix, what's wrong with the config
+            .skip_validation(matches!(
+                self.command,
+                Command::Config { .. } | Command::Doctor
+            ));
+        if let Some(config) = &self.config {
+            builder = builder.config_file(config);
+        }
+        if let Some(save_location) = &self.save_location {
+            builder = builder.save_location(save_location);
+        }
+        builder
+    }
 }
 
 #[derive(Debug, Subcommand)]
 //#[derive(Debug, Subcommand, Clone)]
 pub enum Command {
-    Sync,
+    /// Sync wallpapers from every enabled source that is due
+    Sync {
+        /// Only download and verify these tracked wallpapers
+        #[arg(conflicts_with_all = ["source", "group"])]
+        ids: Vec<String>,
+        /// Only sync the named source (ignores its enabled flag and interval)
+        #[arg(long)]
+        source: Option<String>,
+        /// Verify every file's checksum, ignoring `recheck_after`
+        #[arg(long)]
+        full: bool,
+        /// Only sync the wallpapers of this group
+        #[arg(long, conflicts_with = "source")]
+        group: Option<String>,
+        /// Exit successfully even when some downloads failed
+        #[arg(long)]
+        allow_partial: bool,
+        /// Download even if it would leave less than min_free_space_mb free
+        #[arg(long)]
+        ignore_disk_space: bool,
+        /// Download even if sync_conditions say to wait (battery, metered, not idle)
+        #[arg(long)]
+        now: bool,
+        /// Fetch metadata and show what would be downloaded, without downloading
+        #[arg(long)]
+        dry_run: bool,
+    },
+    #[command(alias = "a")]
     Add {
-        #[arg(required = true)]
+        #[arg(required_unless_present = "local")]
         paths: Vec<String>,
+        /// Import the images in this directory into the save location
+        #[arg(long, value_name = "DIR")]
+        local: Option<std::path::PathBuf>,
+        /// Move the imported images instead of copying them
+        #[arg(long = "move", requires = "local")]
+        move_files: bool,
+        /// Also add the wallpapers to this group
+        #[arg(long)]
+        group: Option<String>,
+        /// Add wallpapers even if they fall outside allowed_purity/allowed_categories
+        #[arg(long)]
+        force: bool,
+        /// Check every new ID against the Wallhaven API and reject the ones that don't exist
+        #[arg(long)]
+        verify: bool,
+        /// Record the upstream file's checksum; later syncs refuse a replaced file
+        #[arg(long)]
+        pin_hash: bool,
+    },
+    /// Track the Wallhaven wallpapers another wallpaper manager downloaded or links to
+    Import {
+        /// variety, styli, wpgtk, or files for any folder of images and link lists
+        #[arg(long, value_parser = clap::value_parser!(crate::migrate::ImportTool))]
+        from: crate::migrate::ImportTool,
+        /// Folder or file to import from (default: the tool's own folder)
+        path: Option<PathBuf>,
+        /// Also add the wallpapers to this group
+        #[arg(long)]
+        group: Option<String>,
+    },
+    /// Pull new uploads of a Wallhaven user into the list on every sync
+    Follow {
+        #[arg(required = true)]
+        username: String,
+    },
+    /// Stop following a Wallhaven user
+    Unfollow {
+        #[arg(required = true)]
+        username: String,
+    },
+    /// List followed Wallhaven users
+    Following,
+    /// Never add these wallpapers again, removing them if tracked
+    Block {
+        /// Wallpaper IDs or URLs
+        #[arg(required = true)]
+        ids: Vec<String>,
+    },
+    /// Allow blocked wallpapers to be added again
+    Unblock {
+        /// Wallpaper IDs or URLs
+        #[arg(required = true)]
+        ids: Vec<String>,
+    },
+    /// List blocked wallpapers
+    Blocked,
+    /// Show the log of downloads, deletions and integrity failures
+    Log {
+        /// Only show entries from this recent period, e.g. "7d"
+        #[arg(long)]
+        since: Option<String>,
     },
+    /// Show the outcome of the last sync
+    Status {
+        /// Print the raw last_sync.json
+        #[arg(long)]
+        json: bool,
+    },
+    /// Check tracked wallpapers against allowed_purity and allowed_categories
+    AuditPurity {
+        /// Move the files that are not allowed out of the save location and stop tracking them
+        #[arg(long = "move")]
+        move_files: bool,
+        /// Folder to move them to (default: quarantine_location from the config)
+        #[arg(long, requires = "move_files")]
+        to: Option<String>,
+        /// Fetch purity and category from the API instead of the metadata cache
+        #[arg(long)]
+        refresh: bool,
+    },
+    /// Inspect or empty the files moved aside after failing their integrity check
+    Quarantine {
+        #[command(subcommand)]
+        action: QuarantineAction,
+    },
+    /// List or forget the wallpapers followed uploaders have offered, which
+    /// sync never adds twice
+    Seen {
+        #[command(subcommand)]
+        action: SeenAction,
+    },
+    /// Rebuild the lock file by hashing every tracked wallpaper on disk
+    Repair,
+    /// Check the config, permissions, network access and local state
+    Doctor,
+    /// Back up or restore the config, list, lock file and state (not the images)
+    Backup {
+        #[command(subcommand)]
+        action: BackupAction,
+    },
+    /// Change or check the config file
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Manage named groups of wallpapers
+    Group {
+        #[command(subcommand)]
+        action: GroupAction,
+    },
+    #[command(alias = "rm")]
     Remove {
         #[arg(required = true)]
         ids: Vec<String>,
     },
+    /// Remove wallpapers from the list and delete their files and variants
+    Delete {
+        #[arg(required = true)]
+        ids: Vec<String>,
+        /// Only stop tracking the wallpapers, like `remove`
+        #[arg(long)]
+        keep_file: bool,
+    },
+    #[command(alias = "ls")]
     List,
-    Clean,
+    Clean {
+        /// Also drop wallpapers that were removed from Wallhaven from the list
+        #[arg(long)]
+        tombstones: bool,
+        /// Ask before removing each file
+        #[arg(short, long)]
+        interactive: bool,
+        /// Only remove files not modified for this long (e.g. 30d, 12h)
+        #[arg(long, value_parser = duration)]
+        older_than: Option<Duration>,
+        /// Only remove files whose name matches this glob (e.g. "*.png")
+        #[arg(long)]
+        pattern: Option<String>,
+    },
+    /// Re-run the configured processing steps on downloaded wallpapers
+    Reprocess {
+        /// Wallpaper IDs to process (default: every tracked wallpaper)
+        ids: Vec<String>,
+    },
+    /// Sync automatically whenever the wallpapers list file changes
+    Watch {
+        /// How long the file must stay unchanged before syncing (e.g. 2s)
+        #[arg(long, default_value = "2s", value_parser = duration)]
+        debounce: Duration,
+    },
+    /// Serve a REST API to manage the collection remotely
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:7878")]
+        listen: String,
+        /// Bearer token clients must send (default: serve_token from the config)
+        #[arg(long)]
+        token: Option<String>,
+    },
+    /// Draw downloaded wallpapers in the terminal
+    #[cfg(feature = "preview")]
+    Preview {
+        /// Wallpaper IDs or URLs
+        #[arg(required = true)]
+        ids: Vec<String>,
+        /// Graphics protocol: kitty, iterm, sixel or blocks (default: detected from the terminal)
+        #[arg(long, value_parser = clap::value_parser!(crate::preview::Protocol))]
+        protocol: Option<crate::preview::Protocol>,
+        /// Width in terminal cells (default: $COLUMNS or 80)
+        #[arg(long)]
+        width: Option<u32>,
+    },
     /// Get wallpaper info (supports both local and API lookup)
+    #[command(alias = "i")]
     Info {
-        #[arg(required = true)]
+        /// Wallpaper ID or URL (default: the current wallpaper)
+        id: Option<String>,
+    },
+    /// Compare two wallpapers: resolution, file size, checksum, how alike
+    /// they look and their tags
+    Diff {
+        /// Wallpaper IDs or URLs
+        ids: Vec<String>,
+        /// Compare this image file instead of a tracked wallpaper (repeatable)
+        #[arg(long, value_name = "FILE")]
+        local: Vec<PathBuf>,
+    },
+    /// List wallpapers not set as the current wallpaper for a while
+    Stale {
+        /// How long unused, e.g. "90d" (default: stale_after from the config)
+        #[arg(long)]
+        older_than: Option<String>,
+        /// Move them into the archived "stale" group
+        #[arg(long, conflicts_with = "remove")]
+        archive: bool,
+        /// Stop tracking them (their files are kept)
+        #[arg(long)]
+        remove: bool,
+    },
+    /// Show or record the wallpaper each monitor currently shows
+    Current {
+        /// Record this tracked wallpaper (ID or file path) as the current one
+        #[arg(long, value_name = "ID|PATH", conflicts_with = "json")]
+        set: Option<String>,
+        /// The monitor it is shown on (default: every monitor)
+        #[arg(long, requires = "set")]
+        monitor: Option<String>,
+        /// Print the current wallpapers as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print attribution crediting a wallpaper's uploader and source
+    Credit {
+        /// Wallpaper ID or URL
         id: String,
+        /// Also copy the attribution to the clipboard
+        #[arg(long)]
+        copy: bool,
+    },
+    /// Apply the additions and removals in another machine's manifest.json
+    Pull {
+        /// Path or URL of the manifest
+        source: String,
     },
     /// Search wallpaper by query or colors
-    Search(SearchArgs),
+    #[command(alias = "s")]
+    Search(Box<SearchArgs>),
+    /// Show the most favorited wallpapers of a recent period
+    Toplist(ToplistArgs),
+    /// Suggest wallpapers with tags similar to a wallpaper
+    Similar(SimilarArgs),
     /// Get tag info
     TagInfo(TagInfoArgs),
     /// Show user settings
@@ -38,18 +347,94 @@ pub enum Command {
     UserCollections(UserCollectionsArgs),
 }
 
-#[derive(Debug, Args)]
+#[derive(Debug, Subcommand)]
+pub enum BackupAction {
+    /// Write a backup to a file
+    Create { path: std::path::PathBuf },
+    /// Restore a backup, overwriting the files it contains
+    Restore { path: std::path::PathBuf },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigAction {
+    /// Change one setting, e.g. `config set max_concurrent_downloads 4`.
+    /// Nested settings use dots, e.g. `sources.follows.interval 12h`
+    Set { key: String, value: String },
+    /// Report invalid values, unwritable folders and conflicting options
+    Check,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum QuarantineAction {
+    /// List the quarantined files
+    List,
+    /// Delete every quarantined file
+    Purge,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SeenAction {
+    /// List the wallpapers seen from followed uploaders
+    List,
+    /// Forget them all, so the next sync adds every current upload again
+    Purge,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum GroupAction {
+    /// Create an empty group
+    Create { name: String },
+    /// Delete a group (its wallpapers stay in the list)
+    Delete { name: String },
+    /// List groups and their wallpapers
+    List,
+    /// Freeze a group: sync only verifies its wallpapers and reports drift
+    Archive { name: String },
+    /// Make an archived group editable and downloadable again
+    Unarchive { name: String },
+}
+
+#[derive(Debug, Args, Clone)]
 #[clap(group(
             ArgGroup::new("search_method")
                 .required(true)
-                .multiple(false)
-                .args(&["query", "colors"]),
+                .multiple(true)
+                .args(&["query", "colors", "preset"]),
         ))]
 pub struct SearchArgs {
     /// Download wallpapers to save_location from config (uses Wallhaven ID as filename)
     #[clap(short = 'd', long, verbatim_doc_comment, help_heading = "DOWNLOAD")]
     pub download: bool,
 
+    /// Number the results and pick which to add or download (e.g. 1-5,8)
+    #[clap(
+        short = 'i',
+        long,
+        conflicts_with = "download",
+        help_heading = "DOWNLOAD"
+    )]
+    pub interactive: bool,
+
+    /// Ask Wallhaven even if the results of this query are cached
+    #[clap(long)]
+    pub no_cache: bool,
+
+    /// Leave out wallpapers with this tag, on top of the config's exclude_tags (repeatable)
+    #[clap(short = 'x', long = "exclude", value_name = "TAG", help_heading = Some("SEARCH"))]
+    pub exclude: Vec<String>,
+
+    /// Preset
+    ///
+    ///    Named bundle of search parameters, from the config's
+    ///    `presets` or built in (autumn, winter, spring, summer,
+    ///    halloween, christmas, cyberpunk, minimal, space, nature).
+    ///    Options given on the command line take precedence.
+    #[clap(long,
+           verbatim_doc_comment,
+           help_heading = Some("SEARCH"),
+    )]
+    pub preset: Option<String>,
+
     /// Query string
     ///
     ///    tagname - search fuzzily for a tag/keyword
@@ -183,7 +568,7 @@ pub struct SearchArgs {
            verbatim_doc_comment,
            help_heading = Some("SEARCH"),
            //required_unless_present_any = ["query"],
-           //conflicts_with = "query",
+           conflicts_with = "query",
            //default_value = "000000",
            value_parser = valid_color
     )]
@@ -214,29 +599,87 @@ pub struct SearchArgs {
     seed: Option<Seed>,
 }
 
+impl SearchArgs {
+    /// Fill the options not given on the command line from `preset`
+    pub fn with_preset(&self, preset: &SearchPreset) -> Result<Self, String> {
+        let mut args = self.clone();
+        if args.query.is_none() && args.colors.is_none() {
+            args.query = preset
+                .query
+                .as_deref()
+                .map(SearchQuery::from_str)
+                .transpose()?;
+            args.colors = preset.colors.as_deref().map(valid_color).transpose()?;
+        }
+        let fill = |arg: &mut Option<String>, value: &Option<String>| {
+            if arg.is_none() {
+                arg.clone_from(value);
+            }
+        };
+        fill(&mut args.categories, &preset.categories);
+        fill(&mut args.purity, &preset.purity);
+        fill(&mut args.sorting, &preset.sorting);
+        fill(&mut args.order, &preset.order);
+        fill(&mut args.toprange, &preset.toprange);
+        fill(&mut args.atleast, &preset.atleast);
+        fill(&mut args.resolutions, &preset.resolutions);
+        fill(&mut args.ratios, &preset.ratios);
+        Ok(args)
+    }
+}
+
+fn duration(s: &str) -> Result<Duration, String> {
+    crate::helper::parse_duration(s).map_err(|e| e.to_string())
+}
+
 fn valid_color(s: &str) -> Result<String, String> {
-    let s = if s.starts_with("#") { &s[1..] } else { s };
+    let s = s.strip_prefix('#').unwrap_or(s);
 
-    let valid_hex = s.chars().into_iter().all(|c| c.is_digit(16));
+    let valid_hex = s.chars().all(|c| c.is_ascii_hexdigit());
 
     if valid_hex && s.len() == 6 {
-        return Ok(String::from(s));
+        Ok(String::from(s))
     } else {
-        return Err(String::from(format!("{s} is not a valid hex color")));
+        Err(format!("{s} is not a valid hex color"))
     }
 }
 
-fn valid_wallpaper_id(s: &str) -> Result<String, String> {
-    let valid_format = s
-        .chars()
-        .into_iter()
-        .all(|c| c.is_ascii_digit() || c.is_ascii_alphabetic());
+#[derive(Debug, Args)]
+pub struct ToplistArgs {
+    /// Period to rank wallpapers over
+    #[arg(short = 't',
+          long,
+          ignore_case = true,
+          default_value = "1M",
+          value_parser = ["1D", "3D", "1W", "1M", "3M", "6M", "1Y"],
+    )]
+    pub range: String,
 
-    if valid_format && s.len() == 6 {
-        return Ok(String::from(s));
-    } else {
-        return Err(String::from(format!("{s} is not a valid wallpaper id")));
-    }
+    /// Number of wallpapers to show
+    #[arg(short = 'n', long, default_value_t = 24, value_parser = clap::value_parser!(u32).range(1..))]
+    pub count: u32,
+
+    /// Add the wallpapers to the list so the next sync downloads them
+    #[arg(long)]
+    pub add: bool,
+
+    /// Download the wallpapers to save_location and add them to the list
+    #[arg(short = 'd', long)]
+    pub download: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct SimilarArgs {
+    /// Wallpaper ID or URL to find similar wallpapers for
+    pub id: String,
+
+    /// Number of suggestions to show
+    #[arg(short = 'n', long, default_value_t = 24, value_parser = clap::value_parser!(u32).range(1..))]
+    pub count: u32,
+
+    /// Add the suggestions to the list so the next sync downloads them
+    #[arg(long)]
+    pub add: bool,
 }
 
 #[derive(Debug, Args)]
@@ -285,9 +728,10 @@ impl FromStr for SearchQuery {
                         // Exclusive parameter
                         // Id is a tag number
                         // Maybe i should force a casting, even if api is resilient to non integer id?
-                        let mut q = Self::default();
-                        q.id = Some(String::from(value));
-                        return Ok(q);
+                        return Ok(Self {
+                            id: Some(String::from(value)),
+                            ..Self::default()
+                        });
                     }
                     "type" => {
                         if value == "png" || value == "jpg" {
@@ -303,7 +747,7 @@ impl FromStr for SearchQuery {
                         q.like = Some(String::from(value));
                     }
                     _ => {
-                        return Err(String::from(format!("{key}:{value} is not a valid query")));
+                        return Err(format!("{key}:{value} is not a valid query"));
                     }
                 }
 
@@ -311,8 +755,8 @@ impl FromStr for SearchQuery {
             }
 
             // Get username if any
-            if token.starts_with("@") {
-                q.username = Some(String::from(&token[1..]));
+            if let Some(username) = token.strip_prefix('@') {
+                q.username = Some(String::from(username));
                 continue;
             }
 
@@ -324,7 +768,7 @@ impl FromStr for SearchQuery {
             q.tags = Some(tags)
         }
 
-        return Ok(q);
+        Ok(q)
     }
 }
 
@@ -339,9 +783,9 @@ impl FromStr for Seed {
             && s.chars()
                 .all(|c| c.is_ascii_alphabetic() || c.is_ascii_digit())
         {
-            return Ok(Seed(String::from(s)));
+            Ok(Seed(String::from(s)))
         } else {
-            return Err(format!("{s} is an invalid seed"));
+            Err(format!("{s} is an invalid seed"))
         }
     }
 }
@@ -414,31 +858,73 @@ impl Url for SearchArgs {
             params.push(format!("ratios={}", ratios.to_ascii_lowercase()));
         }
 
-        return format!("{base_url}/search?{}", params.join("&"));
+        format!("{base_url}/search?{}", params.join("&"))
+    }
+}
+
+impl Url for ToplistArgs {
+    fn to_url(&self, base_url: &str) -> String {
+        // Wallhaven spells months in upper case and everything else in lower case
+        let range = match self.range.as_str() {
+            range if range.ends_with('M') => range.to_string(),
+            range => range.to_ascii_lowercase(),
+        };
+        format!("{base_url}/search?sorting=toplist&topRange={range}")
+    }
+}
+
+impl Url for SimilarArgs {
+    fn to_url(&self, base_url: &str) -> String {
+        let id = crate::helper::parse_wallhaven_url(&self.id);
+        format!("{base_url}/search?q=like:{id}")
     }
 }
 
 impl Url for TagInfoArgs {
     fn to_url(&self, base_url: &str) -> String {
-        return format!("{base_url}/tag/{}", self.id);
+        format!("{base_url}/tag/{}", self.id)
     }
 }
 
 impl Url for UserSettingsArgs {
     fn to_url(&self, base_url: &str) -> String {
-        return format!("{base_url}/settings");
+        format!("{base_url}/settings")
     }
 }
 
 impl Url for UserCollectionsArgs {
     fn to_url(&self, base_url: &str) -> String {
         match &self.username {
-            Some(username) => {
-                return format!("{base_url}/collections/{username}");
-            }
-            None => {
-                return format!("{base_url}/collections");
-            }
+            Some(username) => format!("{base_url}/collections/{username}"),
+            None => format!("{base_url}/collections"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aliases_and_global_overrides() {
+        let cli =
+            Cli::try_parse_from(["rust-paper", "rm", "7pmgv9", "--config", "alt.toml"]).unwrap();
+        assert!(matches!(&cli.command, Command::Remove { ids } if ids == &["7pmgv9"]));
+        assert_eq!(cli.config, Some(PathBuf::from("alt.toml")));
+
+        let cli =
+            Cli::try_parse_from(["rust-paper", "--save-location", "/tmp/walls", "ls"]).unwrap();
+        assert!(matches!(cli.command, Command::List));
+        assert_eq!(cli.save_location, Some(PathBuf::from("/tmp/walls")));
+
+        for alias in ["a", "i"] {
+            assert!(Cli::try_parse_from(["rust-paper", alias, "7pmgv9"]).is_ok());
         }
+        assert!(matches!(
+            Cli::try_parse_from(["rust-paper", "s", "-q", "city"])
+                .unwrap()
+                .command,
+            Command::Search(_)
+        ));
     }
 }
@@ -0,0 +1,137 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tokio::io::AsyncWriteExt;
+
+use crate::helper;
+
+/// What happened to a wallpaper
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEvent {
+    Download,
+    Delete,
+    IntegrityFailure,
+    /// A local image was imported into the save location
+    Import,
+    /// A file was moved out of the save location by `audit-purity --move`
+    Quarantine,
+    /// A download failed `download_checks` and was not saved
+    Rejected,
+}
+
+impl std::fmt::Display for AuditEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuditEvent::Download => write!(f, "download"),
+            AuditEvent::Delete => write!(f, "delete"),
+            AuditEvent::IntegrityFailure => write!(f, "integrity_failure"),
+            AuditEvent::Import => write!(f, "import"),
+            AuditEvent::Quarantine => write!(f, "quarantine"),
+            AuditEvent::Rejected => write!(f, "rejected"),
+        }
+    }
+}
+
+/// One line of the audit log
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuditEntry {
+    /// Unix timestamp in seconds
+    pub time: u64,
+    pub event: AuditEvent,
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Where the file came from or why it was touched
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl AuditEntry {
+    pub fn new(event: AuditEvent, id: impl Into<String>) -> Self {
+        AuditEntry {
+            time: helper::unix_timestamp(),
+            event,
+            id: id.into(),
+            path: None,
+            detail: None,
+        }
+    }
+
+    pub fn path(mut self, path: impl AsRef<Path>) -> Self {
+        self.path = Some(path.as_ref().to_string_lossy().to_string());
+        self
+    }
+
+    pub fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+}
+
+/// Append entries to the JSONL log at `path`
+pub async fn append(path: impl AsRef<Path>, entries: &[AuditEntry]) -> Result<()> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    let mut lines = String::new();
+    for entry in entries {
+        lines.push_str(
+            &serde_json::to_string(entry).context("   Failed to serialize audit entry")?,
+        );
+        lines.push('\n');
+    }
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path.as_ref())
+        .await
+        .context("   Failed to open audit log")?;
+    file.write_all(lines.as_bytes())
+        .await
+        .context("   Failed to write audit log")?;
+    // tokio finishes file writes in the background; make sure this one landed
+    file.flush().await.context("   Failed to write audit log")
+}
+
+/// Read the log, keeping entries at or after `since` (unix seconds); bad lines are skipped
+pub async fn read(path: impl AsRef<Path>, since: u64) -> Result<Vec<AuditEntry>> {
+    let contents = match tokio::fs::read_to_string(path.as_ref()).await {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).context("   Failed to read audit log"),
+    };
+    Ok(contents
+        .lines()
+        .filter_map(|line| serde_json::from_str::<AuditEntry>(line).ok())
+        .filter(|entry| entry.time >= since)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_audit_log_roundtrip() {
+        let path =
+            std::env::temp_dir().join(format!("rust-paper-audit-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut old = AuditEntry::new(AuditEvent::Delete, "p9pzk9").detail("not in list");
+        old.time = 100;
+        append(&path, &[old]).await.unwrap();
+        append(
+            &path,
+            &[AuditEntry::new(AuditEvent::Download, "x6m3gl").path("/walls/x6m3gl.png")],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(read(&path, 0).await.unwrap().len(), 2);
+        let recent = read(&path, 1_000).await.unwrap();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].event, AuditEvent::Download);
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,179 @@
+use anyhow::Context;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+use crate::error::{Error, Result};
+use crate::lock::LockFile;
+use crate::output::say;
+use crate::{helper, load_wallpapers, RustPaper};
+
+/// Format of the backups written by this version; newer ones are refused
+const BACKUP_VERSION: u32 = 1;
+
+/// Config and state files, without the images. Paths are relative to the
+/// config or data folder they were taken from
+#[derive(Debug, Serialize, Deserialize)]
+struct Backup {
+    version: u32,
+    created_at: u64,
+    config: BTreeMap<String, String>,
+    data: BTreeMap<String, String>,
+}
+
+/// Read `names` inside `folder`, skipping the ones that don't exist
+async fn collect(folder: &Path, names: &[PathBuf]) -> anyhow::Result<BTreeMap<String, String>> {
+    let mut files = BTreeMap::new();
+    for name in names {
+        let path = folder.join(name);
+        if !path.is_file() {
+            continue;
+        }
+        let contents = tokio::fs::read_to_string(&path)
+            .await
+            .with_context(|| format!("   Failed to read {}", path.display()))?;
+        files.insert(name.to_string_lossy().replace('\\', "/"), contents);
+    }
+    Ok(files)
+}
+
+/// Write every file under `folder`, refusing paths that would escape it
+async fn unpack(folder: &Path, files: &BTreeMap<String, String>) -> Result<()> {
+    for (name, contents) in files {
+        let relative = Path::new(name);
+        if !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+        {
+            return Err(Error::InvalidInput(format!(
+                "Backup contains an unsafe path '{}'",
+                name
+            )));
+        }
+        let path = folder.join(relative);
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(&path, contents).await?;
+        say!("  ✓ Restored {}", path.display());
+    }
+    Ok(())
+}
+
+/// Name of `path` inside `folder`, if it lives there
+fn relative_to(path: &Path, folder: &Path) -> Option<PathBuf> {
+    path.strip_prefix(folder).ok().map(Path::to_path_buf)
+}
+
+impl RustPaper {
+    /// Location of the lock file, even when integrity checks are off
+    async fn lock_file_location(&self) -> PathBuf {
+        match self.lock_file.lock().await.as_ref() {
+            Some(lock_file) => lock_file.path().to_path_buf(),
+            None => self.data_folder.join("wallpaper.lock"),
+        }
+    }
+
+    /// Write the config, list, lock file, state files and metadata cache to a
+    /// gzip-compressed backup at `path`. Images are not included
+    pub async fn backup_create(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let mut data_files: Vec<PathBuf> = [
+            self.wallpapers_list_file_location.clone(),
+            self.lock_file_location().await,
+            self.source_state_file_location.clone(),
+            self.groups_file_location.clone(),
+            self.follows_file_location.clone(),
+            self.pins_file_location.clone(),
+            self.blocklist_file_location.clone(),
+            self.audit_log_location.clone(),
+            self.tombstones_file_location.clone(),
+            self.seen_file_location.clone(),
+            self.last_sync_location.clone(),
+        ]
+        .iter()
+        .filter_map(|file| relative_to(file, &self.data_folder))
+        .collect();
+        if let Ok(mut entries) = tokio::fs::read_dir(&self.metadata_cache_location).await {
+            while let Some(entry) = entries.next_entry().await? {
+                if let Some(name) = relative_to(&entry.path(), &self.data_folder) {
+                    data_files.push(name);
+                }
+            }
+        }
+
+        let backup = Backup {
+            version: BACKUP_VERSION,
+            created_at: helper::unix_timestamp(),
+            config: collect(&self.config_folder, &[PathBuf::from("config.toml")]).await?,
+            data: collect(&self.data_folder, &data_files).await?,
+        };
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        serde_json::to_writer(&mut encoder, &backup)?;
+        let compressed = encoder.finish()?;
+        tokio::fs::write(path, compressed).await?;
+        say!(
+            "   Backed up {} file(s) to {}",
+            backup.config.len() + backup.data.len(),
+            path.display()
+        );
+        Ok(())
+    }
+
+    /// Restore a backup written by `backup_create`, overwriting the files it
+    /// contains and reloading the list and lock file
+    pub async fn backup_restore(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let compressed = tokio::fs::read(path).await?;
+        let mut json = String::new();
+        GzDecoder::new(compressed.as_slice())
+            .read_to_string(&mut json)
+            .map_err(|e| {
+                Error::InvalidInput(format!("'{}' is not a backup: {}", path.display(), e))
+            })?;
+        let backup: Backup = serde_json::from_str(&json)?;
+        if backup.version > BACKUP_VERSION {
+            return Err(Error::InvalidInput(format!(
+                "Backup format {} is newer than this rust-paper supports ({}); upgrade first",
+                backup.version, BACKUP_VERSION
+            )));
+        }
+
+        unpack(&self.config_folder, &backup.config).await?;
+        unpack(&self.data_folder, &backup.data).await?;
+
+        self.wallpapers = load_wallpapers(&self.wallpapers_list_file_location).await?;
+        let lock_file_location = self.lock_file_location().await;
+        let mut lock_file_guard = self.lock_file.lock().await;
+        if lock_file_guard.is_some() {
+            *lock_file_guard = Some(LockFile::load_or_new_at(lock_file_location).await);
+        }
+        say!(
+            "   Restored {} file(s) from {}; restart to pick up config changes",
+            backup.config.len() + backup.data.len(),
+            path.display()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_unpack_refuses_paths_outside_the_folder() {
+        let dir = std::env::temp_dir().join(format!("rust-paper-backup-{}", std::process::id()));
+        let mut files = BTreeMap::new();
+        files.insert("../escape.txt".to_string(), String::new());
+        assert!(matches!(
+            unpack(&dir, &files).await,
+            Err(Error::InvalidInput(_))
+        ));
+        assert!(!dir.join("../escape.txt").exists());
+    }
+}
@@ -0,0 +1,169 @@
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::error::Result;
+use crate::output::say;
+use crate::{helper, import, RustPaper, WallpaperId};
+
+/// Wallpapers that must never be added again, with when they were blocked
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct Blocklist {
+    blocked: BTreeMap<String, u64>,
+}
+
+impl Blocklist {
+    /// Load the blocklist from disk, starting empty if the file is missing
+    pub async fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        match tokio::fs::read_to_string(path.as_ref()).await {
+            Ok(contents) => serde_json::from_str(&contents).context("   Failed to parse blocklist"),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Save the blocklist to disk
+    pub async fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let json =
+            serde_json::to_string_pretty(&self).context("   Failed to serialize blocklist")?;
+        tokio::fs::write(path.as_ref(), json)
+            .await
+            .context("   Failed to write blocklist")
+    }
+
+    /// Block `id`, keeping the time it was first blocked. Returns whether it is new
+    pub fn block(&mut self, id: &str, now: u64) -> bool {
+        if self.blocked.contains_key(id) {
+            return false;
+        }
+        self.blocked.insert(id.to_string(), now);
+        true
+    }
+
+    pub fn unblock(&mut self, id: &str) -> bool {
+        self.blocked.remove(id).is_some()
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.blocked.contains_key(id)
+    }
+
+    /// Blocked IDs in order, with the time each was blocked
+    pub fn entries(&self) -> impl Iterator<Item = (&String, &u64)> {
+        self.blocked.iter()
+    }
+}
+
+/// Wallhaven IDs named by `inputs`, which may be URLs or comma-separated
+fn parse_ids(inputs: &[String]) -> Result<Vec<String>> {
+    inputs
+        .iter()
+        .flat_map(|input| helper::to_array(input))
+        .map(|id| WallpaperId::parse(&id).map(String::from))
+        .collect()
+}
+
+impl RustPaper {
+    pub(crate) async fn blocklist(&self) -> Result<Blocklist> {
+        Ok(Blocklist::load(&self.blocklist_file_location).await?)
+    }
+
+    /// Block wallpapers from being added again, removing the tracked ones
+    pub async fn block(&mut self, ids: &[String]) -> Result<()> {
+        let ids = parse_ids(ids)?;
+        let mut blocklist = self.blocklist().await?;
+        let now = helper::unix_timestamp();
+        for id in &ids {
+            if blocklist.block(id, now) {
+                say!("   Blocked {}", id);
+            } else {
+                say!("   {} is already blocked", id);
+            }
+        }
+        blocklist.save(&self.blocklist_file_location).await?;
+
+        let tracked: Vec<String> = ids
+            .into_iter()
+            .filter(|id| self.wallpapers.contains(id))
+            .collect();
+        if !tracked.is_empty() {
+            self.remove(&tracked).await?;
+        }
+        Ok(())
+    }
+
+    /// Allow blocked wallpapers to be added again
+    pub async fn unblock(&self, ids: &[String]) -> Result<()> {
+        let ids = parse_ids(ids)?;
+        let mut blocklist = self.blocklist().await?;
+        for id in &ids {
+            if blocklist.unblock(id) {
+                say!("   Unblocked {}", id);
+            } else {
+                say!("   {} wasn't blocked", id);
+            }
+        }
+        blocklist.save(&self.blocklist_file_location).await?;
+        Ok(())
+    }
+
+    /// Print the blocked wallpapers
+    pub async fn blocked(&self) -> Result<()> {
+        let blocklist = self.blocklist().await?;
+        let mut entries = blocklist.entries().peekable();
+        if entries.peek().is_none() {
+            say!("   No wallpapers are blocked.");
+            return Ok(());
+        }
+        for (id, blocked_at) in entries {
+            say!(
+                "  {} (blocked {} UTC)",
+                id,
+                helper::format_timestamp(*blocked_at)
+            );
+        }
+        Ok(())
+    }
+
+    /// Drop blocked wallpapers from `ids`, saying which
+    pub(crate) async fn drop_blocked(&self, ids: &mut Vec<String>) -> Result<()> {
+        let blocklist = self.blocklist().await?;
+        ids.retain(|id| {
+            let blocked = !import::is_local_id(id) && blocklist.contains(id);
+            if blocked {
+                say!(
+                    "  ○ {} is blocked; `rust-paper unblock {}` allows it",
+                    id,
+                    id
+                );
+            }
+            !blocked
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+
+    #[test]
+    fn test_block_and_unblock() {
+        let mut blocklist = Blocklist::default();
+        assert!(blocklist.block("p9pzk9", 100));
+        assert!(!blocklist.block("p9pzk9", 200));
+        assert!(blocklist.contains("p9pzk9"));
+        assert_eq!(
+            blocklist.entries().next(),
+            Some((&"p9pzk9".to_string(), &100))
+        );
+        assert!(blocklist.unblock("p9pzk9"));
+        assert!(!blocklist.unblock("p9pzk9"));
+        assert!(!blocklist.contains("p9pzk9"));
+        assert!(matches!(
+            parse_ids(&["https://wallhaven.cc/w/7pmgv9,nope".to_string()]),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+}
@@ -0,0 +1,191 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Longest pause before the breaker gives up on the rest of a sync, as a
+/// multiple of `circuit_breaker_cooldown`
+const MAX_BACKOFF: u32 = 8;
+
+/// Retries left for one sync run, shared by all of its downloads
+pub(crate) struct RetryBudget {
+    left: Option<AtomicU32>,
+    exhausted: AtomicBool,
+}
+
+impl RetryBudget {
+    /// A budget of `limit` retries, or unlimited without one
+    pub fn new(limit: Option<u32>) -> Self {
+        RetryBudget {
+            left: limit.map(AtomicU32::new),
+            exhausted: AtomicBool::new(false),
+        }
+    }
+
+    /// Take one retry, or refuse once the budget is spent
+    pub fn take(&self) -> bool {
+        let Some(left) = &self.left else {
+            return true;
+        };
+        let taken = left
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |left| {
+                left.checked_sub(1)
+            })
+            .is_ok();
+        if !taken {
+            self.exhausted.store(true, Ordering::Relaxed);
+        }
+        taken
+    }
+
+    /// Whether some retry was refused
+    pub fn exhausted(&self) -> bool {
+        self.exhausted.load(Ordering::Relaxed)
+    }
+}
+
+/// The downloads left when the circuit breaker gave up; they are retried on
+/// the next sync
+#[derive(Debug, thiserror::Error)]
+#[error("not attempted after repeated network failures")]
+pub(crate) struct GaveUp;
+
+/// What recording a download's outcome did to the breaker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Trip {
+    /// Nothing changed
+    None,
+    /// New downloads now wait this long
+    Paused(Duration),
+    /// The breaker gave up on the rest of the sync
+    GaveUp,
+}
+
+#[derive(Debug)]
+struct State {
+    /// Failures since the last success
+    failures: u32,
+    /// Pause of the next trip, doubled after each one
+    cooldown: Duration,
+    open_until: Option<Instant>,
+    gave_up: bool,
+}
+
+/// Pauses new downloads after `threshold` failures in a row, so an outage or
+/// captive portal doesn't fail every remaining download. Each trip pauses
+/// twice as long as the one before; past `MAX_BACKOFF` it gives up
+pub(crate) struct CircuitBreaker {
+    threshold: u32,
+    base: Duration,
+    state: Mutex<State>,
+}
+
+impl CircuitBreaker {
+    /// A breaker tripping after `threshold` failures in a row (0 never trips).
+    /// Without a cooldown it gives up on the first trip
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        CircuitBreaker {
+            threshold,
+            base: cooldown,
+            state: Mutex::new(State {
+                failures: 0,
+                cooldown,
+                open_until: None,
+                gave_up: false,
+            }),
+        }
+    }
+
+    /// Wait until new downloads may start, or `false` once it gave up
+    pub async fn admit(&self) -> bool {
+        loop {
+            let open_until = {
+                let state = self.state.lock().unwrap();
+                if state.gave_up {
+                    return false;
+                }
+                match state.open_until {
+                    Some(until) if until > Instant::now() => until,
+                    _ => return true,
+                }
+            };
+            tokio::time::sleep_until(open_until).await;
+        }
+    }
+
+    /// Count a finished download. After a pause, one failure trips it again
+    pub fn record(&self, success: bool) -> Trip {
+        let mut state = self.state.lock().unwrap();
+        if success {
+            state.failures = 0;
+            state.cooldown = self.base;
+            return Trip::None;
+        }
+        let mut resumed = false;
+        if let Some(until) = state.open_until {
+            if until > Instant::now() {
+                // Started before the pause began
+                return Trip::None;
+            }
+            state.open_until = None;
+            resumed = true;
+        }
+        state.failures += 1;
+        if self.threshold == 0 || state.gave_up || (state.failures < self.threshold && !resumed) {
+            return Trip::None;
+        }
+        if self.base.is_zero() || state.cooldown > self.base * MAX_BACKOFF {
+            state.gave_up = true;
+            return Trip::GaveUp;
+        }
+        let pause = state.cooldown;
+        state.open_until = Some(Instant::now() + pause);
+        state.cooldown = pause * 2;
+        Trip::Paused(pause)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_budget() {
+        let budget = RetryBudget::new(Some(2));
+        assert!(budget.take() && budget.take());
+        assert!(!budget.take());
+        assert!(budget.exhausted());
+        let unlimited = RetryBudget::new(None);
+        assert!((0..100).all(|_| unlimited.take()));
+        assert!(!unlimited.exhausted());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_breaker_backs_off_then_gives_up() {
+        let second = Duration::from_secs(1);
+        let breaker = CircuitBreaker::new(3, second);
+        assert_eq!(breaker.record(false), Trip::None);
+        assert_eq!(breaker.record(true), Trip::None);
+        assert_eq!(breaker.record(false), Trip::None);
+        assert_eq!(breaker.record(false), Trip::None);
+        assert_eq!(breaker.record(false), Trip::Paused(second));
+        // A download already running when it tripped doesn't trip it again
+        assert_eq!(breaker.record(false), Trip::None);
+
+        let started = Instant::now();
+        assert!(breaker.admit().await);
+        assert_eq!(started.elapsed(), second);
+        // Still failing after the pause: wait longer each time
+        assert_eq!(breaker.record(false), Trip::Paused(second * 2));
+        assert!(breaker.admit().await);
+        assert_eq!(breaker.record(false), Trip::Paused(second * 4));
+        assert!(breaker.admit().await);
+        assert_eq!(breaker.record(false), Trip::Paused(second * 8));
+        assert!(breaker.admit().await);
+        assert_eq!(breaker.record(false), Trip::GaveUp);
+        assert!(!breaker.admit().await);
+
+        let breaker = CircuitBreaker::new(1, Duration::ZERO);
+        assert_eq!(breaker.record(false), Trip::GaveUp);
+    }
+}
@@ -0,0 +1,363 @@
+use reqwest::Client;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::fs::create_dir_all;
+use tokio::sync::{Mutex, Semaphore};
+
+use crate::config::Config;
+use crate::decode::DecodePool;
+use crate::error::{Error, Result};
+use crate::fetch::{HttpFetcher, ReqwestFetcher};
+use crate::helper::{self, get_key_from_config_or_env};
+use crate::lock::LockFile;
+use crate::paths::Paths;
+use crate::run_lock::{LockMode, RunLock};
+use crate::staging;
+use crate::storage;
+use crate::{load_wallpapers, RustPaper};
+
+/// Builder for `RustPaper` with injectable config, paths and HTTP client
+///
+/// Anything left unset falls back to what `RustPaper::new()` uses: the confy
+/// config, the standard config and data folders and a client built from the config.
+#[derive(Default)]
+pub struct RustPaperBuilder {
+    config: Option<Config>,
+    config_file: Option<PathBuf>,
+    save_location: Option<PathBuf>,
+    config_folder: Option<PathBuf>,
+    data_folder: Option<PathBuf>,
+    wallpapers_list: Option<PathBuf>,
+    lock_file: Option<PathBuf>,
+    fetcher: Option<Arc<dyn HttpFetcher>>,
+    lock_mode: LockMode,
+    lock_per_operation: bool,
+    skip_validation: bool,
+}
+
+impl RustPaperBuilder {
+    /// Use this configuration instead of loading it from disk
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Load the configuration from this file instead of `<config_folder>/config.toml`
+    pub fn config_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config_file = Some(path.into());
+        self
+    }
+
+    /// Download to this folder instead of the configured `save_location`
+    pub fn save_location(mut self, path: impl Into<PathBuf>) -> Self {
+        self.save_location = Some(path.into());
+        self
+    }
+
+    /// Folder holding `config.toml`, and the data files unless `data_folder` is set
+    pub fn config_folder(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config_folder = Some(path.into());
+        self
+    }
+
+    /// Folder holding the list, lock file, state files and cache
+    pub fn data_folder(mut self, path: impl Into<PathBuf>) -> Self {
+        self.data_folder = Some(path.into());
+        self
+    }
+
+    /// Location of the wallpapers list (default: `<data_folder>/wallpapers.lst`)
+    pub fn wallpapers_list(mut self, path: impl Into<PathBuf>) -> Self {
+        self.wallpapers_list = Some(path.into());
+        self
+    }
+
+    /// Location of the lock file (default: `<data_folder>/wallpaper.lock`)
+    pub fn lock_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.lock_file = Some(path.into());
+        self
+    }
+
+    /// HTTP client used for every request
+    pub fn http_client(mut self, client: Client) -> Self {
+        self.fetcher = Some(Arc::new(ReqwestFetcher::new(client)));
+        self
+    }
+
+    /// Custom network layer, e.g. one serving canned responses in tests
+    pub fn fetcher(mut self, fetcher: Arc<dyn HttpFetcher>) -> Self {
+        self.fetcher = Some(fetcher);
+        self
+    }
+
+    /// How to handle another instance using the same config folder (default: fail)
+    pub fn lock_mode(mut self, mode: LockMode) -> Self {
+        self.lock_mode = mode;
+        self
+    }
+
+    /// Take the run lock only around each sync or change instead of for the
+    /// instance's lifetime, so long-running modes such as `watch` and
+    /// `serve` don't keep other commands out
+    pub fn lock_per_operation(mut self, per_operation: bool) -> Self {
+        self.lock_per_operation = per_operation;
+        self
+    }
+
+    /// Build even when the config doesn't validate, so commands such as
+    /// `config` and `doctor` can report and fix it
+    pub fn skip_validation(mut self, skip: bool) -> Self {
+        self.skip_validation = skip;
+        self
+    }
+
+    /// Create the folders, load the list and lock file and build the instance
+    pub async fn build(self) -> Result<RustPaper> {
+        let mut paths = match self.config_folder {
+            Some(folder) => Paths::new(folder.clone(), folder),
+            None => Paths::resolve()?,
+        };
+        if let Some(folder) = self.data_folder {
+            paths = Paths::new(paths.config_dir, folder);
+        }
+        let config_folder = paths.config_dir.clone();
+        let config_file = match (&self.config, &self.config_file) {
+            (Some(_), _) => None,
+            (None, Some(file)) => Some(file.clone()),
+            (None, None) if config_folder_is_default(&config_folder) => {
+                confy::get_configuration_file_path("rust-paper", "config").ok()
+            }
+            (None, None) => Some(config_folder.join("config.toml")),
+        };
+        let mut config = match (self.config, self.config_file) {
+            (Some(config), _) => config,
+            (None, Some(file)) => {
+                // confy would quietly create a mistyped path, so insist it exists
+                if !file.is_file() {
+                    return Err(Error::Config(format!(
+                        "Config file {} does not exist",
+                        file.display()
+                    )));
+                }
+                confy::load_path(&file).map_err(|e| {
+                    Error::Config(format!("   Failed to load {}: {}", file.display(), e))
+                })?
+            }
+            (None, None) if config_folder_is_default(&config_folder) => {
+                confy::load("rust-paper", "config").map_err(|e| {
+                    Error::Config(format!("   Failed to load configuration: {}", e))
+                })?
+            }
+            (None, None) => confy::load_path(config_folder.join("config.toml"))
+                .map_err(|e| Error::Config(format!("   Failed to load configuration: {}", e)))?,
+        };
+        if let Some(save_location) = self.save_location {
+            config.save_location = save_location.to_string_lossy().to_string();
+        }
+        config.save_location = helper::expand_path(&config.save_location);
+        if !self.skip_validation {
+            config.validate()?;
+        }
+
+        tokio::try_join!(
+            create_dir_all(&config_folder),
+            create_dir_all(&paths.data_dir),
+            create_dir_all(&config.save_location)
+        )?;
+        let (run_lock, operation_lock_mode) = match self.lock_mode {
+            LockMode::Disabled => (None, None),
+            mode if self.lock_per_operation => (None, Some(mode)),
+            mode => (RunLock::acquire(&paths.data_dir, mode).await?, None),
+        };
+        // Left behind by a run that crashed mid-download
+        staging::clean_stale(Path::new(&config.save_location), staging::STALE_AFTER).await;
+
+        let wallpapers_list_file_location = self
+            .wallpapers_list
+            .unwrap_or(paths.wallpapers_list.clone());
+        let wallpapers = load_wallpapers(&wallpapers_list_file_location).await?;
+        let source_state_file_location = paths.data_file("sources.state");
+        let groups_file_location = paths.data_file("groups.json");
+        let follows_file_location = paths.data_file("follows.json");
+        let audit_log_location = paths.data_file("audit.jsonl");
+        let tombstones_file_location = paths.data_file("tombstones.json");
+        let seen_file_location = paths.data_file("seen.json");
+        let last_sync_location = paths.data_file("last_sync.json");
+        let pins_file_location = paths.data_file("pins.json");
+        let blocklist_file_location = paths.data_file("blocklist.json");
+        let current_file_location = paths.data_file("current.json");
+        let integrity_quarantine_location = paths.data_file("quarantine");
+        let manifest_file_location = paths.data_file("manifest.json");
+        let search_cache_location = paths.data_file("search_cache");
+        let metadata_cache_location = paths.cache_dir;
+
+        let lock_file = if config.integrity {
+            let lock_file_location = self.lock_file.unwrap_or(paths.lock_file);
+            Some(LockFile::load_or_new_at(lock_file_location).await)
+        } else {
+            None
+        };
+        let fetcher: Arc<dyn HttpFetcher> = match self.fetcher {
+            Some(fetcher) => fetcher,
+            None => {
+                let api_key = get_key_from_config_or_env(config.api_key.as_deref());
+                let client = helper::create_http_client(&config, api_key.as_ref())?;
+                let download_client = helper::create_download_client(&config)?;
+                Arc::new(ReqwestFetcher::new(client).download_client(download_client))
+            }
+        };
+        let download_semaphore = Arc::new(Semaphore::new(config.max_concurrent_downloads));
+        let storage_mirrors = config
+            .storage_mirrors
+            .iter()
+            .map(|storage| storage::open(storage, &config))
+            .collect::<Result<Vec<_>>>()?;
+        let decode_pool = Arc::new(DecodePool::new(config.decode_threads)?);
+
+        Ok(RustPaper {
+            config,
+            config_file,
+            config_folder,
+            data_folder: paths.data_dir,
+            wallpapers,
+            wallpapers_list_file_location,
+            source_state_file_location,
+            groups_file_location,
+            follows_file_location,
+            audit_log_location,
+            metadata_cache_location,
+            tombstones_file_location,
+            seen_file_location,
+            last_sync_location,
+            pins_file_location,
+            blocklist_file_location,
+            current_file_location,
+            integrity_quarantine_location,
+            manifest_file_location,
+            search_cache_location,
+            offline: false,
+            expected_sizes: HashMap::new(),
+            lock_file: Arc::new(Mutex::new(lock_file)),
+            fetcher,
+            download_semaphore,
+            storage_mirrors,
+            decode_pool,
+            events: None,
+            _run_lock: run_lock,
+            operation_lock_mode,
+        })
+    }
+}
+
+/// Whether `folder` is the standard confy folder, so the usual confy lookup applies
+fn config_folder_is_default(folder: &std::path::Path) -> bool {
+    helper::get_folder_path()
+        .map(|default| default == folder)
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_builder_uses_custom_paths() {
+        let root = std::env::temp_dir().join(format!(
+            "rust-paper-builder-{}-{}",
+            std::process::id(),
+            helper::unix_timestamp()
+        ));
+        let config = Config {
+            save_location: root.join("walls").to_string_lossy().to_string(),
+            ..Config::default()
+        };
+
+        let rust_paper = RustPaper::builder()
+            .config(config)
+            .config_folder(root.join("config"))
+            .lock_file(root.join("custom.lock"))
+            .build()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            rust_paper.wallpapers_list_file_location,
+            root.join("config").join("wallpapers.lst")
+        );
+        assert!(rust_paper.wallpapers.is_empty());
+        assert!(root.join("walls").is_dir());
+        assert!(rust_paper.wallpapers_list_file_location.exists());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn test_builder_loads_config_file_and_overrides_save_location() {
+        let root = std::env::temp_dir().join(format!(
+            "rust-paper-config-file-{}-{}",
+            std::process::id(),
+            helper::unix_timestamp()
+        ));
+        std::fs::create_dir_all(&root).unwrap();
+        let config_file = root.join("alt.toml");
+        std::fs::write(&config_file, "max_concurrent_downloads = 2\n").unwrap();
+
+        let missing = RustPaper::builder()
+            .config_file(root.join("typo.toml"))
+            .config_folder(root.join("config"))
+            .build()
+            .await;
+        assert!(matches!(missing, Err(Error::Config(_))));
+        assert!(!root.join("typo.toml").exists());
+
+        let rust_paper = RustPaper::builder()
+            .config_file(&config_file)
+            .save_location(root.join("walls"))
+            .config_folder(root.join("config"))
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(rust_paper.config.max_concurrent_downloads, 2);
+        assert_eq!(
+            rust_paper.config.save_location,
+            root.join("walls").to_string_lossy()
+        );
+        assert!(root.join("walls").is_dir());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn test_builder_rejects_an_invalid_config() {
+        let root = std::env::temp_dir().join(format!(
+            "rust-paper-invalid-config-{}-{}",
+            std::process::id(),
+            helper::unix_timestamp()
+        ));
+        let config = Config {
+            save_location: root.join("walls").to_string_lossy().to_string(),
+            retry_count: 0,
+            ..Config::default()
+        };
+
+        let rejected = RustPaper::builder()
+            .config(config.clone())
+            .config_folder(root.join("config"))
+            .build()
+            .await;
+        assert!(matches!(rejected, Err(Error::Config(e)) if e.contains("retry_count")));
+        assert!(!root.join("walls").exists());
+
+        let rust_paper = RustPaper::builder()
+            .config(config)
+            .config_folder(root.join("config"))
+            .skip_validation(true)
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(rust_paper.config.retry_count, 0);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
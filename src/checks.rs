@@ -0,0 +1,229 @@
+use anyhow::{anyhow, Context, Result};
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tokio::process::Command;
+
+use crate::config::Config;
+use crate::decode::DecodePool;
+use crate::error::Error;
+
+/// Pixels darker than this (out of 255) count as black
+const BLACK_LEVEL: u8 = 16;
+
+/// Checks every download must pass before it is saved. A download failing
+/// one is rejected: it isn't stored, and the next sync tries again
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct DownloadChecks {
+    /// Reject files smaller than this many KB (default: none)
+    pub min_file_size_kb: Option<u64>,
+    /// Reject images whose brightness has less entropy than this, in bits
+    /// from 0 to 8; blank or flat images are close to 0 (default: none)
+    pub min_entropy: Option<f64>,
+    /// Reject images with more than this share of near-black pixels, from
+    /// 0.0 to 1.0 (default: none)
+    pub max_black_fraction: Option<f64>,
+    /// Command run through `sh -c` with the file in `RUST_PAPER_PATH` and the
+    /// ID in `RUST_PAPER_ID`; a non-zero exit rejects it, e.g. an NSFW
+    /// classifier (default: none)
+    pub command: Option<String>,
+}
+
+impl DownloadChecks {
+    /// Check the settings without looking at any image
+    pub fn validate(&self) -> Result<()> {
+        if self
+            .min_entropy
+            .is_some_and(|bits| !(0.0..=8.0).contains(&bits))
+        {
+            return Err(anyhow!("min_entropy must be between 0 and 8"));
+        }
+        if self
+            .max_black_fraction
+            .is_some_and(|fraction| !(0.0..=1.0).contains(&fraction))
+        {
+            return Err(anyhow!("max_black_fraction must be between 0.0 and 1.0"));
+        }
+        Ok(())
+    }
+
+    fn needs_decode(&self) -> bool {
+        self.min_entropy.is_some() || self.max_black_fraction.is_some()
+    }
+}
+
+/// Brightness entropy in bits and the share of near-black pixels, measured
+/// on a thumbnail so large images stay cheap
+fn image_stats(img: &DynamicImage) -> (f64, f64) {
+    let luma = img.thumbnail(256, 256).to_luma8();
+    let mut histogram = [0u64; 256];
+    for pixel in luma.pixels() {
+        histogram[pixel[0] as usize] += 1;
+    }
+    let total = luma.pixels().len().max(1) as f64;
+    let entropy = histogram
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / total;
+            -p * p.log2()
+        })
+        .sum();
+    let black: u64 = histogram[..BLACK_LEVEL as usize].iter().sum();
+    (entropy, black as f64 / total)
+}
+
+fn rejected(id: &str, reason: String) -> anyhow::Error {
+    Error::Rejected {
+        id: id.to_string(),
+        reason,
+    }
+    .into()
+}
+
+/// Run the built-in `download_checks` on a download before it is written
+pub(crate) async fn check_data(
+    config: &Config,
+    decode: &DecodePool,
+    id: &str,
+    data: Vec<u8>,
+) -> Result<Vec<u8>> {
+    let checks = &config.download_checks;
+    if let Some(min_kb) = checks.min_file_size_kb {
+        if (data.len() as u64) < min_kb * 1024 {
+            return Err(rejected(
+                id,
+                format!(
+                    "{} KB is under min_file_size_kb ({} KB)",
+                    data.len() / 1024,
+                    min_kb
+                ),
+            ));
+        }
+    }
+    if !checks.needs_decode() {
+        return Ok(data);
+    }
+    let (data, stats) = decode
+        .run(move || {
+            let stats = image::load_from_memory(&data).map(|img| image_stats(&img));
+            (data, stats)
+        })
+        .await
+        .context("Image check task failed")?;
+    let (entropy, black) =
+        stats.with_context(|| format!("Downloaded image for {} does not decode", id))?;
+    if let Some(min_entropy) = checks.min_entropy.filter(|&min| entropy < min) {
+        return Err(rejected(
+            id,
+            format!(
+                "entropy {:.2} is under min_entropy ({})",
+                entropy, min_entropy
+            ),
+        ));
+    }
+    if let Some(max_black) = checks.max_black_fraction.filter(|&max| black > max) {
+        return Err(rejected(
+            id,
+            format!(
+                "{:.0}% of it is black, over max_black_fraction ({})",
+                black * 100.0,
+                max_black
+            ),
+        ));
+    }
+    Ok(data)
+}
+
+/// Run the `download_checks` command against the file at `path`
+pub(crate) async fn check_file(config: &Config, id: &str, path: &Path) -> Result<()> {
+    let Some(command) = config
+        .download_checks
+        .command
+        .as_deref()
+        .filter(|command| !command.trim().is_empty())
+    else {
+        return Ok(());
+    };
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("RUST_PAPER_EVENT", "check")
+        .env("RUST_PAPER_ID", id)
+        .env("RUST_PAPER_PATH", path)
+        .output()
+        .await
+        .context("Failed to run the download_checks command")?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let message = String::from_utf8_lossy(&output.stdout);
+    let message = match message.trim() {
+        "" => format!("check command exited with {}", output.status),
+        message => message.to_string(),
+    };
+    Err(rejected(id, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient() -> DynamicImage {
+        DynamicImage::ImageLuma8(image::GrayImage::from_fn(64, 64, |x, y| {
+            image::Luma([((x + y) * 2) as u8])
+        }))
+    }
+
+    #[test]
+    fn test_image_stats() {
+        let black = DynamicImage::ImageLuma8(image::GrayImage::new(64, 64));
+        let (entropy, fraction) = image_stats(&black);
+        assert_eq!(entropy, 0.0);
+        assert_eq!(fraction, 1.0);
+
+        let (entropy, fraction) = image_stats(&gradient());
+        assert!(entropy > 6.0, "{}", entropy);
+        assert!(fraction < 0.1, "{}", fraction);
+    }
+
+    #[tokio::test]
+    async fn test_checks_reject_black_frames() {
+        let mut png = std::io::Cursor::new(Vec::new());
+        DynamicImage::ImageLuma8(image::GrayImage::new(64, 64))
+            .write_to(&mut png, image::ImageFormat::Png)
+            .unwrap();
+        let mut config = Config::default();
+        let decode = DecodePool::new(1).unwrap();
+        let data = check_data(&config, &decode, "abc123", png.get_ref().clone())
+            .await
+            .unwrap();
+        assert_eq!(&data, png.get_ref());
+
+        config.download_checks.max_black_fraction = Some(0.9);
+        let err = check_data(&config, &decode, "abc123", data)
+            .await
+            .unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(Error::Rejected { .. })));
+        assert!(DownloadChecks {
+            min_entropy: Some(9.0),
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_check_command_rejects_on_failure() {
+        let mut config = Config::default();
+        let path = Path::new("/nonexistent/abc123.png");
+        check_file(&config, "abc123", path).await.unwrap();
+
+        config.download_checks.command = Some("test \"$RUST_PAPER_ID\" = abc123".to_string());
+        check_file(&config, "abc123", path).await.unwrap();
+        config.download_checks.command = Some("echo looks explicit; exit 1".to_string());
+        let err = check_file(&config, "abc123", path).await.unwrap_err();
+        assert_eq!(err.to_string(), "Rejected abc123: looks explicit");
+    }
+}
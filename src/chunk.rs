@@ -0,0 +1,129 @@
+use anyhow::{anyhow, Context, Result};
+use futures::future::try_join_all;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use indicatif::ProgressBar;
+
+use crate::config::Config;
+use crate::fetch::HttpFetcher;
+
+/// Split `total` bytes into `(start, len)` ranges for parallel download. Files
+/// below `chunked_download_min_mb` (or with chunking off) stay a single range
+pub fn ranges(total: u64, config: &Config) -> Vec<(u64, u64)> {
+    let min_bytes = config.chunked_download_min_mb.saturating_mul(1024 * 1024);
+    let chunks = config.download_chunks.max(1) as u64;
+    if min_bytes == 0 || total < min_bytes || chunks == 1 {
+        return vec![(0, total)];
+    }
+    let size = total.div_ceil(chunks);
+    (0..chunks)
+        .map(|i| i * size)
+        .take_while(|&start| start < total)
+        .map(|start| (start, size.min(total - start)))
+        .collect()
+}
+
+/// Read `stream` until at least `limit` bytes arrived or it ends
+async fn read_stream(
+    stream: &mut BoxStream<'static, crate::Result<Vec<u8>>>,
+    limit: u64,
+    bars: &[ProgressBar],
+) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    while (data.len() as u64) < limit {
+        let Some(item) = stream.next().await else {
+            break;
+        };
+        let chunk = item.context("Error while downloading file")?;
+        for pb in bars {
+            pb.inc(chunk.len() as u64);
+        }
+        data.extend_from_slice(&chunk);
+    }
+    Ok(data)
+}
+
+/// Fetch every range in parallel, or `None` if the server ignores ranges
+async fn fetch_ranges(
+    fetcher: &dyn HttpFetcher,
+    url: &str,
+    ranges: &[(u64, u64)],
+    bars: &[ProgressBar],
+) -> Result<Option<Vec<Vec<u8>>>> {
+    let parts = try_join_all(ranges.iter().map(|&(start, len)| async move {
+        let part = fetcher.get_range(url, start, len).await?;
+        if let Some(part) = &part {
+            for pb in bars {
+                pb.inc(part.len() as u64);
+            }
+        }
+        match part {
+            Some(part) if part.len() as u64 != len => Err(anyhow!(
+                "Range {}-{} returned {} bytes",
+                start,
+                start + len - 1,
+                part.len()
+            )),
+            part => Ok(part),
+        }
+    }))
+    .await?;
+    Ok(parts.into_iter().collect())
+}
+
+/// Download the body of a `total`-byte file whose first response is `stream`.
+/// Large files fetch the rest of their ranges in parallel while the first
+/// range streams in; servers that ignore ranges are read to the end instead
+pub async fn download_body(
+    fetcher: &dyn HttpFetcher,
+    url: &str,
+    mut stream: BoxStream<'static, crate::Result<Vec<u8>>>,
+    total: u64,
+    config: &Config,
+    bars: &[ProgressBar],
+) -> Result<Vec<u8>> {
+    let ranges = ranges(total, config);
+    if ranges.len() == 1 {
+        return read_stream(&mut stream, u64::MAX, bars).await;
+    }
+    let (head, rest) = tokio::join!(
+        read_stream(&mut stream, ranges[0].1, bars),
+        fetch_ranges(fetcher, url, &ranges[1..], bars)
+    );
+    let mut data = head?;
+    match rest? {
+        Some(parts) => {
+            data.truncate(ranges[0].1 as usize);
+            data.extend(parts.into_iter().flatten());
+        }
+        None => data.extend(read_stream(&mut stream, u64::MAX, bars).await?),
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ranges_cover_the_file() {
+        let config = Config {
+            chunked_download_min_mb: 1,
+            download_chunks: 3,
+            ..Config::default()
+        };
+        let total = 3 * 1024 * 1024 + 1;
+        let parts = ranges(total, &config);
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts[0], (0, 1024 * 1024 + 1));
+        assert_eq!(parts.iter().map(|(_, len)| len).sum::<u64>(), total);
+        assert_eq!(parts[2].0 + parts[2].1, total);
+
+        assert_eq!(ranges(1024, &config), [(0, 1024)]);
+        let off = Config {
+            chunked_download_min_mb: 0,
+            ..config
+        };
+        assert_eq!(ranges(total, &off), [(0, total)]);
+    }
+}
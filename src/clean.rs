@@ -0,0 +1,169 @@
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::hash::HashAlgorithm;
+use crate::lock::LockEntry;
+use crate::output::say;
+use crate::{helper, sidecar};
+
+/// Which orphaned files `clean` removes and whether it asks first
+#[derive(Debug, Clone, Default)]
+pub struct CleanOptions {
+    /// Drop wallpapers removed from Wallhaven from the list first
+    pub tombstones: bool,
+    /// Ask before removing each file
+    pub interactive: bool,
+    /// Only files last modified longer ago than this
+    pub older_than: Option<Duration>,
+    /// Only files whose name matches this glob (`*` and `?`)
+    pub pattern: Option<String>,
+}
+
+impl CleanOptions {
+    /// Whether the filters select `path`, modified at `modified` (unix seconds)
+    pub fn selects(&self, path: &Path, modified: u64) -> bool {
+        if let Some(older_than) = self.older_than {
+            if helper::unix_timestamp().saturating_sub(modified) < older_than.as_secs() {
+                return false;
+            }
+        }
+        match &self.pattern {
+            Some(pattern) => path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| glob_match(pattern, name)),
+            None => true,
+        }
+    }
+}
+
+/// An untracked file `clean` may remove
+#[derive(Debug, Clone)]
+pub struct Orphan {
+    pub path: PathBuf,
+    /// Wallpaper ID the file belongs to
+    pub id: String,
+    /// A processed variant rather than a downloaded original
+    pub variant: bool,
+    /// Bytes removing it frees, or `None` if its metadata could not be read
+    pub size: Option<u64>,
+}
+
+/// Bytes freed by removing `path` and its sidecar. A file hard-linked into the
+/// content store frees nothing, since the store keeps its data
+pub async fn reclaimable(path: &Path) -> Option<u64> {
+    let metadata = tokio::fs::metadata(path).await.ok()?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if metadata.nlink() > 1 {
+            return Some(0);
+        }
+    }
+    let sidecar = tokio::fs::metadata(sidecar::path_for(path))
+        .await
+        .map_or(0, |m| m.len());
+    Some(metadata.len() + sidecar)
+}
+
+/// The tracked wallpaper whose lock entry claims `path`, either by location or
+/// by checksum (e.g. a tracked file that was renamed and awaits repair)
+pub async fn tracked_owner(path: &Path, tracked: &[LockEntry]) -> Option<String> {
+    let location = path.to_string_lossy();
+    if let Some(entry) = tracked.iter().find(|e| e.image_location() == location) {
+        return Some(entry.image_id().to_string());
+    }
+    let mut algorithms: Vec<HashAlgorithm> = Vec::new();
+    for entry in tracked {
+        if !algorithms.contains(&entry.algorithm()) {
+            algorithms.push(entry.algorithm());
+        }
+    }
+    for algorithm in algorithms {
+        let Ok(hash) = helper::calculate_hash(path, algorithm).await else {
+            continue;
+        };
+        if let Some(entry) = tracked
+            .iter()
+            .find(|e| e.algorithm() == algorithm && e.image_hash() == hash)
+        {
+            return Some(entry.image_id().to_string());
+        }
+    }
+    None
+}
+
+/// Answer to a removal prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Answer {
+    Yes,
+    No,
+    All,
+}
+
+/// Ask about `question` until the answer is y, n or a. End of input counts as no
+pub fn confirm(input: &mut impl BufRead, question: &str) -> Answer {
+    loop {
+        print!("{} [y/n/a] ", question);
+        let _ = std::io::stdout().flush();
+        let mut line = String::new();
+        match input.read_line(&mut line) {
+            Ok(0) | Err(_) => return Answer::No,
+            Ok(_) => {}
+        }
+        match line.trim().to_ascii_lowercase().as_str() {
+            "y" | "yes" => return Answer::Yes,
+            "n" | "no" | "" => return Answer::No,
+            "a" | "all" => return Answer::All,
+            _ => say!("  ⚠ Please answer y, n or a (all remaining)"),
+        }
+    }
+}
+
+/// Match `name` against a glob where `*` is any run of characters and `?` one character
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            // Let the last `*` swallow one more character
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*.png", "abc123.png"));
+        assert!(glob_match("abc*", "abc123.png"));
+        assert!(glob_match("a?c*.p*g", "abc123.png"));
+        assert!(!glob_match("*.jpg", "abc123.png"));
+        assert!(!glob_match("abc", "abc123"));
+    }
+
+    #[test]
+    fn test_confirm_reads_answers() {
+        let mut input = "maybe\ny\n".as_bytes();
+        assert_eq!(confirm(&mut input, "Remove?"), Answer::Yes);
+        assert_eq!(confirm(&mut "ALL\n".as_bytes(), "Remove?"), Answer::All);
+        assert_eq!(confirm(&mut "".as_bytes(), "Remove?"), Answer::No);
+    }
+}
@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+use std::process::Stdio;
+use tokio::process::Command;
+
+use crate::{RustPaper, SyncOptions};
+
+/// When a sync may download; unmet conditions defer the downloads to the next run
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct SyncConditions {
+    /// Only download on AC power, as reported by UPower (default: false)
+    pub ac_power: bool,
+    /// Only download while the session is idle, as reported by logind (default: false)
+    pub idle: bool,
+    /// Don't download on a connection NetworkManager considers metered (default: false)
+    pub unmetered: bool,
+}
+
+/// Run a command and return its trimmed output, or `None` if it isn't
+/// installed or fails, e.g. on a system without the D-Bus service
+pub(crate) async fn output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// The value of a `busctl get-property` boolean, e.g. "b true"
+fn parse_bool(reply: &str) -> Option<bool> {
+    match reply.strip_prefix("b ")? {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+/// Whether NetworkManager's `Metered` property, e.g. "u 3", means metered.
+/// 1 is "yes" and 3 "guess yes"; 0 is unknown
+fn parse_metered(reply: &str) -> Option<bool> {
+    match reply.strip_prefix("u ")?.parse::<u32>().ok()? {
+        1 | 3 => Some(true),
+        _ => Some(false),
+    }
+}
+
+async fn on_battery() -> Option<bool> {
+    let reply = output(
+        "busctl",
+        &[
+            "get-property",
+            "org.freedesktop.UPower",
+            "/org/freedesktop/UPower",
+            "org.freedesktop.UPower",
+            "OnBattery",
+        ],
+    )
+    .await?;
+    parse_bool(&reply)
+}
+
+async fn session_idle() -> Option<bool> {
+    let mut args = vec!["show-session"];
+    let session = std::env::var("XDG_SESSION_ID").ok();
+    if let Some(session) = &session {
+        args.push(session);
+    }
+    args.extend(["--property=IdleHint", "--value"]);
+    match output("loginctl", &args).await?.as_str() {
+        "yes" => Some(true),
+        "no" => Some(false),
+        _ => None,
+    }
+}
+
+async fn metered() -> Option<bool> {
+    let reply = output(
+        "busctl",
+        &[
+            "get-property",
+            "org.freedesktop.NetworkManager",
+            "/org/freedesktop/NetworkManager",
+            "org.freedesktop.NetworkManager",
+            "Metered",
+        ],
+    )
+    .await?;
+    parse_metered(&reply)
+}
+
+impl RustPaper {
+    /// Why downloads should wait, if a configured sync condition isn't met.
+    /// A condition that can't be checked never holds a sync back, nor does
+    /// anything hold back a dry run
+    pub(crate) async fn deferral_reason(&self, options: &SyncOptions) -> Option<&'static str> {
+        if options.ignore_conditions || options.dry_run {
+            return None;
+        }
+        let conditions = &self.config.sync_conditions;
+        if conditions.ac_power && on_battery().await == Some(true) {
+            return Some("running on battery");
+        }
+        if conditions.unmetered && metered().await == Some(true) {
+            return Some("the connection is metered");
+        }
+        if conditions.idle && session_idle().await == Some(false) {
+            return Some("the session is in use");
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_busctl_replies() {
+        assert_eq!(parse_bool("b true"), Some(true));
+        assert_eq!(parse_bool("b false"), Some(false));
+        assert_eq!(parse_bool("u 1"), None);
+        assert_eq!(parse_metered("u 1"), Some(true));
+        assert_eq!(parse_metered("u 3"), Some(true));
+        assert_eq!(parse_metered("u 4"), Some(false));
+        assert_eq!(parse_metered("u 0"), Some(false));
+        assert_eq!(parse_metered("garbage"), None);
+    }
+}
@@ -1,38 +1,709 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
 use std::default::Default;
+use std::path::Path;
+use toml_edit::{DocumentMut, Item, TableLike};
 
+use crate::animated::AnimatedPolicy;
+use crate::api::{DownloadQuality, Tag};
+use crate::checks::DownloadChecks;
+use crate::conditions::SyncConditions;
+use crate::error::{Error, Result};
+use crate::follow::FollowConfig;
+use crate::hash::HashAlgorithm;
 use crate::helper;
+use crate::hooks::HooksConfig;
+use crate::output::say;
+use crate::process::{LockscreenConfig, ProcessStep, VariantLayout};
+use crate::source::SourceConfig;
+use crate::stale::StaleAction;
+use crate::storage::StorageConfig;
+use crate::RustPaper;
 
 /// Configuration for Rust Paper
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct Config {
     /// Directory where wallpapers will be saved
     pub save_location: String,
-    /// Whether to enable integrity checks using SHA256
+    /// Whether to enable integrity checks
     pub integrity: bool,
+    /// Checksum for new lock entries: "blake3" or "sha256" (default: blake3)
+    pub hash_algorithm: HashAlgorithm,
     /// Wallhaven API key for higher rate limits (optional)
     pub api_key: Option<String>,
+    /// Proxy for plain HTTP requests, e.g. "http://proxy:3128" (default: HTTP_PROXY)
+    pub http_proxy: Option<String>,
+    /// Proxy for HTTPS requests (default: HTTPS_PROXY)
+    pub https_proxy: Option<String>,
+    /// PEM file with extra CA certificates to trust, e.g. a corporate proxy's (optional)
+    pub ca_bundle: Option<String>,
     /// Maximum number of concurrent downloads (default: 10)
     pub max_concurrent_downloads: usize,
-    /// Request timeout in seconds (default: 30)
-    pub timeout: u64,
+    /// Seconds to wait for a connection to be established (default: 10)
+    pub connect_timeout: u64,
+    /// Total seconds allowed for an API request (default: 30)
+    #[serde(alias = "timeout")]
+    pub metadata_timeout: u64,
+    /// Seconds a download may go without receiving data before it is aborted (default: 60)
+    pub download_idle_timeout: u64,
     /// Number of retry attempts (default: 3)
     pub retry_count: u32,
+    /// Retries all downloads of one sync may use together; once spent, a
+    /// failed download is not retried (default: no limit)
+    pub retry_budget: Option<u32>,
+    /// Pause new downloads after this many fail in a row; 0 never pauses (default: 5)
+    pub circuit_breaker_failures: u32,
+    /// First pause after `circuit_breaker_failures`, doubled each time the
+    /// downloads keep failing until the sync gives up on the rest; "0s" gives up
+    /// at once (default: "30s")
+    pub circuit_breaker_cooldown: String,
+    /// Free space in MB a sync must leave on the save location's disk (default: 500)
+    pub min_free_space_mb: u64,
+    /// Download files at least this large in parallel ranges; 0 turns it off (default: 32)
+    pub chunked_download_min_mb: u64,
+    /// Number of parallel ranges for large files (default: 4)
+    pub download_chunks: usize,
+    /// Hosts serving the same image paths, tried in order when a download
+    /// keeps failing on both Wallhaven CDN hosts, e.g. ["https://mirror.example"]
+    /// (default: none)
+    pub download_mirrors: Vec<String>,
+    /// How long `search` reuses the results of a query, e.g. "10m"; "0s" turns it off (default: 10m)
+    pub search_cache_ttl: String,
+    /// Skip re-hashing files verified more recently than this, e.g. "7d" (optional)
+    pub recheck_after: Option<String>,
+    /// Write a `<id>.json` metadata sidecar next to every download (default: false)
+    pub sidecar: bool,
+    /// Keep images once under their checksum in this folder and link them into
+    /// the save location by ID (optional)
+    pub store_location: Option<String>,
+    /// Also look for wallpapers in subfolders of the save location, except
+    /// processing outputs (default: false)
+    pub scan_subfolders: bool,
+    /// Animated downloads: "keep", "still" for the first frame or "skip" (default: keep)
+    pub animated: AnimatedPolicy,
+    /// Download size: "original", or Wallhaven's "large" or "medium" thumbnail (default: original)
+    pub download_quality: DownloadQuality,
+    /// Hold downloads back on battery, on a metered connection or while
+    /// the session is in use (default: never)
+    pub sync_conditions: SyncConditions,
+    /// Extra places every downloaded wallpaper is copied to: "local" folders,
+    /// "webdav" shares or "s3" buckets (default: none)
+    pub storage_mirrors: Vec<StorageConfig>,
+    /// Threads decoding, validating and converting images; 0 uses one per core (default: 0)
+    pub decode_threads: usize,
+    /// Decode every download before saving it, to catch truncated or corrupt
+    /// files. The saved file is always the original bytes (default: true)
+    pub validate_decode: bool,
+    /// Checks a download must pass to be saved: a minimum size, minimum
+    /// entropy, maximum share of black pixels or a command (default: none)
+    pub download_checks: DownloadChecks,
+    /// Purities `add` and followed uploads may bring in, e.g. ["sfw"] (default: any)
+    pub allowed_purity: Option<Vec<String>>,
+    /// Where `audit-purity --move` puts wallpapers that are no longer allowed (optional)
+    pub quarantine_location: Option<String>,
+    /// Categories `add` and followed uploads may bring in, e.g. ["general", "anime"] (default: any)
+    pub allowed_categories: Option<Vec<String>>,
+    /// Only search for and download wallpapers at least as large as the
+    /// largest connected display (default: false)
+    pub auto_resolution: bool,
+    /// Skip wallpapers whose file is larger than this, judged from their
+    /// metadata before downloading (default: no limit)
+    pub max_file_size_mb: Option<u64>,
+    /// Wallpapers not set as the current wallpaper for this long, e.g. "90d",
+    /// are stale (default: never)
+    pub stale_after: Option<String>,
+    /// What a sync does with stale wallpapers: flag, archive or remove (default: flag)
+    pub stale_action: StaleAction,
+    /// Tags left out of searches and followed uploads, and refused by `add`,
+    /// e.g. ["anime"] (default: none)
+    pub exclude_tags: Vec<String>,
+    /// Per-source enable flag and sync interval, keyed by source name
+    pub sources: HashMap<String, SourceConfig>,
+    /// Post-processing steps applied to every downloaded wallpaper
+    pub process: Vec<ProcessStep>,
+    /// Where processing outputs go: "by_step" for a folder per step or
+    /// "by_id" for `variants/<id>/<step>.<ext>` (default: by_step)
+    pub variant_layout: VariantLayout,
+    /// Generate a lockscreen variant of every wallpaper (optional)
+    pub lockscreen: Option<LockscreenConfig>,
+    /// Commands run on download, sync completion and clean
+    pub hooks: HooksConfig,
+    /// Bearer token the `serve` API requires, unless --token is given (optional)
+    pub serve_token: Option<String>,
+    /// Shared secret that signs the sync manifest and that `pull` checks (optional)
+    pub manifest_key: Option<String>,
+    /// Search filters for uploads of followed users
+    pub follow: FollowConfig,
+    /// Search presets by name, on top of (and overriding) the built-in ones
+    pub presets: HashMap<String, SearchPreset>,
+}
+
+/// Named bundle of search parameters for `search --preset`; values use the
+/// same spelling as the matching search options
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct SearchPreset {
+    pub query: Option<String>,
+    pub colors: Option<String>,
+    pub categories: Option<String>,
+    pub purity: Option<String>,
+    pub sorting: Option<String>,
+    pub order: Option<String>,
+    pub toprange: Option<String>,
+    pub atleast: Option<String>,
+    pub resolutions: Option<String>,
+    pub ratios: Option<String>,
+}
+
+/// Presets available without any configuration
+fn builtin_preset(name: &str) -> Option<SearchPreset> {
+    let (query, sorting) = match name {
+        "autumn" => ("autumn", "FAVORITES"),
+        "winter" => ("winter +snow", "FAVORITES"),
+        "spring" => ("spring +flowers", "FAVORITES"),
+        "summer" => ("summer +beach", "FAVORITES"),
+        "halloween" => ("halloween", "FAVORITES"),
+        "christmas" => ("christmas", "FAVORITES"),
+        "cyberpunk" => ("cyberpunk", "TOPLIST"),
+        "minimal" => ("minimalism", "TOPLIST"),
+        "space" => ("space", "TOPLIST"),
+        "nature" => ("nature +landscape", "TOPLIST"),
+        _ => return None,
+    };
+    Some(SearchPreset {
+        query: Some(query.to_string()),
+        sorting: Some(sorting.to_string()),
+        toprange: (sorting == "TOPLIST").then(|| "1Y".to_string()),
+        ..SearchPreset::default()
+    })
 }
 
 impl Default for Config {
     fn default() -> Self {
-        let username = helper::get_home_location();
-
-        let save_location = format!("{}/Pictures/wall", username);
+        let save_location = Path::new(&helper::get_home_location())
+            .join("Pictures")
+            .join("wall")
+            .to_string_lossy()
+            .to_string();
 
         Config {
             save_location,
             integrity: true,
+            hash_algorithm: HashAlgorithm::default(),
             api_key: None,
+            http_proxy: None,
+            https_proxy: None,
+            ca_bundle: None,
             max_concurrent_downloads: 3,
-            timeout: 30,
+            connect_timeout: 10,
+            metadata_timeout: 30,
+            download_idle_timeout: 60,
             retry_count: 3,
+            retry_budget: None,
+            circuit_breaker_failures: 5,
+            circuit_breaker_cooldown: "30s".to_string(),
+            download_mirrors: Vec::new(),
+            chunked_download_min_mb: 32,
+            download_chunks: 4,
+            min_free_space_mb: 500,
+            recheck_after: None,
+            search_cache_ttl: "10m".to_string(),
+            sidecar: false,
+            scan_subfolders: false,
+            animated: AnimatedPolicy::default(),
+            download_quality: DownloadQuality::default(),
+            sync_conditions: SyncConditions::default(),
+            storage_mirrors: Vec::new(),
+            decode_threads: 0,
+            validate_decode: true,
+            download_checks: DownloadChecks::default(),
+            store_location: None,
+            allowed_purity: None,
+            allowed_categories: None,
+            quarantine_location: None,
+            auto_resolution: false,
+            max_file_size_mb: None,
+            stale_after: None,
+            stale_action: StaleAction::default(),
+            exclude_tags: Vec::new(),
+            sources: HashMap::new(),
+            process: Vec::new(),
+            variant_layout: VariantLayout::default(),
+            lockscreen: None,
+            serve_token: None,
+            manifest_key: None,
+            hooks: HooksConfig::default(),
+            follow: FollowConfig::default(),
+            presets: HashMap::new(),
+        }
+    }
+}
+
+impl Config {
+    /// The search preset called `name`, from the config or built in
+    pub fn preset(&self, name: &str) -> Option<SearchPreset> {
+        self.presets
+            .get(name)
+            .cloned()
+            .or_else(|| builtin_preset(&name.to_ascii_lowercase()))
+    }
+
+    /// All processing steps to run after a download, including the lockscreen variant
+    pub fn process_steps(&self) -> Vec<ProcessStep> {
+        let mut steps = self.process.clone();
+        if let Some(ref lockscreen) = self.lockscreen {
+            steps.push(lockscreen.to_step());
+        }
+        steps
+    }
+
+    /// Whether any purity or category guard is configured
+    pub fn has_content_guard(&self) -> bool {
+        self.allowed_purity.is_some() || self.allowed_categories.is_some()
+    }
+
+    /// Why a wallpaper with these tags is not allowed, if one of them is excluded
+    pub fn excluded_tag(&self, tags: &[Tag]) -> Option<String> {
+        let excluded = tags.iter().find(|tag| {
+            self.exclude_tags.iter().any(|excluded| {
+                excluded
+                    .trim()
+                    .trim_start_matches('-')
+                    .eq_ignore_ascii_case(&tag.name)
+            })
+        })?;
+        Some(format!("tag '{}' is excluded", excluded.name))
+    }
+
+    /// Why a wallpaper with this purity and category is not allowed, if it isn't
+    pub fn content_violation(&self, purity: &str, category: &str) -> Option<String> {
+        let allowed = |list: &Option<Vec<String>>, value: &str| {
+            list.as_ref()
+                .is_none_or(|list| list.iter().any(|v| v.eq_ignore_ascii_case(value)))
+        };
+        if !allowed(&self.allowed_purity, purity) {
+            return Some(format!("purity '{}' is not allowed", purity));
+        }
+        if !allowed(&self.allowed_categories, category) {
+            return Some(format!("category '{}' is not allowed", category));
+        }
+        None
+    }
+
+    /// Check the settings, reporting every problem at once
+    pub fn validate(&self) -> Result<()> {
+        let problems = self.problems();
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Config(problems.join("; ")))
+        }
+    }
+
+    /// Everything wrong with the settings: values that don't parse, folders
+    /// that can't be written to and options that conflict
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.max_concurrent_downloads == 0 {
+            problems.push("max_concurrent_downloads must be at least 1".to_string());
+        }
+        if self.retry_count == 0 {
+            problems.push("retry_count must be at least 1".to_string());
+        }
+        if self.download_chunks == 0 {
+            problems.push("download_chunks must be at least 1".to_string());
+        }
+        for (name, value) in [
+            ("connect_timeout", self.connect_timeout),
+            ("metadata_timeout", self.metadata_timeout),
+            ("download_idle_timeout", self.download_idle_timeout),
+        ] {
+            if value == 0 {
+                problems.push(format!("{} must be at least 1 second", name));
+            }
+        }
+
+        let mut durations = vec![
+            ("search_cache_ttl".to_string(), Some(&self.search_cache_ttl)),
+            ("recheck_after".to_string(), self.recheck_after.as_ref()),
+            (
+                "circuit_breaker_cooldown".to_string(),
+                Some(&self.circuit_breaker_cooldown),
+            ),
+            ("stale_after".to_string(), self.stale_after.as_ref()),
+        ];
+        for (name, source) in &self.sources {
+            durations.push((
+                format!("sources.{}.interval", name),
+                source.interval.as_ref(),
+            ));
+        }
+        for (name, value) in durations {
+            if let Some(Err(e)) = value.map(|value| helper::parse_duration(value)) {
+                problems.push(format!("{}: {}", name, e));
+            }
+        }
+        for step in self.process_steps() {
+            if let Err(e) = step.validate() {
+                problems.push(format!("process step '{}': {:#}", step.name, e));
+            }
+        }
+        if let Err(e) = self.download_checks.validate() {
+            problems.push(format!("download_checks: {:#}", e));
+        }
+        for (name, values, known) in [
+            (
+                "allowed_purity",
+                &self.allowed_purity,
+                &["sfw", "sketchy", "nsfw"][..],
+            ),
+            (
+                "allowed_categories",
+                &self.allowed_categories,
+                &["general", "anime", "people"][..],
+            ),
+        ] {
+            for value in values.iter().flatten() {
+                if !known.iter().any(|known| known.eq_ignore_ascii_case(value)) {
+                    problems.push(format!(
+                        "{}: unknown value '{}' (expected {})",
+                        name,
+                        value,
+                        known.join(", ")
+                    ));
+                }
+            }
+        }
+
+        if self.save_location.trim().is_empty() {
+            problems.push("save_location is empty".to_string());
+        }
+        let expanded = helper::expand_path(&self.save_location);
+        let folders = [
+            ("save_location", Some(&expanded)),
+            ("store_location", self.store_location.as_ref()),
+            ("quarantine_location", self.quarantine_location.as_ref()),
+        ];
+        for (name, folder) in folders {
+            let Some(folder) = folder.filter(|folder| !folder.trim().is_empty()) else {
+                continue;
+            };
+            if let Err(e) = check_writable(Path::new(folder)) {
+                problems.push(format!("{} ({}) is not writable: {}", name, folder, e));
+            }
+        }
+        let save_location = Path::new(&expanded);
+        if self.store_location.as_deref().map(Path::new) == Some(save_location) {
+            problems.push("store_location must not be the save_location".to_string());
+        }
+        if let Some(quarantine) = self.quarantine_location.as_deref() {
+            if Path::new(quarantine).starts_with(save_location) {
+                problems.push(
+                    "quarantine_location must not be inside the save_location, where it would be synced"
+                        .to_string(),
+                );
+            }
+        }
+        if self.stale_action != StaleAction::Flag && self.stale_after.is_none() {
+            problems.push("stale_action needs stale_after to be set".to_string());
+        }
+        problems
+    }
+
+    /// This config with `patch` applied, validated
+    pub fn apply(&self, patch: &ConfigPatch) -> Result<Config> {
+        let mut merged = serde_json::to_value(self)?;
+        for (key, value) in &patch.values {
+            let mut segments = key.split('.');
+            let first = segments.next().unwrap_or_default();
+            if merged.get(first).is_none() {
+                return Err(Error::Config(format!("Unknown setting '{}'", key)));
+            }
+            let mut target = &mut merged[first];
+            for segment in segments {
+                // Fills in unset sections and new map entries, e.g. sources.follows
+                if target.is_null() {
+                    *target = Value::Object(Default::default());
+                }
+                target = match target {
+                    Value::Object(map) => map.entry(segment).or_insert(Value::Null),
+                    _ => return Err(Error::Config(format!("Unknown setting '{}'", key))),
+                };
+            }
+            *target = value.clone();
+        }
+        let config: Config = serde_json::from_value(merged)
+            .map_err(|e| Error::Config(format!("Invalid setting: {}", e)))?;
+        // Unknown keys inside a section are dropped by serde; catch them here
+        let applied = serde_json::to_value(&config)?;
+        for (key, _) in &patch.values {
+            if key
+                .split('.')
+                .try_fold(&applied, |value, segment| value.get(segment))
+                .is_none()
+            {
+                return Err(Error::Config(format!("Unknown setting '{}'", key)));
+            }
+        }
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+/// Whether files can be created in `folder`, or in its closest existing
+/// parent when it doesn't exist yet. Only the permissions are looked at;
+/// nothing is written
+fn check_writable(folder: &Path) -> std::io::Result<()> {
+    let existing = folder
+        .ancestors()
+        .find(|ancestor| ancestor.is_dir())
+        .unwrap_or(Path::new("."));
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        let path = std::ffi::CString::new(existing.as_os_str().as_bytes())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        // SAFETY: `path` is a valid NUL-terminated string that outlives the call
+        if unsafe { libc::access(path.as_ptr(), libc::W_OK) } != 0 {
+            return Err(std::io::Error::last_os_error());
         }
     }
+    #[cfg(not(unix))]
+    if std::fs::metadata(existing)?.permissions().readonly() {
+        return Err(std::io::ErrorKind::PermissionDenied.into());
+    }
+    Ok(())
+}
+
+/// A partial update of the config, naming the settings to change. Nested
+/// settings use dots, e.g. "sources.follows.interval"
+#[derive(Debug, Clone, Default)]
+pub struct ConfigPatch {
+    values: Vec<(String, Value)>,
+}
+
+impl ConfigPatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set `key` to `value`
+    pub fn set(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.values.push((key.to_string(), value.into()));
+        self
+    }
+
+    /// Set `key` from command-line text: numbers, true/false, null, arrays
+    /// and quoted strings are read as JSON, anything else as a plain string
+    pub fn set_str(self, key: &str, text: &str) -> Self {
+        let value = serde_json::from_str(text).unwrap_or_else(|_| Value::String(text.to_string()));
+        self.set(key, value)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+/// `original` with the settings `patch` names taken from `updated`. Every
+/// other line, comments included, is left as it was
+fn edit_toml(
+    original: &str,
+    updated: &Config,
+    patch: &ConfigPatch,
+) -> std::result::Result<String, String> {
+    let mut document = original.parse::<DocumentMut>().map_err(|e| e.to_string())?;
+    let values = toml_edit::ser::to_document(updated).map_err(|e| e.to_string())?;
+    for (key, _) in &patch.values {
+        let segments: Vec<&str> = key.split('.').collect();
+        let Some((last, parents)) = segments.split_last() else {
+            continue;
+        };
+        let value = segments
+            .iter()
+            .try_fold(values.as_item(), |item, segment| item.get(segment))
+            .cloned();
+        let mut table: &mut dyn TableLike = document.as_table_mut();
+        for segment in parents {
+            table = table
+                .entry(segment)
+                .or_insert_with(|| {
+                    let mut parent = toml_edit::Table::new();
+                    parent.set_implicit(true);
+                    Item::Table(parent)
+                })
+                .as_table_like_mut()
+                .ok_or_else(|| format!("'{}' is not a table", segment))?;
+        }
+        match value {
+            Some(mut item) => {
+                // Keep a comment after the old value
+                if let (Some(new), Some(old)) = (
+                    item.as_value_mut(),
+                    table.get(last).and_then(Item::as_value),
+                ) {
+                    *new.decor_mut() = old.decor().clone();
+                }
+                table.insert(last, item);
+            }
+            // Unset, which TOML leaves out
+            None => {
+                table.remove(last);
+            }
+        }
+    }
+    Ok(document.to_string())
+}
+
+impl RustPaper {
+    /// Apply `patch` to the config file and to this instance. Nothing is
+    /// written unless the updated config is valid
+    pub async fn update_config(&mut self, patch: &ConfigPatch) -> Result<()> {
+        let Some(path) = self.config_file.clone() else {
+            return Err(Error::Config(
+                "No config file to update; the config was passed in directly".to_string(),
+            ));
+        };
+        let stored: Config = confy::load_path(&path)
+            .map_err(|e| Error::Config(format!("   Failed to load {}: {}", path.display(), e)))?;
+        let updated = stored.apply(patch)?;
+        self.config = self.config.apply(patch)?;
+        let text = tokio::fs::read_to_string(&path).await?;
+        let edited = edit_toml(&text, &updated, patch)
+            .map_err(|e| Error::Config(format!("   Failed to edit {}: {}", path.display(), e)))?;
+        tokio::fs::write(&path, edited).await?;
+        let keys: Vec<&str> = patch.values.iter().map(|(key, _)| key.as_str()).collect();
+        say!("  ✓ Updated {} in {}", keys.join(", "), path.display());
+        Ok(())
+    }
+
+    /// Validate the loaded config, printing the outcome
+    pub fn check_config_file(&self) -> Result<()> {
+        self.config.validate()?;
+        say!("  ✓ The config is valid");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_violation() {
+        let mut config = Config::default();
+        assert!(!config.has_content_guard());
+        assert_eq!(config.content_violation("nsfw", "people"), None);
+
+        config.allowed_purity = Some(vec!["sfw".to_string()]);
+        config.allowed_categories = Some(vec!["general".to_string(), "anime".to_string()]);
+        assert_eq!(config.content_violation("SFW", "anime"), None);
+        assert!(config.content_violation("sketchy", "anime").is_some());
+        assert!(config.content_violation("sfw", "people").is_some());
+    }
+
+    #[test]
+    fn test_excluded_tag() {
+        let tag = |name: &str| Tag {
+            name: name.to_string(),
+            ..Tag::default()
+        };
+        let mut config = Config::default();
+        assert_eq!(config.excluded_tag(&[tag("anime")]), None);
+        config.exclude_tags = vec!["-Anime".to_string()];
+        assert_eq!(config.excluded_tag(&[tag("city")]), None);
+        assert!(config.excluded_tag(&[tag("city"), tag("anime")]).is_some());
+    }
+
+    #[test]
+    fn test_config_presets_override_builtin_ones() {
+        let mut config = Config::default();
+        assert_eq!(
+            config.preset("Autumn").unwrap().query.as_deref(),
+            Some("autumn")
+        );
+        assert!(config.preset("unknown").is_none());
+
+        let mine = SearchPreset {
+            query: Some("autumn +forest".to_string()),
+            ..SearchPreset::default()
+        };
+        config.presets.insert("autumn".to_string(), mine.clone());
+        assert_eq!(config.preset("autumn"), Some(mine));
+    }
+
+    #[test]
+    fn test_timeout_still_sets_metadata_timeout() {
+        let config: Config = serde_json::from_str(r#"{"timeout": 45}"#).unwrap();
+        assert_eq!(config.metadata_timeout, 45);
+        assert_eq!(config.download_idle_timeout, 60);
+    }
+
+    #[test]
+    fn test_validate_and_apply_patch() {
+        let config = Config {
+            save_location: std::env::temp_dir()
+                .join("rust-paper-validate")
+                .to_string_lossy()
+                .to_string(),
+            ..Config::default()
+        };
+        config.validate().unwrap();
+
+        let patch = ConfigPatch::new()
+            .set_str("max_concurrent_downloads", "4")
+            .set_str("exclude_tags", r#"["anime"]"#)
+            .set_str("sources.follows.interval", "12h")
+            .set("auto_resolution", true);
+        let updated = config.apply(&patch).unwrap();
+        assert_eq!(updated.max_concurrent_downloads, 4);
+        assert_eq!(updated.exclude_tags, ["anime"]);
+        assert_eq!(updated.sources["follows"].interval.as_deref(), Some("12h"));
+        assert!(updated.auto_resolution);
+
+        for bad in [
+            ConfigPatch::new().set_str("max_concurent_downloads", "4"),
+            ConfigPatch::new().set_str("follow.typo", "1"),
+            ConfigPatch::new().set_str("max_concurrent_downloads", "many"),
+            ConfigPatch::new().set_str("recheck_after", "soon"),
+            ConfigPatch::new().set_str("stale_action", "remove"),
+        ] {
+            assert!(
+                matches!(config.apply(&bad), Err(Error::Config(_))),
+                "{:?}",
+                bad
+            );
+        }
+        let mut conflicting = config.clone();
+        conflicting.quarantine_location = Some(format!("{}/quarantine", config.save_location));
+        assert!(conflicting.validate().is_err());
+    }
+
+    #[test]
+    fn test_edit_toml_keeps_comments() {
+        let original = r#"# Where the wallpapers go
+save_location = "/tmp/rust-paper-edit"
+max_concurrent_downloads = 2 # keep it low
+stale_after = "90d"
+
+[follow]
+# SFW only
+purity = "100"
+"#;
+        let patch = ConfigPatch::new()
+            .set_str("max_concurrent_downloads", "4")
+            .set_str("sources.follows.interval", "12h")
+            .set_str("stale_after", "null");
+        let updated = Config::default().apply(&patch).unwrap();
+        let edited = edit_toml(original, &updated, &patch).unwrap();
+        assert!(edited.starts_with("# Where the wallpapers go\n"));
+        assert!(edited.contains("\nmax_concurrent_downloads = 4 # keep it low\n"));
+        assert!(edited.contains("[follow]\n# SFW only\npurity = \"100\"\n"));
+        assert!(edited.contains("[sources.follows]\ninterval = \"12h\"\n"));
+        assert!(!edited.contains("[sources]\n"));
+        assert!(edited.contains("save_location = \"/tmp/rust-paper-edit\"\n"));
+        assert!(!edited.contains("stale_after"));
+    }
 }
@@ -0,0 +1,136 @@
+use anyhow::{anyhow, Context};
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::error::{Error, Result};
+use crate::output::say;
+use crate::{api, helper, import, RustPaper, WallhavenWallpaper, WallpaperId, WALLHAVEN_BASE};
+
+/// Clipboard tools tried in order, with their arguments
+const CLIPBOARD_COMMANDS: &[(&str, &[&str])] = &[
+    ("wl-copy", &[]),
+    ("xclip", &["-selection", "clipboard"]),
+    ("xsel", &["--clipboard", "--input"]),
+    ("pbcopy", &[]),
+    ("clip.exe", &[]),
+];
+
+/// Attribution text crediting the uploader and the original source
+pub fn attribution(data: &WallhavenWallpaper) -> String {
+    let link = if data.short_url.is_empty() {
+        format!("{}/{}", WALLHAVEN_BASE, data.id)
+    } else {
+        data.short_url.clone()
+    };
+    let mut text = match &data.uploader {
+        Some(uploader) => format!("Wallpaper by {} on Wallhaven: {}", uploader.username, link),
+        None => format!("Wallpaper from Wallhaven: {}", link),
+    };
+    if !data.source.is_empty() {
+        text.push_str(&format!("\nOriginal source: {}", data.source));
+    }
+    text
+}
+
+/// Put `text` on the clipboard with the first tool that is installed; returns its name
+async fn copy_to_clipboard(text: &str) -> anyhow::Result<&'static str> {
+    for (program, args) in CLIPBOARD_COMMANDS {
+        let mut child = match Command::new(program)
+            .args(*args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e).with_context(|| format!("Failed to run {}", program)),
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes()).await?;
+        }
+        let status = child.wait().await?;
+        if !status.success() {
+            return Err(anyhow!("{} exited with {}", program, status));
+        }
+        return Ok(program);
+    }
+    Err(anyhow!(
+        "No clipboard tool found; install wl-clipboard, xclip or xsel"
+    ))
+}
+
+impl RustPaper {
+    /// Attribution text for a wallpaper, from cached metadata when it names the uploader
+    pub async fn attribution(&self, id: &str) -> Result<String> {
+        if id == "current" {
+            return Err(Error::InvalidInput(
+                "rust-paper does not set the desktop wallpaper, so it has no current one; pass a wallpaper ID".to_string(),
+            ));
+        }
+        if import::is_local_id(id) {
+            return Err(Error::InvalidInput(format!(
+                "{} is a local import and has no Wallhaven attribution",
+                id
+            )));
+        }
+        let id = WallpaperId::parse(id)?;
+
+        let cached = helper::read_cached_metadata(&self.metadata_cache_location, id.as_str())
+            .await
+            .and_then(|body| api::WallpaperInfoResponse::parse(&body).ok());
+        let data = match cached {
+            // Search results are cached without the uploader
+            Some(data) if data.uploader.is_some() || self.offline => data,
+            _ if self.offline => {
+                return Err(Error::NotFound(format!(
+                    "cached metadata for '{}' (offline)",
+                    id
+                )))
+            }
+            _ => self.fetch_metadata(&id).await?,
+        };
+
+        Ok(attribution(&data))
+    }
+
+    /// Print attribution for a wallpaper, copying it to the clipboard with `copy`
+    pub async fn credit(&self, id: &str, copy: bool) -> Result<()> {
+        let text = self.attribution(id).await?;
+        say!("{}", text);
+        if copy {
+            let tool = copy_to_clipboard(&text).await?;
+            say!("   Copied to the clipboard with {}", tool);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Uploader;
+
+    #[test]
+    fn test_attribution_credits_uploader_and_source() {
+        let mut data: WallhavenWallpaper = serde_json::from_str(
+            r#"{"id":"7pmgv9","short_url":"https://whvn.cc/7pmgv9","source":"https://example.com/art"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            attribution(&data),
+            "Wallpaper from Wallhaven: https://whvn.cc/7pmgv9\nOriginal source: https://example.com/art"
+        );
+
+        data.uploader = Some(Uploader {
+            username: "artist".to_string(),
+            ..Default::default()
+        });
+        data.source.clear();
+        assert_eq!(
+            attribution(&data),
+            "Wallpaper by artist on Wallhaven: https://whvn.cc/7pmgv9"
+        );
+    }
+}
@@ -0,0 +1,254 @@
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+use crate::output::say;
+use crate::{build_file_map, helper, pick, RustPaper};
+
+/// Monitor key for a wallpaper set without `--monitor`, shown on every monitor
+pub const ALL_MONITORS: &str = "*";
+
+/// The wallpaper shown on a monitor
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct CurrentWallpaper {
+    pub id: String,
+    pub path: Option<String>,
+    /// Unix seconds
+    pub set_at: u64,
+}
+
+/// What each monitor shows, as recorded by `current --set`
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct CurrentState {
+    monitors: BTreeMap<String, CurrentWallpaper>,
+    /// When each wallpaper was last set, kept after it is replaced
+    #[serde(default)]
+    last_set: BTreeMap<String, u64>,
+}
+
+impl CurrentState {
+    /// Load the state from disk, starting empty if the file is missing
+    pub async fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        match tokio::fs::read_to_string(path.as_ref()).await {
+            Ok(contents) => {
+                serde_json::from_str(&contents).context("   Failed to parse current wallpapers")
+            }
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Save the state to disk
+    pub async fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let json = serde_json::to_string_pretty(&self)
+            .context("   Failed to serialize current wallpapers")?;
+        tokio::fs::write(path.as_ref(), json)
+            .await
+            .context("   Failed to write current wallpapers")
+    }
+
+    /// Record `wallpaper` on `monitor`, or on every monitor when `None`
+    pub fn set(&mut self, monitor: Option<&str>, wallpaper: CurrentWallpaper) {
+        self.last_set.insert(wallpaper.id.clone(), wallpaper.set_at);
+        match monitor {
+            Some(monitor) => {
+                self.monitors.insert(monitor.to_string(), wallpaper);
+            }
+            None => {
+                self.monitors.clear();
+                self.monitors.insert(ALL_MONITORS.to_string(), wallpaper);
+            }
+        }
+    }
+
+    /// The wallpaper on `monitor`, falling back to one set for every monitor
+    pub fn get(&self, monitor: &str) -> Option<&CurrentWallpaper> {
+        self.monitors
+            .get(monitor)
+            .or_else(|| self.monitors.get(ALL_MONITORS))
+    }
+
+    /// The most recently set wallpaper on any monitor
+    pub fn latest(&self) -> Option<&CurrentWallpaper> {
+        self.monitors
+            .values()
+            .max_by_key(|wallpaper| wallpaper.set_at)
+    }
+
+    /// When `id` was last set on any monitor
+    pub fn last_set(&self, id: &str) -> Option<u64> {
+        self.last_set.get(id).copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.monitors.is_empty()
+    }
+}
+
+impl RustPaper {
+    /// Record that the tracked wallpaper `id_or_path` is now shown, so
+    /// `current` and `info` can report it without asking the compositor
+    pub async fn set_current(&self, id_or_path: &str, monitor: Option<&str>) -> Result<()> {
+        let as_path = Path::new(id_or_path);
+        if !as_path.is_file() {
+            return self.set_current_id(id_or_path, monitor).await;
+        }
+        let id = as_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        self.record_current(&id, id_or_path, Some(as_path.to_path_buf()), monitor)
+            .await
+    }
+
+    /// Like `set_current`, but only for a wallpaper ID or URL, never a file
+    /// path, for callers such as `serve` that mustn't touch arbitrary paths
+    pub async fn set_current_id(&self, id_or_url: &str, monitor: Option<&str>) -> Result<()> {
+        let id = helper::parse_wallhaven_url(id_or_url);
+        self.record_current(&id, id_or_url, None, monitor).await
+    }
+
+    async fn record_current(
+        &self,
+        id: &str,
+        given: &str,
+        path: Option<PathBuf>,
+        monitor: Option<&str>,
+    ) -> Result<()> {
+        if !self.wallpapers.iter().any(|tracked| tracked == id) {
+            return Err(Error::InvalidInput(format!(
+                "'{}' is not a tracked wallpaper",
+                given
+            )));
+        }
+        let path = match path {
+            Some(path) => Some(path),
+            None => build_file_map(&self.config).await?.remove(id),
+        };
+        let wallpaper = CurrentWallpaper {
+            id: id.to_string(),
+            path: path.map(|path| path.to_string_lossy().to_string()),
+            set_at: helper::unix_timestamp(),
+        };
+        let mut state = CurrentState::load(&self.current_file_location).await?;
+        state.set(monitor, wallpaper);
+        state.save(&self.current_file_location).await?;
+        say!(
+            "  ✓ {} is the current wallpaper on {}",
+            id,
+            monitor.unwrap_or("every monitor")
+        );
+        Ok(())
+    }
+
+    /// Record a random downloaded wallpaper as shown on `monitor`, other than
+    /// the one there now when there is a choice. Returns its ID
+    pub async fn set_random(&self, monitor: Option<&str>) -> Result<String> {
+        let file_map = build_file_map(&self.config).await?;
+        let shown = self
+            .current_wallpaper(monitor)
+            .await?
+            .map(|current| current.id);
+        let mut downloaded: Vec<&String> = self
+            .wallpapers
+            .iter()
+            .filter(|id| file_map.contains_key(*id))
+            .collect();
+        if downloaded.len() > 1 {
+            downloaded.retain(|id| Some(id.as_str()) != shown.as_deref());
+        }
+        let id = pick::random_index(downloaded.len())
+            .map(|idx| downloaded[idx].clone())
+            .ok_or_else(|| Error::NotFound("downloaded wallpaper to pick from".to_string()))?;
+        self.set_current_id(&id, monitor).await?;
+        Ok(id)
+    }
+
+    /// The current wallpaper on `monitor`, or the most recently set one
+    pub async fn current_wallpaper(
+        &self,
+        monitor: Option<&str>,
+    ) -> Result<Option<CurrentWallpaper>> {
+        let state = CurrentState::load(&self.current_file_location).await?;
+        Ok(match monitor {
+            Some(monitor) => state.get(monitor),
+            None => state.latest(),
+        }
+        .cloned())
+    }
+
+    /// ID of the most recently set wallpaper, for commands that default to it
+    pub async fn current_id(&self) -> Result<String> {
+        self.current_wallpaper(None)
+            .await?
+            .map(|current| current.id)
+            .ok_or_else(|| {
+                Error::NotFound(
+                    "current wallpaper; record one with `rust-paper current --set <id>`"
+                        .to_string(),
+                )
+            })
+    }
+
+    /// Print the current wallpaper of each monitor
+    pub async fn current(&self, json: bool) -> Result<()> {
+        let state = CurrentState::load(&self.current_file_location).await?;
+        if json {
+            // Machine-readable, so printed as is even with --plain
+            println!("{}", serde_json::to_string_pretty(&state.monitors)?);
+            return Ok(());
+        }
+        if state.is_empty() {
+            say!("   No current wallpaper recorded; use `rust-paper current --set <id>`");
+            return Ok(());
+        }
+        for (monitor, wallpaper) in &state.monitors {
+            let monitor = if monitor == ALL_MONITORS {
+                "all monitors"
+            } else {
+                monitor
+            };
+            say!(
+                "  {}: {} (since {} UTC)",
+                monitor,
+                wallpaper.id,
+                helper::format_timestamp(wallpaper.set_at)
+            );
+            if let Some(path) = &wallpaper.path {
+                say!("    {}", path);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wallpaper(id: &str, set_at: u64) -> CurrentWallpaper {
+        CurrentWallpaper {
+            id: id.to_string(),
+            path: None,
+            set_at,
+        }
+    }
+
+    #[test]
+    fn test_current_state_per_monitor() {
+        let mut state = CurrentState::default();
+        state.set(None, wallpaper("abc123", 1));
+        assert_eq!(state.get("DP-1").unwrap().id, "abc123");
+
+        state.set(Some("DP-1"), wallpaper("def456", 2));
+        assert_eq!(state.get("DP-1").unwrap().id, "def456");
+        assert_eq!(state.get("HDMI-1").unwrap().id, "abc123");
+        assert_eq!(state.latest().unwrap().id, "def456");
+
+        state.set(None, wallpaper("ghi789", 3));
+        assert_eq!(state.get("DP-1").unwrap().id, "ghi789");
+        assert_eq!(state.last_set("abc123"), Some(1));
+        assert_eq!(state.last_set("zzz999"), None);
+    }
+}
@@ -0,0 +1,61 @@
+use anyhow::{anyhow, Context, Result};
+use std::panic::AssertUnwindSafe;
+use tokio::sync::{oneshot, Semaphore};
+
+/// Threads for image decoding, validation and conversion, kept apart from the
+/// async runtime so CPU-heavy images can't stall network tasks
+pub(crate) struct DecodePool {
+    pool: rayon::ThreadPool,
+    /// Bounds the queued jobs, so a fast network can't pile up decoded images
+    permits: Semaphore,
+}
+
+impl DecodePool {
+    /// A pool of `threads` threads, or one per core for 0
+    pub fn new(threads: usize) -> crate::Result<Self> {
+        let threads = match threads {
+            0 => std::thread::available_parallelism().map_or(4, |n| n.get()),
+            threads => threads,
+        };
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("rust-paper-decode-{}", i))
+            .build()
+            .map_err(|e| anyhow!("Failed to start the image decode threads: {}", e))?;
+        Ok(DecodePool {
+            pool,
+            permits: Semaphore::new(threads * 2),
+        })
+    }
+
+    /// Run `work` on the pool and wait for its result, waiting first while
+    /// the pool already has a full queue
+    pub async fn run<T: Send + 'static>(
+        &self,
+        work: impl FnOnce() -> T + Send + 'static,
+    ) -> Result<T> {
+        let _permit = self.permits.acquire().await?;
+        let (tx, rx) = oneshot::channel();
+        self.pool.spawn(move || {
+            // A panic would otherwise abort the process from inside rayon
+            let _ = tx.send(std::panic::catch_unwind(AssertUnwindSafe(work)));
+        });
+        rx.await
+            .context("Image task was dropped")?
+            .map_err(|_| anyhow!("Image task panicked"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_run_returns_results_and_survives_panics() {
+        let pool = DecodePool::new(2).unwrap();
+        assert_eq!(pool.run(|| 2 + 2).await.unwrap(), 4);
+        assert!(pool.run(|| -> i32 { panic!("bad image") }).await.is_err());
+        let results = futures::future::join_all((0..32).map(|i| pool.run(move || i * 2))).await;
+        assert_eq!(results.into_iter().map(Result::unwrap).sum::<i32>(), 992);
+    }
+}
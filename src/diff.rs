@@ -0,0 +1,294 @@
+use image::imageops::FilterType;
+use image::DynamicImage;
+use indicatif::HumanBytes;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+use crate::output::{say, say_err};
+use crate::{api, find_existing_image, helper, import, sidecar, RustPaper, WallpaperId};
+
+/// Perceptual hash distance (out of 64) up to which two images count as
+/// near-duplicates
+const NEAR_DUPLICATE_DISTANCE: u32 = 10;
+
+/// One side of a comparison: a tracked wallpaper or a file on disk
+#[derive(Debug, Default)]
+struct Side {
+    /// Wallpaper ID, or the file name of a local file
+    name: String,
+    /// Width and height in pixels
+    size: Option<(u32, u32)>,
+    file_size: Option<u64>,
+    hash: Option<String>,
+    /// Perceptual hash of the image
+    dhash: Option<u64>,
+    /// `None` when the tags are unknown
+    tags: Option<Vec<String>>,
+}
+
+/// Difference hash: one bit per neighbouring pair of pixels in a 9x8
+/// grayscale thumbnail, set where the left one is brighter. Resized or
+/// recompressed copies of an image hash the same or nearly so
+fn perceptual_hash(img: &DynamicImage) -> u64 {
+    let small = img.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    let mut hash = 0;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+    hash
+}
+
+/// Bits that differ between two perceptual hashes, from 0 (alike) to 64
+fn distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// Tags in both lists, only in `a` and only in `b`
+fn split_tags<'a>(a: &'a [String], b: &'a [String]) -> [Vec<&'a str>; 3] {
+    let a: BTreeSet<&str> = a.iter().map(String::as_str).collect();
+    let b: BTreeSet<&str> = b.iter().map(String::as_str).collect();
+    [
+        a.intersection(&b).copied().collect(),
+        a.difference(&b).copied().collect(),
+        b.difference(&a).copied().collect(),
+    ]
+}
+
+/// Tags listed in the sidecar next to `path`, if it has one
+async fn sidecar_tags(path: &Path) -> Option<Vec<String>> {
+    let body = tokio::fs::read_to_string(sidecar::path_for(path))
+        .await
+        .ok()?;
+    let json: serde_json::Value = serde_json::from_str(&body).ok()?;
+    Some(
+        json.get("tags")?
+            .as_array()?
+            .iter()
+            .filter_map(|tag| tag.as_str().map(str::to_string))
+            .collect(),
+    )
+}
+
+impl RustPaper {
+    /// Measure the image at `path`: its size on disk, checksum, resolution
+    /// and perceptual hash
+    async fn measure(&self, side: &mut Side, path: PathBuf) -> Result<()> {
+        side.file_size = Some(tokio::fs::metadata(&path).await?.len());
+        side.hash = Some(helper::calculate_hash(&path, self.config.hash_algorithm).await?);
+        let decoded = self
+            .decode_pool
+            .run(move || {
+                image::open(&path).map(|img| ((img.width(), img.height()), perceptual_hash(&img)))
+            })
+            .await?;
+        match decoded {
+            Ok((size, dhash)) => {
+                side.size = Some(size);
+                side.dhash = Some(dhash);
+            }
+            Err(e) => say_err!("  ⚠ Could not decode {}: {}", side.name, e),
+        }
+        Ok(())
+    }
+
+    /// A tracked wallpaper, from its downloaded file and metadata
+    async fn wallpaper_side(&self, id: &str) -> Result<Side> {
+        let id = if import::is_local_id(id) {
+            id.to_string()
+        } else {
+            WallpaperId::parse(id)?.as_str().to_string()
+        };
+        let mut side = Side {
+            name: id.clone(),
+            ..Default::default()
+        };
+        let path = find_existing_image(&self.config, &id).await?;
+        if let Some(path) = &path {
+            self.measure(&mut side, path.clone()).await?;
+            side.tags = sidecar_tags(path).await.filter(|tags| !tags.is_empty());
+        }
+
+        if !import::is_local_id(&id) && side.tags.is_none() {
+            let cached = helper::read_cached_metadata(&self.metadata_cache_location, &id)
+                .await
+                .and_then(|body| api::WallpaperInfoResponse::parse(&body).ok());
+            let data = match cached {
+                // Search results are cached without tags
+                Some(data) if !data.tags.is_empty() || self.offline => Some(data),
+                _ if self.offline => None,
+                _ => match self.fetch_metadata(&WallpaperId::parse(&id)?).await {
+                    Ok(data) => Some(data),
+                    Err(e) if path.is_some() => {
+                        say_err!("  ⚠ No metadata for {}: {}", id, e);
+                        None
+                    }
+                    Err(e) => return Err(e.into()),
+                },
+            };
+            if let Some(data) = data {
+                side.tags = Some(data.tags.iter().map(|tag| tag.name.clone()).collect());
+                if path.is_none() {
+                    side.file_size = u64::try_from(data.file_size).ok();
+                    side.size = data
+                        .resolution
+                        .split_once('x')
+                        .and_then(|(w, h)| Some((w.trim().parse().ok()?, h.trim().parse().ok()?)));
+                }
+            }
+        }
+
+        if path.is_none() && side.tags.is_none() {
+            return Err(Error::NotFound(format!(
+                "downloaded image or metadata for '{}'",
+                id
+            )));
+        }
+        Ok(side)
+    }
+
+    /// A file on disk, tracked or not
+    async fn file_side(&self, path: &Path) -> Result<Side> {
+        if !path.is_file() {
+            return Err(Error::NotFound(path.display().to_string()));
+        }
+        let mut side = Side {
+            name: path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.display().to_string()),
+            ..Default::default()
+        };
+        self.measure(&mut side, path.to_path_buf()).await?;
+        side.tags = sidecar_tags(path).await;
+        Ok(side)
+    }
+
+    /// Compare two wallpapers side by side: resolution, file size, checksum,
+    /// how alike the images look and their tags. Each is a tracked wallpaper
+    /// from `ids` or a file from `local`, two in total
+    pub async fn diff(&self, ids: &[String], local: &[PathBuf]) -> Result<()> {
+        if ids.len() + local.len() != 2 {
+            return Err(Error::InvalidInput(
+                "diff compares two wallpapers: give two IDs, an ID and --local <file>, or --local twice"
+                    .to_string(),
+            ));
+        }
+        let mut sides = Vec::new();
+        for id in ids {
+            sides.push(self.wallpaper_side(id).await?);
+        }
+        for path in local {
+            sides.push(self.file_side(path).await?);
+        }
+        let (a, b) = (&sides[0], &sides[1]);
+
+        let width = a.name.len().max(18);
+        let row = |label: &str, left: String, right: String| {
+            say!("  {:<12} {:<width$}  {}", label, left, right, width = width);
+        };
+        let or_dash = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+        let resolution = |side: &Side| or_dash(side.size.map(|(w, h)| format!("{}x{}", w, h)));
+        let file_size =
+            |side: &Side| or_dash(side.file_size.map(|bytes| HumanBytes(bytes).to_string()));
+        let hash = |side: &Side| or_dash(side.hash.as_ref().map(|hash| hash[..16].to_string()));
+
+        say!("  Comparing {} and {}", a.name, b.name);
+        say!("  ─────────────────────");
+        row("", a.name.clone(), b.name.clone());
+        row("Resolution", resolution(a), resolution(b));
+        row("File size", file_size(a), file_size(b));
+        row(
+            &format!("{:?}", self.config.hash_algorithm),
+            hash(a),
+            hash(b),
+        );
+
+        let same_file = a.hash.is_some() && a.hash == b.hash;
+        let dhash_distance = a.dhash.zip(b.dhash).map(|(x, y)| distance(x, y));
+        match dhash_distance {
+            Some(d) => say!(
+                "  {:<12} {:.0}% (perceptual hash distance {} of 64)",
+                "Similarity",
+                (64 - d) as f64 / 64.0 * 100.0,
+                d
+            ),
+            None => say!("  {:<12} - (needs both images downloaded)", "Similarity"),
+        }
+
+        match (&a.tags, &b.tags) {
+            (Some(left), Some(right)) => {
+                let [both, only_a, only_b] = split_tags(left, right);
+                let list = |tags: Vec<&str>| {
+                    if tags.is_empty() {
+                        "-".to_string()
+                    } else {
+                        tags.join(", ")
+                    }
+                };
+                say!("  Tags in both: {}", list(both));
+                say!("  Only {}: {}", a.name, list(only_a));
+                say!("  Only {}: {}", b.name, list(only_b));
+            }
+            (None, _) => say!("  Tags: unknown for {}", a.name),
+            (_, None) => say!("  Tags: unknown for {}", b.name),
+        }
+
+        if same_file {
+            say!("  ✓ The files are identical");
+        } else if dhash_distance.is_some_and(|d| d <= NEAR_DUPLICATE_DISTANCE) {
+            let pixels = |side: &Side| side.size.map_or(0, |(w, h)| w as u64 * h as u64);
+            let larger = match pixels(a).cmp(&pixels(b)) {
+                std::cmp::Ordering::Greater => Some(&a.name),
+                std::cmp::Ordering::Less => Some(&b.name),
+                std::cmp::Ordering::Equal => None,
+            };
+            match larger {
+                Some(name) => say!(
+                    "  ○ Likely near-duplicates; {} has the higher resolution",
+                    name
+                ),
+                None => say!("  ○ Likely near-duplicates at the same resolution"),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageLuma8(image::GrayImage::from_fn(width, height, |x, y| {
+            image::Luma([((x * 255 / width + y * 64 / height) % 256) as u8])
+        }))
+    }
+
+    #[test]
+    fn test_perceptual_hash_survives_resizing() {
+        let large = gradient(640, 360);
+        let small = large.resize_exact(160, 90, FilterType::Lanczos3);
+        assert!(distance(perceptual_hash(&large), perceptual_hash(&small)) <= 2);
+
+        let flipped = large.fliph();
+        assert!(
+            distance(perceptual_hash(&large), perceptual_hash(&flipped)) > NEAR_DUPLICATE_DISTANCE
+        );
+    }
+
+    #[test]
+    fn test_split_tags() {
+        let a = vec!["city".to_string(), "night".to_string()];
+        let b = vec!["rain".to_string(), "city".to_string()];
+        assert_eq!(
+            split_tags(&a, &b),
+            [vec!["city"], vec!["night"], vec!["rain"]]
+        );
+    }
+}
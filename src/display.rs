@@ -0,0 +1,183 @@
+use serde_json::Value;
+
+use crate::conditions::output;
+use crate::output::say_err;
+use crate::RustPaper;
+
+/// Size of a connected display, as the user sees it (rotation applied)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Resolution {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Resolution {
+    fn new(width: u32, height: u32, rotated: bool) -> Self {
+        if rotated {
+            Resolution {
+                width: height,
+                height: width,
+            }
+        } else {
+            Resolution { width, height }
+        }
+    }
+
+    /// Whether an image of `width`x`height` covers this display without upscaling
+    pub fn fits_within(&self, width: u32, height: u32) -> bool {
+        width >= self.width && height >= self.height
+    }
+}
+
+impl std::fmt::Display for Resolution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+fn json_u32(value: &Value, key: &str) -> Option<u32> {
+    value.get(key)?.as_u64()?.try_into().ok()
+}
+
+/// Monitors in `hyprctl monitors -j`; odd transforms are rotated by 90°
+fn parse_hyprctl(json: &str) -> Vec<Resolution> {
+    let Ok(Value::Array(monitors)) = serde_json::from_str(json) else {
+        return Vec::new();
+    };
+    monitors
+        .iter()
+        .filter_map(|monitor| {
+            let rotated = json_u32(monitor, "transform").is_some_and(|t| t % 2 == 1);
+            Some(Resolution::new(
+                json_u32(monitor, "width")?,
+                json_u32(monitor, "height")?,
+                rotated,
+            ))
+        })
+        .collect()
+}
+
+/// Active outputs in `swaymsg -t get_outputs -r`
+fn parse_swaymsg(json: &str) -> Vec<Resolution> {
+    let Ok(Value::Array(outputs)) = serde_json::from_str(json) else {
+        return Vec::new();
+    };
+    outputs
+        .iter()
+        .filter(|output| output.get("active").and_then(Value::as_bool) != Some(false))
+        .filter_map(|output| {
+            let mode = output.get("current_mode")?;
+            let rotated = matches!(
+                output.get("transform").and_then(Value::as_str),
+                Some("90" | "270" | "flipped-90" | "flipped-270")
+            );
+            Some(Resolution::new(
+                json_u32(mode, "width")?,
+                json_u32(mode, "height")?,
+                rotated,
+            ))
+        })
+        .collect()
+}
+
+/// Connected outputs in `xrandr --current`, e.g.
+/// "DP-1 connected primary 2560x1440+0+0 (normal left inverted ...) ..."
+/// xrandr already reports rotated outputs with their rotated size
+fn parse_xrandr(text: &str) -> Vec<Resolution> {
+    text.lines()
+        .filter(|line| line.contains(" connected"))
+        .filter_map(|line| {
+            let geometry = line
+                .split_whitespace()
+                .find(|word| word.contains('x') && word.contains('+'))?;
+            let size = geometry.split('+').next()?;
+            let (width, height) = size.split_once('x')?;
+            Some(Resolution::new(
+                width.parse().ok()?,
+                height.parse().ok()?,
+                false,
+            ))
+        })
+        .collect()
+}
+
+/// Resolutions of the connected displays, asking Hyprland, Sway and then X11.
+/// Empty when none of them answers, e.g. over SSH or on a headless machine
+pub async fn connected_displays() -> Vec<Resolution> {
+    if let Some(json) = output("hyprctl", &["monitors", "-j"]).await {
+        let displays = parse_hyprctl(&json);
+        if !displays.is_empty() {
+            return displays;
+        }
+    }
+    if let Some(json) = output("swaymsg", &["-t", "get_outputs", "-r"]).await {
+        let displays = parse_swaymsg(&json);
+        if !displays.is_empty() {
+            return displays;
+        }
+    }
+    match output("xrandr", &["--current"]).await {
+        Some(text) => parse_xrandr(&text),
+        None => Vec::new(),
+    }
+}
+
+/// The display with the most pixels
+fn largest(displays: &[Resolution]) -> Option<Resolution> {
+    displays
+        .iter()
+        .copied()
+        .max_by_key(|display| display.width as u64 * display.height as u64)
+}
+
+impl RustPaper {
+    /// With `auto_resolution`, the largest connected display, which searches
+    /// and downloads must at least cover
+    pub(crate) async fn minimum_resolution(&self) -> Option<Resolution> {
+        if !self.config.auto_resolution {
+            return None;
+        }
+        let display = largest(&connected_displays().await);
+        if display.is_none() {
+            say_err!("  ⚠ auto_resolution: could not detect the connected displays");
+        }
+        display
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_display_listings() {
+        let hyprctl = r#"[{"name":"DP-1","width":3440,"height":1440,"transform":0},
+            {"name":"HDMI-A-1","width":1920,"height":1080,"transform":1}]"#;
+        let displays = parse_hyprctl(hyprctl);
+        assert_eq!(displays[1], Resolution::new(1080, 1920, false));
+        assert_eq!(largest(&displays), Some(Resolution::new(3440, 1440, false)));
+
+        let swaymsg = r#"[{"name":"eDP-1","active":true,"transform":"90",
+            "current_mode":{"width":2560,"height":1600,"refresh":60000}},
+            {"name":"DP-2","active":false,"current_mode":{"width":7680,"height":4320}}]"#;
+        assert_eq!(parse_swaymsg(swaymsg), [Resolution::new(1600, 2560, false)]);
+
+        let xrandr = "Screen 0: minimum 8 x 8, current 4480 x 1440, maximum 32767 x 32767\n\
+            DP-1 connected primary 2560x1440+0+0 (normal left inverted right) 597mm x 336mm\n\
+            HDMI-1 connected 1920x1080+2560+0 (normal left inverted right) 527mm x 296mm\n\
+            DP-2 disconnected (normal left inverted right x axis y axis)\n";
+        assert_eq!(
+            parse_xrandr(xrandr),
+            [
+                Resolution::new(2560, 1440, false),
+                Resolution::new(1920, 1080, false)
+            ]
+        );
+        assert!(parse_hyprctl("not json").is_empty());
+
+        let display = Resolution::new(2560, 1440, false);
+        assert!(display.fits_within(3840, 2160));
+        assert!(!display.fits_within(3840, 1080));
+        assert_eq!(display.to_string(), "2560x1440");
+    }
+}
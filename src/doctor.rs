@@ -0,0 +1,233 @@
+use std::path::Path;
+
+use crate::api::BASE_URL;
+use crate::error::Result;
+use crate::output::say;
+use crate::{build_file_map, helper, RustPaper};
+
+/// Findings of a `doctor` run, printed as they are collected
+#[derive(Default)]
+struct Report {
+    warnings: usize,
+    errors: usize,
+}
+
+impl Report {
+    fn ok(&mut self, message: impl AsRef<str>) {
+        say!("  ✓ {}", message.as_ref());
+    }
+
+    fn warn(&mut self, message: impl AsRef<str>, fix: &str) {
+        self.warnings += 1;
+        say!("  ⚠ {}", message.as_ref());
+        say!("      → {}", fix);
+    }
+
+    fn error(&mut self, message: impl AsRef<str>, fix: &str) {
+        self.errors += 1;
+        say!("  ✗ {}", message.as_ref());
+        say!("      → {}", fix);
+    }
+}
+
+/// Check that we can create files in `dir`
+async fn check_writable(report: &mut Report, label: &str, dir: &Path) {
+    let probe = dir.join(".rust-paper-doctor");
+    match tokio::fs::write(&probe, b"").await {
+        Ok(()) => {
+            let _ = tokio::fs::remove_file(&probe).await;
+            report.ok(format!("{} is writable ({})", label, dir.display()));
+        }
+        Err(e) => report.error(
+            format!("{} is not writable ({}): {}", label, dir.display(), e),
+            "Fix the directory permissions or point the config at another location",
+        ),
+    }
+}
+
+impl RustPaper {
+    /// Diagnose the config, environment and local state, suggesting fixes
+    pub async fn doctor(&self) -> Result<()> {
+        let mut report = Report::default();
+
+        say!("  Configuration");
+        self.check_config(&mut report);
+
+        say!("\n  Permissions");
+        check_writable(&mut report, "Config folder", &self.config_folder).await;
+        if self.data_folder != self.config_folder {
+            check_writable(&mut report, "Data folder", &self.data_folder).await;
+        }
+        check_writable(
+            &mut report,
+            "Save location",
+            Path::new(&self.config.save_location),
+        )
+        .await;
+
+        say!("\n  Network");
+        self.check_network(&mut report).await;
+
+        say!("\n  Local state");
+        self.check_state(&mut report).await?;
+
+        say!();
+        if report.errors == 0 && report.warnings == 0 {
+            say!("  No problems found.");
+        } else {
+            say!(
+                "  Found {} error(s) and {} warning(s).",
+                report.errors,
+                report.warnings
+            );
+        }
+        Ok(())
+    }
+
+    fn check_config(&self, report: &mut Report) {
+        let config = &self.config;
+        for problem in config.problems() {
+            report.error(
+                problem,
+                "Fix it with `rust-paper config set` or in the config file",
+            );
+        }
+        if let Err(e) = helper::client_builder(config) {
+            report.error(
+                format!("{:#}", e),
+                "Fix http_proxy, https_proxy or ca_bundle in the config",
+            );
+        }
+        for name in config.sources.keys() {
+            if !self.source_names().contains(name) {
+                report.warn(
+                    format!("Unknown source '{}' in config", name),
+                    &format!("Use one of: {}", self.source_names().join(", ")),
+                );
+            }
+        }
+        if report.errors == 0 {
+            report.ok("Config values are valid");
+        }
+    }
+
+    async fn check_network(&self, report: &mut Report) {
+        if self.offline {
+            report.warn("Skipped: running offline", "Run without --offline");
+            return;
+        }
+        if !self.fetcher.is_online().await {
+            report.error(
+                "Wallhaven is unreachable",
+                "Check your network connection and proxy settings",
+            );
+            return;
+        }
+        report.ok("Wallhaven is reachable");
+
+        let Some(api_key) = helper::get_key_from_config_or_env(self.config.api_key.as_deref())
+        else {
+            report.warn(
+                "No API key configured; search, user commands and NSFW downloads are unavailable",
+                "Set api_key in the config or WALLHAVEN_API_KEY",
+            );
+            return;
+        };
+        let url = format!("{}/settings", BASE_URL);
+        match self.fetcher.get_text(&url, Some(&api_key)).await {
+            Ok(body) if !body.contains("\"error\"") => report.ok("API key is valid"),
+            Ok(body) => report.error(
+                format!("API key was rejected: {}", body.trim()),
+                "Generate a new key under Settings → API on wallhaven.cc",
+            ),
+            Err(e) => report.error(
+                format!("API key was rejected: {}", e),
+                "Generate a new key under Settings → API on wallhaven.cc",
+            ),
+        }
+    }
+
+    async fn check_state(&self, report: &mut Report) -> Result<()> {
+        let file_map = build_file_map(&self.config).await?;
+
+        let orphaned: Vec<&String> = file_map
+            .keys()
+            .filter(|id| !self.wallpapers.contains(id))
+            .collect();
+        if orphaned.is_empty() {
+            report.ok("No orphaned files in the save location");
+        } else {
+            report.warn(
+                format!(
+                    "{} file(s) in the save location are not in the list",
+                    orphaned.len()
+                ),
+                "Run `rust-paper clean` to remove them, or `rust-paper add` to track them",
+            );
+        }
+
+        let missing = self
+            .wallpapers
+            .iter()
+            .filter(|id| !file_map.contains_key(*id))
+            .count();
+        if missing == 0 {
+            report.ok(format!(
+                "All {} listed wallpaper(s) are downloaded",
+                self.wallpapers.len()
+            ));
+        } else {
+            report.warn(
+                format!("{} listed wallpaper(s) are not downloaded", missing),
+                "Run `rust-paper sync`",
+            );
+        }
+
+        let lock_file_guard = self.lock_file.lock().await;
+        let Some(lock_file) = lock_file_guard.as_ref() else {
+            return Ok(());
+        };
+        let stale = lock_file
+            .entries()
+            .iter()
+            .filter(|e| !self.wallpapers.iter().any(|id| id == e.image_id()))
+            .count();
+        let moved = lock_file
+            .entries()
+            .iter()
+            .filter(|e| {
+                file_map
+                    .get(e.image_id())
+                    .is_some_and(|path| path.to_string_lossy() != e.image_location())
+            })
+            .count();
+        let untracked = self
+            .wallpapers
+            .iter()
+            .filter(|id| file_map.contains_key(*id))
+            .filter(|id| !lock_file.entries().iter().any(|e| e.image_id() == *id))
+            .count();
+        if stale > 0 {
+            report.warn(
+                format!(
+                    "{} lock entries belong to wallpapers not in the list",
+                    stale
+                ),
+                "Run `rust-paper repair` to rebuild the lock file",
+            );
+        }
+        if moved > 0 || untracked > 0 {
+            report.warn(
+                format!(
+                    "{} downloaded wallpaper(s) have no or an outdated lock entry",
+                    moved + untracked
+                ),
+                "Run `rust-paper repair` to rebuild the lock file",
+            );
+        }
+        if stale == 0 && moved == 0 && untracked == 0 {
+            report.ok("Lock file matches the list");
+        }
+        Ok(())
+    }
+}
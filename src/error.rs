@@ -0,0 +1,144 @@
+use thiserror::Error as ThisError;
+
+/// Errors returned by the public `RustPaper` API
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Wallhaven answered with an error payload or an unexpected status
+    #[error("API error: {0}")]
+    ApiError(String),
+    /// Wallhaven rejected the API key, or the request needs one (HTTP 401)
+    #[error("Wallhaven rejected the API key; set a valid api_key in the config or WALLHAVEN_API_KEY (see https://wallhaven.cc/settings/account)")]
+    Unauthorized,
+    /// Wallhaven rejected the request because of rate limiting (HTTP 429)
+    #[error("Rate limited by Wallhaven (45 requests per minute); wait a minute or lower max_concurrent_downloads")]
+    RateLimited,
+    /// The requested wallpaper or resource does not exist (HTTP 404)
+    #[error("Not found: {0}; check the ID or URL, the wallpaper may have been removed")]
+    NotFound(String),
+    /// A file's checksum does not match the recorded one
+    #[error("Integrity mismatch for {id}: expected {expected}, got {actual}")]
+    IntegrityMismatch {
+        id: String,
+        expected: String,
+        actual: String,
+    },
+    /// The user supplied something we cannot work with (bad ID, unknown source, ...)
+    #[error("{0}")]
+    InvalidInput(String),
+    /// Another instance is already working on the same config folder
+    #[error("Another rust-paper instance is running (lock held on {0}); use --wait or --no-lock")]
+    Locked(String),
+    /// A sync would leave less free space than `min_free_space_mb`
+    #[error("Not enough disk space on {path}: the sync needs about {needed_mb} MB (including min_free_space_mb) but only {available_mb} MB are free; free up space or pass --ignore-disk-space")]
+    InsufficientSpace {
+        path: String,
+        needed_mb: u64,
+        available_mb: u64,
+    },
+    /// A wallpaper was left out on purpose, e.g. an animation with `animated = "skip"`
+    #[error("Skipped {id}: {reason}")]
+    Skipped { id: String, reason: String },
+    /// A wallpaper smaller than the largest display, with `auto_resolution` on
+    #[error("Skipped {id}: {resolution} is smaller than the {display} display")]
+    TooSmall {
+        id: String,
+        resolution: String,
+        display: String,
+    },
+    /// A wallpaper over `max_file_size_mb`
+    #[error("Skipped {id}: {size} is over the {limit} MB max_file_size_mb")]
+    TooLarge {
+        id: String,
+        size: String,
+        limit: u64,
+    },
+    /// A download that failed one of the `download_checks`
+    #[error("Rejected {id}: {reason}")]
+    Rejected { id: String, reason: String },
+    /// Configuration could not be loaded or is invalid
+    #[error("Configuration error: {0}")]
+    Config(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    /// Any other failure, with its context chain
+    #[error("{0:#}")]
+    Other(anyhow::Error),
+}
+
+impl From<anyhow::Error> for Error {
+    /// Recover typed errors raised deeper in the crate, keeping the rest as `Other`
+    fn from(err: anyhow::Error) -> Self {
+        match err.downcast::<Error>() {
+            Ok(err) => err,
+            Err(err) => Error::Other(err),
+        }
+    }
+}
+
+impl Error {
+    /// Map the message of a Wallhaven `{"error": ...}` payload to a typed error
+    pub fn from_api_message(message: String) -> Self {
+        match message.to_ascii_lowercase().as_str() {
+            "unauthorized" => Error::Unauthorized,
+            "too many requests" => Error::RateLimited,
+            _ => Error::ApiError(message),
+        }
+    }
+
+    /// Process exit code for this error, so scripts can tell failures apart
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::InvalidInput(_) => 2,
+            Error::Unauthorized => 3,
+            Error::RateLimited => 4,
+            Error::NotFound(_) => 5,
+            Error::Locked(_) => 6,
+            Error::InsufficientSpace { .. } => 7,
+            Error::Config(_) => 8,
+            _ => 1,
+        }
+    }
+}
+
+/// Result type of the public `RustPaper` API
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_typed_error_survives_anyhow() {
+        let err: anyhow::Error = Error::RateLimited.into();
+        let err = Err::<(), _>(err).context("while syncing").unwrap_err();
+        assert!(matches!(Error::from(err), Error::RateLimited));
+    }
+
+    #[test]
+    fn test_api_messages_and_exit_codes() {
+        assert!(matches!(
+            Error::from_api_message("Unauthorized".to_string()),
+            Error::Unauthorized
+        ));
+        assert!(matches!(
+            Error::from_api_message("Nothing here".to_string()),
+            Error::ApiError(_)
+        ));
+        assert_eq!(Error::Unauthorized.exit_code(), 3);
+        assert_eq!(Error::RateLimited.exit_code(), 4);
+        assert_eq!(Error::NotFound("x".to_string()).exit_code(), 5);
+        assert_eq!(Error::ApiError("x".to_string()).exit_code(), 1);
+    }
+
+    #[test]
+    fn test_plain_anyhow_becomes_other() {
+        let err = Error::from(anyhow::anyhow!("boom"));
+        assert!(matches!(err, Error::Other(_)));
+        assert_eq!(err.to_string(), "boom");
+    }
+}
@@ -0,0 +1,17 @@
+use crate::error::Result;
+use crate::SyncReport;
+
+/// Progress of a sync, for frontends that render their own output
+#[derive(Debug)]
+pub enum SyncEvent {
+    /// A source is about to be synced
+    Started { source: String },
+    /// A wallpaper was downloaded to `path`
+    Downloaded { id: String, path: String },
+    /// A wallpaper could not be downloaded
+    DownloadFailed { id: String, error: String },
+    /// A file on disk no longer matched its lock entry and will be re-downloaded
+    IntegrityFailed { id: String },
+    /// The sync finished; always the last event
+    Completed(Result<SyncReport>),
+}
@@ -0,0 +1,184 @@
+use futures::future::BoxFuture;
+use futures::stream::BoxStream;
+use futures::{FutureExt, StreamExt};
+use reqwest::{Client, StatusCode};
+
+use crate::error::{Error, Result};
+
+/// Streamed response body with its advertised length
+pub struct FetchResponse {
+    /// Value of the Content-Length header, if any
+    pub content_length: Option<u64>,
+    /// Body chunks in arrival order
+    pub body: BoxStream<'static, Result<Vec<u8>>>,
+}
+
+/// Network access used by `RustPaper`, so it can be swapped out in tests or embedders
+pub trait HttpFetcher: Send + Sync {
+    /// GET a URL (optionally passing the API key as `apikey`) and return the body as text
+    fn get_text<'a>(
+        &'a self,
+        url: &'a str,
+        api_key: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<String>>;
+
+    /// GET a URL and stream the body
+    fn get_stream<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<FetchResponse>>;
+
+    /// GET `len` bytes of a URL starting at `start`, or `None` when ranges
+    /// aren't supported and the whole file has to be streamed instead
+    fn get_range<'a>(
+        &'a self,
+        _url: &'a str,
+        _start: u64,
+        _len: u64,
+    ) -> BoxFuture<'a, Result<Option<Vec<u8>>>> {
+        async { Ok(None) }.boxed()
+    }
+
+    /// Whether Wallhaven can be reached at all
+    fn is_online(&self) -> BoxFuture<'_, bool>;
+}
+
+/// Default fetcher backed by a `reqwest::Client`
+#[derive(Clone)]
+pub struct ReqwestFetcher {
+    client: Client,
+    download_client: Client,
+}
+
+impl ReqwestFetcher {
+    /// Use `client` for API requests and downloads alike
+    pub fn new(client: Client) -> Self {
+        ReqwestFetcher {
+            download_client: client.clone(),
+            client,
+        }
+    }
+
+    /// Stream downloads through a separate client, e.g. one with different timeouts
+    pub fn download_client(mut self, client: Client) -> Self {
+        self.download_client = client;
+        self
+    }
+}
+
+/// Turn non-success statuses into typed errors
+fn check_status(status: StatusCode, url: &str) -> Result<()> {
+    if status == StatusCode::UNAUTHORIZED {
+        return Err(Error::Unauthorized);
+    }
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        return Err(Error::RateLimited);
+    }
+    if status == StatusCode::NOT_FOUND {
+        return Err(Error::NotFound(url.to_string()));
+    }
+    if !status.is_success() {
+        return Err(Error::ApiError(format!(
+            "HTTP request failed with status {}: {}",
+            status.as_u16(),
+            status.canonical_reason().unwrap_or("Unknown error")
+        )));
+    }
+    Ok(())
+}
+
+impl HttpFetcher for ReqwestFetcher {
+    fn get_text<'a>(
+        &'a self,
+        url: &'a str,
+        api_key: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<String>> {
+        async move {
+            let mut request = self.client.get(url);
+            if let Some(key) = api_key {
+                request = request.query(&[("apikey", key)]);
+            }
+            let response = request.send().await?;
+            check_status(response.status(), url)?;
+            Ok(response.text().await?)
+        }
+        .boxed()
+    }
+
+    fn get_stream<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<FetchResponse>> {
+        async move {
+            let response = self.download_client.get(url).send().await?;
+            check_status(response.status(), url)?;
+            Ok(FetchResponse {
+                content_length: response.content_length(),
+                body: response
+                    .bytes_stream()
+                    .map(|chunk| chunk.map(|b| b.to_vec()).map_err(Error::from))
+                    .boxed(),
+            })
+        }
+        .boxed()
+    }
+
+    fn get_range<'a>(
+        &'a self,
+        url: &'a str,
+        start: u64,
+        len: u64,
+    ) -> BoxFuture<'a, Result<Option<Vec<u8>>>> {
+        async move {
+            let response = self
+                .download_client
+                .get(url)
+                .header(
+                    reqwest::header::RANGE,
+                    format!("bytes={}-{}", start, start + len.max(1) - 1),
+                )
+                .send()
+                .await?;
+            check_status(response.status(), url)?;
+            // A plain 200 means the range was ignored; drop the body unread
+            if response.status() != StatusCode::PARTIAL_CONTENT {
+                return Ok(None);
+            }
+            Ok(Some(response.bytes().await?.to_vec()))
+        }
+        .boxed()
+    }
+
+    fn is_online(&self) -> BoxFuture<'_, bool> {
+        async move {
+            self.client
+                .head("https://wallhaven.cc")
+                .timeout(std::time::Duration::from_secs(5))
+                .send()
+                .await
+                .is_ok()
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_status_maps_wallhaven_errors() {
+        let url = "https://wallhaven.cc/api/v1/w/abc123";
+        assert!(check_status(StatusCode::OK, url).is_ok());
+        assert!(matches!(
+            check_status(StatusCode::UNAUTHORIZED, url),
+            Err(Error::Unauthorized)
+        ));
+        assert!(matches!(
+            check_status(StatusCode::TOO_MANY_REQUESTS, url),
+            Err(Error::RateLimited)
+        ));
+        assert!(matches!(
+            check_status(StatusCode::NOT_FOUND, url),
+            Err(Error::NotFound(found)) if found == url
+        ));
+        assert!(matches!(
+            check_status(StatusCode::BAD_GATEWAY, url),
+            Err(Error::ApiError(_))
+        ));
+    }
+}
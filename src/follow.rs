@@ -0,0 +1,150 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Search filters applied when checking followed uploaders
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct FollowConfig {
+    /// Purity bits as used by the search API, e.g. "100" for SFW only (optional)
+    pub purity: Option<String>,
+    /// Category bits (general/anime/people), e.g. "110" (optional)
+    pub categories: Option<String>,
+    /// Minimum resolution such as "1920x1080" (optional)
+    pub atleast: Option<String>,
+    /// Comma-separated aspect ratios such as "16x9,16x10" (optional)
+    pub ratios: Option<String>,
+    /// Pages of uploads (24 each) to check per uploader on every sync (default: 1)
+    pub max_pages: Option<u32>,
+}
+
+impl FollowConfig {
+    /// Search URL listing the latest uploads of `username`
+    pub fn search_url(&self, base_url: &str, username: &str) -> String {
+        let mut url = format!(
+            "{}/search?q=@{}&sorting=date_added&order=desc",
+            base_url, username
+        );
+        for (key, value) in [
+            ("purity", &self.purity),
+            ("categories", &self.categories),
+            ("atleast", &self.atleast),
+            ("ratios", &self.ratios),
+        ] {
+            if let Some(value) = value {
+                url.push_str(&format!("&{}={}", key, value));
+            }
+        }
+        url
+    }
+}
+
+/// Followed uploaders and the uploads already seen from each
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct Follows {
+    users: BTreeMap<String, Vec<String>>,
+}
+
+/// Whether `username` looks like a Wallhaven username
+fn validate_username(username: &str) -> Result<()> {
+    if username.is_empty()
+        || !username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        return Err(anyhow!("Invalid username '{}'", username));
+    }
+    Ok(())
+}
+
+impl Follows {
+    /// Load the follows from disk, starting empty if the file is missing
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self> {
+        match tokio::fs::read_to_string(path.as_ref()).await {
+            Ok(contents) => serde_json::from_str(&contents).context("   Failed to parse follows"),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Save the follows to disk
+    pub async fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let json =
+            serde_json::to_string_pretty(&self).context("   Failed to serialize follows")?;
+        tokio::fs::write(path.as_ref(), json)
+            .await
+            .context("   Failed to write follows")
+    }
+
+    /// Start following an uploader
+    pub fn follow(&mut self, username: &str) -> Result<()> {
+        let username = username.trim_start_matches('@');
+        validate_username(username)?;
+        if self.users.contains_key(username) {
+            return Err(anyhow!("Already following '{}'", username));
+        }
+        self.users.insert(username.to_string(), Vec::new());
+        Ok(())
+    }
+
+    /// Stop following an uploader
+    pub fn unfollow(&mut self, username: &str) -> Result<()> {
+        let username = username.trim_start_matches('@');
+        self.users
+            .remove(username)
+            .map(|_| ())
+            .ok_or_else(|| anyhow!("Not following '{}'", username))
+    }
+
+    /// Names of all followed uploaders
+    pub fn usernames(&self) -> Vec<String> {
+        self.users.keys().cloned().collect()
+    }
+
+    /// Remember uploads of `username`, returning the ones not seen before
+    pub fn record(&mut self, username: &str, ids: impl IntoIterator<Item = String>) -> Vec<String> {
+        let seen = self.users.entry(username.to_string()).or_default();
+        let fresh: Vec<String> = ids.into_iter().filter(|id| !seen.contains(id)).collect();
+        seen.extend(fresh.iter().cloned());
+        fresh
+    }
+
+    /// Forget the uploads seen from every followed uploader, keeping the follows
+    pub fn forget_uploads(&mut self) {
+        self.users.values_mut().for_each(Vec::clear);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_follow_search_url() {
+        let config = FollowConfig {
+            purity: Some("100".to_string()),
+            atleast: Some("1920x1080".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.search_url("https://wallhaven.cc/api/v1", "artist"),
+            "https://wallhaven.cc/api/v1/search?q=@artist&sorting=date_added&order=desc&purity=100&atleast=1920x1080"
+        );
+    }
+
+    #[test]
+    fn test_follow_records_new_uploads() {
+        let mut follows = Follows::default();
+        follows.follow("@artist").unwrap();
+        assert!(follows.follow("artist").is_err());
+        assert!(follows.follow("bad name").is_err());
+
+        let fresh = follows.record("artist", ["p9pzk9".to_string(), "x6m3gl".to_string()]);
+        assert_eq!(fresh.len(), 2);
+        let fresh = follows.record("artist", ["p9pzk9".to_string(), "gpl8d3".to_string()]);
+        assert_eq!(fresh, ["gpl8d3".to_string()]);
+
+        follows.unfollow("artist").unwrap();
+        assert!(follows.usernames().is_empty());
+    }
+}
@@ -0,0 +1,175 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::Path;
+
+/// Named subsets of the wallpapers list, persisted next to it
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct Groups {
+    groups: BTreeMap<String, Vec<String>>,
+    /// Groups whose wallpapers sync only verifies, never downloads or changes
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    archived: BTreeSet<String>,
+}
+
+/// Whether `name` can be used as a group name
+fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(anyhow!(
+            "Invalid group name '{}': use letters, digits, '-' and '_'",
+            name
+        ));
+    }
+    Ok(())
+}
+
+impl Groups {
+    /// Load the groups from disk, starting empty if the file is missing
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self> {
+        match tokio::fs::read_to_string(path.as_ref()).await {
+            Ok(contents) => serde_json::from_str(&contents).context("   Failed to parse groups"),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Save the groups to disk
+    pub async fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let json = serde_json::to_string_pretty(&self).context("   Failed to serialize groups")?;
+        tokio::fs::write(path.as_ref(), json)
+            .await
+            .context("   Failed to write groups")
+    }
+
+    /// Create an empty group
+    pub fn create(&mut self, name: &str) -> Result<()> {
+        validate_name(name)?;
+        if self.groups.contains_key(name) {
+            return Err(anyhow!("Group '{}' already exists", name));
+        }
+        self.groups.insert(name.to_string(), Vec::new());
+        Ok(())
+    }
+
+    /// Delete a group; its wallpapers stay in the list
+    pub fn delete(&mut self, name: &str) -> Result<()> {
+        self.archived.remove(name);
+        self.groups
+            .remove(name)
+            .map(|_| ())
+            .ok_or_else(|| anyhow!("Unknown group '{}'", name))
+    }
+
+    /// Mark a group as archived, or make it editable again
+    pub fn set_archived(&mut self, name: &str, archived: bool) -> Result<()> {
+        if !self.groups.contains_key(name) {
+            return Err(anyhow!("Unknown group '{}'", name));
+        }
+        if archived {
+            self.archived.insert(name.to_string());
+        } else {
+            self.archived.remove(name);
+        }
+        Ok(())
+    }
+
+    /// Whether a group is archived
+    pub fn is_archived(&self, name: &str) -> bool {
+        self.archived.contains(name)
+    }
+
+    /// Wallpaper IDs in any archived group
+    pub fn archived_ids(&self) -> HashSet<String> {
+        self.archived
+            .iter()
+            .filter_map(|name| self.groups.get(name))
+            .flatten()
+            .cloned()
+            .collect()
+    }
+
+    /// Add wallpaper IDs to an existing group
+    pub fn add(&mut self, name: &str, ids: &[String]) -> Result<()> {
+        if self.is_archived(name) {
+            return Err(anyhow!(
+                "Group '{}' is archived; unarchive it to add wallpapers",
+                name
+            ));
+        }
+        let members = self
+            .groups
+            .get_mut(name)
+            .ok_or_else(|| anyhow!("Unknown group '{}'", name))?;
+        members.extend(ids.iter().cloned());
+        members.sort_unstable();
+        members.dedup();
+        Ok(())
+    }
+
+    /// Drop wallpaper IDs from every group; returns whether anything changed
+    pub fn remove_ids(&mut self, ids: &[String]) -> bool {
+        let mut changed = false;
+        for members in self.groups.values_mut() {
+            let len = members.len();
+            members.retain(|id| !ids.contains(id));
+            changed |= members.len() != len;
+        }
+        changed
+    }
+
+    /// Wallpaper IDs in a group
+    pub fn members(&self, name: &str) -> Option<&[String]> {
+        self.groups.get(name).map(Vec::as_slice)
+    }
+
+    /// All groups with their members, sorted by name
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[String])> {
+        self.groups
+            .iter()
+            .map(|(name, members)| (name.as_str(), members.as_slice()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_lifecycle() {
+        let mut groups = Groups::default();
+        groups.create("anime").unwrap();
+        assert!(groups.create("anime").is_err());
+        assert!(groups.create("bad name").is_err());
+
+        groups
+            .add("anime", &["p9pzk9".to_string(), "x6m3gl".to_string()])
+            .unwrap();
+        assert!(groups.add("minimal", &["p9pzk9".to_string()]).is_err());
+        assert_eq!(groups.members("anime").unwrap().len(), 2);
+
+        assert!(groups.remove_ids(&["p9pzk9".to_string()]));
+        assert_eq!(groups.members("anime").unwrap(), ["x6m3gl".to_string()]);
+
+        groups.delete("anime").unwrap();
+        assert!(groups.members("anime").is_none());
+    }
+
+    #[test]
+    fn test_archived_groups_are_frozen() {
+        let mut groups = Groups::default();
+        groups.create("finished").unwrap();
+        groups.add("finished", &["p9pzk9".to_string()]).unwrap();
+        groups.set_archived("finished", true).unwrap();
+        assert!(groups.set_archived("missing", true).is_err());
+
+        assert!(groups.add("finished", &["x6m3gl".to_string()]).is_err());
+        assert!(groups.archived_ids().contains("p9pzk9"));
+
+        groups.set_archived("finished", false).unwrap();
+        assert!(groups.archived_ids().is_empty());
+        groups.add("finished", &["x6m3gl".to_string()]).unwrap();
+    }
+}
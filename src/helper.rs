@@ -1,25 +1,63 @@
 use anyhow::{anyhow, Context, Error, Result};
-use futures::StreamExt;
 use image::{self, guess_format, ImageFormat};
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use reqwest::Client;
-use sha2::{Digest, Sha256};
 use std::{
     collections::HashMap,
     path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 use tokio::{
-    fs::{File, OpenOptions},
-    io::{AsyncReadExt, AsyncWriteExt, BufWriter},
+    fs::File,
+    io::{AsyncReadExt, AsyncWriteExt},
 };
 
+use crate::animated::{self, AnimatedPolicy};
+use crate::breaker::RetryBudget;
+use crate::config::Config;
+use crate::decode::DecodePool;
+use crate::fetch::HttpFetcher;
+use crate::hash::HashAlgorithm;
+use crate::pin::Pin;
 use crate::RustPaper;
 
 const ENV_API_KEY: &str = "WALLHAVEN_API_KEY";
 
 pub struct DownloadResult {
     pub file_path: String,
-    pub sha256: Option<String>,
+    pub hash: Option<String>,
+    /// Bytes transferred
+    pub bytes: u64,
+}
+
+/// Where a download reports its progress, and what it shares with the
+/// other downloads of its sync
+#[derive(Clone)]
+pub struct DownloadProgress {
+    /// Draw the download's bar here instead of on its own
+    pub multi: Option<MultiProgress>,
+    /// Bar counting the bytes of every download in a sync
+    pub total: Option<ProgressBar>,
+    /// Size `total` assumed for this download, replaced by its Content-Length
+    pub expected_size: u64,
+    /// Retries left for the whole sync
+    pub retry_budget: Option<Arc<RetryBudget>>,
+    /// Where the download is decoded and checked
+    pub decode: Arc<DecodePool>,
+}
+
+impl DownloadProgress {
+    /// No progress bars and no retry budget
+    pub(crate) fn new(decode: Arc<DecodePool>) -> Self {
+        DownloadProgress {
+            multi: None,
+            total: None,
+            expected_size: 0,
+            retry_budget: None,
+            decode,
+        }
+    }
 }
 
 pub fn get_key_from_config_or_env(config_key: Option<&str>) -> Option<String> {
@@ -52,56 +90,79 @@ pub fn get_img_extension(format: &ImageFormat) -> &'static str {
     extensions.get(format).unwrap_or(&"jpg")
 }
 
-/// Create an HTTP client with the given timeout
-pub fn create_http_client(timeout_secs: u64, api_key: Option<&String>) -> Result<Client> {
+/// Client builder with the settings every client shares: user agent, connect
+/// timeout, proxies and extra CA certificates. Without configured proxies,
+/// reqwest falls back to the standard proxy environment variables
+pub(crate) fn client_builder(config: &Config) -> Result<reqwest::ClientBuilder> {
+    let mut builder = reqwest::ClientBuilder::new()
+        .user_agent("rust-paper/0.1.2")
+        .connect_timeout(Duration::from_secs(config.connect_timeout));
+    if let Some(proxy) = config.http_proxy.as_deref() {
+        let proxy = reqwest::Proxy::http(proxy)
+            .with_context(|| format!("Invalid http_proxy '{}'", proxy))?;
+        builder = builder.proxy(proxy);
+    }
+    if let Some(proxy) = config.https_proxy.as_deref() {
+        let proxy = reqwest::Proxy::https(proxy)
+            .with_context(|| format!("Invalid https_proxy '{}'", proxy))?;
+        builder = builder.proxy(proxy);
+    }
+    if let Some(path) = config.ca_bundle.as_deref() {
+        let pem =
+            std::fs::read(path).with_context(|| format!("Failed to read ca_bundle {}", path))?;
+        let certificates = reqwest::Certificate::from_pem_bundle(&pem)
+            .with_context(|| format!("Invalid certificates in ca_bundle {}", path))?;
+        for certificate in certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+    Ok(builder)
+}
+
+/// Create the HTTP client for API requests, bounded by `metadata_timeout`
+pub fn create_http_client(config: &Config, api_key: Option<&String>) -> Result<Client> {
     let mut headers = reqwest::header::HeaderMap::new();
     if let Some(k) = api_key {
         let header_api_value =
-            reqwest::header::HeaderValue::from_str(&k).context("Invalid API key format")?;
+            reqwest::header::HeaderValue::from_str(k).context("Invalid API key format")?;
         headers.insert("X-API-KEY", header_api_value);
     }
-    reqwest::ClientBuilder::new()
+    client_builder(config)?
         .default_headers(headers)
-        .user_agent("rust-paper/0.1.2")
-        .timeout(std::time::Duration::from_secs(timeout_secs))
+        .timeout(Duration::from_secs(config.metadata_timeout))
         .build()
         .context("Failed to create HTTP client")
 }
 
-/// Fetch content from a URL with proper error handling
-pub async fn get_curl_content(
-    link: &str,
-    client: &Client,
-    api_key: Option<&str>,
-) -> Result<String> {
-    let mut request = client.get(link);
-    if let Some(key) = api_key {
-        request = request.query(&[("apikey", key)]);
-    }
-    let response = request
-        .send()
-        .await
-        .context("Failed to send HTTP request")?;
+/// Create the HTTP client for image downloads. There is no overall limit, so
+/// large files on slow links finish, but a stalled transfer is aborted
+pub fn create_download_client(config: &Config) -> Result<Client> {
+    client_builder(config)?
+        .read_timeout(Duration::from_secs(config.download_idle_timeout))
+        .build()
+        .context("Failed to create HTTP client")
+}
 
-    let status = response.status();
-    if !status.is_success() {
-        return Err(anyhow::anyhow!(
-            "HTTP request failed with status {}: {}",
-            status.as_u16(),
-            status.canonical_reason().unwrap_or("Unknown error")
-        ));
+/// Store a raw API response for a wallpaper so it can be served offline
+pub async fn write_cached_metadata(cache_dir: &Path, id: &str, data: &str) {
+    if tokio::fs::create_dir_all(cache_dir).await.is_ok() {
+        if let Ok(path) = file_in(cache_dir, &format!("{}.json", id)) {
+            let _ = tokio::fs::write(path, data).await;
+        }
     }
+}
 
-    let body = response
-        .text()
-        .await
-        .context("Failed to read response body")?;
-
-    Ok(body)
+/// Read a cached API response for a wallpaper, if any
+pub async fn read_cached_metadata(cache_dir: &Path, id: &str) -> Option<String> {
+    let path = file_in(cache_dir, &format!("{}.json", id)).ok()?;
+    tokio::fs::read_to_string(path).await.ok()
 }
 
-/// Calculate SHA256 hash of a file
-pub async fn calculate_sha256(file_path: impl AsRef<Path>) -> Result<String> {
+/// Calculate the hash of a file
+pub async fn calculate_hash(
+    file_path: impl AsRef<Path>,
+    algorithm: HashAlgorithm,
+) -> Result<String> {
     let file_path = file_path.as_ref();
 
     if !file_path.exists() {
@@ -112,7 +173,7 @@ pub async fn calculate_sha256(file_path: impl AsRef<Path>) -> Result<String> {
         .await
         .with_context(|| format!(" 󱀷  Failed to open file: {}", file_path.display()))?;
 
-    let mut hasher = Sha256::new();
+    let mut hasher = algorithm.hasher();
     let mut buffer = [0; 8192];
 
     loop {
@@ -128,42 +189,165 @@ pub async fn calculate_sha256(file_path: impl AsRef<Path>) -> Result<String> {
         hasher.update(&buffer[..n]);
     }
 
-    Ok(format!("{:x}", hasher.finalize()))
+    Ok(hasher.finalize())
+}
+
+/// Wallhaven CDN hosts serving the same full-size images
+const WALLHAVEN_CDN_HOSTS: &[&str] = &["https://w.wallhaven.cc", "https://th.wallhaven.cc"];
+
+/// `url` followed by the same path on the other Wallhaven CDN host, when it
+/// is on one, then on every mirror host
+pub fn download_candidates(url: &str, mirrors: &[String]) -> Vec<String> {
+    let mut candidates = vec![url.to_string()];
+    if let Ok(parsed) = reqwest::Url::parse(url) {
+        let origin = parsed.origin().ascii_serialization();
+        let cdn_hosts: &[&str] = if WALLHAVEN_CDN_HOSTS.contains(&origin.as_str()) {
+            WALLHAVEN_CDN_HOSTS
+        } else {
+            &[]
+        };
+        for mirror in cdn_hosts
+            .iter()
+            .copied()
+            .chain(mirrors.iter().map(String::as_str))
+        {
+            let candidate = format!("{}{}", mirror.trim_end_matches('/'), parsed.path());
+            if !candidates.contains(&candidate) {
+                candidates.push(candidate);
+            }
+        }
+    }
+    candidates
+}
+
+/// Download a wallpaper, retrying `retry_count` times on every candidate from
+/// `download_candidates` before giving up. A 404 moves on to the next host at
+/// once. Retries also come out of the sync's retry budget, if it has one
+pub async fn download_with_fallback(
+    url: &str,
+    id: &str,
+    config: &Config,
+    pin: Option<&Pin>,
+    fetcher: &dyn HttpFetcher,
+    show_progress: bool,
+    progress: DownloadProgress,
+) -> Result<DownloadResult> {
+    let mut first_error = None;
+    'candidates: for candidate in download_candidates(url, &config.download_mirrors) {
+        for attempt in 0..config.retry_count.max(1) {
+            let retry = attempt > 0 || first_error.is_some();
+            if retry
+                && !progress
+                    .retry_budget
+                    .as_deref()
+                    .is_none_or(RetryBudget::take)
+            {
+                break 'candidates;
+            }
+            if attempt > 0 {
+                tokio::time::sleep(Duration::from_secs(2_u64.pow(attempt - 1))).await;
+            }
+            match download_with_progress(
+                &candidate,
+                id,
+                config,
+                pin,
+                fetcher,
+                show_progress,
+                progress.clone(),
+            )
+            .await
+            {
+                Ok(result) => return store_download(config, result).await,
+                // A mirror or retry would serve the same file
+                Err(e) if is_skipped(&e) || is_pin_mismatch(&e) => return Err(e),
+                Err(e) => {
+                    let not_found = e
+                        .chain()
+                        .any(|c| matches!(c.downcast_ref(), Some(crate::Error::NotFound(_))));
+                    first_error.get_or_insert(e);
+                    if not_found {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    Err(first_error.unwrap_or_else(|| anyhow!("No URL to download {} from", id)))
+}
+
+/// Whether `err` means the wallpaper was left out on purpose
+pub fn is_skipped(err: &Error) -> bool {
+    matches!(
+        err.downcast_ref(),
+        Some(
+            crate::Error::Skipped { .. }
+                | crate::Error::TooSmall { .. }
+                | crate::Error::TooLarge { .. }
+                | crate::Error::Rejected { .. }
+        )
+    )
+}
+
+/// Whether a download failed because upstream no longer serves the pinned file
+pub fn is_pin_mismatch(err: &Error) -> bool {
+    matches!(
+        err.downcast_ref(),
+        Some(crate::Error::IntegrityMismatch { .. })
+    )
+}
+
+/// Check that `data` decodes as an image, on the decode pool. Only the
+/// check decodes; the bytes are handed back untouched
+async fn validate_decode(decode: &DecodePool, id: &str, data: Vec<u8>) -> Result<Vec<u8>> {
+    let (data, decoded) = decode
+        .run(move || {
+            let decoded = image::load_from_memory(&data).map(|_| ());
+            (data, decoded)
+        })
+        .await
+        .context("Image validation task failed")?;
+    decoded.with_context(|| format!("Downloaded image for {} does not decode", id))?;
+    Ok(data)
+}
+
+/// Move a download into the content store, if one is configured
+async fn store_download(config: &Config, result: DownloadResult) -> Result<DownloadResult> {
+    if let Some(store) = config.store_location.as_deref() {
+        let hash = match result.hash.as_deref() {
+            Some(hash) => hash.to_string(),
+            None => calculate_hash(&result.file_path, config.hash_algorithm).await?,
+        };
+        crate::store::adopt(Path::new(store), Path::new(&result.file_path), &hash).await?;
+    }
+    Ok(result)
 }
 
 /// Download an image from a URL and save it to disk
 /// Unified download function with progress bar, hash calculation, and file saving
-/// Returns the saved file path and optional SHA256 hash
+/// Returns the saved file path and, with integrity checks on, its hash
 pub async fn download_with_progress(
     url: &str,
     id: &str,
-    save_location: &str,
-    client: &Client,
-    calculate_hash: bool,
+    config: &Config,
+    pin: Option<&Pin>,
+    fetcher: &dyn HttpFetcher,
     show_progress: bool,
-    multi_progress: Option<MultiProgress>,
+    progress: DownloadProgress,
 ) -> Result<DownloadResult> {
-    let url = reqwest::Url::parse(url).context("Invalid image URL")?;
-    let response = client
-        .get(url)
-        .send()
+    reqwest::Url::parse(url).context("Invalid image URL")?;
+    let decode = progress.decode.clone();
+    let response = fetcher
+        .get_stream(url)
         .await
         .context("Failed to download image")?;
 
-    let status = response.status();
-    if !status.is_success() {
-        return Err(anyhow::anyhow!(
-            "Failed to download image: HTTP {}",
-            status.as_u16()
-        ));
-    }
-
     let total_size = response
-        .content_length()
+        .content_length
         .ok_or_else(|| anyhow!("Failed to get content length"))?;
 
     // --- Progress Bar Setup ---
-    let pb = if let Some(mp) = multi_progress {
+    let pb = if let Some(mp) = progress.multi {
         let pb = mp.add(ProgressBar::new(total_size));
         let style = ProgressStyle::with_template(
             "{msg} {spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})",
@@ -186,55 +370,101 @@ pub async fn download_with_progress(
     } else {
         None
     };
-    let mut downloaded_data = Vec::with_capacity(total_size as usize);
-    let mut stream = response.bytes_stream();
-    let mut downloaded: u64 = 0;
-
-    while let Some(item) = stream.next().await {
-        let chunk = item.context("Error while downloading file")?;
-        downloaded_data.extend_from_slice(&chunk);
-        let new = std::cmp::min(downloaded + (chunk.len() as u64), total_size);
-        downloaded = new;
+    // Counts this attempt's bytes even when no bar is drawn
+    let counter = pb.clone().unwrap_or_else(ProgressBar::hidden);
+    let mut bars = vec![counter.clone()];
+    if let Some(total) = &progress.total {
+        // The real size replaces the estimate the sync started with
+        total.inc_length(total_size);
+        total.dec_length(progress.expected_size);
+        bars.push(total.clone());
+    }
+    let result: Result<DownloadResult> = async {
+        let mut downloaded_data =
+            crate::chunk::download_body(fetcher, url, response.body, total_size, config, &bars)
+                .await?;
         if let Some(ref pb) = pb {
-            pb.set_position(new);
+            pb.finish_and_clear();
+        }
+        let bytes = downloaded_data.len() as u64;
+
+        if let Some(pin) = pin {
+            pin.check(id, &downloaded_data)?;
+        }
+        if config.validate_decode {
+            downloaded_data = validate_decode(&decode, id, downloaded_data).await?;
+        }
+
+        let save_location = Path::new(&config.save_location);
+        tokio::fs::create_dir_all(save_location)
+            .await
+            .context("Failed to create save directory")?;
+        let mut img_format =
+            guess_format(&downloaded_data).context("Failed to detect image format")?;
+        let animated = config.animated;
+        if animated != AnimatedPolicy::Keep {
+            let (data, is_animated) = decode
+                .run(move || {
+                    let is_animated = animated::is_animated(&downloaded_data, img_format);
+                    (downloaded_data, is_animated)
+                })
+                .await?;
+            downloaded_data = data;
+            if is_animated {
+                if animated == AnimatedPolicy::Skip {
+                    return Err(crate::Error::Skipped {
+                        id: id.to_string(),
+                        reason: "animated (animated = \"skip\")".to_string(),
+                    }
+                    .into());
+                }
+                downloaded_data = decode
+                    .run(move || animated::still_frame(&downloaded_data))
+                    .await??;
+                img_format = ImageFormat::Png;
+            }
         }
+        downloaded_data = crate::checks::check_data(config, &decode, id, downloaded_data).await?;
+        let extension = get_img_extension(&img_format);
+        let file_path = file_in(save_location, &format!("{}.{}", id, extension))?;
+        let file_path_ref = file_path.as_path();
+        let mut staged = crate::staging::Staged::create(file_path_ref)
+            .await
+            .context("Failed to create file")?;
+        staged
+            .file()
+            .write_all(&downloaded_data)
+            .await
+            .context("Error writing to file")?;
+        staged.file().flush().await?;
+        crate::checks::check_file(config, id, staged.path()).await?;
+        staged
+            .commit(file_path_ref)
+            .await
+            .context("Failed to save file")?;
+
+        // Calculate Hash if requested
+        let hash = config
+            .integrity
+            .then(|| config.hash_algorithm.digest(&downloaded_data));
+
+        Ok(DownloadResult {
+            file_path: file_path.to_string_lossy().to_string(),
+            hash,
+            bytes,
+        })
+    }
+    .await;
+    if let (Err(_), Some(total)) = (&result, &progress.total) {
+        // Take a failed attempt back out of the total, so a retry starts clean
+        total.set_position(total.position().saturating_sub(counter.position()));
+        total.dec_length(total_size);
+        total.inc_length(progress.expected_size);
     }
     if let Some(ref pb) = pb {
         pb.finish_and_clear();
     }
-
-    let file_path = PathBuf::from(save_location);
-    tokio::fs::create_dir_all(&file_path)
-        .await
-        .context("Failed to create save directory")?;
-    let img_format = guess_format(&downloaded_data).context("Failed to detect image format")?;
-    let extension = get_img_extension(&img_format);
-    let file_name = format!("{}/{}.{}", save_location, id, extension);
-    let file_path_ref = Path::new(&file_name);
-    let mut file = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open(file_path_ref)
-        .await
-        .context("Failed to create file")?;
-    file.write_all(&downloaded_data)
-        .await
-        .context("Error writing to file")?;
-
-    // Calculate Hash if requested
-    let sha256 = if calculate_hash {
-        let mut hasher = Sha256::new();
-        hasher.update(&downloaded_data);
-        Some(format!("{:x}", hasher.finalize()))
-    } else {
-        None
-    };
-
-    Ok(DownloadResult {
-        file_path: file_name,
-        sha256,
-    })
+    result
 }
 
 pub fn scrape_img_link(curl_data: String) -> Result<String> {
@@ -274,7 +504,7 @@ pub async fn update_wallpapers_list_and_lock(
             let mut has_updates = false;
             for (id, location, hash) in updates {
                 if let Some(hash) = hash {
-                    lock_file.add_entry(id, location, hash);
+                    lock_file.add_entry(id, location, hash, rust_paper.config.hash_algorithm);
                     has_updates = true;
                 }
             }
@@ -288,23 +518,7 @@ pub async fn update_wallpapers_list_and_lock(
 
 /// Update the wallpaper list file with the given list of wallpapers
 pub async fn update_wallpaper_list(list: &[String], file_given: impl AsRef<Path>) -> Result<()> {
-    let file_path = file_given.as_ref();
-    let file = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open(file_path)
-        .await?;
-
-    let mut writer = BufWriter::new(file);
-
-    for wallpaper in list {
-        writer.write_all(wallpaper.as_bytes()).await?;
-        writer.write_all(b"\n").await?;
-    }
-
-    writer.flush().await?;
-    Ok(())
+    crate::list::save_ids(list, file_given).await
 }
 
 /// Get the home directory path as a string
@@ -314,6 +528,41 @@ pub fn get_home_location() -> String {
         .unwrap_or_else(|| "~".to_string())
 }
 
+/// Expand a leading `~` and `$VAR` or `${VAR}` in a configured path.
+/// Unset variables are left as written
+pub fn expand_path(path: &str) -> String {
+    let path = match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with(['/', '\\']) => match dirs::home_dir() {
+            Some(home) => format!("{}{}", home.display(), rest),
+            None => path.to_string(),
+        },
+        _ => path.to_string(),
+    };
+    let variable = regex::Regex::new(r"\$(?:\{(\w+)\}|(\w+))").expect("valid regex");
+    variable
+        .replace_all(&path, |captures: &regex::Captures| {
+            let name = captures.get(1).or_else(|| captures.get(2)).unwrap();
+            std::env::var(name.as_str()).unwrap_or_else(|_| captures[0].to_string())
+        })
+        .to_string()
+}
+
+/// Whether `name` stays inside the folder it is joined to: no path
+/// separator (either kind, as on Windows), drive, or `.` and `..`
+pub fn is_safe_file_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\', ':', '\0'])
+}
+
+/// `dir` joined with `name`, refusing a name that would land outside `dir`
+pub fn file_in(dir: impl AsRef<Path>, name: &str) -> Result<PathBuf> {
+    if !is_safe_file_name(name) {
+        return Err(
+            crate::Error::InvalidInput(format!("'{}' is not a safe file name", name)).into(),
+        );
+    }
+    Ok(dir.as_ref().join(name))
+}
+
 /// Get the configuration folder path
 pub fn get_folder_path() -> Result<PathBuf> {
     let path = confy::get_configuration_file_path("rust-paper", "config").map_err(Error::new)?;
@@ -339,9 +588,143 @@ pub fn is_url(input: &str) -> bool {
     url::Url::parse(input).is_ok()
 }
 
-/// Validate wallpaper ID format (6 alphanumeric characters)
-pub fn validate_wallpaper_id(id: &str) -> bool {
-    id.len() == 6 && id.chars().all(|c| c.is_ascii_alphanumeric())
+/// Extract a wallpaper ID from anything a user might paste: a bare ID, a
+/// `wallhaven.cc/w/<id>` or `whvn.cc/<id>` link, a full-size or thumbnail image
+/// URL, or a `like:<id>` search link. Anything else is returned trimmed, as is
+pub fn parse_wallhaven_url(input: &str) -> String {
+    let input = input.trim();
+    if !is_url(input) {
+        return input.to_string();
+    }
+    let Ok(url) = url::Url::parse(input) else {
+        return input.to_string();
+    };
+    if let Some((_, query)) = url.query_pairs().find(|(key, _)| key == "q") {
+        if let Some(id) = query.trim().strip_prefix("like:") {
+            return id.to_string();
+        }
+    }
+    let Some(last) = url
+        .path_segments()
+        .and_then(|mut segments| segments.rfind(|s| !s.is_empty()))
+    else {
+        return input.to_string();
+    };
+    // Full-size images are named wallhaven-<id>.<ext>, thumbnails <id>.<ext>
+    let stem = last.split('.').next().unwrap_or(last);
+    stem.strip_prefix("wallhaven-").unwrap_or(stem).to_string()
+}
+
+/// Add `-tag` exclusions to the `q` parameter of a search URL. Exact tag
+/// searches (`id:`) can't be combined with anything and are left alone
+pub fn exclude_tags(url: &str, tags: &[String]) -> String {
+    let exclusions: Vec<String> = tags
+        .iter()
+        .map(|tag| tag.trim().trim_start_matches('-'))
+        .filter(|tag| !tag.is_empty())
+        .map(|tag| format!("-{}", tag))
+        .collect();
+    let Some((base, query)) = url.split_once('?') else {
+        return url.to_string();
+    };
+    if exclusions.is_empty() {
+        return url.to_string();
+    }
+    let mut params: Vec<String> = query.split('&').map(String::from).collect();
+    match params.iter_mut().find(|param| param.starts_with("q=")) {
+        Some(param) if param.starts_with("q=id:") => return url.to_string(),
+        Some(param) if param.len() > 2 => {
+            param.push(' ');
+            param.push_str(&exclusions.join(" "));
+        }
+        Some(param) => param.push_str(&exclusions.join(" ")),
+        None => params.push(format!("q={}", exclusions.join(" "))),
+    }
+    format!("{}?{}", base, params.join("&"))
+}
+
+/// Parse a human duration such as "30s", "15m", "12h", "7d" or "2w"
+pub fn parse_duration(input: &str) -> Result<Duration> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| anyhow!("Missing unit in duration '{}'", input))?;
+    let (value, unit) = input.split_at(split);
+    let value: u64 = value
+        .parse()
+        .with_context(|| format!("Invalid duration '{}'", input))?;
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(anyhow!("Unknown unit '{}' in duration '{}'", unit, input)),
+    };
+    let secs = value
+        .checked_mul(multiplier)
+        .ok_or_else(|| anyhow!("Invalid duration '{}'", input))?;
+    Ok(Duration::from_secs(secs))
+}
+
+/// Move `from` to `to`, creating its folder and copying when it is on another filesystem
+pub async fn move_file(from: &Path, to: &Path) -> Result<()> {
+    if let Some(folder) = to.parent() {
+        tokio::fs::create_dir_all(folder)
+            .await
+            .with_context(|| format!("   Failed to create {}", folder.display()))?;
+    }
+    if tokio::fs::rename(from, to).await.is_err() {
+        tokio::fs::copy(from, to)
+            .await
+            .with_context(|| format!("   Failed to move {}", from.display()))?;
+        tokio::fs::remove_file(from).await?;
+    }
+    Ok(())
+}
+
+/// Last modification time of a file in unix seconds, or now if unknown
+pub async fn modified_timestamp(path: impl AsRef<Path>) -> u64 {
+    tokio::fs::metadata(path.as_ref())
+        .await
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or_else(unix_timestamp)
+}
+
+/// Format unix seconds as "YYYY-MM-DD HH:MM:SS" (UTC)
+pub fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    // Civil date from days since the epoch (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60
+    )
+}
+
+/// Current time as seconds since the Unix epoch
+pub fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
 }
 
 #[cfg(test)]
@@ -366,15 +749,66 @@ mod tests {
     }
 
     #[test]
-    fn test_validate_wallpaper_id() {
-        assert!(validate_wallpaper_id("7pmgv9"));
-        assert!(validate_wallpaper_id("abcdef"));
-        assert!(validate_wallpaper_id("123456"));
-        assert!(validate_wallpaper_id("ABC123"));
-        assert!(!validate_wallpaper_id("7pmgv")); // too short
-        assert!(!validate_wallpaper_id("7pmgv90")); // too long
-        assert!(!validate_wallpaper_id("7pmgv-9")); // invalid character
-        assert!(!validate_wallpaper_id(""));
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00:00");
+        assert_eq!(format_timestamp(1_709_210_096), "2024-02-29 12:34:56");
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("15m").unwrap(), Duration::from_secs(900));
+        assert_eq!(parse_duration("12h").unwrap(), Duration::from_secs(43_200));
+        assert_eq!(parse_duration("7d").unwrap(), Duration::from_secs(604_800));
+        assert_eq!(
+            parse_duration("2w").unwrap(),
+            Duration::from_secs(1_209_600)
+        );
+        assert!(parse_duration("7").is_err());
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("7y").is_err());
+        assert!(parse_duration("99999999999999999w").is_err());
+    }
+
+    #[test]
+    fn test_expand_path_and_file_in() {
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(
+            expand_path("~/Pictures/wall"),
+            format!("{}/Pictures/wall", home.display())
+        );
+        assert_eq!(expand_path("/srv/~user"), "/srv/~user");
+        std::env::set_var("RUST_PAPER_TEST_PICTURES", "/data/pictures");
+        assert_eq!(
+            expand_path("$RUST_PAPER_TEST_PICTURES/wall"),
+            "/data/pictures/wall"
+        );
+        assert_eq!(
+            expand_path("${RUST_PAPER_TEST_PICTURES}_old"),
+            "/data/pictures_old"
+        );
+        assert_eq!(
+            expand_path("$RUST_PAPER_UNSET/wall"),
+            "$RUST_PAPER_UNSET/wall"
+        );
+
+        let dir = Path::new("walls");
+        assert_eq!(file_in(dir, "abc123.png").unwrap(), dir.join("abc123.png"));
+        assert_eq!(
+            file_in(dir, "東京-夜.jpg").unwrap(),
+            dir.join("東京-夜.jpg")
+        );
+        for name in [
+            "",
+            ".",
+            "..",
+            "../abc123.png",
+            "a/b.png",
+            "..\\b.png",
+            "C:b.png",
+        ] {
+            assert!(file_in(dir, name).is_err(), "{}", name);
+        }
     }
 
     #[test]
@@ -389,18 +823,84 @@ mod tests {
     fn test_remove_url_extraction() {
         // Test that URLs are correctly parsed to extract wallpaper IDs
         let url = "https://wallhaven.cc/w/7pmgv9";
-        let processed = if is_url(url) {
-            url.split('/')
-                .last()
-                .unwrap_or_default()
-                .split('?')
-                .next()
-                .unwrap_or_default()
-                .to_string()
-        } else {
-            url.to_string()
-        };
+        let processed = parse_wallhaven_url(url);
         assert_eq!(processed, "7pmgv9");
-        assert!(validate_wallpaper_id(&processed));
+        assert!(crate::WallpaperId::is_valid(&processed));
+    }
+
+    #[test]
+    fn test_parse_wallhaven_url() {
+        for input in [
+            "7pmgv9",
+            " 7pmgv9 ",
+            "https://wallhaven.cc/w/7pmgv9",
+            "https://wallhaven.cc/w/7pmgv9/",
+            "https://wallhaven.cc/w/7pmgv9?ref=search#top",
+            "https://whvn.cc/7pmgv9",
+            "https://w.wallhaven.cc/full/7p/wallhaven-7pmgv9.jpg",
+            "https://th.wallhaven.cc/small/7p/7pmgv9.jpg",
+            "https://wallhaven.cc/search?q=like%3A7pmgv9&sorting=relevance",
+        ] {
+            assert_eq!(parse_wallhaven_url(input), "7pmgv9", "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_exclude_tags() {
+        let tags = vec!["anime".to_string(), "-people".to_string()];
+        assert_eq!(
+            exclude_tags(
+                "https://wallhaven.cc/api/v1/search?q=@artist&purity=100",
+                &tags
+            ),
+            "https://wallhaven.cc/api/v1/search?q=@artist -anime -people&purity=100"
+        );
+        assert_eq!(
+            exclude_tags("https://wallhaven.cc/api/v1/search?colors=660000", &tags),
+            "https://wallhaven.cc/api/v1/search?colors=660000&q=-anime -people"
+        );
+        let exact = "https://wallhaven.cc/api/v1/search?q=id:37";
+        assert_eq!(exclude_tags(exact, &tags), exact);
+        assert_eq!(exclude_tags(exact, &[]), exact);
+    }
+
+    #[test]
+    fn test_download_candidates() {
+        let url = "https://w.wallhaven.cc/full/7p/wallhaven-7pmgv9.jpg";
+        let mirrors = vec![
+            "https://mirror.example/".to_string(),
+            "https://w.wallhaven.cc".to_string(),
+        ];
+        assert_eq!(
+            download_candidates(url, &mirrors),
+            [
+                url,
+                "https://th.wallhaven.cc/full/7p/wallhaven-7pmgv9.jpg",
+                "https://mirror.example/full/7p/wallhaven-7pmgv9.jpg"
+            ]
+        );
+        assert_eq!(
+            download_candidates("https://th.wallhaven.cc/full/7p/wallhaven-7pmgv9.jpg", &[]),
+            ["https://th.wallhaven.cc/full/7p/wallhaven-7pmgv9.jpg", url]
+        );
+        // Other hosts only get the configured mirrors
+        let other = "https://example.com/wallhaven-7pmgv9.jpg";
+        assert_eq!(download_candidates(other, &[]), [other]);
+    }
+
+    #[test]
+    fn test_client_builder_checks_proxy_and_ca_bundle() {
+        let mut config = Config {
+            https_proxy: Some("http://proxy.example:3128".to_string()),
+            ..Config::default()
+        };
+        assert!(client_builder(&config).unwrap().build().is_ok());
+
+        config.http_proxy = Some("not a url".to_string());
+        assert!(client_builder(&config).is_err());
+
+        config.http_proxy = None;
+        config.ca_bundle = Some("/nonexistent/ca.pem".to_string());
+        assert!(client_builder(&config).is_err());
     }
 }
@@ -0,0 +1,65 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tokio::process::Command;
+
+use crate::output::say_err;
+
+/// User commands run on lifecycle events, executed through `sh -c`
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct HooksConfig {
+    /// Run after each wallpaper is downloaded
+    pub on_download: Option<String>,
+    /// Run once after a sync finishes
+    pub on_sync_complete: Option<String>,
+    /// Run for each file removed by `clean`
+    pub on_clean: Option<String>,
+}
+
+/// Environment describing a single wallpaper: ID, path and resolution
+pub fn wallpaper_env(id: &str, path: impl AsRef<Path>) -> Vec<(&'static str, String)> {
+    let path = path.as_ref();
+    let mut env = vec![
+        ("RUST_PAPER_ID", id.to_string()),
+        ("RUST_PAPER_PATH", path.to_string_lossy().to_string()),
+    ];
+    if let Ok((width, height)) = image::image_dimensions(path) {
+        env.push(("RUST_PAPER_RESOLUTION", format!("{}x{}", width, height)));
+    }
+    env
+}
+
+/// Run a hook command if configured; failures are reported but never abort the caller
+pub async fn run(command: Option<&str>, event: &str, env: &[(&str, String)]) {
+    let Some(command) = command.filter(|c| !c.trim().is_empty()) else {
+        return;
+    };
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("RUST_PAPER_EVENT", event)
+        .envs(env.iter().map(|(k, v)| (*k, v.as_str())))
+        .status()
+        .await;
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => say_err!("  ⚠ Hook '{}' exited with {}", event, status),
+        Err(e) => say_err!("  ⚠ Failed to run hook '{}': {}", event, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wallpaper_env_without_file() {
+        let env = wallpaper_env("7pmgv9", "/nonexistent/7pmgv9.png");
+        assert_eq!(env[0], ("RUST_PAPER_ID", "7pmgv9".to_string()));
+        assert_eq!(
+            env[1],
+            ("RUST_PAPER_PATH", "/nonexistent/7pmgv9.png".to_string())
+        );
+        assert_eq!(env.len(), 2);
+    }
+}
@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::{Error, Result};
+use crate::{helper, WALLHAVEN_API, WALLHAVEN_BASE};
+
+/// A Wallhaven wallpaper ID, validated on construction. URLs pointing at
+/// Wallhaven are only ever built from one of these
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct WallpaperId(String);
+
+impl WallpaperId {
+    /// Parse an ID, or a wallpaper page, short link or image URL naming one
+    pub fn parse(input: &str) -> Result<Self> {
+        let id = helper::parse_wallhaven_url(input.trim());
+        if Self::is_valid(&id) {
+            Ok(WallpaperId(id))
+        } else {
+            Err(Error::InvalidInput(format!(
+                "Invalid wallpaper ID format: '{}'",
+                id
+            )))
+        }
+    }
+
+    /// Whether `id` is a bare wallpaper ID (6 alphanumeric characters)
+    pub fn is_valid(id: &str) -> bool {
+        id.len() == 6 && id.chars().all(|c| c.is_ascii_alphanumeric())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The API endpoint with this wallpaper's metadata
+    pub fn api_url(&self) -> String {
+        format!("{}/{}", WALLHAVEN_API, self.0)
+    }
+
+    /// This wallpaper's page on Wallhaven
+    pub fn page_url(&self) -> String {
+        format!("{}/{}", WALLHAVEN_BASE, self.0)
+    }
+}
+
+impl fmt::Display for WallpaperId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromStr for WallpaperId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+impl TryFrom<String> for WallpaperId {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        Self::parse(&s)
+    }
+}
+
+impl From<WallpaperId> for String {
+    fn from(id: WallpaperId) -> Self {
+        id.0
+    }
+}
+
+impl AsRef<str> for WallpaperId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_wallpaper_id() {
+        assert!(WallpaperId::is_valid("7pmgv9"));
+        assert!(WallpaperId::is_valid("abcdef"));
+        assert!(WallpaperId::is_valid("123456"));
+        assert!(WallpaperId::is_valid("ABC123"));
+        assert!(!WallpaperId::is_valid("7pmgv")); // too short
+        assert!(!WallpaperId::is_valid("7pmgv90")); // too long
+        assert!(!WallpaperId::is_valid("7pmgv-9")); // invalid character
+        assert!(!WallpaperId::is_valid(""));
+    }
+
+    #[test]
+    fn test_parse_accepts_urls_and_round_trips() {
+        let id: WallpaperId = "https://wallhaven.cc/w/7pmgv9".parse().unwrap();
+        assert_eq!(id.as_str(), "7pmgv9");
+        assert_eq!(id.api_url(), "https://wallhaven.cc/api/v1/w/7pmgv9");
+        assert!(matches!(
+            WallpaperId::parse("../etc"),
+            Err(Error::InvalidInput(_))
+        ));
+
+        let json = serde_json::to_string(&id).unwrap();
+        assert_eq!(json, "\"7pmgv9\"");
+        assert_eq!(serde_json::from_str::<WallpaperId>(&json).unwrap(), id);
+        assert!(serde_json::from_str::<WallpaperId>("\"nope\"").is_err());
+    }
+}
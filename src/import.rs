@@ -0,0 +1,137 @@
+use anyhow::Context;
+use std::path::{Path, PathBuf};
+
+use crate::audit::{self, AuditEntry, AuditEvent};
+use crate::error::{Error, Result};
+use crate::group::Groups;
+use crate::helper::{self, update_wallpaper_list};
+use crate::output::say;
+use crate::{process, sidecar, RustPaper};
+
+/// Prefix of the IDs given to imported local images
+const LOCAL_PREFIX: &str = "local-";
+
+/// Whether `id` belongs to an imported local image rather than a Wallhaven wallpaper
+pub fn is_local_id(id: &str) -> bool {
+    id.starts_with(LOCAL_PREFIX)
+}
+
+/// Images directly inside `dir`, sorted by path
+async fn find_images(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut images = Vec::new();
+    let mut entries = tokio::fs::read_dir(dir)
+        .await
+        .with_context(|| format!("   Failed to read {}", dir.display()))?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path.is_file() && image::ImageFormat::from_path(&path).is_ok() {
+            images.push(path);
+        }
+    }
+    images.sort();
+    Ok(images)
+}
+
+/// Move `from` to `to`, copying when they are on different filesystems
+async fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    if tokio::fs::rename(from, to).await.is_ok() {
+        return Ok(());
+    }
+    tokio::fs::copy(from, to).await?;
+    tokio::fs::remove_file(from).await
+}
+
+impl RustPaper {
+    /// Copy (or move) the images in `dir` into the save location and track them
+    /// like downloaded wallpapers, under IDs derived from their content
+    pub async fn import_local(
+        &mut self,
+        dir: impl AsRef<Path>,
+        move_files: bool,
+        group: Option<&str>,
+    ) -> Result<()> {
+        let dir = dir.as_ref();
+        if !dir.is_dir() {
+            return Err(Error::InvalidInput(format!(
+                "'{}' is not a directory",
+                dir.display()
+            )));
+        }
+        let images = find_images(dir).await?;
+        if images.is_empty() {
+            say!("   No images found in {}", dir.display());
+            return Ok(());
+        }
+        let save_location = PathBuf::from(&self.config.save_location);
+        tokio::fs::create_dir_all(&save_location).await?;
+
+        let algorithm = self.config.hash_algorithm;
+        let mut imported = Vec::new();
+        let mut lock_updates = Vec::new();
+        let mut audit_entries = Vec::new();
+        for source in images {
+            let hash = helper::calculate_hash(&source, algorithm).await?;
+            let id = format!("{}{}", LOCAL_PREFIX, &hash[..8]);
+            if self.wallpapers.contains(&id) || imported.contains(&id) {
+                say!(
+                    "   Skipping {}: already imported as {}",
+                    source.display(),
+                    id
+                );
+                continue;
+            }
+            let extension = source
+                .extension()
+                .and_then(|e| e.to_str())
+                .unwrap_or("jpg")
+                .to_lowercase();
+            let target = save_location.join(format!("{}.{}", id, extension));
+            if move_files {
+                move_file(&source, &target).await?;
+            } else {
+                tokio::fs::copy(&source, &target).await?;
+            }
+            say!("  ✓ Imported {} - {}", source.display(), target.display());
+            if let Some(store) = self.config.store_location.as_deref() {
+                crate::store::adopt(Path::new(store), &target, &hash).await?;
+            }
+
+            if self.config.sidecar {
+                sidecar::Sidecar::local(&id, &source).write(&target).await?;
+            }
+            if let Err(e) = process::run_steps(&self.config, &self.decode_pool, &target).await {
+                say!("  ⚠ Processing failed for {}: {:#}", id, e);
+            }
+            audit_entries.push(
+                AuditEntry::new(AuditEvent::Import, &id)
+                    .path(&target)
+                    .detail(source.to_string_lossy()),
+            );
+            lock_updates.push((id.clone(), target.to_string_lossy().to_string(), hash));
+            imported.push(id);
+        }
+
+        if self.config.integrity && !lock_updates.is_empty() {
+            let mut lock_file_guard = self.lock_file.lock().await;
+            if let Some(lock_file) = lock_file_guard.as_mut() {
+                for (id, location, hash) in lock_updates {
+                    lock_file.add_entry(id, location, hash, algorithm);
+                }
+                lock_file.save().await?;
+            }
+        }
+        audit::append(&self.audit_log_location, &audit_entries).await?;
+
+        if let Some(name) = group {
+            let mut groups = Groups::load(&self.groups_file_location).await?;
+            groups.add(name, &imported)?;
+            groups.save(&self.groups_file_location).await?;
+        }
+
+        say!("   Imported {} image(s)", imported.len());
+        self.wallpapers.extend(imported);
+        self.wallpapers.sort_unstable();
+        update_wallpaper_list(&self.wallpapers, &self.wallpapers_list_file_location).await?;
+        Ok(())
+    }
+}
@@ -1,29 +1,126 @@
-use anyhow::{Context, Result};
-use futures::stream::{self, FuturesUnordered, StreamExt};
-use indicatif::MultiProgress;
-use reqwest::Client;
-use serde_json::Value;
+use futures::channel::mpsc;
+use futures::stream::{self, Stream, StreamExt};
+use indicatif::{HumanBytes, HumanDuration, MultiProgress, ProgressBar, ProgressStyle};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::fs::{create_dir_all, File};
-use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
 use tokio::sync::{Mutex, Semaphore};
 use tokio::time::sleep;
 
+mod animated;
 mod api;
 mod args;
+mod audit;
+mod backup;
+mod blocklist;
+mod breaker;
+mod builder;
+mod checks;
+mod chunk;
+mod clean;
+mod conditions;
 mod config;
+mod credit;
+mod current;
+mod decode;
+mod diff;
+mod display;
+mod doctor;
+mod error;
+mod events;
+mod fetch;
+mod follow;
+mod group;
+mod hash;
 mod helper;
+mod hooks;
+mod id;
+mod import;
+mod list;
 mod lock;
+mod manifest;
+mod migrate;
+pub mod output;
+mod paths;
+mod pick;
+mod pin;
+mod plan;
+#[cfg(feature = "preview")]
+mod preview;
+mod process;
+mod purity;
+mod quarantine;
+mod report;
+mod run_lock;
+#[cfg(feature = "s3")]
+mod s3;
+mod search_cache;
+mod seen;
+mod serve;
+mod sidecar;
+mod source;
+mod space;
+mod staging;
+mod stale;
+mod storage;
+mod store;
+#[cfg(feature = "test-support")]
+pub mod test_support;
+mod tombstone;
+mod watch;
+#[cfg(feature = "webdav")]
+mod webdav;
 
-use lock::LockFile;
+use audit::{AuditEntry, AuditEvent};
+use breaker::{CircuitBreaker, GaveUp, RetryBudget, Trip};
+use decode::DecodePool;
+use display::Resolution;
+use follow::Follows;
+use group::Groups;
+use list::WallpaperList;
+use lock::{LockEntry, LockFile};
+use pin::{Pin, Pins};
+use run_lock::RunLock;
+use seen::Seen;
+use source::{SourceState, FOLLOW_SOURCE, MANUAL_SOURCE};
+use tombstone::Tombstones;
 
-use crate::helper::{get_key_from_config_or_env, update_wallpaper_list};
+use crate::helper::update_wallpaper_list;
+use crate::output::{say, say_err, styled};
 
-pub use api::{WallhavenClient, WallhavenClientError};
-pub use args::{Cli, Command};
+pub use animated::AnimatedPolicy;
+pub use api::{
+    Avatar, DownloadQuality, Tag, Thumbs, Uploader, WallhavenClient, WallhavenClientError,
+    WallhavenWallpaper,
+};
+pub use args::{
+    BackupAction, Cli, Command, ConfigAction, GroupAction, QuarantineAction, SeenAction,
+};
+pub use builder::RustPaperBuilder;
+pub use checks::DownloadChecks;
+pub use clean::CleanOptions;
+pub use conditions::SyncConditions;
+pub use config::{Config, ConfigPatch, SearchPreset};
+pub use current::CurrentWallpaper;
+pub use error::{Error, Result};
+pub use events::SyncEvent;
+pub use fetch::{FetchResponse, HttpFetcher, ReqwestFetcher};
+pub use id::WallpaperId;
+pub use manifest::Manifest;
+pub use migrate::ImportTool;
+pub use paths::Paths;
+#[cfg(feature = "preview")]
+pub use preview::Protocol;
+pub use process::VariantLayout;
+pub use purity::PurityViolation;
+pub use quarantine::QuarantinedFile;
+pub use report::{DownloadStats, LastSync, SyncFailure, SyncReport};
+pub use run_lock::LockMode;
+pub use stale::{StaleAction, StaleWallpaper};
+pub use storage::{LocalStorage, Storage, StorageConfig};
 
 pub const WALLHAVEN_API: &str = "https://wallhaven.cc/api/v1/w";
 pub const WALLHAVEN_BASE: &str = "https://wallhaven.cc/w";
@@ -31,30 +128,110 @@ pub const WALLHAVEN_BASE: &str = "https://wallhaven.cc/w";
 /// Main RustPaper struct for managing wallpapers
 pub struct RustPaper {
     pub config: config::Config,
+    /// File the config was loaded from, reloaded by `watch` (none when passed in)
+    pub config_file: Option<PathBuf>,
     pub config_folder: PathBuf,
+    /// Folder holding the list, lock file, state files and cache
+    pub data_folder: PathBuf,
     pub wallpapers: Vec<String>,
     pub wallpapers_list_file_location: PathBuf,
+    pub source_state_file_location: PathBuf,
+    pub groups_file_location: PathBuf,
+    pub follows_file_location: PathBuf,
+    pub audit_log_location: PathBuf,
+    pub metadata_cache_location: PathBuf,
+    pub tombstones_file_location: PathBuf,
+    /// Wallpapers followed uploaders have offered, so they are only added once
+    pub seen_file_location: PathBuf,
+    /// Summary of the most recent sync, for monitoring
+    pub last_sync_location: PathBuf,
+    pub pins_file_location: PathBuf,
+    /// Wallpapers that must never be added again
+    pub blocklist_file_location: PathBuf,
+    /// The wallpaper each monitor currently shows
+    pub current_file_location: PathBuf,
+    /// Where files that fail their integrity check are moved before re-downloading
+    pub integrity_quarantine_location: PathBuf,
+    /// Tracked and removed IDs for `pull` on other machines, rewritten after every sync
+    pub manifest_file_location: PathBuf,
+    /// Recent search responses, reused within `search_cache_ttl`
+    pub search_cache_location: PathBuf,
+    /// Skip every network request and serve from local state only
+    pub offline: bool,
+    /// File sizes learned from search results, used to estimate a sync's size
+    pub(crate) expected_sizes: HashMap<String, u64>,
     pub lock_file: Arc<Mutex<Option<LockFile>>>,
-    pub http_client: Client,
+    pub fetcher: Arc<dyn HttpFetcher>,
     pub download_semaphore: Arc<Semaphore>,
+    /// Backends downloaded wallpapers are mirrored to, from `storage_mirrors`
+    pub storage_mirrors: Vec<Arc<dyn Storage>>,
+    /// Threads decoding and converting images, sized by `decode_threads`
+    pub(crate) decode_pool: Arc<DecodePool>,
+    /// Receives progress while `sync_with_events` runs
+    pub(crate) events: Option<mpsc::UnboundedSender<SyncEvent>>,
+    /// Held for the lifetime of the instance to keep other runs out
+    _run_lock: Option<RunLock>,
+    /// How to take the run lock around each sync or change instead, when
+    /// built with `lock_per_operation`
+    operation_lock_mode: Option<LockMode>,
 }
 
-/// INFO: Build a map of wallpaper IDs to file paths (cached directory listing)
-async fn build_file_map(save_location: &str) -> Result<HashMap<String, PathBuf>> {
-    let save_path = Path::new(save_location);
-    let mut file_map = HashMap::new();
-    if !save_path.exists() {
-        return Ok(file_map);
+/// Whether `path` is an image, judged by its extension or else by its first bytes
+async fn is_image_file(path: &Path) -> bool {
+    if sidecar::is_sidecar(path) {
+        return false;
     }
-    let mut entries = tokio::fs::read_dir(save_path).await?;
-    while let Some(entry) = entries.next_entry().await? {
-        let path = entry.path();
-        if path.is_file() {
-            if let Some(file_stem) = path.file_stem().and_then(|s| s.to_str()) {
-                file_map.insert(file_stem.to_string(), path);
+    if image::ImageFormat::from_path(path).is_ok() {
+        return true;
+    }
+    let Ok(mut file) = File::open(path).await else {
+        return false;
+    };
+    let mut header = [0u8; 32];
+    match file.read(&mut header).await {
+        Ok(read) => image::guess_format(&header[..read]).is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Image files in the save location. Subfolders are only searched with
+/// `scan_subfolders`, and never the output folders of processing steps
+async fn scan_save_location(config: &config::Config) -> anyhow::Result<Vec<PathBuf>> {
+    let root = PathBuf::from(&config.save_location);
+    let mut files = Vec::new();
+    if !root.exists() {
+        return Ok(files);
+    }
+    let outputs = process::variant_roots(config);
+    let mut pending = vec![root];
+    while let Some(dir) = pending.pop() {
+        let mut entries = tokio::fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if entry.file_type().await?.is_dir() {
+                // Unfinished downloads live in the staging folder
+                if config.scan_subfolders
+                    && !outputs.contains(&path)
+                    && entry.file_name() != staging::STAGING_DIR
+                {
+                    pending.push(path);
+                }
+            } else if path.is_file() && is_image_file(&path).await {
+                files.push(path);
             }
         }
     }
+    Ok(files)
+}
+
+/// INFO: Build a map of wallpaper IDs to file paths (cached directory listing)
+async fn build_file_map(config: &config::Config) -> anyhow::Result<HashMap<String, PathBuf>> {
+    let mut file_map = HashMap::new();
+    for path in scan_save_location(config).await? {
+        if let Some(file_stem) = path.file_stem().and_then(|s| s.to_str()) {
+            file_map.insert(file_stem.to_string(), path.clone());
+        }
+    }
     Ok(file_map)
 }
 
@@ -62,57 +239,76 @@ async fn build_file_map(save_location: &str) -> Result<HashMap<String, PathBuf>>
 struct ProcessResult {
     wallpaper_id: String,
     image_location: String,
-    sha256: Option<String>,
+    image_url: String,
+    hash: Option<String>,
+    bytes: u64,
+    processing_error: Option<String>,
 }
 
+/// Download one wallpaper. `metadata` comes from the metadata phase of the
+/// sync (see `plan_downloads`); without it the wallpaper page is scraped
 async fn process_wallpaper_optimized(
     config: &config::Config,
+    pin: Option<&Pin>,
     wallpaper: &str,
-    client: &Client,
-    show_progress: bool,
-    multi_progress: Option<MultiProgress>,
-) -> Result<ProcessResult> {
-    let img_link: String = if let Some(api_key) = config.api_key.as_deref() {
-        let wallhaven_img_link = format!("{}/{}", WALLHAVEN_API, wallpaper.trim());
-        let curl_data = retry_get_curl_content(
-            &wallhaven_img_link,
-            client,
-            Some(api_key),
-            config.retry_count,
-        )
-        .await?;
-        let res: Value = serde_json::from_str(&curl_data)?;
-        if let Some(error) = res.get("error") {
-            eprintln!("Error : {}", error);
-            return Err(anyhow::anyhow!("❌ API error: {}", error));
-        }
-        res.get("data")
-            .and_then(|data| data.get("path"))
-            .and_then(Value::as_str)
-            .ok_or_else(|| anyhow::anyhow!("Failed to get image link from API response"))?
-            .to_string()
+    fetcher: &dyn HttpFetcher,
+    metadata: Option<WallhavenWallpaper>,
+    minimum: Option<Resolution>,
+    progress: helper::DownloadProgress,
+) -> anyhow::Result<ProcessResult> {
+    let id = WallpaperId::parse(wallpaper)?;
+    let img_link: String = if let Some(data) = &metadata {
+        config
+            .download_quality
+            .url(id.as_str(), &data.path, Some(&data.thumbs))
     } else {
-        let wallhaven_img_link = format!("{}/{}", WALLHAVEN_BASE, wallpaper.trim());
         let curl_data =
-            retry_get_curl_content(&wallhaven_img_link, client, None, config.retry_count).await?;
-        helper::scrape_img_link(curl_data)?
+            retry_get_curl_content(&id.page_url(), fetcher, None, config.retry_count).await?;
+        let full = helper::scrape_img_link(curl_data)?;
+        config.download_quality.url(id.as_str(), &full, None)
     };
-    match helper::download_with_progress(
-        &img_link,
-        wallpaper,
-        &config.save_location,
-        client,
-        config.integrity,
-        show_progress,
-        multi_progress,
-    )
-    .await
+    let progress_decode = progress.decode.clone();
+    // Pins hash the original file, so a thumbnail can't be checked against one
+    let pin = pin.filter(|_| config.download_quality == api::DownloadQuality::Original);
+    match helper::download_with_fallback(&img_link, wallpaper, config, pin, fetcher, true, progress)
+        .await
     {
-        Ok(result) => Ok(ProcessResult {
-            wallpaper_id: wallpaper.to_string(),
-            image_location: result.file_path,
-            sha256: result.sha256,
-        }),
+        Ok(result) => {
+            // Without metadata the size is only known once downloaded.
+            // Thumbnails are smaller on purpose, so only originals are checked
+            if let Some(display) = minimum.filter(|_| {
+                metadata.is_none() && config.download_quality == api::DownloadQuality::Original
+            }) {
+                let (width, height) = image::image_dimensions(&result.file_path)?;
+                if !display.fits_within(width, height) {
+                    let _ = tokio::fs::remove_file(&result.file_path).await;
+                    return Err(Error::TooSmall {
+                        id: wallpaper.to_string(),
+                        resolution: format!("{}x{}", width, height),
+                        display: display.to_string(),
+                    }
+                    .into());
+                }
+            }
+            if config.sidecar {
+                sidecar::Sidecar::new(wallpaper, &img_link, metadata.as_ref())
+                    .write(&result.file_path)
+                    .await?;
+            }
+            let processing_error = process::run_steps(config, &progress_decode, &result.file_path)
+                .await
+                .err()
+                .map(|e| format!("{:#}", e));
+            Ok(ProcessResult {
+                wallpaper_id: wallpaper.to_string(),
+                image_location: result.file_path,
+                image_url: img_link,
+                hash: result.hash,
+                bytes: result.bytes,
+                processing_error,
+            })
+        }
+        Err(e) if helper::is_skipped(&e) || helper::is_pin_mismatch(&e) => Err(e),
         Err(e) => Err(anyhow::anyhow!("Failed to download {}: {}", &wallpaper, e)),
     }
 }
@@ -125,77 +321,461 @@ impl RustPaper {
 
     /// Create a new RustPaper instance with loaded configuration
     pub async fn new() -> Result<Self> {
-        let config: config::Config =
-            confy::load("rust-paper", "config").context("   Failed to load configuration")?;
+        Self::builder().build().await
+    }
 
-        let config_folder = helper::get_folder_path().context("   Failed to get folder path")?;
+    /// Start building a RustPaper instance with custom config, paths or HTTP client
+    pub fn builder() -> RustPaperBuilder {
+        RustPaperBuilder::default()
+    }
 
-        tokio::try_join!(
-            create_dir_all(&config_folder),
-            create_dir_all(&config.save_location)
-        )?;
+    /// Names of all sources that can be synced
+    pub fn source_names(&self) -> Vec<String> {
+        vec![MANUAL_SOURCE.to_string(), FOLLOW_SOURCE.to_string()]
+    }
 
-        let wallpapers_list_file_location = config_folder.join("wallpapers.lst");
-        let wallpapers = load_wallpapers(&wallpapers_list_file_location).await?;
+    /// Sync every enabled source that is due, or only `only_source` when given
+    pub async fn sync(
+        &mut self,
+        only_source: Option<&str>,
+        options: &SyncOptions,
+    ) -> Result<SyncReport> {
+        let started = std::time::Instant::now();
+        let started_at = helper::unix_timestamp();
+        let result: Result<SyncReport> = async {
+            let mut report = self.sync_sources(only_source, options).await?;
+            if !options.dry_run {
+                self.apply_stale_policy().await;
+                report
+                    .failures
+                    .extend(self.mirror_wallpapers(&self.wallpapers).await?);
+                self.write_manifest().await?;
+            }
+            Ok(report)
+        }
+        .await;
+        self.record_last_sync(
+            only_source.unwrap_or("all"),
+            options,
+            started_at,
+            started,
+            &result,
+        )
+        .await;
+        result
+    }
 
-        let lock_file = if config.integrity {
-            Some(LockFile::load_or_new().await)
-        } else {
-            None
+    /// Write `last_sync.json`; failing to do so only warns, so monitoring can't break a sync.
+    /// Dry runs are not recorded
+    async fn record_last_sync(
+        &self,
+        scope: &str,
+        options: &SyncOptions,
+        started_at: u64,
+        started: std::time::Instant,
+        result: &Result<SyncReport>,
+    ) {
+        if options.dry_run {
+            return;
+        }
+        let last_sync = LastSync {
+            started_at,
+            finished_at: helper::unix_timestamp(),
+            duration_ms: started.elapsed().as_millis() as u64,
+            scope: scope.to_string(),
+            report: result.as_ref().ok().cloned(),
+            error: result.as_ref().err().map(|e| e.to_string()),
         };
-        let api_key = get_key_from_config_or_env(config.api_key.as_deref());
-        let http_client = helper::create_http_client(config.timeout, api_key.as_ref())?;
-        let download_semaphore = Arc::new(Semaphore::new(config.max_concurrent_downloads));
-
-        Ok(Self {
-            config,
-            config_folder,
-            wallpapers,
-            wallpapers_list_file_location,
-            lock_file: Arc::new(Mutex::new(lock_file)),
-            http_client,
-            download_semaphore,
+        if let Err(e) = last_sync.save(&self.last_sync_location).await {
+            say_err!("  ⚠ {:#}", e);
+        }
+    }
+
+    /// Summary of the most recent sync, if any
+    pub async fn last_sync(&self) -> Result<Option<LastSync>> {
+        Ok(LastSync::load(&self.last_sync_location).await?)
+    }
+
+    /// Print the summary of the most recent sync, as JSON with `json`
+    pub async fn status(&self, json: bool) -> Result<()> {
+        let last_sync = self.last_sync().await?;
+        if json {
+            // Machine-readable, so printed as is even with --plain
+            println!("{}", serde_json::to_string_pretty(&last_sync)?);
+            return Ok(());
+        }
+        let Some(last_sync) = last_sync else {
+            say!("   No sync has been recorded yet.");
+            return Ok(());
+        };
+        say!(
+            "  Last sync ({}): {} UTC, took {:.1}s",
+            last_sync.scope,
+            helper::format_timestamp(last_sync.finished_at),
+            last_sync.duration_ms as f64 / 1000.0
+        );
+        if let Some(error) = &last_sync.error {
+            say!("  ✗ Stopped early: {}", error);
+        }
+        if let Some(report) = &last_sync.report {
+            say!(
+                "  Downloaded: {} ({:.2} MB, {}/s)",
+                report.downloaded,
+                report.bytes_downloaded as f64 / 1_048_576.0,
+                HumanBytes(report.bytes_per_sec())
+            );
+            say!("  Failed: {}", report.failed);
+            say!("  Integrity failures: {}", report.integrity_failures);
+            say!("  Skipped offline: {}", report.skipped_offline);
+            say!("  Deferred: {}", report.deferred);
+            say!("  Skipped animated: {}", report.skipped_animated);
+            say!("  Skipped too small: {}", report.skipped_small);
+            say!("  Skipped too large: {}", report.skipped_large);
+            say!("  Rejected by download_checks: {}", report.rejected);
+            say!("  Unchecked uploaders: {}", report.unchecked_uploaders);
+            for failure in &report.failures {
+                say!("  ✗ {}: {}", failure.id, failure.reason);
+            }
+            for drift in &report.archive_drift {
+                say!("  ✗ {} (archived): {}", drift.id, drift.reason);
+            }
+        }
+        Ok(())
+    }
+
+    async fn sync_sources(
+        &mut self,
+        only_source: Option<&str>,
+        options: &SyncOptions,
+    ) -> Result<SyncReport> {
+        let source_names = self.source_names();
+        let mut state = SourceState::load(&self.source_state_file_location).await;
+        let now = helper::unix_timestamp();
+
+        let due: Vec<String> = match only_source {
+            Some(name) => {
+                if !source_names.iter().any(|s| s == name) {
+                    return Err(Error::InvalidInput(format!(
+                        "Unknown source '{}' (available: {})",
+                        name,
+                        source_names.join(", ")
+                    )));
+                }
+                vec![name.to_string()]
+            }
+            None => {
+                let mut due = Vec::new();
+                for name in source_names {
+                    let source_config = self.config.sources.get(&name).cloned().unwrap_or_default();
+                    if state.is_due(&name, &source_config, now)? {
+                        due.push(name);
+                    } else {
+                        say!("   Skipping source '{}': disabled or not due yet", name);
+                    }
+                }
+                due
+            }
+        };
+
+        if due.is_empty() {
+            say!("   No sources due for sync.");
+            return Ok(SyncReport::default());
+        }
+
+        let mut report = SyncReport::default();
+        for name in &due {
+            self.emit(SyncEvent::Started {
+                source: name.clone(),
+            });
+            let (source_report, complete) = match name.as_str() {
+                MANUAL_SOURCE => {
+                    let source_report = self.sync_wallpapers(&self.wallpapers, options).await?;
+                    let complete =
+                        source_report.skipped_offline == 0 && source_report.deferred == 0;
+                    (source_report, complete)
+                }
+                FOLLOW_SOURCE if options.dry_run => {
+                    say!("   Skipping source '{}' in a dry run", name);
+                    continue;
+                }
+                FOLLOW_SOURCE => self.sync_follows(options).await?,
+                _ => (SyncReport::default(), true),
+            };
+            if complete && !options.dry_run {
+                state.mark_synced(name, now);
+            }
+            report.merge(source_report);
+        }
+        if !options.dry_run {
+            state.save(&self.source_state_file_location).await?;
+        }
+        Ok(report)
+    }
+
+    /// Like `sync`, but also reports progress as a stream of events ending
+    /// with `SyncEvent::Completed`. The sync only runs while the stream is polled
+    pub fn sync_with_events<'a>(
+        &'a mut self,
+        only_source: Option<&'a str>,
+        options: &'a SyncOptions,
+    ) -> impl Stream<Item = SyncEvent> + 'a {
+        let (sender, receiver) = mpsc::unbounded();
+        self.events = Some(sender);
+        // Drives the sync without yielding anything itself; sending `Completed`
+        // through the channel keeps it behind every other event
+        let driver = stream::once(async move {
+            let result = self.sync(only_source, options).await;
+            if let Some(events) = self.events.take() {
+                let _ = events.unbounded_send(SyncEvent::Completed(result));
+            }
         })
+        .filter_map(|()| futures::future::ready(None));
+        stream::select(receiver, driver)
     }
 
-    /// Sync all wallpapers in the list
-    pub async fn sync(&self) -> Result<()> {
-        let file_map = build_file_map(&self.config.save_location).await?;
-        let lock_file_map: Option<HashMap<String, (String, String)>> = if self.config.integrity {
-            let lock_file_guard = self.lock_file.lock().await;
-            if let Some(ref lock_file) = *lock_file_guard {
-                Some(
-                    lock_file
-                        .entries()
-                        .iter()
-                        .map(|e| {
-                            (
-                                e.image_id().to_string(),
-                                (e.image_location().to_string(), e.image_sha256().to_string()),
-                            )
-                        })
-                        .collect(),
-                )
-            } else {
-                None
+    fn emit(&self, event: SyncEvent) {
+        if let Some(events) = &self.events {
+            let _ = events.unbounded_send(event);
+        }
+    }
+
+    /// Add new uploads of followed users to the list and download them.
+    /// The flag is false when some uploader or download had to be skipped
+    async fn sync_follows(&mut self, options: &SyncOptions) -> anyhow::Result<(SyncReport, bool)> {
+        let mut report = SyncReport::default();
+        let mut follows = Follows::load(&self.follows_file_location).await?;
+        let usernames = follows.usernames();
+        if usernames.is_empty() {
+            return Ok((report, true));
+        }
+        if self.offline {
+            say!(
+                "   Offline: not checking {} followed uploader(s)",
+                usernames.len()
+            );
+            return Ok((report, false));
+        }
+
+        let api_key = helper::get_key_from_config_or_env(self.config.api_key.as_deref());
+        let minimum = match self.config.follow.atleast {
+            Some(_) => None,
+            None => self.minimum_resolution().await,
+        };
+        let mut seen = Seen::load(&self.seen_file_location).await?;
+        let now = helper::unix_timestamp();
+        let mut new_wallpapers = Vec::new();
+        for username in &usernames {
+            let mut url = helper::exclude_tags(
+                &self.config.follow.search_url(api::BASE_URL, username),
+                &self.config.exclude_tags,
+            );
+            if let Some(display) = minimum {
+                url.push_str(&format!("&atleast={}", display));
+            }
+            let result = api::fetch_all_pages(
+                self.fetcher.as_ref(),
+                &url,
+                api_key.as_deref(),
+                self.config.retry_count,
+                Some(self.config.follow.max_pages.unwrap_or(1)),
+                |page, last_page| {
+                    if last_page > 1 {
+                        say!(
+                            "   Checked page {} of {} for @{}",
+                            page,
+                            last_page,
+                            username
+                        );
+                    }
+                },
+            )
+            .await;
+            match result {
+                Ok(uploads) => {
+                    let uploads = uploads.into_iter().filter_map(|w| {
+                        match self.config.content_violation(&w.purity, &w.category) {
+                            None => {
+                                self.expected_sizes
+                                    .insert(w.id.clone(), w.file_size.max(0) as u64);
+                                Some(w.id)
+                            }
+                            Some(reason) => {
+                                say!("   Skipping {} from @{}: {}", w.id, username, reason);
+                                None
+                            }
+                        }
+                    });
+                    let mut fresh = follows.record(username, uploads);
+                    // Also offered by another uploader's list or before an unfollow
+                    fresh.retain(|id| seen.offer(id, now));
+                    if !fresh.is_empty() {
+                        say!("   {} new wallpaper(s) from @{}", fresh.len(), username);
+                    }
+                    new_wallpapers.extend(fresh);
+                }
+                Err(e) => {
+                    say_err!("  ✗ Failed to check uploads of @{}: {:#}", username, e);
+                    report.unchecked_uploaders += 1;
+                }
             }
+        }
+        follows.save(&self.follows_file_location).await?;
+        seen.save(&self.seen_file_location).await?;
+
+        new_wallpapers.retain(|id| !self.wallpapers.contains(id));
+        self.drop_blocked(&mut new_wallpapers).await?;
+        if !self.config.exclude_tags.is_empty() {
+            new_wallpapers = self.drop_excluded_tags(new_wallpapers).await;
+        }
+        if !new_wallpapers.is_empty() {
+            self.wallpapers.extend(new_wallpapers.iter().cloned());
+            self.wallpapers.sort_unstable();
+            self.wallpapers.dedup();
+            update_wallpaper_list(&self.wallpapers, &self.wallpapers_list_file_location).await?;
+            report.merge(self.sync_wallpapers(&new_wallpapers, options).await?);
+        }
+        let complete =
+            report.skipped_offline == 0 && report.deferred == 0 && report.unchecked_uploaders == 0;
+        Ok((report, complete))
+    }
+
+    /// Start tracking the uploads of a Wallhaven user
+    pub async fn follow(&self, username: &str) -> Result<()> {
+        let mut follows = Follows::load(&self.follows_file_location).await?;
+        follows
+            .follow(username)
+            .map_err(|e| Error::InvalidInput(e.to_string()))?;
+        follows.save(&self.follows_file_location).await?;
+        say!(
+            "   Following @{}; their uploads are added on the next sync",
+            username.trim_start_matches('@')
+        );
+        Ok(())
+    }
+
+    /// Stop tracking a Wallhaven user; wallpapers already added stay in the list
+    pub async fn unfollow(&self, username: &str) -> Result<()> {
+        let mut follows = Follows::load(&self.follows_file_location).await?;
+        follows
+            .unfollow(username)
+            .map_err(|e| Error::InvalidInput(e.to_string()))?;
+        follows.save(&self.follows_file_location).await?;
+        say!("   Unfollowed @{}", username.trim_start_matches('@'));
+        Ok(())
+    }
+
+    /// Print every followed uploader
+    pub async fn following(&self) -> Result<()> {
+        let follows = Follows::load(&self.follows_file_location).await?;
+        let usernames = follows.usernames();
+        if usernames.is_empty() {
+            say!("   Not following anyone.");
+        }
+        for username in usernames {
+            say!("  @{}", username);
+        }
+        Ok(())
+    }
+
+    /// Re-run processing for verified wallpapers missing one of the configured
+    /// variants. Variants are rebuilt from the original instead of being hashed
+    async fn regenerate_missing_variants(
+        &self,
+        wallpapers: &[String],
+        file_map: &HashMap<String, PathBuf>,
+        needs_download: &[String],
+    ) {
+        let steps = self.config.process_steps();
+        if steps.is_empty() {
+            return;
+        }
+        let variants = process::variant_map(&self.config).await;
+        for id in wallpapers.iter().filter(|id| !needs_download.contains(id)) {
+            let Some(path) = file_map.get(id) else {
+                continue;
+            };
+            let existing = variants.get(id).map(Vec::as_slice).unwrap_or_default();
+            if steps
+                .iter()
+                .all(|step| existing.iter().any(|v| v.step == step.name))
+            {
+                continue;
+            }
+            match process::run_steps(&self.config, &self.decode_pool, path).await {
+                Ok(_) => say!("  ✓ Regenerated missing variants of {}", id),
+                Err(e) => say_err!("  ⚠ Processing failed for {}: {:#}", id, e),
+            }
+        }
+    }
+
+    /// Download or verify the given wallpapers
+    async fn sync_wallpapers(
+        &self,
+        wallpapers: &[String],
+        options: &SyncOptions,
+    ) -> anyhow::Result<SyncReport> {
+        let mut report = SyncReport::default();
+        let file_map = build_file_map(&self.config).await?;
+        let lock_file_map: Option<HashMap<String, LockEntry>> = if self.config.integrity {
+            let lock_file_guard = self.lock_file.lock().await;
+            lock_file_guard.as_ref().map(|lock_file| {
+                lock_file
+                    .entries()
+                    .iter()
+                    .map(|e| (e.image_id().to_string(), e.clone()))
+                    .collect()
+            })
         } else {
             None
         };
+        let recheck_after = match self.config.recheck_after.as_deref() {
+            Some(window) if !options.full_check => Some(
+                helper::parse_duration(window)
+                    .map_err(|e| e.context("Invalid recheck_after"))?
+                    .as_secs(),
+            ),
+            _ => None,
+        };
+        let now = helper::unix_timestamp();
+        let mut recently_checked = 0;
+        let store = self.config.store_location.as_ref().map(PathBuf::from);
+        let mut linked_to_store = 0;
+        let mut tombstones = Tombstones::load(&self.tombstones_file_location).await?;
+        let mut gone_upstream = 0;
+        let archived = Groups::load(&self.groups_file_location)
+            .await?
+            .archived_ids();
 
         let mut needs_download = Vec::new();
         let mut integrity_checks = Vec::new();
-        for wallpaper in &self.wallpapers {
+        for wallpaper in wallpapers {
             if let Some(existing_path) = file_map.get(wallpaper) {
                 if self.config.integrity {
                     if let Some(ref lock_map) = lock_file_map {
-                        if let Some((lock_location, expected_sha256)) = lock_map.get(wallpaper) {
+                        if let Some(entry) = lock_map.get(wallpaper) {
                             let path_str = existing_path.to_string_lossy().to_string();
-                            if lock_location == &path_str {
+                            if entry.image_location() == path_str {
+                                if store.as_deref().is_some_and(|store| {
+                                    store::is_linked(store, entry.image_hash(), existing_path)
+                                }) {
+                                    linked_to_store += 1;
+                                    continue;
+                                }
+                                // Archived wallpapers are always hashed in full
+                                if let Some(window) =
+                                    recheck_after.filter(|_| !archived.contains(wallpaper))
+                                {
+                                    let modified = helper::modified_timestamp(existing_path).await;
+                                    if entry.is_fresh(window, modified, now) {
+                                        recently_checked += 1;
+                                        continue;
+                                    }
+                                }
                                 integrity_checks.push((
                                     wallpaper.clone(),
                                     existing_path.clone(),
-                                    expected_sha256.clone(),
+                                    entry.image_hash().to_string(),
+                                    entry.algorithm(),
                                 ));
                                 continue;
                             }
@@ -203,204 +783,856 @@ impl RustPaper {
                     }
                     needs_download.push(wallpaper.clone());
                 } else {
-                    println!("   Skipping {}: already exists", wallpaper);
+                    say!("   Skipping {}: already exists", wallpaper);
                 }
+            } else if tombstones.contains(wallpaper) {
+                gone_upstream += 1;
             } else {
                 needs_download.push(wallpaper.clone());
             }
         }
+        if gone_upstream > 0 {
+            say!(
+                "   Skipping {} wallpaper(s) removed from Wallhaven; `rust-paper clean --tombstones` drops them",
+                gone_upstream
+            );
+        }
 
+        if options.dry_run && !integrity_checks.is_empty() {
+            say!(
+                "   Not verifying {} file(s) in a dry run",
+                integrity_checks.len()
+            );
+            integrity_checks.clear();
+        }
         if !integrity_checks.is_empty() {
-            let check_tasks: FuturesUnordered<_> = integrity_checks
-                .into_iter()
-                .map(|(wallpaper_id, path, expected_hash)| {
-                    tokio::spawn(async move {
-                        match helper::calculate_sha256(&path).await {
-                            Ok(actual_sha256) => {
-                                if actual_sha256 == expected_hash {
-                                    Ok::<(String, bool), anyhow::Error>((wallpaper_id, false))
-                                } else {
-                                    println!(
-                                        "   Integrity check failed for {}: re-downloading",
-                                        wallpaper_id
-                                    );
-                                    Ok::<(String, bool), anyhow::Error>((wallpaper_id, true))
-                                }
-                            }
-                            Err(_) => Ok::<(String, bool), anyhow::Error>((wallpaper_id, true)),
-                        }
-                    })
+            let pb = ProgressBar::new(integrity_checks.len() as u64);
+            pb.set_style(
+                ProgressStyle::with_template("{msg} [{wide_bar:.cyan/blue}] {pos}/{len}")
+                    .unwrap()
+                    .progress_chars("#>-"),
+            );
+            pb.set_message("Verifying");
+            // Hash on as many workers as there are cores, never one task per file
+            let workers = std::thread::available_parallelism().map_or(4, |n| n.get());
+            let mut checks = stream::iter(integrity_checks)
+                .map(|(wallpaper_id, path, expected_hash, algorithm)| {
+                    let check = tokio::spawn(async move {
+                        let actual_hash = helper::calculate_hash(&path, algorithm).await?;
+                        anyhow::Ok(actual_hash == expected_hash)
+                    });
+                    async move { (wallpaper_id, check.await) }
                 })
-                .collect();
-
-            let mut check_tasks = check_tasks;
-            while let Some(result) = check_tasks.next().await {
-                match result {
-                    Ok(Ok((wallpaper_id, should_download))) => {
-                        if should_download {
-                            needs_download.push(wallpaper_id);
-                        }
-                    }
-                    _ => {
-                        unreachable!()
+                .buffer_unordered(workers);
+
+            let mut verified = Vec::new();
+            let mut failures = Vec::new();
+            let mut corrupt = Vec::new();
+            while let Some((wallpaper_id, outcome)) = checks.next().await {
+                pb.inc(1);
+                let error = match outcome {
+                    Ok(Ok(true)) => {
+                        verified.push(wallpaper_id);
+                        continue;
                     }
+                    Ok(Ok(false)) => None,
+                    Ok(Err(e)) => Some(format!("{:#}", e)),
+                    Err(e) => Some(format!("verification task failed: {}", e)),
+                };
+                let exists = file_map
+                    .get(&wallpaper_id)
+                    .is_some_and(|path| path.exists());
+                if let Some(error) = error.filter(|_| exists) {
+                    // An unreadable file is not proof of corruption; leave it alone
+                    pb.println(styled!("  ⚠ Could not verify {}: {}", wallpaper_id, error));
+                    report.failures.push(SyncFailure {
+                        id: wallpaper_id,
+                        reason: format!("could not verify: {}", error),
+                    });
+                    continue;
+                }
+                self.emit(SyncEvent::IntegrityFailed {
+                    id: wallpaper_id.clone(),
+                });
+                if archived.contains(&wallpaper_id) {
+                    failures.push(
+                        AuditEntry::new(AuditEvent::IntegrityFailure, &wallpaper_id)
+                            .detail("checksum mismatch in an archived group"),
+                    );
+                } else {
+                    pb.println(styled!(
+                        "   Integrity check failed for {}: re-downloading",
+                        wallpaper_id
+                    ));
+                    corrupt.push(wallpaper_id.clone());
+                }
+                needs_download.push(wallpaper_id);
+            }
+            pb.finish_and_clear();
+            if !verified.is_empty() {
+                let mut lock_file_guard = self.lock_file.lock().await;
+                if let Some(lock_file) = lock_file_guard.as_mut() {
+                    lock_file.mark_checked(&verified, now);
+                    lock_file.save().await?;
                 }
             }
+            // Keep corrupt files for inspection rather than overwriting them
+            for id in corrupt {
+                let entry = AuditEntry::new(AuditEvent::IntegrityFailure, &id);
+                let moved = match file_map.get(&id) {
+                    Some(path) if !import::is_local_id(&id) => Some(
+                        quarantine::quarantine_file(path, &self.integrity_quarantine_location)
+                            .await,
+                    ),
+                    _ => None,
+                };
+                failures.push(match moved {
+                    Some(Ok(target)) => entry
+                        .path(&target)
+                        .detail("checksum mismatch, quarantined and re-downloading"),
+                    Some(Err(e)) => {
+                        say_err!("  ⚠ Could not quarantine {}: {:#}", id, e);
+                        entry.detail("checksum mismatch, re-downloading")
+                    }
+                    None => entry.detail("checksum mismatch, re-downloading"),
+                });
+            }
+            report.integrity_failures = failures.len();
+            audit::append(&self.audit_log_location, &failures).await?;
+        }
+        if linked_to_store > 0 {
+            say!(
+                "   Verified {} wallpaper(s) by their link into the store",
+                linked_to_store
+            );
+        }
+        if recently_checked > 0 {
+            say!(
+                "   Skipped verifying {} recently checked wallpaper(s)",
+                recently_checked
+            );
         }
 
+        // Archived wallpapers are never downloaded again; report them as drift
+        needs_download.retain(|id| {
+            if !archived.contains(id) {
+                return true;
+            }
+            let locked = lock_file_map
+                .as_ref()
+                .is_some_and(|lock_map| lock_map.contains_key(id));
+            let reason = match (file_map.contains_key(id), locked) {
+                (false, _) => "file is missing",
+                (true, true) => "file no longer matches the lock file",
+                (true, false) => "file is not recorded in the lock file",
+            };
+            say!("  ✗ {} - archived, {}", id, reason);
+            report.archive_drift.push(SyncFailure {
+                id: id.clone(),
+                reason: reason.to_string(),
+            });
+            false
+        });
+        needs_download.retain(|id| {
+            if import::is_local_id(id) {
+                say!(
+                    "  ⚠ {} is a local import and cannot be downloaded again",
+                    id
+                );
+            }
+            !import::is_local_id(id)
+        });
+        if !options.dry_run {
+            self.regenerate_missing_variants(wallpapers, &file_map, &needs_download)
+                .await;
+        }
         if needs_download.is_empty() {
-            println!("   All wallpapers are up to date.");
-            return Ok(());
+            if report.archive_drift.is_empty() {
+                say!("   All wallpapers are up to date.");
+            }
+            self.run_sync_complete_hook(0, 0, 0).await;
+            return Ok(report);
+        }
+        let offline = self.offline || {
+            let online = self.fetcher.is_online().await;
+            if !online {
+                say!("   Wallhaven is unreachable, continuing offline.");
+            }
+            !online
+        };
+        if offline {
+            say!(
+                "   Offline: skipping {} pending download(s)",
+                needs_download.len()
+            );
+            for wallpaper in &needs_download {
+                say!("  ○ {} - Not downloaded", wallpaper);
+            }
+            report.skipped_offline = needs_download.len();
+            return Ok(report);
+        }
+        if let Some(reason) = self.deferral_reason(options).await {
+            say!(
+                "   Deferring {} download(s): {} (use --now to sync anyway)",
+                needs_download.len(),
+                reason
+            );
+            report.deferred = needs_download.len();
+            return Ok(report);
         }
-        println!("Downloading {} wallpapers...", needs_download.len());
+        // Metadata first, so filters apply before anything is downloaded
+        let minimum = self.minimum_resolution().await;
+        let plan = self.plan_downloads(&needs_download, minimum).await;
+        let mut sizes = self.expected_download_sizes(&needs_download).await;
+        for ((_, planned), size) in plan.iter().zip(sizes.iter_mut()) {
+            if planned.is_err() {
+                *size = 0;
+            }
+        }
+        if options.dry_run {
+            self.print_plan(&plan, &sizes);
+            return Ok(report);
+        }
+        let expected_total: u64 = sizes.iter().sum();
+        self.check_free_space(expected_total, options)?;
+        say!(
+            "Downloading {} wallpapers (about {})...",
+            plan.iter().filter(|(_, planned)| planned.is_ok()).count(),
+            HumanBytes(expected_total)
+        );
 
         // --- FIX STARTS HERE ---
-        let max_concurrent = self.config.max_concurrent_downloads as usize;
+        let max_concurrent = self.config.max_concurrent_downloads;
         let m = MultiProgress::new(); // Supervisor for all bars
-        let mut tasks = stream::iter(needs_download.iter())
-            .map(|w| {
-                let client = self.http_client.clone();
+        let total_pb = m.add(ProgressBar::new(expected_total));
+        total_pb.set_style(
+            ProgressStyle::with_template(
+                "{msg} [{wide_bar:.green/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})",
+            )
+            .unwrap()
+            .progress_chars("#>-"),
+        );
+        total_pb.set_message("Total");
+        let pins = Pins::load(&self.pins_file_location).await?;
+        let download_started = std::time::Instant::now();
+        let budget = Arc::new(RetryBudget::new(self.config.retry_budget));
+        let breaker = CircuitBreaker::new(
+            self.config.circuit_breaker_failures,
+            helper::parse_duration(&self.config.circuit_breaker_cooldown)?,
+        );
+        let mut tasks = stream::iter(plan.into_iter().zip(sizes))
+            .map(|((w, planned), expected_size)| {
+                let fetcher = self.fetcher.clone();
                 let config = self.config.clone();
-                let mp = m.clone();
+                let progress = helper::DownloadProgress {
+                    multi: Some(m.clone()),
+                    total: Some(total_pb.clone()),
+                    expected_size,
+                    retry_budget: Some(budget.clone()),
+                    decode: self.decode_pool.clone(),
+                };
+                let pin = pins.get(&w).cloned();
+                let breaker = &breaker;
                 async move {
-                    let res =
-                        process_wallpaper_optimized(&config, w, &client, true, Some(mp)).await;
-                    (w, res)
+                    if !breaker.admit().await {
+                        return (w, Err(GaveUp.into()), Duration::ZERO);
+                    }
+                    let started = std::time::Instant::now();
+                    let res = match planned {
+                        Ok(metadata) => {
+                            process_wallpaper_optimized(
+                                &config,
+                                pin.as_ref(),
+                                &w,
+                                fetcher.as_ref(),
+                                metadata,
+                                minimum,
+                                progress,
+                            )
+                            .await
+                        }
+                        Err(e) => Err(e),
+                    };
+                    (w, res, started.elapsed())
                 }
             })
             .buffer_unordered(max_concurrent);
 
         let mut errors = 0;
+        let mut skipped = 0;
+        let mut skipped_small = 0;
+        let mut skipped_large = 0;
+        let mut rejected = 0;
+        let mut gave_up = 0;
         let mut completed = 0;
         let total = needs_download.len();
         let mut lock_file_updates = Vec::new();
+        let mut audit_entries = Vec::new();
 
-        while let Some((w, result)) = tasks.next().await {
+        while let Some((w, result, elapsed)) = tasks.next().await {
+            let w = &w;
             completed += 1;
+            let counts = match &result {
+                Ok(_) => Some(true),
+                Err(e) if helper::is_skipped(e) || e.is::<GaveUp>() => None,
+                Err(e) => Some(matches!(e.downcast_ref(), Some(Error::NotFound(_)))),
+            };
+            match counts.map(|success| breaker.record(success)) {
+                Some(Trip::Paused(pause)) => {
+                    let _ = m.println(styled!(
+                        "  ⚠ {} downloads failed in a row, pausing new ones for {}",
+                        self.config.circuit_breaker_failures,
+                        HumanDuration(pause)
+                    ));
+                }
+                Some(Trip::GaveUp) => {
+                    let _ = m.println(styled!(
+                        "  ⚠ Downloads keep failing, leaving the rest for the next sync"
+                    ));
+                }
+                _ => {}
+            }
             match result {
                 Ok(process_result) => {
-                    let _ = m.println(format!(
-                        "  ✓ Downloaded {} - {}",
-                        w, process_result.image_location
+                    let download = DownloadStats {
+                        id: w.clone(),
+                        bytes: process_result.bytes,
+                        duration_ms: elapsed.as_millis() as u64,
+                    };
+                    let _ = m.println(styled!(
+                        "  ✓ Downloaded {} - {} ({}, {}/s)",
+                        w,
+                        process_result.image_location,
+                        HumanBytes(download.bytes),
+                        HumanBytes(download.bytes_per_sec())
                     ));
+                    report.bytes_downloaded += download.bytes;
+                    report.downloads.push(download);
+                    self.emit(SyncEvent::Downloaded {
+                        id: w.clone(),
+                        path: process_result.image_location.clone(),
+                    });
+                    if let Some(ref e) = process_result.processing_error {
+                        let _ = m.println(styled!("  ⚠ Processing failed for {}: {}", w, e));
+                    }
+                    hooks::run(
+                        self.config.hooks.on_download.as_deref(),
+                        "download",
+                        &hooks::wallpaper_env(w, &process_result.image_location),
+                    )
+                    .await;
+                    audit_entries.push(
+                        AuditEntry::new(AuditEvent::Download, w)
+                            .path(&process_result.image_location)
+                            .detail(&process_result.image_url),
+                    );
                     if self.config.integrity {
-                        if let Some(sha256) = process_result.sha256 {
+                        if let Some(hash) = process_result.hash {
                             lock_file_updates.push((
                                 process_result.wallpaper_id,
                                 process_result.image_location,
-                                sha256,
+                                hash,
                             ));
                         }
                     }
                 }
+                Err(e) if e.is::<GaveUp>() => gave_up += 1,
+                Err(e) if helper::is_skipped(&e) => {
+                    let _ = m.println(styled!("  ○ {}", e));
+                    match e.downcast_ref() {
+                        Some(Error::TooSmall { .. }) => skipped_small += 1,
+                        Some(Error::TooLarge { .. }) => skipped_large += 1,
+                        Some(Error::Rejected { reason, .. }) => {
+                            rejected += 1;
+                            audit_entries
+                                .push(AuditEntry::new(AuditEvent::Rejected, w).detail(reason));
+                        }
+                        _ => {}
+                    }
+                    skipped += 1;
+                }
+                Err(e) if matches!(e.downcast_ref::<Error>(), Some(Error::NotFound(_))) => {
+                    let _ = m.println(styled!(
+                        "  ✗ {} was removed from Wallhaven, not retrying it",
+                        w
+                    ));
+                    tombstones.bury(w, now);
+                    self.emit(SyncEvent::DownloadFailed {
+                        id: w.clone(),
+                        error: format!("{:#}", e),
+                    });
+                    report.failures.push(SyncFailure {
+                        id: w.clone(),
+                        reason: "removed from Wallhaven".to_string(),
+                    });
+                    errors += 1;
+                }
                 Err(e) => {
-                    let _ = m.println(format!("  ✗ Failed: {}", e));
+                    let _ = m.println(styled!("  ✗ Failed: {}", e));
+                    if helper::is_pin_mismatch(&e) {
+                        audit_entries.push(
+                            AuditEntry::new(AuditEvent::IntegrityFailure, w)
+                                .detail("upstream file differs from the pinned hash"),
+                        );
+                    }
+                    self.emit(SyncEvent::DownloadFailed {
+                        id: w.clone(),
+                        error: format!("{:#}", e),
+                    });
+                    report.failures.push(SyncFailure {
+                        id: w.clone(),
+                        reason: format!("{:#}", e),
+                    });
                     errors += 1;
                 }
             }
         }
 
+        total_pb.finish_and_clear();
+        if let Some(limit) = self.config.retry_budget.filter(|_| budget.exhausted()) {
+            say!(
+                "  ⚠ Used up retry_budget ({}), later failures were not retried",
+                limit
+            );
+        }
+        if gave_up > 0 {
+            say!(
+                "   Offline: skipped {} download(s) after repeated failures",
+                gave_up
+            );
+        }
+        report.download_ms = download_started.elapsed().as_millis() as u64;
+        if report.bytes_downloaded > 0 {
+            say!(
+                "   Downloaded {} in {} ({}/s)",
+                HumanBytes(report.bytes_downloaded),
+                HumanDuration(download_started.elapsed()),
+                HumanBytes(report.bytes_per_sec())
+            );
+        }
+
         if self.config.integrity && !lock_file_updates.is_empty() {
             let mut lock_file_guard = self.lock_file.lock().await;
             if let Some(ref mut lock_file) = *lock_file_guard {
-                for (image_id, image_location, sha256) in lock_file_updates {
-                    lock_file.add_entry(image_id, image_location, sha256);
+                for (image_id, image_location, hash) in lock_file_updates {
+                    lock_file.add_entry(image_id, image_location, hash, self.config.hash_algorithm);
                 }
                 lock_file.save().await?;
             }
         }
+        audit::append(&self.audit_log_location, &audit_entries).await?;
+        tombstones.save(&self.tombstones_file_location).await?;
         if errors > 0 {
-            eprintln!(
+            say_err!(
                 "✔️ Completed {} of {} with {} error(s)",
-                completed, total, errors
+                completed,
+                total,
+                errors
             );
         } else {
-            println!("\n ✅ Sync complete!");
+            say!("\n ✅ Sync complete!");
         }
+        let downloaded = completed - errors - skipped - gave_up;
+        self.run_sync_complete_hook(total, downloaded, errors).await;
 
-        Ok(())
+        report.downloaded = downloaded;
+        report.skipped_offline = gave_up;
+        report.failed = errors;
+        report.skipped_animated = skipped - skipped_small - skipped_large - rejected;
+        report.skipped_small = skipped_small;
+        report.skipped_large = skipped_large;
+        report.rejected = rejected;
+        Ok(report)
+    }
+
+    async fn run_sync_complete_hook(&self, total: usize, downloaded: usize, failed: usize) {
+        hooks::run(
+            self.config.hooks.on_sync_complete.as_deref(),
+            "sync_complete",
+            &[
+                ("RUST_PAPER_TOTAL", total.to_string()),
+                ("RUST_PAPER_DOWNLOADED", downloaded.to_string()),
+                ("RUST_PAPER_FAILED", failed.to_string()),
+            ],
+        )
+        .await;
     }
 
     /// Add new wallpapers to the list
-    pub async fn add(&mut self, new_wallpapers: &mut Vec<String>) -> Result<()> {
+    pub async fn add(
+        &mut self,
+        new_wallpapers: &mut Vec<String>,
+        options: &AddOptions,
+    ) -> Result<()> {
         *new_wallpapers = new_wallpapers
             .iter()
-            .map(|wall| {
-                if helper::is_url(wall) {
-                    wall.split('/')
-                        .last()
-                        .unwrap_or_default()
-                        .split('?')
-                        .next()
-                        .unwrap_or_default()
-                        .to_string()
-                } else {
-                    wall.to_string()
-                }
-            })
+            .flat_map(|s| helper::to_array(s))
+            .map(|wall| helper::parse_wallhaven_url(&wall))
             .collect();
 
         // Validate wallpaper IDs
         let mut valid_wallpapers = Vec::new();
-        for wallpaper in new_wallpapers.iter().flat_map(|s| helper::to_array(s)) {
-            if helper::validate_wallpaper_id(&wallpaper) {
-                valid_wallpapers.push(wallpaper);
+        for wallpaper in new_wallpapers.iter() {
+            if let Ok(id) = WallpaperId::parse(wallpaper) {
+                valid_wallpapers.push(id.into());
             } else {
-                eprintln!(
+                say_err!(
                     "‼️ Warning: Invalid wallpaper ID format '{}', skipping",
                     wallpaper
                 );
             }
         }
 
+        self.drop_blocked(&mut valid_wallpapers).await?;
+        if options.verify || self.config.has_content_guard() || !self.config.exclude_tags.is_empty()
+        {
+            let (listed, new): (Vec<String>, Vec<String>) = valid_wallpapers
+                .into_iter()
+                .partition(|id| self.wallpapers.contains(id));
+            valid_wallpapers = listed;
+            valid_wallpapers.extend(self.check_new_wallpapers(new, options).await);
+        }
+        if options.pin_hash {
+            self.pin_wallpapers(&valid_wallpapers).await?;
+        }
+
+        if let Some(name) = &options.group {
+            let mut groups = Groups::load(&self.groups_file_location).await?;
+            groups.add(name, &valid_wallpapers)?;
+            groups.save(&self.groups_file_location).await?;
+        }
+
         self.wallpapers.extend(valid_wallpapers);
         self.wallpapers.sort_unstable();
         self.wallpapers.dedup();
-        update_wallpaper_list(&self.wallpapers, &self.wallpapers_list_file_location).await
+        update_wallpaper_list(&self.wallpapers, &self.wallpapers_list_file_location).await?;
+        Ok(())
+    }
+
+    /// Record the upstream checksum of every wallpaper in `ids` that isn't pinned yet
+    async fn pin_wallpapers(&self, ids: &[String]) -> Result<()> {
+        let mut pins = Pins::load(&self.pins_file_location).await?;
+        let unpinned: Vec<&String> = ids.iter().filter(|id| pins.get(id).is_none()).collect();
+        if unpinned.is_empty() {
+            return Ok(());
+        }
+        if self.offline {
+            return Err(Error::InvalidInput(
+                "--pin-hash downloads each wallpaper and can't run offline".to_string(),
+            ));
+        }
+        for id in unpinned {
+            let data = self.fetch_metadata(&WallpaperId::parse(id)?).await?;
+            let pin = pin::fetch_pin(
+                self.fetcher.as_ref(),
+                &data.path,
+                self.config.hash_algorithm,
+            )
+            .await
+            .map_err(|e| e.context(format!("Failed to pin {}", id)))?;
+            say!("   Pinned {} to {}", id, pin.hash);
+            pins.insert(id, pin);
+        }
+        pins.save(&self.pins_file_location).await?;
+        Ok(())
+    }
+
+    /// Fetch the API metadata of a wallpaper, caching it for offline use
+    async fn fetch_metadata(&self, id: &WallpaperId) -> anyhow::Result<WallhavenWallpaper> {
+        let api_key = helper::get_key_from_config_or_env(self.config.api_key.as_deref());
+        let body = retry_get_curl_content(
+            &id.api_url(),
+            self.fetcher.as_ref(),
+            api_key.as_deref(),
+            self.config.retry_count,
+        )
+        .await?;
+        let data = api::WallpaperInfoResponse::parse(&body)?;
+        helper::write_cached_metadata(&self.metadata_cache_location, id.as_str(), &body).await;
+        Ok(data)
+    }
+
+    /// Look up new wallpapers through the API before adding them.
+    /// Drops IDs that don't exist (with `verify`) and wallpapers outside the allowed
+    /// purity/categories or with an excluded tag (only warning with `force`)
+    async fn check_new_wallpapers(&self, ids: Vec<String>, options: &AddOptions) -> Vec<String> {
+        let checks: Vec<(String, anyhow::Result<WallhavenWallpaper>)> = stream::iter(ids)
+            .map(|id| async move {
+                let metadata = if self.offline {
+                    Err(anyhow::anyhow!("offline"))
+                } else {
+                    match WallpaperId::parse(&id) {
+                        Ok(wallpaper_id) => self.fetch_metadata(&wallpaper_id).await,
+                        Err(e) => Err(e.into()),
+                    }
+                };
+                (id, metadata)
+            })
+            .buffered(self.config.max_concurrent_downloads.max(1))
+            .collect()
+            .await;
+
+        let guarded = self.config.has_content_guard();
+        let mut accepted = Vec::new();
+        for (id, metadata) in checks {
+            let violation = match metadata {
+                Ok(data) => {
+                    if options.verify {
+                        say!(
+                            "  ✓ {} - {} {}/{}",
+                            id,
+                            data.resolution,
+                            data.category,
+                            data.purity
+                        );
+                    }
+                    self.config
+                        .content_violation(&data.purity, &data.category)
+                        .or_else(|| self.config.excluded_tag(&data.tags))
+                }
+                Err(e) if matches!(e.downcast_ref::<Error>(), Some(Error::NotFound(_))) => {
+                    say_err!("  ✗ Rejecting {}: no such wallpaper on Wallhaven", id);
+                    continue;
+                }
+                Err(e) if guarded => Some(format!("could not check purity/category: {:#}", e)),
+                Err(e) => {
+                    say_err!("  ⚠ Could not verify {}, adding anyway: {:#}", id, e);
+                    None
+                }
+            };
+            match violation {
+                None => accepted.push(id),
+                Some(reason) if options.force => {
+                    say_err!("  ⚠ Adding {} anyway (--force): {}", id, reason);
+                    accepted.push(id);
+                }
+                Some(reason) => {
+                    say_err!("  ✗ Refusing {}: {} (use --force to add it)", id, reason)
+                }
+            }
+        }
+        accepted
+    }
+
+    /// Drop uploads tagged with an excluded tag. Search results carry no tags,
+    /// so each upload is looked up; one that can't be checked is kept
+    async fn drop_excluded_tags(&self, ids: Vec<String>) -> Vec<String> {
+        stream::iter(ids)
+            .map(|id| async move {
+                let excluded = match WallpaperId::parse(&id) {
+                    Ok(wallpaper_id) => match self.fetch_metadata(&wallpaper_id).await {
+                        Ok(data) => self.config.excluded_tag(&data.tags),
+                        Err(e) => {
+                            say_err!("  ⚠ Could not check the tags of {}: {:#}", id, e);
+                            None
+                        }
+                    },
+                    Err(_) => None,
+                };
+                (id, excluded)
+            })
+            .buffered(self.config.max_concurrent_downloads.max(1))
+            .filter_map(|(id, excluded)| async move {
+                match excluded {
+                    Some(reason) => {
+                        say!("   Skipping {}: {}", id, reason);
+                        None
+                    }
+                    None => Some(id),
+                }
+            })
+            .collect()
+            .await
+    }
+
+    /// Create an empty wallpaper group
+    pub async fn create_group(&self, name: &str) -> Result<()> {
+        let mut groups = Groups::load(&self.groups_file_location).await?;
+        groups
+            .create(name)
+            .map_err(|e| Error::InvalidInput(e.to_string()))?;
+        groups.save(&self.groups_file_location).await?;
+        say!("   Created group '{}'", name);
+        Ok(())
+    }
+
+    /// Delete a wallpaper group, keeping its wallpapers in the list
+    pub async fn delete_group(&self, name: &str) -> Result<()> {
+        let mut groups = Groups::load(&self.groups_file_location).await?;
+        groups
+            .delete(name)
+            .map_err(|e| Error::InvalidInput(e.to_string()))?;
+        groups.save(&self.groups_file_location).await?;
+        say!("   Deleted group '{}'", name);
+        Ok(())
+    }
+
+    /// Archive a group so sync only verifies it, or unarchive it
+    pub async fn set_group_archived(&self, name: &str, archived: bool) -> Result<()> {
+        let mut groups = Groups::load(&self.groups_file_location).await?;
+        groups
+            .set_archived(name, archived)
+            .map_err(|e| Error::InvalidInput(e.to_string()))?;
+        groups.save(&self.groups_file_location).await?;
+        if archived {
+            say!("   Archived group '{}'; sync now only verifies it", name);
+        } else {
+            say!("   Unarchived group '{}'", name);
+        }
+        Ok(())
+    }
+
+    /// Print every group with its wallpapers
+    pub async fn list_groups(&self) -> Result<()> {
+        let groups = Groups::load(&self.groups_file_location).await?;
+        let mut empty = true;
+        for (name, members) in groups.iter() {
+            empty = false;
+            let archived = if groups.is_archived(name) {
+                ", archived"
+            } else {
+                ""
+            };
+            say!("  {} ({} wallpaper(s){})", name, members.len(), archived);
+            for id in members {
+                say!("    {}", id);
+            }
+        }
+        if empty {
+            say!("   No groups defined.");
+        }
+        Ok(())
+    }
+
+    /// Sync only the wallpapers of one group
+    pub async fn sync_group(&self, name: &str, options: &SyncOptions) -> Result<SyncReport> {
+        let started = std::time::Instant::now();
+        let started_at = helper::unix_timestamp();
+        let result = self.sync_group_members(name, options).await;
+        let scope = format!("group:{}", name);
+        self.record_last_sync(&scope, options, started_at, started, &result)
+            .await;
+        result
+    }
+
+    async fn sync_group_members(&self, name: &str, options: &SyncOptions) -> Result<SyncReport> {
+        let groups = Groups::load(&self.groups_file_location).await?;
+        // `group=<name>` annotations in the list file add to the group
+        let annotated = WallpaperList::load(&self.wallpapers_list_file_location)
+            .await?
+            .annotated("group", name);
+        let mut members = match groups.members(name) {
+            Some(members) => members.to_vec(),
+            None if !annotated.is_empty() => Vec::new(),
+            None => return Err(Error::InvalidInput(format!("Unknown group '{}'", name))),
+        };
+        for id in annotated {
+            if !members.contains(&id) {
+                members.push(id);
+            }
+        }
+        if members.is_empty() {
+            say!("   Group '{}' is empty.", name);
+            return Ok(SyncReport::default());
+        }
+        let mut report = self.sync_wallpapers(&members, options).await?;
+        if !options.dry_run {
+            report
+                .failures
+                .extend(self.mirror_wallpapers(&members).await?);
+        }
+        Ok(report)
+    }
+
+    /// Sync only the given tracked wallpapers, e.g. one that was just added
+    pub async fn sync_ids(&self, ids: &[String], options: &SyncOptions) -> Result<SyncReport> {
+        let started = std::time::Instant::now();
+        let started_at = helper::unix_timestamp();
+        let result = self.sync_selected(ids, options).await;
+        let scope = format!("ids:{}", ids.join(","));
+        self.record_last_sync(&scope, options, started_at, started, &result)
+            .await;
+        result
+    }
+
+    async fn sync_selected(&self, ids: &[String], options: &SyncOptions) -> Result<SyncReport> {
+        let mut selected = Vec::new();
+        for id in ids.iter().flat_map(|id| helper::to_array(id)) {
+            let id = if import::is_local_id(&id) {
+                id
+            } else {
+                WallpaperId::parse(&id)?.into()
+            };
+            if !self.wallpapers.contains(&id) {
+                return Err(Error::InvalidInput(format!(
+                    "{} is not in the wallpapers list; add it first",
+                    id
+                )));
+            }
+            if !selected.contains(&id) {
+                selected.push(id);
+            }
+        }
+        let mut report = self.sync_wallpapers(&selected, options).await?;
+        if !options.dry_run {
+            report
+                .failures
+                .extend(self.mirror_wallpapers(&selected).await?);
+        }
+        Ok(report)
     }
 
     /// Remove wallpapers from the list
     pub async fn remove(&mut self, ids_to_remove: &[String]) -> Result<()> {
+        self.remove_tracked(ids_to_remove).await.map(|_| ())
+    }
+
+    /// Remove wallpapers from the list, returning the IDs that were tracked
+    async fn remove_tracked(&mut self, ids_to_remove: &[String]) -> Result<Vec<String>> {
         // Extract and validate wallpaper IDs (support URLs and comma-separated)
         let ids: Vec<String> = ids_to_remove
             .iter()
-            .flat_map(|id| {
-                let processed = if helper::is_url(id) {
-                    id.split('/')
-                        .last()
-                        .unwrap_or_default()
-                        .split('?')
-                        .next()
-                        .unwrap_or_default()
-                        .to_string()
-                } else {
-                    id.clone()
-                };
-                helper::to_array(&processed)
-            })
-            .filter(|id| helper::validate_wallpaper_id(id))
+            .flat_map(|id| helper::to_array(id))
+            .map(|id| helper::parse_wallhaven_url(&id))
+            .filter(|id| WallpaperId::is_valid(id) || import::is_local_id(id))
             .collect();
 
         if ids.is_empty() {
-            return Err(anyhow::anyhow!("No valid wallpaper IDs provided"));
+            return Err(Error::InvalidInput(
+                "No valid wallpaper IDs provided".to_string(),
+            ));
         }
 
         // Track what was removed
-        let original_len = self.wallpapers.len();
+        let removed: Vec<String> = self
+            .wallpapers
+            .iter()
+            .filter(|id| ids.contains(id))
+            .cloned()
+            .collect();
 
         // Remove IDs from the list
         self.wallpapers.retain(|id| !ids.contains(id));
 
-        let removed_count = original_len - self.wallpapers.len();
+        let removed_count = removed.len();
 
         if removed_count == 0 {
-            println!("   No matching wallpaper IDs found in the list");
-            return Ok(());
+            say!("   No matching wallpaper IDs found in the list");
+            return Ok(removed);
         }
 
         // Update the wallpapers list file
         update_wallpaper_list(&self.wallpapers, &self.wallpapers_list_file_location).await?;
 
+        let mut groups = Groups::load(&self.groups_file_location).await?;
+        if groups.remove_ids(&ids) {
+            groups.save(&self.groups_file_location).await?;
+        }
+        let mut pins = Pins::load(&self.pins_file_location).await?;
+        if pins.remove_ids(&ids) {
+            pins.save(&self.pins_file_location).await?;
+        }
+
         // Optionally remove from lock file if integrity is enabled
         if self.config.integrity {
             let mut lock_file_guard = self.lock_file.lock().await;
@@ -412,117 +1644,491 @@ impl RustPaper {
         }
 
         if removed_count == ids.len() {
-            println!(
+            say!(
                 "   Removed {} wallpaper ID(s) from the list",
                 removed_count
             );
         } else {
-            println!(
+            say!(
                 "   Removed {} of {} requested wallpaper ID(s) from the list",
                 removed_count,
                 ids.len()
             );
         }
 
+        Ok(removed)
+    }
+
+    /// Remove wallpapers from the list like `remove`, and also delete their
+    /// files, sidecars and variants, recording each deletion in the audit log.
+    /// With `keep_file` the files stay and only the tracking goes
+    pub async fn delete(&mut self, ids: &[String], keep_file: bool) -> Result<()> {
+        let files = build_file_map(&self.config).await?;
+        let variants = process::variant_map(&self.config).await;
+        // Only files of wallpapers that were tracked, so a mistyped ID
+        // can't delete an unrelated file
+        let ids = self.remove_tracked(ids).await?;
+        if keep_file {
+            return Ok(());
+        }
+
+        let mut audit_entries = Vec::new();
+        let mut unmirrored = Vec::new();
+        for id in &ids {
+            if let Some(path) = files.get(id) {
+                match tokio::fs::remove_file(path).await {
+                    Ok(()) => {
+                        let _ = tokio::fs::remove_file(sidecar::path_for(path)).await;
+                        say!("   Deleted: {} ({})", id, path.display());
+                        audit_entries.push(
+                            AuditEntry::new(AuditEvent::Delete, id)
+                                .path(path)
+                                .detail("deleted (delete)"),
+                        );
+                        if let Some(name) = path.file_name() {
+                            unmirrored.push(name.to_string_lossy().to_string());
+                        }
+                    }
+                    Err(e) => say_err!("   Error deleting {}: {}", path.display(), e),
+                }
+            }
+            for variant in variants.get(id).into_iter().flatten() {
+                if tokio::fs::remove_file(&variant.path).await.is_ok() {
+                    audit_entries.push(
+                        AuditEntry::new(AuditEvent::Delete, id)
+                            .path(&variant.path)
+                            .detail("variant of a deleted wallpaper (delete)"),
+                    );
+                    // Drops the per-ID folder once it is empty
+                    if self.config.variant_layout == VariantLayout::ById {
+                        if let Some(parent) = variant.path.parent() {
+                            let _ = tokio::fs::remove_dir(parent).await;
+                        }
+                    }
+                }
+            }
+        }
+        audit::append(&self.audit_log_location, &audit_entries).await?;
+        self.unmirror(&unmirrored).await;
         Ok(())
     }
 
+    /// Print the audit log, optionally only entries newer than `since` (e.g. "7d")
+    pub async fn log(&self, since: Option<&str>) -> Result<()> {
+        let since = match since {
+            Some(window) => helper::unix_timestamp().saturating_sub(
+                helper::parse_duration(window)
+                    .map_err(|e| Error::InvalidInput(format!("Invalid --since: {:#}", e)))?
+                    .as_secs(),
+            ),
+            None => 0,
+        };
+        let entries = audit::read(&self.audit_log_location, since).await?;
+        if entries.is_empty() {
+            say!("   No matching log entries.");
+            return Ok(());
+        }
+        for entry in entries {
+            let mut line = format!(
+                "  {}  {:<17}  {}",
+                helper::format_timestamp(entry.time),
+                entry.event.to_string(),
+                entry.id
+            );
+            if let Some(path) = entry.path {
+                line.push_str(&format!("  {}", path));
+            }
+            if let Some(detail) = entry.detail {
+                line.push_str(&format!("  ({})", detail));
+            }
+            say!("{}", line);
+        }
+        Ok(())
+    }
+
+    /// Rebuild the lock file from the files on disk, hashing every tracked wallpaper
+    pub async fn repair(&self) -> Result<()> {
+        let mut lock_file_guard = self.lock_file.lock().await;
+        let Some(lock_file) = lock_file_guard.as_mut() else {
+            say!("   Integrity checks are disabled, there is no lock file to repair.");
+            return Ok(());
+        };
+        let previous = lock_file.entries().len();
+        let file_map = build_file_map(&self.config).await?;
+        let tracked: Vec<(String, PathBuf)> = self
+            .wallpapers
+            .iter()
+            .filter_map(|id| file_map.get(id).map(|path| (id.clone(), path.clone())))
+            .collect();
+        say!("   Hashing {} file(s)...", tracked.len());
+
+        let algorithm = self.config.hash_algorithm;
+        let mut hashes = stream::iter(tracked)
+            .map(|(id, path)| async move {
+                let hash = helper::calculate_hash(&path, algorithm).await;
+                (id, path, hash)
+            })
+            .buffer_unordered(self.config.max_concurrent_downloads.max(1));
+
+        lock_file.clear();
+        let mut failed = 0;
+        while let Some((id, path, hash)) = hashes.next().await {
+            match hash {
+                Ok(hash) => {
+                    lock_file.add_entry(id, path.to_string_lossy().to_string(), hash, algorithm)
+                }
+                Err(e) => {
+                    say_err!("  ✗ {}: {:#}", id, e);
+                    failed += 1;
+                }
+            }
+        }
+        lock_file.save().await?;
+
+        let rebuilt = lock_file.entries().len();
+        say!(
+            "   Rebuilt the lock file with {} entries (previously {})",
+            rebuilt,
+            previous
+        );
+        if failed > 0 {
+            say_err!("  ⚠ {} file(s) could not be hashed", failed);
+        }
+        Ok(())
+    }
+
+    /// Re-run the configured processing steps on already downloaded wallpapers
+    pub async fn reprocess(&self, ids: &[String]) -> Result<()> {
+        let steps = self.config.process_steps();
+        if steps.is_empty() {
+            say!("   No processing steps configured.");
+            return Ok(());
+        }
+        let targets: Vec<String> = if ids.is_empty() {
+            self.wallpapers.clone()
+        } else {
+            ids.iter().flat_map(|id| helper::to_array(id)).collect()
+        };
+        let file_map = build_file_map(&self.config).await?;
+
+        let mut processed = 0;
+        let mut errors = 0;
+        for id in &targets {
+            let Some(path) = file_map.get(id) else {
+                say!("   Skipping {}: not downloaded", id);
+                continue;
+            };
+            match process::run_steps(&self.config, &self.decode_pool, path).await {
+                Ok(outputs) => {
+                    say!("  ✓ Processed {} ({} output(s))", id, outputs.len());
+                    processed += 1;
+                }
+                Err(e) => {
+                    say_err!("  ✗ Failed to process {}: {:#}", id, e);
+                    errors += 1;
+                }
+            }
+        }
+
+        if errors > 0 {
+            say_err!(
+                "✔️ Processed {} of {} with {} error(s)",
+                processed,
+                targets.len(),
+                errors
+            );
+        } else {
+            say!("\n ✅ Processed {} wallpaper(s)", processed);
+        }
+        Ok(())
+    }
+
+    /// Download status of every tracked wallpaper, in list order, from a
+    /// single scan of the save location
+    pub async fn statuses(&self) -> Result<Vec<(String, WallpaperStatus)>> {
+        let (file_map, tombstones) = tokio::try_join!(
+            build_file_map(&self.config),
+            Tombstones::load(&self.tombstones_file_location),
+        )?;
+        Ok(self
+            .wallpapers
+            .iter()
+            .map(|id| {
+                let status = match file_map.get(id) {
+                    Some(path) => WallpaperStatus::Downloaded { path: path.clone() },
+                    None if tombstones.contains(id) => WallpaperStatus::GoneUpstream,
+                    None => WallpaperStatus::NotDownloaded,
+                };
+                (id.clone(), status)
+            })
+            .collect())
+    }
+
     /// List all tracked wallpapers with their download status
     pub async fn list(&self) -> Result<()> {
         if self.wallpapers.is_empty() {
-            println!("   No wallpapers tracked.");
+            say!("   No wallpapers tracked.");
             return Ok(());
         }
 
-        println!("  Tracked wallpapers ({} total):", self.wallpapers.len());
-        println!();
+        say!("  Tracked wallpapers ({} total):", self.wallpapers.len());
+        say!();
 
         let mut downloaded_count = 0;
         let mut not_downloaded_count = 0;
+        let mut gone_count = 0;
+        let variants = process::variant_map(&self.config).await;
+        let annotations = WallpaperList::load(&self.wallpapers_list_file_location).await?;
 
-        for wallpaper_id in &self.wallpapers {
-            let status =
-                check_download_status(&self.config.save_location, wallpaper_id, &self.lock_file)
-                    .await?;
-
+        for (wallpaper_id, status) in self.statuses().await? {
+            let tags = annotations.annotation(&wallpaper_id, "tags");
+            let tags = if tags.is_empty() {
+                String::new()
+            } else {
+                format!(" #{}", tags.join(" #"))
+            };
             match status {
                 WallpaperStatus::Downloaded { path } => {
-                    println!("  ✓ {} - Downloaded ({})", wallpaper_id, path.display());
+                    let steps = variants
+                        .get(&wallpaper_id)
+                        .map(|list| {
+                            let names: Vec<&str> = list.iter().map(|v| v.step.as_str()).collect();
+                            format!(" [{}]", names.join(", "))
+                        })
+                        .unwrap_or_default();
+                    say!(
+                        "  ✓ {} - Downloaded ({}){}{}",
+                        wallpaper_id,
+                        path.display(),
+                        steps,
+                        tags
+                    );
                     downloaded_count += 1;
                 }
+                WallpaperStatus::GoneUpstream => {
+                    say!("  ✗ {} - Removed from Wallhaven{}", wallpaper_id, tags);
+                    gone_count += 1;
+                }
                 WallpaperStatus::NotDownloaded => {
-                    println!("  ○ {} - Not downloaded", wallpaper_id);
+                    say!("  ○ {} - Not downloaded{}", wallpaper_id, tags);
                     not_downloaded_count += 1;
                 }
             }
         }
 
-        println!();
-        println!(
+        say!();
+        say!(
             "  Summary: {} downloaded, {} not downloaded",
-            downloaded_count, not_downloaded_count
+            downloaded_count,
+            not_downloaded_count
         );
+        if gone_count > 0 {
+            say!(
+                "  {} wallpaper(s) were removed from Wallhaven; `rust-paper clean --tombstones` drops them",
+                gone_count
+            );
+        }
 
         Ok(())
     }
 
-    /// Clean up downloaded wallpapers that are no longer in the list
-    pub async fn clean(&mut self) -> Result<()> {
+    /// Clean up downloaded wallpapers that are no longer in the list, limited
+    /// to the ones `options` selects. With `tombstones`, wallpapers removed
+    /// from Wallhaven are dropped from the list first
+    pub async fn clean(&mut self, options: &CleanOptions) -> Result<()> {
+        if options.tombstones {
+            let mut gone = Tombstones::load(&self.tombstones_file_location).await?;
+            let ids = gone.ids();
+            if ids.is_empty() {
+                say!("   No wallpapers removed from Wallhaven.");
+            } else {
+                self.remove(&ids).await?;
+                gone.clear();
+                gone.save(&self.tombstones_file_location).await?;
+            }
+        }
         let save_location = Path::new(&self.config.save_location);
         if !save_location.exists() {
-            println!(
+            say!(
                 "  Save location does not exist: {}",
                 save_location.display()
             );
             return Ok(());
         }
-        let mut entries = tokio::fs::read_dir(save_location).await?;
-        let mut removed_count = 0;
-        let mut total_size = 0u64;
         let mut files_to_check = Vec::new();
-        while let Some(entry) = entries.next_entry().await? {
-            let path = entry.path();
-            if path.is_file() {
-                if let Some(file_stem) = path.file_stem().and_then(|s| s.to_str()) {
-                    files_to_check.push((path.clone(), file_stem.to_string()));
-                }
+        for path in scan_save_location(&self.config).await? {
+            if let Some(file_stem) = path.file_stem().and_then(|s| s.to_str()) {
+                files_to_check.push((path.clone(), file_stem.to_string(), false));
             }
         }
-        println!(
+        // Variants are named by step in the per-ID layout, so go by their ID
+        for (id, variants) in process::variant_map(&self.config).await {
+            files_to_check.extend(variants.into_iter().map(|v| (v.path, id.clone(), true)));
+        }
+        say!(
             "  Checking {} file(s) in save location...",
             files_to_check.len()
         );
-        for (file_path, file_stem) in files_to_check {
-            if !self.wallpapers.contains(&file_stem) {
-                if let Ok(metadata) = tokio::fs::metadata(&file_path).await {
-                    total_size += metadata.len();
-                }
-                if self.config.integrity {
-                    let mut lock_file_guard = self.lock_file.lock().await;
-                    if let Some(ref mut lock_file) = *lock_file_guard {
-                        lock_file.remove(&file_stem).await?;
+        let mut candidates = Vec::new();
+        for (path, id, variant) in files_to_check {
+            if self.wallpapers.contains(&id) {
+                continue;
+            }
+            let modified = helper::modified_timestamp(&path).await;
+            if options.selects(&path, modified) {
+                candidates.push((path, id, variant));
+            }
+        }
+
+        let concurrency = self.config.max_concurrent_downloads.max(1);
+        if self.config.integrity {
+            let tracked: Vec<LockEntry> = match self.lock_file.lock().await.as_ref() {
+                Some(lock_file) => lock_file
+                    .entries()
+                    .iter()
+                    .filter(|e| self.wallpapers.iter().any(|id| id == e.image_id()))
+                    .cloned()
+                    .collect(),
+                None => Vec::new(),
+            };
+            if !tracked.is_empty() {
+                let tracked = &tracked;
+                let owners: Vec<Option<String>> = stream::iter(&candidates)
+                    .map(|(path, _, variant)| async move {
+                        match variant {
+                            true => None,
+                            false => clean::tracked_owner(path, tracked).await,
+                        }
+                    })
+                    .buffered(concurrency)
+                    .collect()
+                    .await;
+                let mut owners = owners.into_iter();
+                candidates.retain(|(path, _, _)| match owners.next().flatten() {
+                    Some(owner) => {
+                        say!(
+                            "  ⚠ Keeping {}: the lock file records it as tracked wallpaper {}",
+                            path.display(),
+                            owner
+                        );
+                        false
                     }
+                    None => true,
+                });
+            }
+        }
+
+        let orphans: Vec<clean::Orphan> = stream::iter(candidates)
+            .map(|(path, id, variant)| async move {
+                let size = clean::reclaimable(&path).await;
+                clean::Orphan {
+                    path,
+                    id,
+                    variant,
+                    size,
                 }
-                match tokio::fs::remove_file(&file_path).await {
-                    Ok(_) => {
-                        println!("   Removed: {} ({})", file_stem, file_path.display());
-                        removed_count += 1;
-                    }
-                    Err(e) => {
-                        eprintln!("   Error removing {}: {}", file_path.display(), e);
+            })
+            .buffered(concurrency)
+            .collect()
+            .await;
+        let mut to_remove = Vec::new();
+        let mut remove_all = !options.interactive;
+        for orphan in orphans {
+            if !remove_all {
+                let size = match orphan.size {
+                    Some(size) => format!("{:.2} MB", size as f64 / 1_048_576.0),
+                    None => "unknown size".to_string(),
+                };
+                let question = format!("  Remove {} ({})?", orphan.path.display(), size);
+                match clean::confirm(&mut std::io::stdin().lock(), &question) {
+                    clean::Answer::Yes => {}
+                    clean::Answer::No => continue,
+                    clean::Answer::All => remove_all = true,
+                }
+            }
+            to_remove.push(orphan);
+        }
+
+        let results: Vec<(clean::Orphan, std::io::Result<()>)> = stream::iter(to_remove)
+            .map(|orphan| async move {
+                let result = tokio::fs::remove_file(&orphan.path).await;
+                if result.is_ok() {
+                    let _ = tokio::fs::remove_file(sidecar::path_for(&orphan.path)).await;
+                }
+                (orphan, result)
+            })
+            .buffer_unordered(concurrency)
+            .collect()
+            .await;
+
+        let mut removed_count = 0;
+        let mut total_size = 0u64;
+        let mut unknown_size = 0;
+        let mut audit_entries = Vec::new();
+        let mut unmirrored = Vec::new();
+        for (orphan, result) in results {
+            if let Err(e) = result {
+                say_err!("   Error removing {}: {}", orphan.path.display(), e);
+                continue;
+            }
+            say!("   Removed: {} ({})", orphan.id, orphan.path.display());
+            removed_count += 1;
+            match orphan.size {
+                Some(size) => total_size += size,
+                None => unknown_size += 1,
+            }
+            let detail = if orphan.variant {
+                "variant of a wallpaper not in the list (clean)"
+            } else {
+                "not in the wallpapers list (clean)"
+            };
+            audit_entries.push(
+                AuditEntry::new(AuditEvent::Delete, &orphan.id)
+                    .path(&orphan.path)
+                    .detail(detail),
+            );
+            if orphan.variant {
+                // Drops the per-ID folder once it is empty
+                if self.config.variant_layout == VariantLayout::ById {
+                    if let Some(parent) = orphan.path.parent() {
+                        let _ = tokio::fs::remove_dir(parent).await;
                     }
                 }
+                continue;
+            }
+            if self.config.integrity {
+                let mut lock_file_guard = self.lock_file.lock().await;
+                if let Some(ref mut lock_file) = *lock_file_guard {
+                    lock_file.remove(&orphan.id).await?;
+                }
+            }
+            hooks::run(
+                self.config.hooks.on_clean.as_deref(),
+                "clean",
+                &hooks::wallpaper_env(&orphan.id, &orphan.path),
+            )
+            .await;
+            if let Some(name) = orphan.path.file_name() {
+                unmirrored.push(name.to_string_lossy().to_string());
             }
         }
+        audit::append(&self.audit_log_location, &audit_entries).await?;
+        self.unmirror(&unmirrored).await;
 
         if removed_count == 0 {
-            println!("   No orphaned files found. Everything is clean!");
+            say!("   No orphaned files found. Everything is clean!");
         } else {
-            println!();
-            println!(
-                "  Cleaned up {} file(s), freed approximately {:.2} MB",
+            say!();
+            let unknown = if unknown_size > 0 {
+                format!(" ({} file(s) of unknown size not counted)", unknown_size)
+            } else {
+                String::new()
+            };
+            say!(
+                "  Cleaned up {} file(s), freed {:.2} MB{}",
                 removed_count,
-                total_size as f64 / 1_048_576.0
+                total_size as f64 / 1_048_576.0,
+                unknown
             );
         }
 
@@ -530,185 +2136,158 @@ impl RustPaper {
     }
 
     pub async fn info(&self, id: &str) -> Result<()> {
-        let wallpaper_id = if helper::is_url(id) {
-            id.split('/')
-                .last()
-                .unwrap_or_default()
-                .split('?')
-                .next()
-                .unwrap_or_default()
-                .to_string()
+        let wallpaper_id = WallpaperId::parse(id)?;
+
+        let cached = || async {
+            helper::read_cached_metadata(&self.metadata_cache_location, wallpaper_id.as_str()).await
+        };
+        let data = if self.offline {
+            let body = cached().await.ok_or_else(|| {
+                Error::NotFound(format!("cached metadata for '{}' (offline)", wallpaper_id))
+            })?;
+            api::WallpaperInfoResponse::parse(&body)?
         } else {
-            id.to_string()
+            match retry_get_curl_content(
+                &wallpaper_id.api_url(),
+                self.fetcher.as_ref(),
+                self.config.api_key.as_deref(),
+                self.config.retry_count,
+            )
+            .await
+            {
+                Ok(response_data) => {
+                    let data = api::WallpaperInfoResponse::parse(&response_data)?;
+                    helper::write_cached_metadata(
+                        &self.metadata_cache_location,
+                        wallpaper_id.as_str(),
+                        &response_data,
+                    )
+                    .await;
+                    data
+                }
+                Err(e) => match cached().await {
+                    Some(body) => {
+                        say_err!(
+                            "   Network request failed ({}), showing cached metadata",
+                            e
+                        );
+                        api::WallpaperInfoResponse::parse(&body)?
+                    }
+                    None => return Err(e.into()),
+                },
+            }
         };
 
-        if !helper::validate_wallpaper_id(&wallpaper_id) {
-            return Err(anyhow::anyhow!(
-                "Invalid wallpaper ID format: '{}'",
-                wallpaper_id
-            ));
+        say!("  Wallpaper Information:");
+        say!("  ─────────────────────");
+        say!("  ID: {}", data.id);
+        say!("  URL: {}", data.url);
+        say!("  Resolution: {}", data.resolution);
+        say!("  File Size: {:.2} MB", data.file_size as f64 / 1_048_576.0);
+        say!("  Category: {}", data.category);
+        say!("  Purity: {}", data.purity);
+        say!("  Views: {}", data.views);
+        say!("  Favorites: {}", data.favorites);
+        say!("  Uploaded: {}", data.created_at);
+        if let Some(uploader) = &data.uploader {
+            say!("  Uploader: {}", uploader.username);
         }
-
-        let api_url = format!("{}/{}", WALLHAVEN_API, wallpaper_id);
-        let response_data = retry_get_curl_content(
-            &api_url,
-            &self.http_client,
-            self.config.api_key.as_deref(),
-            self.config.retry_count,
-        )
-        .await?;
-        let json: Value = serde_json::from_str(&response_data)?;
-        if let Some(error) = json.get("error") {
-            return Err(anyhow::anyhow!("API error: {}", error));
+        if !data.tags.is_empty() {
+            let tag_names: Vec<&str> = data.tags.iter().map(|tag| tag.name.as_str()).collect();
+            say!("  Tags: {}", tag_names.join(", "));
         }
-        if let Some(data) = json.get("data") {
-            println!("  Wallpaper Information:");
-            println!("  ─────────────────────");
-            if let Some(id_val) = data.get("id").and_then(Value::as_str) {
-                println!("  ID: {}", id_val);
-            }
-            if let Some(url) = data.get("url").and_then(Value::as_str) {
-                println!("  URL: {}", url);
-            }
-            if let Some(width) = data.get("resolution").and_then(Value::as_str) {
-                println!("  Resolution: {}", width);
-            }
-            if let Some(size) = data.get("file_size").and_then(Value::as_u64) {
-                println!("  File Size: {:.2} MB", size as f64 / 1_048_576.0);
-            }
-            if let Some(category) = data.get("category").and_then(Value::as_str) {
-                println!("  Category: {}", category);
-            }
-            if let Some(purity) = data.get("purity").and_then(Value::as_str) {
-                println!("  Purity: {}", purity);
-            }
-            if let Some(views) = data.get("views").and_then(Value::as_u64) {
-                println!("  Views: {}", views);
-            }
-            if let Some(favorites) = data.get("favorites").and_then(Value::as_u64) {
-                println!("  Favorites: {}", favorites);
-            }
-            if let Some(date) = data.get("created_at").and_then(Value::as_str) {
-                println!("  Uploaded: {}", date);
-            }
-            if let Some(uploader) = data.get("uploader") {
-                if let Some(username) = uploader.get("username").and_then(Value::as_str) {
-                    println!("  Uploader: {}", username);
-                }
-            }
-            if let Some(tags) = data.get("tags").and_then(Value::as_array) {
-                if !tags.is_empty() {
-                    let tag_names: Vec<String> = tags
-                        .iter()
-                        .filter_map(|tag| tag.get("name").and_then(Value::as_str))
-                        .map(String::from)
-                        .collect();
-                    if !tag_names.is_empty() {
-                        println!("  Tags: {}", tag_names.join(", "));
-                    }
-                }
-            }
-            if let Some(path) = data.get("path").and_then(Value::as_str) {
-                println!("  Image URL: {}", path);
-            }
-            if self.wallpapers.contains(&wallpaper_id) {
-                println!("  Status: Tracked");
-                if let Some(local_path) =
-                    find_existing_image(&self.config.save_location, &wallpaper_id).await?
-                {
-                    println!("  Local: {}", local_path.display());
-                } else {
-                    println!("  Local: Not downloaded");
-                }
+        say!("  Image URL: {}", data.path);
+        if self.wallpapers.iter().any(|w| w == wallpaper_id.as_str()) {
+            say!("  Status: Tracked");
+            if let Some(local_path) =
+                find_existing_image(&self.config, wallpaper_id.as_str()).await?
+            {
+                say!("  Local: {}", local_path.display());
             } else {
-                println!("  Status: Not tracked");
+                say!("  Local: Not downloaded");
             }
         } else {
-            return Err(anyhow::anyhow!("Invalid API response: no data field"));
+            say!("  Status: Not tracked");
         }
 
         Ok(())
     }
 }
 
-/// Status of a wallpaper
-enum WallpaperStatus {
-    Downloaded { path: PathBuf },
-    NotDownloaded,
+/// How a single `sync` runs
+#[derive(Debug, Clone, Default)]
+pub struct SyncOptions {
+    /// Re-hash every file, ignoring `recheck_after`
+    pub full_check: bool,
+    /// Download even if the disk would drop below `min_free_space_mb`
+    pub ignore_disk_space: bool,
+    /// Download even when `sync_conditions` say to wait
+    pub ignore_conditions: bool,
+    /// Plan the downloads and print them instead of downloading
+    pub dry_run: bool,
 }
 
-/// Check the download status of a wallpaper
-async fn check_download_status(
-    save_location: &str,
-    wallpaper_id: &str,
-    lock_file: &Arc<Mutex<Option<LockFile>>>,
-) -> Result<WallpaperStatus> {
-    if let Some(existing_path) = find_existing_image(save_location, wallpaper_id).await? {
-        // Check if integrity is enabled and verified
-        let lock_file_guard = lock_file.lock().await;
-        if let Some(_) = *lock_file_guard {
-            return Ok(WallpaperStatus::Downloaded {
-                path: existing_path,
-            });
-        }
-        // File exists but integrity is not enabled
-        Ok(WallpaperStatus::Downloaded {
-            path: existing_path,
-        })
-    } else {
-        Ok(WallpaperStatus::NotDownloaded)
-    }
+/// How `add` checks and records new wallpapers
+#[derive(Debug, Clone, Default)]
+pub struct AddOptions {
+    /// Also add the wallpapers to this group
+    pub group: Option<String>,
+    /// Add wallpapers even if they fall outside the allowed purity/categories
+    pub force: bool,
+    /// Check that wallpapers exist on Wallhaven before adding them
+    pub verify: bool,
+    /// Record the upstream file's checksum
+    pub pin_hash: bool,
+}
+
+/// Status of a tracked wallpaper
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WallpaperStatus {
+    Downloaded {
+        path: PathBuf,
+    },
+    NotDownloaded,
+    /// Not downloaded, and Wallhaven no longer has it
+    GoneUpstream,
 }
 
 /// Load wallpaper IDs from a file
-async fn load_wallpapers(given_file: impl AsRef<Path>) -> Result<Vec<String>> {
+pub(crate) async fn load_wallpapers(given_file: impl AsRef<Path>) -> anyhow::Result<Vec<String>> {
     let file_path = given_file.as_ref();
     if !file_path.exists() {
         File::create(file_path).await?;
         return Ok(vec![]);
     }
 
-    let file = File::open(file_path).await?;
-    let reader = BufReader::new(file);
-    let mut lines = Vec::new();
-    let mut lines_stream = reader.lines();
-
-    while let Some(line) = lines_stream.next_line().await? {
-        lines.extend(helper::to_array(&line));
-    }
-
-    Ok(lines)
+    Ok(WallpaperList::load(file_path).await?.ids())
 }
 
 /// Find an existing image file for a wallpaper ID
 async fn find_existing_image(
-    save_location_given: impl AsRef<Path>,
+    config: &config::Config,
     wallpaper: &str,
-) -> Result<Option<PathBuf>> {
-    let save_location = save_location_given.as_ref();
-    let mut entries = tokio::fs::read_dir(save_location).await?;
-    while let Some(entry) = entries.next_entry().await? {
-        let path = entry.path();
-        if path.file_stem().and_then(|s| s.to_str()) == Some(wallpaper) {
-            return Ok(Some(path));
-        }
-    }
-    Ok(None)
+) -> anyhow::Result<Option<PathBuf>> {
+    Ok(scan_save_location(config)
+        .await?
+        .into_iter()
+        .find(|path| path.file_stem().and_then(|s| s.to_str()) == Some(wallpaper)))
 }
 
 /// Retry fetching content from a URL with exponential backoff
 async fn retry_get_curl_content(
     url: &str,
-    client: &Client,
+    fetcher: &dyn HttpFetcher,
     api_key: Option<&str>,
     max_retry: u32,
-) -> Result<String> {
+) -> anyhow::Result<String> {
     for retry_count in 0..max_retry {
-        match helper::get_curl_content(url, client, api_key).await {
+        match fetcher.get_text(url, api_key).await {
             Ok(content) => return Ok(content),
+            // No point retrying a missing wallpaper
+            Err(e @ (Error::NotFound(_) | Error::Unauthorized)) => return Err(e.into()),
             Err(e) if retry_count + 1 < max_retry => {
                 let delay = 2_u64.pow(retry_count); // Exponential backoff
-                eprintln!(
+                say_err!(
                     "   Error fetching content (attempt {} of {}): {}. Retrying in {}s...",
                     retry_count + 1,
                     max_retry,
@@ -717,7 +2296,7 @@ async fn retry_get_curl_content(
                 );
                 sleep(Duration::from_secs(delay)).await;
             }
-            Err(e) => return Err(e),
+            Err(e) => return Err(e.into()),
         }
     }
     unreachable!()
@@ -0,0 +1,253 @@
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::helper;
+
+/// A line listing wallpaper IDs, with optional `key=value` annotations and a comment
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    ids: Vec<String>,
+    annotations: Vec<(String, String)>,
+    /// Trailing `# ...` comment, with the whitespace before it
+    comment: String,
+    /// The line as read, written back untouched unless `ids` change
+    raw: Option<String>,
+}
+
+impl Entry {
+    fn parse(line: &str) -> Self {
+        let (content, comment) = match line.find('#') {
+            Some(at) => {
+                let start = line[..at].trim_end().len();
+                (&line[..at], line[start..].to_string())
+            }
+            None => (line, String::new()),
+        };
+        let mut ids = Vec::new();
+        let mut annotations = Vec::new();
+        for token in content.split_whitespace() {
+            match token.split_once('=') {
+                Some((key, value)) => annotations.push((key.to_string(), value.to_string())),
+                None => ids.extend(helper::to_array(token)),
+            }
+        }
+        Entry {
+            ids,
+            annotations,
+            comment,
+            raw: Some(line.to_string()),
+        }
+    }
+
+    fn new(id: &str) -> Self {
+        Entry {
+            ids: vec![id.to_string()],
+            annotations: Vec::new(),
+            comment: String::new(),
+            raw: None,
+        }
+    }
+
+    fn render(&self) -> String {
+        if let Some(raw) = &self.raw {
+            return raw.clone();
+        }
+        let mut line = self.ids.join(",");
+        for (key, value) in &self.annotations {
+            line.push_str(&format!(" {}={}", key, value));
+        }
+        line.push_str(&self.comment);
+        line
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Line {
+    /// Comments, section headers and blank lines, kept verbatim
+    Text(String),
+    Entry(Entry),
+}
+
+/// The wallpapers list file. Comments (`#`), blank lines and per-line
+/// annotations such as `tags=dark,city` or `group=anime` survive rewrites
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WallpaperList {
+    lines: Vec<Line>,
+}
+
+impl WallpaperList {
+    pub fn parse(text: &str) -> Self {
+        let lines = text
+            .lines()
+            .map(|line| {
+                let trimmed = line.trim();
+                if trimmed.is_empty() || trimmed.starts_with('#') {
+                    Line::Text(line.to_string())
+                } else {
+                    Line::Entry(Entry::parse(line))
+                }
+            })
+            .collect();
+        WallpaperList { lines }
+    }
+
+    /// Load the list, starting empty if the file is missing
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self> {
+        match tokio::fs::read_to_string(path.as_ref()).await {
+            Ok(text) => Ok(Self::parse(&text)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).context("   Failed to read the wallpapers list"),
+        }
+    }
+
+    fn entries(&self) -> impl Iterator<Item = &Entry> {
+        self.lines.iter().filter_map(|line| match line {
+            Line::Entry(entry) => Some(entry),
+            Line::Text(_) => None,
+        })
+    }
+
+    /// Wallpaper IDs in file order, without duplicates
+    pub fn ids(&self) -> Vec<String> {
+        let mut seen = HashSet::new();
+        self.entries()
+            .flat_map(|entry| entry.ids.iter())
+            .filter(|id| seen.insert(id.as_str()))
+            .cloned()
+            .collect()
+    }
+
+    /// Values of annotation `key` on the line of `id`, split on commas
+    pub fn annotation(&self, id: &str, key: &str) -> Vec<String> {
+        self.entries()
+            .filter(|entry| entry.ids.iter().any(|i| i == id))
+            .flat_map(|entry| entry.annotations.iter())
+            .filter(|(k, _)| k == key)
+            .flat_map(|(_, value)| helper::to_array(value))
+            .collect()
+    }
+
+    /// IDs whose line carries `key` with `value` among its values
+    pub fn annotated(&self, key: &str, value: &str) -> Vec<String> {
+        self.ids()
+            .into_iter()
+            .filter(|id| self.annotation(id, key).iter().any(|v| v == value))
+            .collect()
+    }
+
+    /// Make the list hold exactly `ids`. Removed IDs disappear from their
+    /// lines; new ones get a line each in the last block of entries, in
+    /// sorted position if that block is sorted
+    pub fn set_ids(&mut self, ids: &[String]) {
+        let wanted: HashSet<&str> = ids.iter().map(String::as_str).collect();
+        let mut seen = HashSet::new();
+        for line in &mut self.lines {
+            if let Line::Entry(entry) = line {
+                let len = entry.ids.len();
+                entry
+                    .ids
+                    .retain(|id| wanted.contains(id.as_str()) && seen.insert(id.clone()));
+                if entry.ids.len() != len {
+                    entry.raw = None;
+                }
+            }
+        }
+        self.lines
+            .retain(|line| !matches!(line, Line::Entry(entry) if entry.ids.is_empty()));
+
+        let missing: Vec<&String> = ids.iter().filter(|id| !seen.contains(*id)).collect();
+        if missing.is_empty() {
+            return;
+        }
+        let Some(last) = self
+            .lines
+            .iter()
+            .rposition(|line| matches!(line, Line::Entry(_)))
+        else {
+            self.lines
+                .extend(missing.into_iter().map(|id| Line::Entry(Entry::new(id))));
+            return;
+        };
+        let first = self.lines[..last]
+            .iter()
+            .rposition(|line| !matches!(line, Line::Entry(_)))
+            .map_or(0, |at| at + 1);
+        let first_id = |line: &Line| match line {
+            Line::Entry(entry) => entry.ids[0].clone(),
+            Line::Text(_) => unreachable!(),
+        };
+        let sorted = self.lines[first..=last]
+            .windows(2)
+            .all(|pair| first_id(&pair[0]) <= first_id(&pair[1]));
+        for (end, id) in (last + 1..).zip(missing) {
+            let at = if sorted {
+                first + self.lines[first..end].partition_point(|line| first_id(line) < *id)
+            } else {
+                end
+            };
+            self.lines.insert(at, Line::Entry(Entry::new(id)));
+        }
+    }
+
+    pub fn render(&self) -> String {
+        self.lines
+            .iter()
+            .map(|line| match line {
+                Line::Text(text) => format!("{}\n", text),
+                Line::Entry(entry) => format!("{}\n", entry.render()),
+            })
+            .collect()
+    }
+}
+
+/// Update the list file at `path` to hold `ids`, keeping comments and
+/// annotations. The file is only written when its contents change
+pub async fn save_ids(ids: &[String], path: impl AsRef<Path>) -> Result<()> {
+    let path = path.as_ref();
+    let existing = tokio::fs::read_to_string(path).await.ok();
+    let mut list = WallpaperList::parse(existing.as_deref().unwrap_or_default());
+    list.set_ids(ids);
+    let text = list.render();
+    if existing.as_deref() == Some(text.as_str()) {
+        return Ok(());
+    }
+    tokio::fs::write(path, text)
+        .await
+        .context("   Failed to write the wallpapers list")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(list: &[&str]) -> Vec<String> {
+        list.iter().map(|id| id.to_string()).collect()
+    }
+
+    const LIST: &str = "# Favourites\nabc123 tags=dark,city  # the skyline\nfgh456,ijk789\n\n## Anime\nxyz000 group=anime\n";
+
+    #[test]
+    fn test_parse_keeps_comments_and_annotations() {
+        let list = WallpaperList::parse(LIST);
+        assert_eq!(list.ids(), ids(&["abc123", "fgh456", "ijk789", "xyz000"]));
+        assert_eq!(list.annotation("abc123", "tags"), ids(&["dark", "city"]));
+        assert_eq!(list.annotated("group", "anime"), ids(&["xyz000"]));
+        assert_eq!(list.render(), LIST);
+    }
+
+    #[test]
+    fn test_set_ids_only_touches_changed_lines() {
+        let mut list = WallpaperList::parse(LIST);
+        list.set_ids(&ids(&["abc123", "ijk789", "xyz000"]));
+        list.set_ids(&ids(&["abc123", "ijk789", "aaa111", "zzz999", "xyz000"]));
+        assert_eq!(
+            list.render(),
+            "# Favourites\nabc123 tags=dark,city  # the skyline\nijk789\n\n## Anime\naaa111\nxyz000 group=anime\nzzz999\n"
+        );
+
+        let mut empty = WallpaperList::default();
+        empty.set_ids(&ids(&["abc123"]));
+        assert_eq!(empty.render(), "abc123\n");
+    }
+}
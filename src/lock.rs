@@ -1,44 +1,76 @@
 use anyhow::{anyhow, Context, Result};
 use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 use tokio::fs::{File, OpenOptions};
 use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader, BufWriter};
 
+use crate::hash::HashAlgorithm;
 use crate::helper;
+use crate::paths::Paths;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
-pub(crate) struct LockEntry {
+pub struct LockEntry {
     image_id: String,
     image_location: String,
-    sha256: String,
+    #[serde(rename = "hash", alias = "sha256")]
+    hash: String,
+    /// Algorithm of `hash`; entries written before it was recorded are SHA256
+    #[serde(default = "HashAlgorithm::legacy")]
+    algorithm: HashAlgorithm,
+    /// When the checksum was last verified (unix seconds)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_checked: Option<u64>,
 }
 
 /// Lock file for tracking wallpaper integrity checksums
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LockFile {
     entries: Vec<LockEntry>,
+    #[serde(skip)]
+    path: PathBuf,
 }
 
 impl LockFile {
-    /// Create a new empty lock file
+    /// Create a new empty lock file at the default location
     pub fn new() -> Self {
+        Self::with_path(Self::default_path().unwrap_or_default())
+    }
+
+    /// Create a new empty lock file stored at `path`
+    pub fn with_path(path: impl Into<PathBuf>) -> Self {
         LockFile {
             entries: Vec::new(),
+            path: path.into(),
         }
     }
 
+    /// Where the lock file is stored
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Default lock file location inside the data folder
+    pub fn default_path() -> Result<PathBuf> {
+        Ok(Paths::resolve()?.lock_file)
+    }
+
     /// Load lock file from disk asynchronously
     pub async fn load() -> Result<Self> {
-        let lock_file_location = helper::get_folder_path()
-            .context("   Failed to get folder path")?
-            .join("wallpaper.lock");
+        Self::load_from(Self::default_path()?).await
+    }
+
+    /// Load lock file stored at `path`
+    pub async fn load_from(path: impl AsRef<Path>) -> Result<Self> {
+        let lock_file_location = path.as_ref();
 
         if tokio::fs::metadata(&lock_file_location).await.is_ok() {
             let file = File::open(&lock_file_location).await?;
             let mut reader = BufReader::new(file);
             let mut contents = String::new();
             reader.read_to_string(&mut contents).await?;
-            let lock_file: LockFile =
+            let mut lock_file: LockFile =
                 serde_json::from_str(&contents).context("   Failed to parse lock file")?;
+            lock_file.path = lock_file_location.to_path_buf();
             Ok(lock_file)
         } else {
             Err(anyhow!("   Lock file does not exist"))
@@ -50,35 +82,65 @@ impl LockFile {
         Self::load().await.unwrap_or_else(|_| Self::new())
     }
 
+    /// Load the lock file stored at `path`, or start an empty one there
+    pub async fn load_or_new_at(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        Self::load_from(path)
+            .await
+            .unwrap_or_else(|_| Self::with_path(path))
+    }
+
     /// Add or update an entry in memory (does not write to disk)
-    pub fn add_entry(&mut self, image_id: String, image_location: String, sha256: String) {
+    pub fn add_entry(
+        &mut self,
+        image_id: String,
+        image_location: String,
+        hash: String,
+        algorithm: HashAlgorithm,
+    ) {
         if let Some(entry) = self
             .entries
             .iter_mut()
             .find(|entry| entry.image_id == image_id)
         {
             entry.image_location = image_location;
-            entry.sha256 = sha256;
+            entry.hash = hash;
+            entry.algorithm = algorithm;
+            entry.last_checked = Some(helper::unix_timestamp());
         } else {
             self.entries.push(LockEntry {
                 image_id,
                 image_location,
-                sha256,
+                hash,
+                algorithm,
+                last_checked: Some(helper::unix_timestamp()),
             });
         }
     }
 
+    /// Drop every entry (does not write to disk)
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Record that the checksums of `image_ids` were verified at `now` (does not write to disk)
+    pub fn mark_checked(&mut self, image_ids: &[String], now: u64) {
+        for entry in self
+            .entries
+            .iter_mut()
+            .filter(|entry| image_ids.contains(&entry.image_id))
+        {
+            entry.last_checked = Some(now);
+        }
+    }
+
     /// Save the lock file to disk
     pub async fn save(&self) -> Result<()> {
-        let lock_file_location = helper::get_folder_path()
-            .context("  Failed to get folder path")?
-            .join("wallpaper.lock");
-
         let file = OpenOptions::new()
             .create(true)
             .write(true)
             .truncate(true)
-            .open(&lock_file_location)
+            .open(&self.path)
             .await
             .context("   Failed to open lock file for writing")?;
 
@@ -102,9 +164,10 @@ impl LockFile {
         &mut self,
         image_id: String,
         image_location: String,
-        sha256: String,
+        hash: String,
+        algorithm: HashAlgorithm,
     ) -> Result<()> {
-        self.add_entry(image_id, image_location, sha256);
+        self.add_entry(image_id, image_location, hash, algorithm);
         self.save().await
     }
 
@@ -112,7 +175,7 @@ impl LockFile {
     pub fn contains(&self, image_id: &str, hash: &str) -> bool {
         self.entries
             .iter()
-            .any(|entry| entry.image_id == image_id && entry.sha256 == hash)
+            .any(|entry| entry.image_id == image_id && entry.hash == hash)
     }
 
     /// Get a reference to the entries (for fast lookups without mutex)
@@ -127,29 +190,7 @@ impl LockFile {
 
         // Only update file if an entry was actually removed
         if self.entries.len() < initial_len {
-            let lock_file_location = helper::get_folder_path()
-                .context("   Failed to get folder path")?
-                .join("wallpaper.lock");
-
-            let file = OpenOptions::new()
-                .create(true)
-                .write(true)
-                .truncate(true)
-                .open(&lock_file_location)
-                .await
-                .context("   Failed to open lock file for writing")?;
-
-            let mut writer = BufWriter::new(file);
-            let json =
-                serde_json::to_string_pretty(&self).context("   Failed to serialize lock file")?;
-            writer
-                .write_all(json.as_bytes())
-                .await
-                .context("   Failed to write lock file")?;
-            writer
-                .flush()
-                .await
-                .context("   Failed to flush lock file")?;
+            self.save().await?;
         }
 
         Ok(())
@@ -165,8 +206,24 @@ impl LockEntry {
         &self.image_location
     }
 
-    pub fn image_sha256(&self) -> &str {
-        &self.sha256
+    pub fn image_hash(&self) -> &str {
+        &self.hash
+    }
+
+    pub fn algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    pub fn last_checked(&self) -> Option<u64> {
+        self.last_checked
+    }
+
+    /// Whether the file was verified within `recheck_after` seconds and not modified since
+    pub fn is_fresh(&self, recheck_after: u64, modified: u64, now: u64) -> bool {
+        match self.last_checked {
+            Some(checked) => modified <= checked && now.saturating_sub(checked) < recheck_after,
+            None => false,
+        }
     }
 }
 
@@ -186,6 +243,36 @@ mod tests {
         assert!(lock_file.entries.is_empty());
     }
 
+    #[test]
+    fn test_lock_entry_freshness() {
+        let mut lock_file = LockFile::with_path("/nonexistent/wallpaper.lock");
+        lock_file.entries.push(LockEntry {
+            image_id: "test123".to_string(),
+            image_location: "/path/to/image.jpg".to_string(),
+            hash: "abcd1234".to_string(),
+            algorithm: HashAlgorithm::Sha256,
+            last_checked: None,
+        });
+        assert!(!lock_file.entries[0].is_fresh(3_600, 0, 1_000));
+
+        lock_file.mark_checked(&["test123".to_string()], 1_000);
+        let entry = &lock_file.entries[0];
+        assert!(entry.is_fresh(3_600, 900, 1_000 + 60));
+        assert!(!entry.is_fresh(3_600, 900, 1_000 + 3_600));
+        // Modified after the last check
+        assert!(!entry.is_fresh(3_600, 1_001, 1_000 + 60));
+    }
+
+    #[test]
+    fn test_legacy_entries_are_sha256() {
+        let lock_file: LockFile = serde_json::from_str(
+            r#"{"entries":[{"image_id":"test123","image_location":"/a.jpg","sha256":"abcd1234"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(lock_file.entries[0].algorithm(), HashAlgorithm::Sha256);
+        assert!(lock_file.contains("test123", "abcd1234"));
+    }
+
     #[tokio::test]
     async fn test_lock_file_contains() {
         // Skip if config directory doesn't exist
@@ -207,6 +294,7 @@ mod tests {
                 "test123".to_string(),
                 "/path/to/image.jpg".to_string(),
                 "abcd1234".to_string(),
+                HashAlgorithm::Sha256,
             )
             .await
             .unwrap();
@@ -237,6 +325,7 @@ mod tests {
                 "test123".to_string(),
                 "/path/to/image.jpg".to_string(),
                 "abcd1234".to_string(),
+                HashAlgorithm::Sha256,
             )
             .await
             .unwrap();
@@ -245,6 +334,7 @@ mod tests {
                 "test456".to_string(),
                 "/path/to/image2.jpg".to_string(),
                 "efgh5678".to_string(),
+                HashAlgorithm::Sha256,
             )
             .await
             .unwrap();
@@ -1,56 +1,313 @@
-use anyhow::Error;
+use anyhow::{anyhow, Error};
 use clap::Parser;
-use rust_paper::{Cli, Command, RustPaper, WallhavenClient};
+use rust_paper::output;
+use rust_paper::{
+    AddOptions, BackupAction, CleanOptions, Cli, Command, ConfigAction, ConfigPatch, GroupAction,
+    QuarantineAction, SeenAction, SyncOptions, WallhavenClient, WallhavenClientError,
+};
+use std::process::ExitCode;
 
 #[tokio::main]
-async fn main() -> Result<(), Error> {
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", output::text(&format!("{:?}", e)));
+            ExitCode::from(exit_code(&e))
+        }
+    }
+}
+
+/// Exit code of the first typed library error in the chain, 1 for anything else
+fn exit_code(err: &Error) -> u8 {
+    err.chain()
+        .find_map(|cause| cause.downcast_ref::<rust_paper::Error>())
+        .map_or(1, rust_paper::Error::exit_code)
+}
+
+async fn run() -> Result<(), Error> {
     let cli = Cli::parse();
+    output::set_plain(cli.plain);
 
     match &cli.command {
         // Original commands - don't require API key
-        Command::Sync
+        Command::Sync { .. }
         | Command::Add { .. }
         | Command::Remove { .. }
+        | Command::Delete { .. }
         | Command::List
-        | Command::Clean
-        | Command::Info { .. } => {
-            let mut rust_paper = RustPaper::new().await?;
+        | Command::Clean { .. }
+        | Command::Reprocess { .. }
+        | Command::Info { .. }
+        | Command::Diff { .. }
+        | Command::Current { .. }
+        | Command::Stale { .. }
+        | Command::Credit { .. }
+        | Command::Pull { .. }
+        | Command::Config { .. }
+        | Command::Group { .. }
+        | Command::Import { .. }
+        | Command::Follow { .. }
+        | Command::Unfollow { .. }
+        | Command::Following
+        | Command::Block { .. }
+        | Command::Unblock { .. }
+        | Command::Blocked
+        | Command::Doctor
+        | Command::Backup { .. }
+        | Command::Repair
+        | Command::Quarantine { .. }
+        | Command::Seen { .. }
+        | Command::AuditPurity { .. }
+        | Command::Status { .. }
+        | Command::Serve { .. }
+        | Command::Watch { .. }
+        | Command::Log { .. } => {
+            let mut rust_paper = cli.builder().build().await?;
+            rust_paper.offline = cli.offline;
             match cli.command {
-                Command::Sync => {
-                    rust_paper.sync().await?;
+                Command::Sync {
+                    ids,
+                    source,
+                    full,
+                    group,
+                    allow_partial,
+                    ignore_disk_space,
+                    now,
+                    dry_run,
+                } => {
+                    let options = SyncOptions {
+                        full_check: full,
+                        ignore_disk_space,
+                        ignore_conditions: now,
+                        dry_run,
+                    };
+                    let report = match group {
+                        Some(group) => rust_paper.sync_group(&group, &options).await?,
+                        None if !ids.is_empty() => rust_paper.sync_ids(&ids, &options).await?,
+                        None => rust_paper.sync(source.as_deref(), &options).await?,
+                    };
+                    if report.has_failures() && !allow_partial {
+                        return Err(anyhow!(
+                            "Sync incomplete: {} download(s) failed, {} followed uploader(s) not checked, {} archived wallpaper(s) drifted",
+                            report.failed,
+                            report.unchecked_uploaders,
+                            report.archive_drift.len()
+                        ));
+                    }
+                }
+                Command::Add {
+                    mut paths,
+                    local,
+                    move_files,
+                    group,
+                    force,
+                    verify,
+                    pin_hash,
+                } => {
+                    if let Some(dir) = local {
+                        rust_paper
+                            .import_local(&dir, move_files, group.as_deref())
+                            .await?;
+                    }
+                    if !paths.is_empty() {
+                        rust_paper
+                            .add(
+                                &mut paths,
+                                &AddOptions {
+                                    group,
+                                    force,
+                                    verify,
+                                    pin_hash,
+                                },
+                            )
+                            .await?;
+                    }
+                }
+                Command::Follow { username } => {
+                    rust_paper.follow(&username).await?;
+                }
+                Command::Unfollow { username } => {
+                    rust_paper.unfollow(&username).await?;
+                }
+                Command::Following => {
+                    rust_paper.following().await?;
+                }
+                Command::Block { ids } => {
+                    rust_paper.block(&ids).await?;
+                }
+                Command::Unblock { ids } => {
+                    rust_paper.unblock(&ids).await?;
+                }
+                Command::Blocked => {
+                    rust_paper.blocked().await?;
+                }
+                Command::Import { from, path, group } => {
+                    rust_paper
+                        .import_from(from, path.as_deref(), group.as_deref())
+                        .await?;
                 }
-                Command::Add { mut paths } => {
-                    rust_paper.add(&mut paths).await?;
+                Command::Log { since } => {
+                    rust_paper.log(since.as_deref()).await?;
                 }
+                Command::Status { json } => {
+                    rust_paper.status(json).await?;
+                }
+                Command::Repair => {
+                    rust_paper.repair().await?;
+                }
+                Command::AuditPurity {
+                    move_files,
+                    to,
+                    refresh,
+                } => {
+                    let quarantine =
+                        match to.or_else(|| rust_paper.config().quarantine_location.clone()) {
+                            Some(folder) if move_files => Some(std::path::PathBuf::from(folder)),
+                            None if move_files => {
+                                return Err(anyhow!(
+                                    "--move needs --to or quarantine_location in the config"
+                                ))
+                            }
+                            _ => None,
+                        };
+                    rust_paper
+                        .audit_purity(refresh, quarantine.as_deref())
+                        .await?;
+                }
+                Command::Doctor => {
+                    rust_paper.doctor().await?;
+                }
+                Command::Config { action } => match action {
+                    ConfigAction::Set { key, value } => {
+                        rust_paper
+                            .update_config(&ConfigPatch::new().set_str(&key, &value))
+                            .await?
+                    }
+                    ConfigAction::Check => rust_paper.check_config_file()?,
+                },
+                Command::Quarantine { action } => match action {
+                    QuarantineAction::List => rust_paper.quarantine_list().await?,
+                    QuarantineAction::Purge => rust_paper.quarantine_purge().await?,
+                },
+                Command::Seen { action } => match action {
+                    SeenAction::List => rust_paper.seen_list().await?,
+                    SeenAction::Purge => rust_paper.seen_purge().await?,
+                },
+                Command::Backup { action } => match action {
+                    BackupAction::Create { path } => rust_paper.backup_create(&path).await?,
+                    BackupAction::Restore { path } => rust_paper.backup_restore(&path).await?,
+                },
+                Command::Group { action } => match action {
+                    GroupAction::Create { name } => rust_paper.create_group(&name).await?,
+                    GroupAction::Delete { name } => rust_paper.delete_group(&name).await?,
+                    GroupAction::List => rust_paper.list_groups().await?,
+                    GroupAction::Archive { name } => {
+                        rust_paper.set_group_archived(&name, true).await?
+                    }
+                    GroupAction::Unarchive { name } => {
+                        rust_paper.set_group_archived(&name, false).await?
+                    }
+                },
                 Command::Remove { ids } => {
                     rust_paper.remove(&ids).await?;
                 }
+                Command::Delete { ids, keep_file } => {
+                    rust_paper.delete(&ids, keep_file).await?;
+                }
                 Command::List => {
                     rust_paper.list().await?;
                 }
-                Command::Clean => {
-                    rust_paper.clean().await?;
+                Command::Clean {
+                    tombstones,
+                    interactive,
+                    older_than,
+                    pattern,
+                } => {
+                    rust_paper
+                        .clean(&CleanOptions {
+                            tombstones,
+                            interactive,
+                            older_than,
+                            pattern,
+                        })
+                        .await?;
+                }
+                Command::Reprocess { ids } => {
+                    rust_paper.reprocess(&ids).await?;
                 }
                 Command::Info { id } => {
+                    let id = match id {
+                        Some(id) => id,
+                        None => rust_paper.current_id().await?,
+                    };
                     rust_paper.info(&id).await?;
                 }
+                Command::Diff { ids, local } => {
+                    rust_paper.diff(&ids, &local).await?;
+                }
+                Command::Stale {
+                    older_than,
+                    archive,
+                    remove,
+                } => {
+                    let action = match (archive, remove) {
+                        (true, _) => rust_paper::StaleAction::Archive,
+                        (_, true) => rust_paper::StaleAction::Remove,
+                        _ => rust_paper::StaleAction::Flag,
+                    };
+                    rust_paper.stale(older_than.as_deref(), action).await?;
+                }
+                Command::Current { set, monitor, json } => match set {
+                    Some(id) => rust_paper.set_current(&id, monitor.as_deref()).await?,
+                    None => rust_paper.current(json).await?,
+                },
+                Command::Credit { id, copy } => {
+                    rust_paper.credit(&id, copy).await?;
+                }
+                Command::Pull { source } => {
+                    rust_paper.pull(&source).await?;
+                }
+                Command::Watch { debounce } => {
+                    rust_paper.watch(debounce).await?;
+                }
+                Command::Serve { listen, token } => {
+                    let token = token
+                        .or_else(|| rust_paper.config().serve_token.clone())
+                        .unwrap_or_default();
+                    rust_paper.serve(&listen, &token).await?;
+                }
                 _ => unreachable!(),
             }
         }
+        #[cfg(feature = "preview")]
+        Command::Preview { .. } => {
+            let rust_paper = cli.builder().build().await?;
+            if let Command::Preview {
+                ids,
+                protocol,
+                width,
+            } = cli.command
+            {
+                rust_paper.preview(&ids, protocol, width).await?;
+            }
+        }
         // New API commands - require API key
         Command::Search(_)
+        | Command::Toplist(_)
+        | Command::Similar(_)
         | Command::TagInfo(_)
         | Command::UserSettings(_)
         | Command::UserCollections(_) => {
-            let mut client = WallhavenClient::new(cli.command)
-                .await
+            let rust_paper = cli.builder().build().await?;
+            let mut client = WallhavenClient::with_rust_paper(cli.command, rust_paper)
                 .map_err(|e| anyhow::anyhow!("Failed to create API client: {}", e))?;
-            let result = client
-                .execute()
-                .await
-                .map_err(|e| anyhow::anyhow!("API request failed: {}", e))?;
+            let result = client.execute().await.map_err(|e| match e {
+                WallhavenClientError::Wallhaven(e) => Error::new(e),
+                e => anyhow!("API request failed: {}", e),
+            })?;
             if !result.is_empty() {
-                println!("{}", result);
+                println!("{}", output::text(&result));
             }
         }
     }
@@ -0,0 +1,231 @@
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use crate::error::{Error, Result};
+use crate::hash::hmac_sha256;
+use crate::output::say;
+use crate::{helper, import, RustPaper};
+
+/// One machine's tracked wallpapers and the ones it removed, written after
+/// every sync so `pull` can bring another machine in line
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct Manifest {
+    pub generated_at: u64,
+    /// Tracked IDs with the checksum of their file, when known
+    pub wallpapers: BTreeMap<String, Option<String>>,
+    /// IDs that were tracked in an earlier manifest and have since been removed
+    pub removed: BTreeSet<String>,
+    /// Hex HMAC-SHA256 of the rest of the manifest, keyed with `manifest_key`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+impl Manifest {
+    /// Parse a manifest, starting empty if the file is missing
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self> {
+        match tokio::fs::read_to_string(path.as_ref()).await {
+            Ok(text) => Self::parse(&text),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        serde_json::from_str(text)
+            .map_err(|e| Error::InvalidInput(format!("Not a rust-paper manifest: {}", e)))
+    }
+
+    pub async fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let json = serde_json::to_string_pretty(self).context("Failed to encode the manifest")?;
+        tokio::fs::write(path.as_ref(), json)
+            .await
+            .context("   Failed to write the manifest")?;
+        Ok(())
+    }
+
+    /// The manifest following `previous` for the wallpapers tracked now.
+    /// Whatever `previous` tracked that is gone now counts as removed
+    pub fn next(previous: &Manifest, wallpapers: BTreeMap<String, Option<String>>) -> Self {
+        let removed = previous
+            .removed
+            .iter()
+            .chain(previous.wallpapers.keys())
+            .filter(|id| !wallpapers.contains_key(*id))
+            .cloned()
+            .collect();
+        Manifest {
+            generated_at: helper::unix_timestamp(),
+            wallpapers,
+            removed,
+            signature: None,
+        }
+    }
+
+    fn mac(&self, key: &str) -> String {
+        let unsigned = Manifest {
+            signature: None,
+            ..self.clone()
+        };
+        let payload = serde_json::to_vec(&unsigned).unwrap_or_default();
+        hmac_sha256(key.as_bytes(), &payload)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    pub fn sign(&mut self, key: &str) {
+        self.signature = Some(self.mac(key));
+    }
+
+    /// Whether the manifest carries a valid signature for `key`
+    pub fn verify(&self, key: &str) -> bool {
+        self.signature.as_deref() == Some(self.mac(key).as_str())
+    }
+
+    /// What pulling `other` does to a machine whose own manifest is `self`:
+    /// the IDs to add and the tracked IDs to remove. Wallpapers removed here
+    /// aren't brought back, and local imports can't be shared
+    pub fn changes(&self, tracked: &[String], other: &Manifest) -> (Vec<String>, Vec<String>) {
+        let additions = other
+            .wallpapers
+            .keys()
+            .filter(|id| !tracked.contains(id) && !self.removed.contains(*id))
+            .filter(|id| !import::is_local_id(id))
+            .cloned()
+            .collect();
+        let removals = tracked
+            .iter()
+            .filter(|id| other.removed.contains(*id))
+            .cloned()
+            .collect();
+        (additions, removals)
+    }
+}
+
+impl RustPaper {
+    /// Write the manifest for the current list, signed with `manifest_key` if set
+    pub async fn write_manifest(&self) -> Result<Manifest> {
+        let previous = Manifest::load(&self.manifest_file_location).await?;
+        let hashes: BTreeMap<String, String> = match self.lock_file.lock().await.as_ref() {
+            Some(lock_file) => lock_file
+                .entries()
+                .iter()
+                .map(|entry| (entry.image_id().to_string(), entry.image_hash().to_string()))
+                .collect(),
+            None => BTreeMap::new(),
+        };
+        let wallpapers = self
+            .wallpapers
+            .iter()
+            .map(|id| (id.clone(), hashes.get(id).cloned()))
+            .collect();
+        let mut manifest = Manifest::next(&previous, wallpapers);
+        if let Some(key) = &self.config.manifest_key {
+            manifest.sign(key);
+        }
+        manifest.save(&self.manifest_file_location).await?;
+        Ok(manifest)
+    }
+
+    /// Apply the additions and removals of another machine's manifest, read
+    /// from a file or URL. Run `sync` afterwards to download the additions
+    pub async fn pull(&mut self, source: &str) -> Result<()> {
+        let text = if helper::is_url(source) {
+            if self.offline {
+                return Err(Error::InvalidInput(
+                    "Can't pull a manifest URL while offline".to_string(),
+                ));
+            }
+            self.fetcher.get_text(source, None).await?
+        } else {
+            tokio::fs::read_to_string(source)
+                .await
+                .with_context(|| format!("   Failed to read {}", source))?
+        };
+        let other = Manifest::parse(&text)?;
+        match (&self.config.manifest_key, &other.signature) {
+            (Some(key), _) if !other.verify(key) => {
+                return Err(Error::IntegrityMismatch {
+                    id: source.to_string(),
+                    expected: "a manifest signed with manifest_key".to_string(),
+                    actual: other
+                        .signature
+                        .clone()
+                        .unwrap_or_else(|| "no signature".to_string()),
+                });
+            }
+            (None, Some(_)) => {
+                say!("  ⚠ Set manifest_key to check the signature of this manifest");
+            }
+            _ => {}
+        }
+
+        // Record this machine's own removals first so the pull can't undo them
+        let own = self.write_manifest().await?;
+        let (mut additions, removals) = own.changes(&self.wallpapers, &other);
+        if additions.is_empty() && removals.is_empty() {
+            say!("   Already in line with the manifest.");
+            return Ok(());
+        }
+        if !removals.is_empty() {
+            self.remove(&removals).await?;
+        }
+        if !additions.is_empty() {
+            self.add(&mut additions, &Default::default()).await?;
+        }
+        self.write_manifest().await?;
+        say!(
+            "   Pulled {} addition(s) and {} removal(s); run `rust-paper sync` to download",
+            additions.len(),
+            removals.len()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(list: &[&str]) -> Vec<String> {
+        list.iter().map(|id| id.to_string()).collect()
+    }
+
+    fn manifest(tracked: &[&str]) -> BTreeMap<String, Option<String>> {
+        tracked.iter().map(|id| (id.to_string(), None)).collect()
+    }
+
+    #[test]
+    fn test_next_records_removals() {
+        let first = Manifest::next(&Manifest::default(), manifest(&["abc123", "def456"]));
+        let second = Manifest::next(&first, manifest(&["def456"]));
+        assert_eq!(second.removed, BTreeSet::from(["abc123".to_string()]));
+        let third = Manifest::next(&second, manifest(&["abc123", "def456"]));
+        assert!(third.removed.is_empty());
+    }
+
+    #[test]
+    fn test_changes_and_signature() {
+        let mut other = Manifest::next(&manifest_with(&["abc123"]), manifest(&["def456"]));
+        let own = Manifest::next(&manifest_with(&["ghi789"]), manifest(&["abc123"]));
+        let (additions, removals) = own.changes(&ids(&["abc123"]), &other);
+        assert_eq!(additions, ids(&["def456"]));
+        assert_eq!(removals, ids(&["abc123"]));
+
+        other.sign("secret");
+        assert!(other.verify("secret"));
+        assert!(!other.verify("guess"));
+        other.wallpapers.insert("zzz999".to_string(), None);
+        assert!(!other.verify("secret"));
+    }
+
+    fn manifest_with(tracked: &[&str]) -> Manifest {
+        Manifest {
+            wallpapers: manifest(tracked),
+            ..Manifest::default()
+        }
+    }
+}
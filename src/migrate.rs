@@ -0,0 +1,218 @@
+use anyhow::Context;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::audit::{self, AuditEntry, AuditEvent};
+use crate::error::{Error, Result};
+use crate::output::say;
+use crate::{build_file_map, helper, AddOptions, RustPaper};
+
+/// Text files larger than this are not searched for Wallhaven links
+const MAX_TEXT_SIZE: u64 = 1024 * 1024;
+
+/// Another wallpaper manager `import --from` migrates from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportTool {
+    Variety,
+    Styli,
+    Wpgtk,
+    /// Any folder of images and text files, or a single text file of links
+    Files,
+}
+
+impl FromStr for ImportTool {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "variety" => Ok(ImportTool::Variety),
+            "styli" | "styli.sh" => Ok(ImportTool::Styli),
+            "wpgtk" | "wpg" => Ok(ImportTool::Wpgtk),
+            "files" => Ok(ImportTool::Files),
+            other => Err(format!(
+                "Unknown tool '{}'; expected variety, styli, wpgtk or files",
+                other
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for ImportTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportTool::Variety => write!(f, "variety"),
+            ImportTool::Styli => write!(f, "styli.sh"),
+            ImportTool::Wpgtk => write!(f, "wpgtk"),
+            ImportTool::Files => write!(f, "files"),
+        }
+    }
+}
+
+impl ImportTool {
+    /// Where the tool keeps its downloads and history, if it has a fixed place
+    fn default_path(self) -> Option<PathBuf> {
+        let home = dirs::home_dir()?;
+        match self {
+            ImportTool::Variety => Some(home.join(".config/variety")),
+            ImportTool::Wpgtk => Some(home.join(".config/wpg/wallpapers")),
+            ImportTool::Styli | ImportTool::Files => None,
+        }
+    }
+}
+
+/// Wallhaven IDs referenced in `text`: wallpaper pages, short links and
+/// `wallhaven-<id>` image names, which is how the tools save Wallhaven downloads
+fn ids_in_text(text: &str) -> Vec<String> {
+    let pattern = regex::Regex::new(r"(?:wallhaven\.cc/w/|whvn\.cc/|wallhaven-)([a-z0-9]{6})\b")
+        .expect("valid regex");
+    pattern
+        .captures_iter(text)
+        .map(|capture| capture[1].to_string())
+        .collect()
+}
+
+/// What a scan turned up
+#[derive(Debug, Default)]
+struct Found {
+    ids: BTreeSet<String>,
+    /// Images already downloaded by the other tool, by ID
+    files: BTreeMap<String, PathBuf>,
+}
+
+/// Look for Wallhaven wallpapers in `path`: image names and links in text
+/// files such as history lists, through every subfolder
+async fn scan(path: &Path) -> anyhow::Result<Found> {
+    let mut found = Found::default();
+    let mut pending = vec![path.to_path_buf()];
+    while let Some(path) = pending.pop() {
+        let metadata = tokio::fs::metadata(&path)
+            .await
+            .with_context(|| format!("   Failed to read {}", path.display()))?;
+        if metadata.is_dir() {
+            let mut entries = tokio::fs::read_dir(&path).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                pending.push(entry.path());
+            }
+            continue;
+        }
+        if image::ImageFormat::from_path(&path).is_ok() {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            if let Some(id) = ids_in_text(&name).into_iter().next() {
+                found.ids.insert(id.clone());
+                found.files.entry(id).or_insert(path);
+            }
+        } else if metadata.len() <= MAX_TEXT_SIZE {
+            // Binary files fail to read as text and are skipped
+            if let Ok(text) = tokio::fs::read_to_string(&path).await {
+                found.ids.extend(ids_in_text(&text));
+            }
+        }
+    }
+    Ok(found)
+}
+
+impl RustPaper {
+    /// Track the Wallhaven wallpapers another manager downloaded or links to,
+    /// copying the images it already has so `sync` doesn't fetch them again
+    pub async fn import_from(
+        &mut self,
+        tool: ImportTool,
+        path: Option<&Path>,
+        group: Option<&str>,
+    ) -> Result<()> {
+        let path = match path.map(Path::to_path_buf).or_else(|| tool.default_path()) {
+            Some(path) => path,
+            None => {
+                return Err(Error::InvalidInput(format!(
+                    "Pass the folder or file {} keeps its wallpapers in",
+                    tool
+                )))
+            }
+        };
+        if !path.exists() {
+            return Err(Error::InvalidInput(format!(
+                "'{}' does not exist",
+                path.display()
+            )));
+        }
+        let found = scan(&path).await?;
+        if found.ids.is_empty() {
+            say!("   No Wallhaven wallpapers found in {}", path.display());
+            return Ok(());
+        }
+        say!(
+            "   Found {} Wallhaven wallpaper(s) in {}",
+            found.ids.len(),
+            path.display()
+        );
+        let mut ids: Vec<String> = found.ids.into_iter().collect();
+        let options = AddOptions {
+            group: group.map(str::to_string),
+            ..Default::default()
+        };
+        self.add(&mut ids, &options).await?;
+
+        // Bring over the images the other tool already downloaded
+        let existing = build_file_map(&self.config).await?;
+        let save_location = PathBuf::from(&self.config.save_location);
+        tokio::fs::create_dir_all(&save_location).await?;
+        let mut lock_updates = Vec::new();
+        let mut audit_entries = Vec::new();
+        for (id, source) in &found.files {
+            if !self.wallpapers.contains(id) || existing.contains_key(id) {
+                continue;
+            }
+            let extension = source
+                .extension()
+                .and_then(|e| e.to_str())
+                .unwrap_or("jpg")
+                .to_lowercase();
+            let target = helper::file_in(&save_location, &format!("{}.{}", id, extension))?;
+            tokio::fs::copy(source, &target)
+                .await
+                .with_context(|| format!("   Failed to copy {}", source.display()))?;
+            say!("  ✓ Copied {} - {}", source.display(), target.display());
+            audit_entries.push(
+                AuditEntry::new(AuditEvent::Import, id)
+                    .path(&target)
+                    .detail(format!("{} ({})", source.display(), tool)),
+            );
+            if self.config.integrity {
+                let hash = helper::calculate_hash(&target, self.config.hash_algorithm).await?;
+                lock_updates.push((id.clone(), target.to_string_lossy().to_string(), hash));
+            }
+        }
+        if !lock_updates.is_empty() {
+            let mut lock_file_guard = self.lock_file.lock().await;
+            if let Some(lock_file) = lock_file_guard.as_mut() {
+                for (id, location, hash) in lock_updates {
+                    lock_file.add_entry(id, location, hash, self.config.hash_algorithm);
+                }
+                lock_file.save().await?;
+            }
+        }
+        audit::append(&self.audit_log_location, &audit_entries).await?;
+        say!(
+            "   Copied {} image(s); run `rust-paper sync` to download the rest",
+            audit_entries.len()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ids_in_text() {
+        let history = "/home/me/.config/variety/Downloaded/wallhaven_city/wallhaven-7pmgv9.jpg\n\
+            https://wallhaven.cc/w/l8o2op and https://whvn.cc/x6m3gl\n\
+            /home/me/Pictures/beach.jpg\n\
+            https://wallhaven.cc/search?q=city\n";
+        assert_eq!(ids_in_text(history), ["7pmgv9", "l8o2op", "x6m3gl"]);
+        assert_eq!("Styli.sh".parse(), Ok(ImportTool::Styli));
+        assert!("nitrogen".parse::<ImportTool>().is_err());
+    }
+}
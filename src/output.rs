@@ -0,0 +1,134 @@
+//! Everything rust-paper prints goes through here, so the Nerd Font glyphs
+//! and emoji in its messages can be swapped for ASCII where they would show
+//! up as boxes: with `--plain`, in a non-UTF-8 locale, on the Linux console
+//! or a dumb terminal.
+
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+static PLAIN: AtomicBool = AtomicBool::new(false);
+
+/// Print ASCII instead of glyphs and emoji, e.g. for `--plain`. Plain output
+/// is also used when the locale or terminal can't show them
+pub fn set_plain(plain: bool) {
+    PLAIN.store(plain, Ordering::Relaxed);
+}
+
+/// Whether messages are printed without glyphs and emoji
+pub fn is_plain() -> bool {
+    static DETECTED: OnceLock<bool> = OnceLock::new();
+    PLAIN.load(Ordering::Relaxed)
+        || *DETECTED.get_or_init(|| {
+            let var = |name| std::env::var(name).ok().filter(|value| !value.is_empty());
+            let locale = var("LC_ALL")
+                .or_else(|| var("LC_CTYPE"))
+                .or_else(|| var("LANG"));
+            let term = var("TERM");
+            plain_for(locale.as_deref(), term.as_deref())
+        })
+}
+
+/// Whether `locale` and `term` call for plain output. An unset locale is
+/// taken to be UTF-8, as on most desktops
+fn plain_for(locale: Option<&str>, term: Option<&str>) -> bool {
+    let utf8 = locale.is_none_or(|locale| {
+        let locale = locale.to_ascii_lowercase();
+        locale.contains("utf-8") || locale.contains("utf8")
+    });
+    !utf8 || matches!(term, Some("linux") | Some("dumb"))
+}
+
+/// The ASCII stand-in for `c`, or `None` to keep it
+fn ascii(c: char) -> Option<&'static str> {
+    match c {
+        '✓' | '✔' => Some("+"),
+        '✗' | '❌' => Some("x"),
+        '⚠' | '‼' => Some("!"),
+        '○' => Some("-"),
+        '→' => Some("->"),
+        '─' => Some("-"),
+        // Nerd Font icons live in the private use areas
+        '\u{e000}'..='\u{f8ff}' | '\u{f0000}'.. => Some(""),
+        // Emoji, and the selector asking for their colored form
+        '\u{1f300}'..='\u{1faff}' | '✅' | '\u{fe0f}' => Some(""),
+        _ => None,
+    }
+}
+
+/// `text` as it should be shown: unchanged, or in plain mode with every
+/// glyph and emoji replaced
+pub fn text(text: &str) -> Cow<'_, str> {
+    if !is_plain() {
+        return Cow::Borrowed(text);
+    }
+    plain(text)
+}
+
+fn plain(text: &str) -> Cow<'_, str> {
+    if !text.chars().any(|c| ascii(c).is_some()) {
+        return Cow::Borrowed(text);
+    }
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match ascii(c) {
+            Some(replacement) => out.push_str(replacement),
+            None => out.push(c),
+        }
+    }
+    Cow::Owned(out)
+}
+
+/// `println!` through the output layer
+macro_rules! say {
+    () => {
+        println!()
+    };
+    ($($arg:tt)*) => {
+        println!("{}", $crate::output::text(&format!($($arg)*)))
+    };
+}
+
+/// `eprintln!` through the output layer
+macro_rules! say_err {
+    ($($arg:tt)*) => {
+        eprintln!("{}", $crate::output::text(&format!($($arg)*)))
+    };
+}
+
+/// `format!` through the output layer, for progress bars printing a line
+macro_rules! styled {
+    ($($arg:tt)*) => {
+        $crate::output::text(&format!($($arg)*)).into_owned()
+    };
+}
+
+pub(crate) use {say, say_err, styled};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_replaces_glyphs() {
+        assert_eq!(plain("  ✓ Downloaded abc123"), "  + Downloaded abc123");
+        assert_eq!(plain("  ⚠ Careful"), "  ! Careful");
+        assert_eq!(
+            plain(" \u{f00c}  Synced 東京-夜 → done ✅"),
+            "   Synced 東京-夜 -> done "
+        );
+        assert_eq!(plain("‼\u{fe0f} Warning"), "! Warning");
+        assert!(matches!(plain("nothing to do"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_plain_for_locale_and_terminal() {
+        assert!(!plain_for(Some("en_US.UTF-8"), Some("xterm-256color")));
+        assert!(!plain_for(Some("de_DE.utf8"), None));
+        assert!(!plain_for(None, Some("alacritty")));
+        assert!(plain_for(Some("C"), Some("xterm")));
+        assert!(plain_for(Some("POSIX"), None));
+        assert!(plain_for(Some("en_US.UTF-8"), Some("linux")));
+        assert!(plain_for(None, Some("dumb")));
+    }
+}
@@ -0,0 +1,85 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+use crate::helper;
+
+/// Overrides the folder holding `config.toml`
+pub const CONFIG_DIR_ENV: &str = "RUST_PAPER_CONFIG_DIR";
+/// Overrides the folder holding the list, lock file, state files and cache
+pub const DATA_DIR_ENV: &str = "RUST_PAPER_DATA_DIR";
+
+const WALLPAPERS_LIST: &str = "wallpapers.lst";
+
+/// Every location rust-paper reads or writes, resolved once at startup
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Paths {
+    pub config_dir: PathBuf,
+    pub data_dir: PathBuf,
+    pub wallpapers_list: PathBuf,
+    pub lock_file: PathBuf,
+    pub cache_dir: PathBuf,
+}
+
+impl Paths {
+    /// Config in `config_dir`, everything else in `data_dir`
+    pub fn new(config_dir: impl Into<PathBuf>, data_dir: impl Into<PathBuf>) -> Self {
+        let config_dir = config_dir.into();
+        let data_dir = data_dir.into();
+        Paths {
+            wallpapers_list: data_dir.join(WALLPAPERS_LIST),
+            lock_file: data_dir.join("wallpaper.lock"),
+            cache_dir: data_dir.join("cache"),
+            config_dir,
+            data_dir,
+        }
+    }
+
+    /// The standard locations, honouring `RUST_PAPER_CONFIG_DIR` and `RUST_PAPER_DATA_DIR`
+    pub fn resolve() -> Result<Self> {
+        let config_dir = match std::env::var_os(CONFIG_DIR_ENV) {
+            Some(dir) => PathBuf::from(dir),
+            None => helper::get_folder_path().context("   Failed to get folder path")?,
+        };
+        let data_dir = match std::env::var_os(DATA_DIR_ENV) {
+            Some(dir) => PathBuf::from(dir),
+            None => default_data_dir(&config_dir),
+        };
+        Ok(Self::new(config_dir, data_dir))
+    }
+
+    /// A state file in the data folder
+    pub fn data_file(&self, name: &str) -> PathBuf {
+        self.data_dir.join(name)
+    }
+}
+
+/// The XDG data folder, unless an existing install keeps its list next to the config
+fn default_data_dir(config_dir: &Path) -> PathBuf {
+    if config_dir.join(WALLPAPERS_LIST).exists() {
+        return config_dir.to_path_buf();
+    }
+    dirs::data_dir()
+        .map(|dir| dir.join("rust-paper"))
+        .unwrap_or_else(|| config_dir.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_existing_install_keeps_data_next_to_config() {
+        let dir = std::env::temp_dir().join(format!("rust-paper-paths-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(WALLPAPERS_LIST), "").unwrap();
+        assert_eq!(default_data_dir(&dir), dir);
+
+        let paths = Paths::new("/config", "/data");
+        assert_eq!(paths.lock_file, PathBuf::from("/data/wallpaper.lock"));
+        assert_eq!(
+            paths.data_file("groups.json"),
+            PathBuf::from("/data/groups.json")
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
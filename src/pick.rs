@@ -0,0 +1,159 @@
+use std::collections::hash_map::RandomState;
+use std::collections::BTreeSet;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{BufRead, Write};
+
+use crate::api::Wallpaper;
+use crate::output::say;
+
+/// What to do with the wallpapers picked from search results
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PickAction {
+    /// Track them, for the next sync to download
+    Add,
+    /// Download them now
+    Download,
+}
+
+/// Parse a selection such as `1-5,8` or `all` against `count` numbered
+/// results, into sorted 0-based indexes
+pub(crate) fn parse_selection(input: &str, count: usize) -> Result<Vec<usize>, String> {
+    let input = input.trim();
+    if input.eq_ignore_ascii_case("all") || input == "*" {
+        return Ok((0..count).collect());
+    }
+    let number = |text: &str| -> Result<usize, String> {
+        match text.trim().parse::<usize>() {
+            Ok(n) if (1..=count).contains(&n) => Ok(n - 1),
+            _ => Err(format!(
+                "'{}' is not a result number (1-{})",
+                text.trim(),
+                count
+            )),
+        }
+    };
+    let mut picked = BTreeSet::new();
+    for part in input
+        .split([',', ' '])
+        .filter(|part| !part.trim().is_empty())
+    {
+        match part.split_once('-') {
+            Some((start, end)) => {
+                let (start, end) = (number(start)?, number(end)?);
+                if start > end {
+                    return Err(format!("'{}' is a backwards range", part.trim()));
+                }
+                picked.extend(start..=end);
+            }
+            None => {
+                picked.insert(number(part)?);
+            }
+        }
+    }
+    Ok(picked.into_iter().collect())
+}
+
+/// A random index below `count`, or `None` when there is nothing to pick
+pub(crate) fn random_index(count: usize) -> Option<usize> {
+    if count == 0 {
+        return None;
+    }
+    // Every RandomState gets fresh keys from the OS, plenty for picking a wallpaper
+    let seed = RandomState::new().build_hasher().finish();
+    Some((seed % count as u64) as usize)
+}
+
+fn prompt(input: &mut impl BufRead, question: &str) -> Option<String> {
+    print!("{} ", question);
+    let _ = std::io::stdout().flush();
+    let mut line = String::new();
+    match input.read_line(&mut line) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(line.trim().to_string()),
+    }
+}
+
+/// Number `wallpapers`, then ask which to take and whether to add or
+/// download them. An empty answer or end of input cancels
+pub(crate) fn pick(
+    input: &mut impl BufRead,
+    wallpapers: &[Wallpaper],
+) -> Option<(Vec<usize>, PickAction)> {
+    for (idx, wallpaper) in wallpapers.iter().enumerate() {
+        say!(
+            "  {:>3}) {}  {:>9}  {:.2} MB  {} / {}",
+            idx + 1,
+            wallpaper.id,
+            wallpaper.resolution,
+            wallpaper.file_size as f64 / 1_048_576.0,
+            wallpaper.category,
+            wallpaper.purity
+        );
+    }
+    let picked = loop {
+        let answer = prompt(
+            input,
+            "  Pick wallpapers (e.g. 1-5,8 or all, empty to cancel):",
+        )?;
+        if answer.is_empty() {
+            return None;
+        }
+        match parse_selection(&answer, wallpapers.len()) {
+            Ok(picked) if !picked.is_empty() => break picked,
+            Ok(_) => return None,
+            Err(e) => say!("  ⚠ {}", e),
+        }
+    };
+    loop {
+        let answer = prompt(input, "  [a]dd to the list or [d]ownload now?")?;
+        match answer.to_ascii_lowercase().as_str() {
+            "a" | "add" => return Some((picked, PickAction::Add)),
+            "d" | "download" => return Some((picked, PickAction::Download)),
+            "" => return None,
+            _ => say!("  ⚠ Please answer a or d"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_selection() {
+        assert_eq!(parse_selection("1-3,5", 6), Ok(vec![0, 1, 2, 4]));
+        assert_eq!(parse_selection(" 2, 2 1 ", 3), Ok(vec![0, 1]));
+        assert_eq!(parse_selection("all", 3), Ok(vec![0, 1, 2]));
+        assert!(parse_selection("0", 3).is_err());
+        assert!(parse_selection("2-9", 3).is_err());
+        assert!(parse_selection("3-1", 3).is_err());
+        assert!(parse_selection("x", 3).is_err());
+    }
+
+    #[test]
+    fn test_random_index() {
+        assert_eq!(random_index(0), None);
+        assert_eq!(random_index(1), Some(0));
+        assert!((0..20).all(|_| random_index(3).is_some_and(|idx| idx < 3)));
+    }
+
+    #[test]
+    fn test_pick_reads_selection_then_action() {
+        let wallpapers: Vec<Wallpaper> = ["abc123", "def456", "ghi789"]
+            .iter()
+            .map(|id| Wallpaper {
+                id: id.to_string(),
+                ..Default::default()
+            })
+            .collect();
+        let mut input = "9\n1,3\nd\n".as_bytes();
+        assert_eq!(
+            pick(&mut input, &wallpapers),
+            Some((vec![0, 2], PickAction::Download))
+        );
+        let mut input = "2\n".as_bytes();
+        assert_eq!(pick(&mut input, &wallpapers), None);
+        let mut input = "\n".as_bytes();
+        assert_eq!(pick(&mut input, &wallpapers), None);
+    }
+}
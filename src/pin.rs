@@ -0,0 +1,115 @@
+use anyhow::{Context, Result};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::fetch::HttpFetcher;
+use crate::hash::HashAlgorithm;
+
+/// Checksum of the upstream file recorded by `add --pin-hash`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Pin {
+    pub hash: String,
+    pub algorithm: HashAlgorithm,
+}
+
+impl Pin {
+    /// Pin the checksum of `data`
+    pub fn of(data: &[u8], algorithm: HashAlgorithm) -> Self {
+        Pin {
+            hash: algorithm.digest(data),
+            algorithm,
+        }
+    }
+
+    /// Fail with `Error::IntegrityMismatch` unless `data` is the pinned file
+    pub fn check(&self, id: &str, data: &[u8]) -> crate::Result<()> {
+        let actual = self.algorithm.digest(data);
+        if actual != self.hash {
+            return Err(crate::Error::IntegrityMismatch {
+                id: id.to_string(),
+                expected: self.hash.clone(),
+                actual,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Pinned upstream checksums by wallpaper ID, persisted next to the list
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct Pins {
+    pins: BTreeMap<String, Pin>,
+}
+
+impl Pins {
+    /// Load the pins from disk, starting empty if the file is missing
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self> {
+        match tokio::fs::read_to_string(path.as_ref()).await {
+            Ok(contents) => serde_json::from_str(&contents).context("   Failed to parse pins"),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Save the pins to disk
+    pub async fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let json = serde_json::to_string_pretty(&self).context("   Failed to serialize pins")?;
+        tokio::fs::write(path.as_ref(), json)
+            .await
+            .context("   Failed to write pins")
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Pin> {
+        self.pins.get(id)
+    }
+
+    pub fn insert(&mut self, id: &str, pin: Pin) {
+        self.pins.insert(id.to_string(), pin);
+    }
+
+    /// Drop the pins of `ids`; returns whether anything changed
+    pub fn remove_ids(&mut self, ids: &[String]) -> bool {
+        let len = self.pins.len();
+        self.pins.retain(|id, _| !ids.contains(id));
+        self.pins.len() != len
+    }
+}
+
+/// Download `url` completely to pin its checksum
+pub async fn fetch_pin(
+    fetcher: &dyn HttpFetcher,
+    url: &str,
+    algorithm: HashAlgorithm,
+) -> Result<Pin> {
+    let mut body = fetcher
+        .get_stream(url)
+        .await
+        .context("Failed to download image")?
+        .body;
+    let mut data = Vec::new();
+    while let Some(chunk) = body.next().await {
+        data.extend_from_slice(&chunk.context("Error while downloading file")?);
+    }
+    Ok(Pin::of(&data, algorithm))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pin_detects_replaced_files() {
+        let pin = Pin::of(b"original", HashAlgorithm::Sha256);
+        assert!(pin.check("abc123", b"original").is_ok());
+        assert!(matches!(
+            pin.check("abc123", b"replaced"),
+            Err(crate::Error::IntegrityMismatch { .. })
+        ));
+
+        let mut pins = Pins::default();
+        pins.insert("abc123", pin);
+        assert!(pins.remove_ids(&["abc123".to_string()]));
+        assert!(pins.get("abc123").is_none());
+    }
+}
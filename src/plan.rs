@@ -0,0 +1,145 @@
+use futures::{stream, StreamExt};
+use indicatif::HumanBytes;
+
+use crate::api::WallhavenWallpaper;
+use crate::config::Config;
+use crate::display::Resolution;
+use crate::error::Error;
+use crate::id::WallpaperId;
+use crate::output::say;
+use crate::{helper, RustPaper};
+
+const MIB: u64 = 1024 * 1024;
+
+/// A pending download and what the metadata phase found: the metadata to
+/// download it with (none without an API key, as the page is scraped then),
+/// or why it won't be downloaded
+pub(crate) type Planned = (String, anyhow::Result<Option<WallhavenWallpaper>>);
+
+/// Refuse `data` when a filter that only needs metadata rules it out
+fn check_filters(
+    config: &Config,
+    data: &WallhavenWallpaper,
+    minimum: Option<Resolution>,
+) -> Result<(), Error> {
+    let (width, height) = (
+        data.dimension_x.max(0) as u32,
+        data.dimension_y.max(0) as u32,
+    );
+    if let Some(display) = minimum.filter(|d| width > 0 && !d.fits_within(width, height)) {
+        return Err(Error::TooSmall {
+            id: data.id.clone(),
+            resolution: format!("{}x{}", width, height),
+            display: display.to_string(),
+        });
+    }
+    let size = data.file_size.max(0) as u64;
+    if let Some(limit) = config.max_file_size_mb.filter(|&limit| size > limit * MIB) {
+        return Err(Error::TooLarge {
+            id: data.id.clone(),
+            size: HumanBytes(size).to_string(),
+            limit,
+        });
+    }
+    Ok(())
+}
+
+impl RustPaper {
+    /// Fetch the metadata of every pending download up front, at most
+    /// `max_concurrent_downloads` at a time, and drop the wallpapers a filter
+    /// rules out before any image is downloaded
+    pub(crate) async fn plan_downloads(
+        &self,
+        ids: &[String],
+        minimum: Option<Resolution>,
+    ) -> Vec<Planned> {
+        let prefetch = self.config.api_key.is_some();
+        stream::iter(ids)
+            .map(|id| async move {
+                if !prefetch {
+                    return (id.clone(), Ok(None));
+                }
+                let planned = match WallpaperId::parse(id) {
+                    Ok(wallpaper_id) => self.fetch_metadata(&wallpaper_id).await,
+                    Err(e) => Err(e.into()),
+                }
+                .and_then(|data| {
+                    if data.path.is_empty() {
+                        return Err(anyhow::anyhow!(
+                            "Failed to get image link from API response"
+                        ));
+                    }
+                    check_filters(&self.config, &data, minimum)?;
+                    Ok(Some(data))
+                });
+                (id.clone(), planned)
+            })
+            .buffered(self.config.max_concurrent_downloads.max(1))
+            .collect()
+            .await
+    }
+
+    /// Print what a sync would download, for `sync --dry-run`
+    pub(crate) fn print_plan(&self, plan: &[Planned], sizes: &[u64]) {
+        let downloads = plan.iter().filter(|(_, planned)| planned.is_ok()).count();
+        let total: u64 = plan
+            .iter()
+            .zip(sizes)
+            .filter(|((_, planned), _)| planned.is_ok())
+            .map(|(_, size)| size)
+            .sum();
+        say!(
+            "   Dry run: would download {} wallpaper(s) (about {})",
+            downloads,
+            HumanBytes(total)
+        );
+        for ((id, planned), &size) in plan.iter().zip(sizes) {
+            match planned {
+                Ok(Some(data)) => say!(
+                    "  ○ {} - {}, {}, {}",
+                    id,
+                    data.resolution,
+                    HumanBytes(size),
+                    self.config
+                        .download_quality
+                        .url(id, &data.path, Some(&data.thumbs))
+                ),
+                Ok(None) => say!("  ○ {} - about {}", id, HumanBytes(size)),
+                Err(e) if helper::is_skipped(e) => say!("  ○ {}", e),
+                Err(e) => say!("  ✗ {} - {:#}", id, e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filters_use_metadata() {
+        let data: WallhavenWallpaper = crate::api::WallpaperInfoResponse::parse(
+            r#"{"data":{"id":"abc123","url":"","purity":"sfw","category":"general","dimension_x":1920,"dimension_y":1080,"resolution":"1920x1080","file_size":5242880,"file_type":"image/png","path":"https://w.wallhaven.cc/full/ab/wallhaven-abc123.png","tags":[]}}"#,
+        )
+        .unwrap();
+        let mut config = Config::default();
+        assert!(check_filters(&config, &data, None).is_ok());
+
+        let display = Resolution {
+            width: 2560,
+            height: 1440,
+        };
+        assert!(matches!(
+            check_filters(&config, &data, Some(display)),
+            Err(Error::TooSmall { .. })
+        ));
+
+        config.max_file_size_mb = Some(4);
+        assert!(matches!(
+            check_filters(&config, &data, None),
+            Err(Error::TooLarge { limit: 4, .. })
+        ));
+        config.max_file_size_mb = Some(5);
+        assert!(check_filters(&config, &data, None).is_ok());
+    }
+}
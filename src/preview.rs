@@ -0,0 +1,226 @@
+use anyhow::{anyhow, Context};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, ImageFormat};
+use std::io::Cursor;
+use std::str::FromStr;
+
+use crate::error::{Error, Result};
+use crate::output::say;
+use crate::{find_existing_image, RustPaper};
+
+/// Terminal graphics protocol used to draw a preview
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Kitty,
+    Iterm,
+    Sixel,
+    /// Coloured half blocks, readable on any true-colour terminal
+    Blocks,
+}
+
+impl FromStr for Protocol {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "kitty" => Ok(Protocol::Kitty),
+            "iterm" => Ok(Protocol::Iterm),
+            "sixel" => Ok(Protocol::Sixel),
+            "blocks" => Ok(Protocol::Blocks),
+            other => Err(format!("Unknown preview protocol '{}'", other)),
+        }
+    }
+}
+
+impl Protocol {
+    /// Guess the protocol from the environment the terminal sets
+    pub fn detect() -> Self {
+        let var = |name| std::env::var(name).unwrap_or_default();
+        if var("TERM") == "xterm-kitty" || !var("KITTY_WINDOW_ID").is_empty() {
+            Protocol::Kitty
+        } else if matches!(var("TERM_PROGRAM").as_str(), "iTerm.app" | "WezTerm") {
+            Protocol::Iterm
+        } else if var("TERM").contains("sixel") || var("TERM") == "foot" {
+            Protocol::Sixel
+        } else {
+            Protocol::Blocks
+        }
+    }
+}
+
+/// Terminal width in cells, from `COLUMNS` or a conservative default
+pub fn terminal_columns() -> u32 {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .unwrap_or(80)
+}
+
+/// Escape sequences drawing `img` `columns` cells wide
+pub fn render(img: &DynamicImage, protocol: Protocol, columns: u32) -> Result<String> {
+    let columns = columns.max(1);
+    match protocol {
+        Protocol::Kitty => {
+            let encoded = STANDARD.encode(png_bytes(img)?);
+            let chunks: Vec<&[u8]> = encoded.as_bytes().chunks(4096).collect();
+            let mut out = String::new();
+            for (idx, chunk) in chunks.iter().enumerate() {
+                let more = u8::from(idx + 1 < chunks.len());
+                let chunk = std::str::from_utf8(chunk).unwrap_or_default();
+                if idx == 0 {
+                    out.push_str(&format!(
+                        "\x1b_Gf=100,a=T,c={},m={};{}\x1b\\",
+                        columns, more, chunk
+                    ));
+                } else {
+                    out.push_str(&format!("\x1b_Gm={};{}\x1b\\", more, chunk));
+                }
+            }
+            out.push('\n');
+            Ok(out)
+        }
+        Protocol::Iterm => Ok(format!(
+            "\x1b]1337;File=inline=1;width={};preserveAspectRatio=1:{}\x07\n",
+            columns,
+            STANDARD.encode(png_bytes(img)?)
+        )),
+        // Cells are roughly 10 pixels wide
+        Protocol::Sixel => Ok(sixel(&fit(img, columns * 10, 1))),
+        Protocol::Blocks => Ok(blocks(&fit(img, columns, 2))),
+    }
+}
+
+/// Resize to `width` pixels, keeping the aspect ratio and a height divisible by `step`
+fn fit(img: &DynamicImage, width: u32, step: u32) -> DynamicImage {
+    let (w, h) = img.dimensions();
+    let width = width.min(w.max(1));
+    let height = ((u64::from(h) * u64::from(width) / u64::from(w.max(1))) as u32).max(step);
+    img.resize_exact(width, height - height % step, FilterType::Triangle)
+}
+
+fn png_bytes(img: &DynamicImage) -> Result<Vec<u8>> {
+    let mut bytes = Cursor::new(Vec::new());
+    img.write_to(&mut bytes, ImageFormat::Png)
+        .context("   Failed to encode preview")?;
+    Ok(bytes.into_inner())
+}
+
+/// Two pixels per cell: the upper one as foreground, the lower as background
+fn blocks(img: &DynamicImage) -> String {
+    let rgb = img.to_rgb8();
+    let mut out = String::new();
+    for y in (0..rgb.height()).step_by(2) {
+        for x in 0..rgb.width() {
+            let top = rgb.get_pixel(x, y);
+            let bottom = rgb.get_pixel(x, (y + 1).min(rgb.height() - 1));
+            out.push_str(&format!(
+                "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m▀",
+                top[0], top[1], top[2], bottom[0], bottom[1], bottom[2]
+            ));
+        }
+        out.push_str("\x1b[0m\n");
+    }
+    out
+}
+
+/// Sixel image using a fixed 6x6x6 colour cube
+fn sixel(img: &DynamicImage) -> String {
+    let rgb = img.to_rgb8();
+    let (width, height) = rgb.dimensions();
+    let level = |v: u8| (u16::from(v) * 5 + 127) / 255;
+    let index = |x, y| {
+        let p = rgb.get_pixel(x, y);
+        (level(p[0]) * 36 + level(p[1]) * 6 + level(p[2])) as usize
+    };
+
+    let mut out = String::from("\x1bPq");
+    for i in 0..216u16 {
+        let percent = |l: u16| l * 100 / 5;
+        out.push_str(&format!(
+            "#{};2;{};{};{}",
+            i,
+            percent(i / 36),
+            percent(i / 6 % 6),
+            percent(i % 6)
+        ));
+    }
+    for band in (0..height).step_by(6) {
+        let rows = (band..(band + 6).min(height)).collect::<Vec<_>>();
+        let mut used = [false; 216];
+        for &y in &rows {
+            for x in 0..width {
+                used[index(x, y)] = true;
+            }
+        }
+        for color in (0..216).filter(|&c| used[c]) {
+            out.push_str(&format!("#{}", color));
+            for x in 0..width {
+                let bits = rows
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, &y)| index(x, y) == color)
+                    .fold(0u8, |bits, (bit, _)| bits | (1 << bit));
+                out.push((63 + bits) as char);
+            }
+            out.push('$');
+        }
+        out.push('-');
+    }
+    out.push_str("\x1b\\\n");
+    out
+}
+
+impl RustPaper {
+    /// Draw downloaded wallpapers in the terminal, one after another
+    pub async fn preview(
+        &self,
+        ids: &[String],
+        protocol: Option<Protocol>,
+        columns: Option<u32>,
+    ) -> Result<()> {
+        let protocol = protocol.unwrap_or_else(Protocol::detect);
+        let columns = columns.unwrap_or_else(terminal_columns);
+        for id in ids {
+            let id = crate::helper::parse_wallhaven_url(id);
+            let path = find_existing_image(&self.config, &id)
+                .await?
+                .ok_or_else(|| Error::NotFound(format!("downloaded image for '{}'", id)))?;
+            let img = image::open(&path)
+                .map_err(|e| anyhow!("   Failed to open {}: {}", path.display(), e))?;
+            say!("   {} ({}x{})", id, img.width(), img.height());
+            print!("{}", render(&img, protocol, columns)?);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image() -> DynamicImage {
+        DynamicImage::ImageRgb8(image::RgbImage::from_pixel(40, 20, image::Rgb([255, 0, 0])))
+    }
+
+    #[test]
+    fn test_blocks_fit_the_width() {
+        let out = render(&image(), Protocol::Blocks, 10).unwrap();
+        // 10x4 pixels become 2 rows of 10 cells
+        assert_eq!(out.lines().count(), 2);
+        assert_eq!(out.lines().next().unwrap().matches('▀').count(), 10);
+        assert!(out.contains("38;2;255;0;0"));
+    }
+
+    #[test]
+    fn test_graphics_protocol_framing() {
+        let sixel = render(&image(), Protocol::Sixel, 2).unwrap();
+        assert!(sixel.starts_with("\x1bPq") && sixel.ends_with("\x1b\\\n"));
+        let kitty = render(&image(), Protocol::Kitty, 20).unwrap();
+        assert!(kitty.starts_with("\x1b_Gf=100,a=T,c=20,m=0;"));
+        let iterm = render(&image(), Protocol::Iterm, 20).unwrap();
+        assert!(iterm.starts_with("\x1b]1337;File=inline=1;width=20"));
+        assert_eq!("KITTY".parse(), Ok(Protocol::Kitty));
+    }
+}
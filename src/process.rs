@@ -0,0 +1,603 @@
+use anyhow::{anyhow, Context, Result};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::decode::DecodePool;
+use crate::helper;
+
+/// Folder under the save location holding per-ID variants
+pub const VARIANTS_DIR: &str = "variants";
+
+/// Where processing outputs are written
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum VariantLayout {
+    /// One folder per step: `<output_dir>/<id>.<ext>`
+    #[default]
+    ByStep,
+    /// One folder per wallpaper: `<save_location>/variants/<id>/<step>.<ext>`
+    ById,
+}
+
+/// A processed variant of a downloaded wallpaper
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variant {
+    /// Name of the step that produced it
+    pub step: String,
+    pub path: PathBuf,
+}
+
+/// How an image is scaled to the target resolution
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FitMode {
+    /// Scale to cover the target and crop the overflow
+    #[default]
+    Fill,
+    /// Scale to fit inside the target, keeping the whole image
+    Fit,
+}
+
+/// Which part of the image survives when it is cropped to another aspect ratio
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CropAnchor {
+    /// Keep the middle
+    #[default]
+    Center,
+    /// Keep the most detailed part, so a subject off to one side isn't cut off
+    Smart,
+}
+
+/// A single post-processing step, producing one output per wallpaper
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProcessStep {
+    /// Output name; results are written to `<save_location>/<name>/<id>.<ext>`
+    pub name: String,
+    /// Target resolution such as "2560x1440" (optional)
+    pub resolution: Option<String>,
+    /// Scaling mode used with `resolution` (default: fill)
+    #[serde(default)]
+    pub mode: FitMode,
+    /// Crop to an aspect ratio such as "21:9" or "9:16" without scaling (optional)
+    pub aspect: Option<String>,
+    /// What `aspect` and `fill` keep when they crop (default: center)
+    #[serde(default)]
+    pub crop: CropAnchor,
+    /// Gaussian blur sigma (optional)
+    pub blur: Option<f32>,
+    /// Darken the image by this percentage, 0-100 (optional)
+    pub dim: Option<u8>,
+    /// Output format: "jpeg", "png" or "webp" (default: same as original)
+    pub format: Option<String>,
+    /// JPEG quality, 1-100 (default: 90)
+    #[serde(default = "default_quality")]
+    pub quality: u8,
+    /// Directory for the outputs (default: `<save_location>/<name>`)
+    pub output_dir: Option<String>,
+}
+
+fn default_quality() -> u8 {
+    90
+}
+
+/// Blurred and dimmed variant of every wallpaper for hyprlock/swaylock
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct LockscreenConfig {
+    /// Gaussian blur sigma (default: 12)
+    pub blur: f32,
+    /// Darken the image by this percentage, 0-100 (default: 30)
+    pub dim: u8,
+    /// Directory for the lockscreen images (default: `<save_location>/lockscreen`)
+    pub output_dir: Option<String>,
+}
+
+impl Default for LockscreenConfig {
+    fn default() -> Self {
+        LockscreenConfig {
+            blur: 12.0,
+            dim: 30,
+            output_dir: None,
+        }
+    }
+}
+
+impl LockscreenConfig {
+    /// The processing step producing the lockscreen image
+    pub fn to_step(&self) -> ProcessStep {
+        ProcessStep {
+            name: "lockscreen".to_string(),
+            resolution: None,
+            mode: FitMode::default(),
+            aspect: None,
+            crop: CropAnchor::default(),
+            blur: (self.blur > 0.0).then_some(self.blur),
+            dim: (self.dim > 0).then_some(self.dim),
+            format: None,
+            quality: default_quality(),
+            output_dir: self.output_dir.clone(),
+        }
+    }
+}
+
+impl ProcessStep {
+    /// Directory holding the outputs of this step
+    pub fn output_dir(&self, save_location: impl AsRef<Path>) -> PathBuf {
+        match self.output_dir.as_deref() {
+            Some(dir) => PathBuf::from(dir),
+            None => save_location.as_ref().join(&self.name),
+        }
+    }
+
+    /// Path of the output for wallpaper `id` in `format`
+    pub fn output_path(
+        &self,
+        save_location: impl AsRef<Path>,
+        layout: VariantLayout,
+        id: &str,
+        format: ImageFormat,
+    ) -> PathBuf {
+        let extension = helper::get_img_extension(&format);
+        match layout {
+            VariantLayout::ByStep => self
+                .output_dir(save_location)
+                .join(format!("{}.{}", id, extension)),
+            VariantLayout::ById => save_location
+                .as_ref()
+                .join(VARIANTS_DIR)
+                .join(id)
+                .join(format!("{}.{}", self.name, extension)),
+        }
+    }
+
+    /// Apply the step to an already decoded image
+    pub fn apply(&self, mut img: DynamicImage) -> Result<DynamicImage> {
+        if let Some(resolution) = self.resolution.as_deref() {
+            let (width, height) = parse_resolution(resolution)?;
+            img = match (self.mode, self.crop) {
+                (FitMode::Fill, CropAnchor::Center) => {
+                    img.resize_to_fill(width, height, FilterType::Lanczos3)
+                }
+                (FitMode::Fill, CropAnchor::Smart) => crop_to_aspect(
+                    &img, width, height, self.crop,
+                )
+                .resize_exact(width, height, FilterType::Lanczos3),
+                (FitMode::Fit, _) => img.resize(width, height, FilterType::Lanczos3),
+            };
+        } else if let Some(aspect) = self.aspect.as_deref() {
+            let (width, height) = parse_aspect(aspect)?;
+            img = crop_to_aspect(&img, width, height, self.crop);
+        }
+        if let Some(sigma) = self.blur {
+            img = img.blur(sigma);
+        }
+        if let Some(dim) = self.dim {
+            img = dim_image(img, dim);
+        }
+        Ok(img)
+    }
+
+    /// Check the step's settings without touching any file
+    pub fn validate(&self) -> Result<()> {
+        if !helper::is_safe_file_name(&self.name) {
+            return Err(anyhow!(
+                "name '{}' must be a plain folder name, without separators or '..'",
+                self.name
+            ));
+        }
+        if let Some(resolution) = self.resolution.as_deref() {
+            parse_resolution(resolution)?;
+        }
+        if let Some(aspect) = self.aspect.as_deref() {
+            if self.resolution.is_some() {
+                return Err(anyhow!(
+                    "aspect and resolution can't be combined; resolution sets the aspect"
+                ));
+            }
+            parse_aspect(aspect)?;
+        }
+        self.output_format(ImageFormat::Png)?;
+        if !(1..=100).contains(&self.quality) {
+            return Err(anyhow!("quality must be between 1 and 100"));
+        }
+        if self.dim.is_some_and(|dim| dim > 100) {
+            return Err(anyhow!("dim must be between 0 and 100"));
+        }
+        Ok(())
+    }
+
+    /// Format the output is written in, given the original file's format
+    fn output_format(&self, original: ImageFormat) -> Result<ImageFormat> {
+        match self.format.as_deref() {
+            None => Ok(original),
+            Some("jpeg") | Some("jpg") => Ok(ImageFormat::Jpeg),
+            Some("png") => Ok(ImageFormat::Png),
+            Some("webp") => Ok(ImageFormat::WebP),
+            Some(other) => Err(anyhow!("Unsupported output format '{}'", other)),
+        }
+    }
+}
+
+/// Parse a "WIDTHxHEIGHT" resolution string
+pub fn parse_resolution(resolution: &str) -> Result<(u32, u32)> {
+    let (width, height) = resolution
+        .split_once('x')
+        .ok_or_else(|| anyhow!("Invalid resolution '{}', expected WIDTHxHEIGHT", resolution))?;
+    let width: u32 = width
+        .trim()
+        .parse()
+        .with_context(|| format!("Invalid width in '{}'", resolution))?;
+    let height: u32 = height
+        .trim()
+        .parse()
+        .with_context(|| format!("Invalid height in '{}'", resolution))?;
+    if width == 0 || height == 0 {
+        return Err(anyhow!("Resolution '{}' must not be zero", resolution));
+    }
+    Ok((width, height))
+}
+
+/// Parse a "WIDTH:HEIGHT" aspect ratio such as "21:9"
+pub fn parse_aspect(aspect: &str) -> Result<(u32, u32)> {
+    let (width, height) = aspect
+        .split_once(':')
+        .ok_or_else(|| anyhow!("Invalid aspect '{}', expected WIDTH:HEIGHT", aspect))?;
+    let width: u32 = width
+        .trim()
+        .parse()
+        .with_context(|| format!("Invalid width in '{}'", aspect))?;
+    let height: u32 = height
+        .trim()
+        .parse()
+        .with_context(|| format!("Invalid height in '{}'", aspect))?;
+    if width == 0 || height == 0 {
+        return Err(anyhow!("Aspect '{}' must not be zero", aspect));
+    }
+    Ok((width, height))
+}
+
+/// The largest part of `img` with the aspect ratio `width`:`height`
+fn crop_to_aspect(img: &DynamicImage, width: u32, height: u32, anchor: CropAnchor) -> DynamicImage {
+    let (img_width, img_height) = (img.width() as u64, img.height() as u64);
+    let (width, height) = (width as u64, height as u64);
+    if img_width * height > img_height * width {
+        // Too wide: keep the full height
+        let crop_width = (img_height * width / height).max(1) as u32;
+        let x = crop_offset(img, crop_width, true, anchor);
+        img.crop_imm(x, 0, crop_width, img.height())
+    } else {
+        let crop_height = (img_width * height / width).max(1) as u32;
+        let y = crop_offset(img, crop_height, false, anchor);
+        img.crop_imm(0, y, img.width(), crop_height)
+    }
+}
+
+/// Where a `size`-pixel window along one axis starts. `Smart` picks the
+/// window with the most edge detail, measured on a small grayscale copy
+fn crop_offset(img: &DynamicImage, size: u32, horizontal: bool, anchor: CropAnchor) -> u32 {
+    let length = if horizontal {
+        img.width()
+    } else {
+        img.height()
+    };
+    let slack = length.saturating_sub(size);
+    if anchor == CropAnchor::Center || slack == 0 {
+        return slack / 2;
+    }
+    let small = img.thumbnail(256, 256).to_luma8();
+    let (w, h) = small.dimensions();
+    let luma = |x: u32, y: u32| i32::from(small.get_pixel(x, y).0[0]);
+    let cells = if horizontal { w } else { h };
+    let mut energy = vec![0u64; cells as usize];
+    for y in 0..h {
+        for x in 0..w {
+            let dx = (luma((x + 1).min(w - 1), y) - luma(x.saturating_sub(1), y)).unsigned_abs();
+            let dy = (luma(x, (y + 1).min(h - 1)) - luma(x, y.saturating_sub(1))).unsigned_abs();
+            energy[if horizontal { x } else { y } as usize] += u64::from(dx + dy);
+        }
+    }
+    let window = ((size as u64 * cells as u64 / length as u64) as usize).clamp(1, energy.len());
+    let mut sum: u64 = energy[..window].iter().sum();
+    let (mut best, mut best_start) = (sum, 0);
+    for start in 1..=energy.len() - window {
+        sum = sum + energy[start + window - 1] - energy[start - 1];
+        if sum > best {
+            (best, best_start) = (sum, start);
+        }
+    }
+    ((best_start as u64 * length as u64 / cells as u64) as u32).min(slack)
+}
+
+/// Darken every pixel by `percent`
+fn dim_image(img: DynamicImage, percent: u8) -> DynamicImage {
+    let factor = 1.0 - f32::from(percent.min(100)) / 100.0;
+    let mut rgba = img.to_rgba8();
+    for pixel in rgba.pixels_mut() {
+        for channel in pixel.0.iter_mut().take(3) {
+            *channel = (f32::from(*channel) * factor).round() as u8;
+        }
+    }
+    DynamicImage::ImageRgba8(rgba)
+}
+
+/// Run one step on an original file and write its output, returning the output path
+fn run_step(
+    step: &ProcessStep,
+    layout: VariantLayout,
+    original: &Path,
+    save_location: &Path,
+) -> Result<PathBuf> {
+    let id = original
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .ok_or_else(|| anyhow!("Invalid file name: {}", original.display()))?;
+    let original_format = ImageFormat::from_path(original)
+        .with_context(|| format!("Unknown image format: {}", original.display()))?;
+    let format = step.output_format(original_format)?;
+
+    let img = image::open(original)
+        .with_context(|| format!("Failed to decode {}", original.display()))?;
+    let img = step.apply(img)?;
+
+    let output = step.output_path(save_location, layout, id, format);
+    if let Some(output_dir) = output.parent() {
+        std::fs::create_dir_all(output_dir)
+            .with_context(|| format!("Failed to create {}", output_dir.display()))?;
+    }
+
+    if format == ImageFormat::Jpeg {
+        let file = File::create(&output)
+            .with_context(|| format!("Failed to create {}", output.display()))?;
+        let encoder =
+            JpegEncoder::new_with_quality(BufWriter::new(file), step.quality.clamp(1, 100));
+        // JPEG has no alpha channel
+        DynamicImage::ImageRgb8(img.to_rgb8())
+            .write_with_encoder(encoder)
+            .with_context(|| format!("Failed to encode {}", output.display()))?;
+    } else {
+        img.save_with_format(&output, format)
+            .with_context(|| format!("Failed to encode {}", output.display()))?;
+    }
+    Ok(output)
+}
+
+/// Run every configured step on a downloaded file, on the decode pool
+pub async fn run_steps(
+    config: &Config,
+    decode: &DecodePool,
+    original: impl AsRef<Path>,
+) -> Result<Vec<PathBuf>> {
+    let steps = config.process_steps();
+    if steps.is_empty() {
+        return Ok(Vec::new());
+    }
+    let layout = config.variant_layout;
+    let original = original.as_ref().to_path_buf();
+    let save_location = PathBuf::from(&config.save_location);
+    decode
+        .run(move || {
+            steps
+                .iter()
+                .map(|step| run_step(step, layout, &original, &save_location))
+                .collect()
+        })
+        .await
+        .context("Image processing task failed")?
+}
+
+/// Folders holding processing outputs, which never contain originals
+pub fn variant_roots(config: &Config) -> Vec<PathBuf> {
+    let save_location = Path::new(&config.save_location);
+    match config.variant_layout {
+        VariantLayout::ByStep => config
+            .process_steps()
+            .iter()
+            .map(|step| step.output_dir(save_location))
+            .collect(),
+        VariantLayout::ById => vec![save_location.join(VARIANTS_DIR)],
+    }
+}
+
+/// Files in `dir`, or none if it can't be read
+async fn files_in(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    if let Ok(mut entries) = tokio::fs::read_dir(dir).await {
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            if entry.file_type().await.is_ok_and(|t| t.is_file()) {
+                files.push(entry.path());
+            }
+        }
+    }
+    files
+}
+
+/// Every processed variant on disk, by wallpaper ID. With the per-step layout
+/// only the configured steps' folders are searched
+pub async fn variant_map(config: &Config) -> HashMap<String, Vec<Variant>> {
+    let stem = |path: &Path| {
+        path.file_stem()
+            .and_then(|s| s.to_str())
+            .map(str::to_string)
+    };
+    let mut variants: HashMap<String, Vec<Variant>> = HashMap::new();
+    let save_location = Path::new(&config.save_location);
+    match config.variant_layout {
+        VariantLayout::ByStep => {
+            for step in config.process_steps() {
+                for path in files_in(&step.output_dir(save_location)).await {
+                    if let Some(id) = stem(&path) {
+                        let step = step.name.clone();
+                        variants.entry(id).or_default().push(Variant { step, path });
+                    }
+                }
+            }
+        }
+        VariantLayout::ById => {
+            let root = save_location.join(VARIANTS_DIR);
+            if let Ok(mut entries) = tokio::fs::read_dir(&root).await {
+                while let Ok(Some(entry)) = entries.next_entry().await {
+                    let id = entry.file_name().to_string_lossy().to_string();
+                    for path in files_in(&entry.path()).await {
+                        if let Some(step) = stem(&path) {
+                            variants
+                                .entry(id.clone())
+                                .or_default()
+                                .push(Variant { step, path });
+                        }
+                    }
+                }
+            }
+        }
+    }
+    for list in variants.values_mut() {
+        list.sort_by(|a, b| a.step.cmp(&b.step));
+    }
+    variants
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    fn step() -> ProcessStep {
+        ProcessStep {
+            name: "test".to_string(),
+            resolution: None,
+            mode: FitMode::Fill,
+            aspect: None,
+            crop: CropAnchor::Center,
+            blur: None,
+            dim: None,
+            format: None,
+            quality: 90,
+            output_dir: None,
+        }
+    }
+
+    #[test]
+    fn test_aspect_crop_follows_detail() {
+        // A wide image whose only detail is a checkerboard on the right
+        let mut pixels = RgbaImage::from_pixel(400, 100, Rgba([40, 40, 40, 255]));
+        for (x, y, pixel) in pixels.enumerate_pixels_mut() {
+            if x >= 300 && (x / 5 + y / 5) % 2 == 0 {
+                *pixel = Rgba([255, 255, 255, 255]);
+            }
+        }
+        let img = DynamicImage::ImageRgba8(pixels);
+
+        let mut portrait = step();
+        portrait.aspect = Some("9:16".to_string());
+        let out = portrait.apply(img.clone()).unwrap();
+        assert_eq!((out.width(), out.height()), (56, 100));
+        assert_eq!(crop_offset(&img, 56, true, CropAnchor::Center), 172);
+        assert!(crop_offset(&img, 56, true, CropAnchor::Smart) >= 300);
+
+        let mut smart = step();
+        smart.resolution = Some("90x160".to_string());
+        smart.crop = CropAnchor::Smart;
+        let out = smart.apply(img).unwrap();
+        assert_eq!((out.width(), out.height()), (90, 160));
+
+        assert_eq!(parse_aspect("21:9").unwrap(), (21, 9));
+        assert!(parse_aspect("21x9").is_err());
+        let mut both = smart.clone();
+        both.aspect = Some("21:9".to_string());
+        assert!(both.validate().is_err());
+    }
+
+    #[test]
+    fn test_parse_resolution() {
+        assert_eq!(parse_resolution("1920x1080").unwrap(), (1920, 1080));
+        assert!(parse_resolution("1920").is_err());
+        assert!(parse_resolution("0x1080").is_err());
+        assert!(parse_resolution("axb").is_err());
+    }
+
+    #[test]
+    fn test_apply_fill_and_fit() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::new(200, 100));
+        let mut fill = step();
+        fill.resolution = Some("50x50".to_string());
+        let out = fill.apply(img.clone()).unwrap();
+        assert_eq!((out.width(), out.height()), (50, 50));
+
+        let mut fit = fill.clone();
+        fit.mode = FitMode::Fit;
+        let out = fit.apply(img).unwrap();
+        assert_eq!((out.width(), out.height()), (50, 25));
+    }
+
+    #[test]
+    fn test_apply_dim() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, Rgba([200, 100, 50, 255])));
+        let mut dim = step();
+        dim.dim = Some(50);
+        let out = dim.apply(img).unwrap().to_rgba8();
+        assert_eq!(out.get_pixel(0, 0), &Rgba([100, 50, 25, 255]));
+    }
+
+    #[test]
+    fn test_lockscreen_step() {
+        let lockscreen = LockscreenConfig::default();
+        let step = lockscreen.to_step();
+        assert_eq!(step.blur, Some(12.0));
+        assert_eq!(step.dim, Some(30));
+        assert_eq!(
+            step.output_dir("/walls"),
+            PathBuf::from("/walls/lockscreen")
+        );
+
+        let custom = LockscreenConfig {
+            blur: 0.0,
+            dim: 50,
+            output_dir: Some("/locks".to_string()),
+        };
+        let step = custom.to_step();
+        assert_eq!(step.blur, None);
+        assert_eq!(step.output_dir("/walls"), PathBuf::from("/locks"));
+    }
+
+    #[test]
+    fn test_output_format() {
+        let mut s = step();
+        assert_eq!(s.output_format(ImageFormat::Png).unwrap(), ImageFormat::Png);
+        s.format = Some("jpeg".to_string());
+        assert_eq!(
+            s.output_format(ImageFormat::Png).unwrap(),
+            ImageFormat::Jpeg
+        );
+        s.format = Some("bmp".to_string());
+        assert!(s.output_format(ImageFormat::Png).is_err());
+        assert!(s.validate().is_err());
+    }
+
+    #[test]
+    fn test_step_name_stays_in_save_location() {
+        let mut s = step();
+        s.validate().unwrap();
+        s.name = "../outside".to_string();
+        assert!(s.validate().is_err());
+    }
+
+    #[test]
+    fn test_output_path_layouts() {
+        let s = step();
+        assert_eq!(
+            s.output_path("/walls", VariantLayout::ByStep, "abc123", ImageFormat::Png),
+            PathBuf::from("/walls/test/abc123.png")
+        );
+        assert_eq!(
+            s.output_path("/walls", VariantLayout::ById, "abc123", ImageFormat::Jpeg),
+            PathBuf::from("/walls/variants/abc123/test.jpeg")
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use std::path::{Path, PathBuf};
+
+use crate::audit::{self, AuditEntry, AuditEvent};
+use crate::error::{Error, Result};
+use crate::output::{say, say_err};
+use crate::{api, build_file_map, helper, import, process, sidecar, RustPaper, WallpaperId};
+
+/// A tracked wallpaper the purity/category guard no longer allows
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PurityViolation {
+    pub id: String,
+    pub reason: String,
+    /// Downloaded file, if any
+    pub path: Option<PathBuf>,
+}
+
+/// Move `path` into `folder`, copying when it is on another filesystem
+async fn move_into(path: &Path, folder: &Path) -> anyhow::Result<PathBuf> {
+    let target = folder.join(path.file_name().unwrap_or_default());
+    helper::move_file(path, &target).await?;
+    Ok(target)
+}
+
+impl RustPaper {
+    /// Check every tracked wallpaper against `allowed_purity` and
+    /// `allowed_categories`, using cached metadata unless `refresh`
+    pub async fn purity_violations(&self, refresh: bool) -> Result<Vec<PurityViolation>> {
+        let file_map = build_file_map(&self.config).await?;
+        let mut violations = Vec::new();
+        for id in &self.wallpapers {
+            if import::is_local_id(id) {
+                continue;
+            }
+            let Ok(wallpaper_id) = WallpaperId::parse(id) else {
+                say_err!("  ⚠ {} is not a Wallhaven ID", id);
+                continue;
+            };
+            let cached = if refresh {
+                None
+            } else {
+                helper::read_cached_metadata(&self.metadata_cache_location, id)
+                    .await
+                    .and_then(|data| api::WallpaperInfoResponse::parse(&data).ok())
+            };
+            let data = match cached {
+                Some(data) => data,
+                None if self.offline => {
+                    say_err!("  ⚠ {}: no cached metadata while offline", id);
+                    continue;
+                }
+                None => match self.fetch_metadata(&wallpaper_id).await {
+                    Ok(data) => data,
+                    Err(e) => {
+                        say_err!("  ⚠ Could not check {}: {:#}", id, e);
+                        continue;
+                    }
+                },
+            };
+            if let Some(reason) = self.config.content_violation(&data.purity, &data.category) {
+                violations.push(PurityViolation {
+                    id: id.clone(),
+                    reason,
+                    path: file_map.get(id).cloned(),
+                });
+            }
+        }
+        Ok(violations)
+    }
+
+    /// Report tracked wallpapers outside the purity/category guard. With
+    /// `quarantine`, move their files there, drop their variants and stop tracking them
+    pub async fn audit_purity(&mut self, refresh: bool, quarantine: Option<&Path>) -> Result<()> {
+        if !self.config.has_content_guard() {
+            return Err(Error::Config(
+                "Set allowed_purity or allowed_categories to audit against".to_string(),
+            ));
+        }
+        let violations = self.purity_violations(refresh).await?;
+        if violations.is_empty() {
+            say!("   Every tracked wallpaper is allowed.");
+            return Ok(());
+        }
+        for violation in &violations {
+            say!("  ✗ {} - {}", violation.id, violation.reason);
+        }
+        let Some(quarantine) = quarantine else {
+            say!(
+                "   {} wallpaper(s) not allowed; pass --move to move them out of the save location",
+                violations.len()
+            );
+            return Ok(());
+        };
+
+        let variants = process::variant_map(&self.config).await;
+        let mut entries = Vec::new();
+        for violation in &violations {
+            if let Some(path) = &violation.path {
+                let target = move_into(path, quarantine).await?;
+                let sidecar = sidecar::path_for(path);
+                if tokio::fs::metadata(&sidecar).await.is_ok() {
+                    move_into(&sidecar, quarantine).await?;
+                }
+                say!("   Moved {} to {}", violation.id, target.display());
+                entries.push(
+                    AuditEntry::new(AuditEvent::Quarantine, &violation.id)
+                        .path(&target)
+                        .detail(&violation.reason),
+                );
+            }
+            for variant in variants.get(&violation.id).into_iter().flatten() {
+                let _ = tokio::fs::remove_file(&variant.path).await;
+            }
+        }
+        audit::append(&self.audit_log_location, &entries).await?;
+        let ids: Vec<String> = violations.into_iter().map(|v| v.id).collect();
+        self.remove(&ids).await
+    }
+}
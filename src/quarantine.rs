@@ -0,0 +1,95 @@
+use anyhow::Context;
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+use crate::output::say;
+use crate::{helper, RustPaper};
+
+/// A file set aside after failing its integrity check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuarantinedFile {
+    pub path: PathBuf,
+    pub size: u64,
+    /// When it was moved aside, in unix seconds
+    pub quarantined_at: u64,
+}
+
+/// Move the corrupt file `path` into `folder`. The name gets a timestamp
+/// prefix so repeated failures of one wallpaper are all kept
+pub async fn quarantine_file(path: &Path, folder: &Path) -> anyhow::Result<PathBuf> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let target = folder.join(format!("{}-{}", helper::unix_timestamp(), name));
+    helper::move_file(path, &target).await?;
+    Ok(target)
+}
+
+impl RustPaper {
+    /// Files in the integrity quarantine, oldest first
+    pub async fn quarantined(&self) -> Result<Vec<QuarantinedFile>> {
+        let mut entries = match tokio::fs::read_dir(&self.integrity_quarantine_location).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut files = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let metadata = entry.metadata().await?;
+            if !metadata.is_file() {
+                continue;
+            }
+            let name = entry.file_name().to_string_lossy().to_string();
+            let quarantined_at = match name.split_once('-').and_then(|(at, _)| at.parse().ok()) {
+                Some(at) => at,
+                None => helper::modified_timestamp(entry.path()).await,
+            };
+            files.push(QuarantinedFile {
+                path: entry.path(),
+                size: metadata.len(),
+                quarantined_at,
+            });
+        }
+        files.sort_by(|a, b| (a.quarantined_at, &a.path).cmp(&(b.quarantined_at, &b.path)));
+        Ok(files)
+    }
+
+    /// Print the files in the integrity quarantine
+    pub async fn quarantine_list(&self) -> Result<()> {
+        let files = self.quarantined().await?;
+        if files.is_empty() {
+            say!("   The quarantine is empty.");
+            return Ok(());
+        }
+        for file in &files {
+            say!(
+                "  {}  {:>8.2} MB  {}",
+                helper::format_timestamp(file.quarantined_at),
+                file.size as f64 / 1_048_576.0,
+                file.path.display()
+            );
+        }
+        say!(
+            "   {} file(s) in {}",
+            files.len(),
+            self.integrity_quarantine_location.display()
+        );
+        Ok(())
+    }
+
+    /// Delete every file in the integrity quarantine
+    pub async fn quarantine_purge(&self) -> Result<()> {
+        let files = self.quarantined().await?;
+        let mut freed = 0;
+        for file in &files {
+            tokio::fs::remove_file(&file.path)
+                .await
+                .with_context(|| format!("   Failed to delete {}", file.path.display()))?;
+            freed += file.size;
+        }
+        say!(
+            "   Purged {} file(s), freed {:.2} MB",
+            files.len(),
+            freed as f64 / 1_048_576.0
+        );
+        Ok(())
+    }
+}
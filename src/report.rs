@@ -0,0 +1,163 @@
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Outcome of a sync
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyncReport {
+    /// Wallpapers downloaded, including re-downloads after a failed integrity check
+    pub downloaded: usize,
+    /// Wallpapers that could not be downloaded
+    pub failed: usize,
+    /// Files whose checksum no longer matched the lock file
+    pub integrity_failures: usize,
+    /// Downloads not attempted because Wallhaven was unreachable or we ran offline
+    pub skipped_offline: usize,
+    /// Downloads put off because a `sync_conditions` check wasn't met
+    pub deferred: usize,
+    /// Animated wallpapers left out because of `animated = "skip"`
+    pub skipped_animated: usize,
+    /// Wallpapers smaller than the largest display, with `auto_resolution` on
+    pub skipped_small: usize,
+    /// Wallpapers over `max_file_size_mb`
+    pub skipped_large: usize,
+    /// Downloads that failed `download_checks` and were not saved
+    pub rejected: usize,
+    /// Followed uploaders whose uploads could not be checked
+    pub unchecked_uploaders: usize,
+    /// Bytes transferred by the downloads
+    pub bytes_downloaded: u64,
+    /// Time spent downloading, from the first download to the last
+    pub download_ms: u64,
+    /// Size and duration of every download
+    pub downloads: Vec<DownloadStats>,
+    /// Why each failed wallpaper could not be downloaded
+    pub failures: Vec<SyncFailure>,
+    /// Wallpapers of archived groups that are missing or changed on disk
+    pub archive_drift: Vec<SyncFailure>,
+}
+
+/// One finished download
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DownloadStats {
+    pub id: String,
+    pub bytes: u64,
+    pub duration_ms: u64,
+}
+
+impl DownloadStats {
+    /// Average transfer speed
+    pub fn bytes_per_sec(&self) -> u64 {
+        bytes_per_sec(self.bytes, self.duration_ms)
+    }
+}
+
+fn bytes_per_sec(bytes: u64, ms: u64) -> u64 {
+    (bytes as u128 * 1000 / ms.max(1) as u128) as u64
+}
+
+/// A wallpaper that could not be downloaded or verified
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncFailure {
+    pub id: String,
+    pub reason: String,
+}
+
+impl SyncReport {
+    /// Whether some wallpaper or uploader failed, as opposed to being skipped on purpose
+    pub fn has_failures(&self) -> bool {
+        self.failed > 0 || self.unchecked_uploaders > 0 || !self.archive_drift.is_empty()
+    }
+
+    /// Average transfer speed over the time spent downloading
+    pub fn bytes_per_sec(&self) -> u64 {
+        bytes_per_sec(self.bytes_downloaded, self.download_ms)
+    }
+
+    /// Add the counts of another source's sync
+    pub fn merge(&mut self, other: SyncReport) {
+        self.downloaded += other.downloaded;
+        self.failed += other.failed;
+        self.integrity_failures += other.integrity_failures;
+        self.skipped_offline += other.skipped_offline;
+        self.deferred += other.deferred;
+        self.skipped_animated += other.skipped_animated;
+        self.skipped_small += other.skipped_small;
+        self.skipped_large += other.skipped_large;
+        self.rejected += other.rejected;
+        self.unchecked_uploaders += other.unchecked_uploaders;
+        self.bytes_downloaded += other.bytes_downloaded;
+        self.download_ms += other.download_ms;
+        self.downloads.extend(other.downloads);
+        self.failures.extend(other.failures);
+        self.archive_drift.extend(other.archive_drift);
+    }
+}
+
+/// Summary of the most recent sync, kept in `last_sync.json` for monitoring
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LastSync {
+    /// Unix time the sync started
+    pub started_at: u64,
+    /// Unix time the sync finished
+    pub finished_at: u64,
+    pub duration_ms: u64,
+    /// What was synced: "all", a source name, "group:<name>" or "ids:<id>,..."
+    pub scope: String,
+    /// Counts of a sync that ran to the end
+    pub report: Option<SyncReport>,
+    /// Why the sync stopped early
+    pub error: Option<String>,
+}
+
+impl LastSync {
+    /// Load the summary, if a sync has been recorded
+    pub async fn load(path: impl AsRef<Path>) -> anyhow::Result<Option<Self>> {
+        match tokio::fs::read_to_string(path.as_ref()).await {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map(Some)
+                .context("   Failed to parse last sync summary"),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Save the summary to disk
+    pub async fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let json = serde_json::to_string_pretty(&self)
+            .context("   Failed to serialize last sync summary")?;
+        tokio::fs::write(path.as_ref(), json)
+            .await
+            .context("   Failed to write last sync summary")
+    }
+
+    /// Whether the sync stopped early or some download failed
+    pub fn has_failures(&self) -> bool {
+        self.error.is_some() || self.report.as_ref().is_some_and(SyncReport::has_failures)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_keeps_failure_reasons() {
+        let mut report = SyncReport {
+            downloaded: 1,
+            bytes_downloaded: 100,
+            ..SyncReport::default()
+        };
+        report.merge(SyncReport {
+            failed: 1,
+            failures: vec![SyncFailure {
+                id: "abc123".to_string(),
+                reason: "timed out".to_string(),
+            }],
+            ..SyncReport::default()
+        });
+        assert_eq!(report.bytes_downloaded, 100);
+        assert_eq!(report.failures[0].id, "abc123");
+        assert!(report.has_failures());
+    }
+}
@@ -0,0 +1,103 @@
+use fs2::FileExt;
+use std::fs::{File, OpenOptions};
+use std::path::Path;
+
+use crate::error::{Error, Result};
+use crate::lock::LockFile;
+use crate::output::say;
+use crate::{load_wallpapers, RustPaper};
+
+/// File in the config folder holding the advisory lock
+pub const RUN_LOCK_FILE: &str = "rust-paper.run.lock";
+
+/// How concurrent runs on the same config folder are handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LockMode {
+    /// Fail right away if another instance holds the lock
+    #[default]
+    Fail,
+    /// Block until the other instance is done
+    Wait,
+    /// Don't take the lock at all
+    Disabled,
+}
+
+/// Advisory lock (flock) on the config folder, released when dropped
+#[derive(Debug)]
+pub struct RunLock {
+    _file: File,
+}
+
+impl RunLock {
+    /// Take the lock on `folder` according to `mode`
+    pub async fn acquire(folder: &Path, mode: LockMode) -> Result<Option<Self>> {
+        if mode == LockMode::Disabled {
+            return Ok(None);
+        }
+        let path = folder.join(RUN_LOCK_FILE);
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)?;
+        if file.try_lock_exclusive().is_ok() {
+            return Ok(Some(RunLock { _file: file }));
+        }
+        if mode == LockMode::Fail {
+            return Err(Error::Locked(path.display().to_string()));
+        }
+        say!("   Waiting for another rust-paper instance to finish...");
+        let file = tokio::task::spawn_blocking(move || file.lock_exclusive().map(|_| file))
+            .await
+            .map_err(|e| Error::Other(e.into()))??;
+        Ok(Some(RunLock { _file: file }))
+    }
+}
+
+impl RustPaper {
+    /// Take the run lock for a single sync or change, for instances built
+    /// with `lock_per_operation`, and re-read the list and lock file another
+    /// run may have changed in the meantime. Others already hold the lock
+    /// and get `None`
+    pub(crate) async fn lock_operation(&mut self) -> Result<Option<RunLock>> {
+        let Some(mode) = self.operation_lock_mode else {
+            return Ok(None);
+        };
+        let run_lock = RunLock::acquire(&self.data_folder, mode).await?;
+        self.wallpapers = load_wallpapers(&self.wallpapers_list_file_location).await?;
+        let mut lock_file = self.lock_file.lock().await;
+        if let Some(path) = lock_file.as_ref().map(|lock| lock.path().to_path_buf()) {
+            *lock_file = Some(LockFile::load_or_new_at(path).await);
+        }
+        Ok(run_lock)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_second_lock_fails_until_released() {
+        let dir = std::env::temp_dir().join(format!("rust-paper-run-lock-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let first = RunLock::acquire(&dir, LockMode::Fail).await.unwrap();
+        assert!(first.is_some());
+        assert!(matches!(
+            RunLock::acquire(&dir, LockMode::Fail).await,
+            Err(Error::Locked(_))
+        ));
+        assert!(RunLock::acquire(&dir, LockMode::Disabled)
+            .await
+            .unwrap()
+            .is_none());
+
+        drop(first);
+        assert!(RunLock::acquire(&dir, LockMode::Fail)
+            .await
+            .unwrap()
+            .is_some());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
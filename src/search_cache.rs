@@ -0,0 +1,110 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::helper;
+
+/// A search response as Wallhaven sent it
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedSearch {
+    fetched_at: u64,
+    url: String,
+    body: String,
+}
+
+/// Recent search responses, so repeating a query or paging back and forth
+/// doesn't spend API quota. Entries older than `ttl` are ignored
+pub(crate) struct SearchCache {
+    dir: PathBuf,
+    ttl: Duration,
+    /// Part of every key, so results fetched with one API key (which may
+    /// include NSFW wallpapers) aren't served to another
+    api_key: Option<String>,
+}
+
+impl SearchCache {
+    pub fn new(dir: PathBuf, ttl: Duration, api_key: Option<String>) -> Self {
+        SearchCache { dir, ttl, api_key }
+    }
+
+    /// Whether `url` gives the same results when asked again; a random
+    /// sort without a seed doesn't
+    pub fn is_cacheable(url: &str) -> bool {
+        !has_param(url, "sorting=random") || has_param(url, "seed=")
+    }
+
+    fn path(&self, url: &str) -> PathBuf {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(url.as_bytes());
+        if let Some(key) = &self.api_key {
+            hasher.update(b"\0");
+            hasher.update(key.as_bytes());
+        }
+        self.dir
+            .join(format!("{}.json", &hasher.finalize().to_hex()[..32]))
+    }
+
+    /// The cached body for `url`, if it was fetched within the TTL
+    pub async fn get(&self, url: &str) -> Option<String> {
+        if self.ttl.is_zero() {
+            return None;
+        }
+        let text = tokio::fs::read_to_string(self.path(url)).await.ok()?;
+        let cached: CachedSearch = serde_json::from_str(&text).ok()?;
+        let age = helper::unix_timestamp().saturating_sub(cached.fetched_at);
+        (cached.url == url && age < self.ttl.as_secs()).then_some(cached.body)
+    }
+
+    /// Remember `body` as the response for `url`; failures only cost a future request
+    pub async fn put(&self, url: &str, body: &str) {
+        if self.ttl.is_zero() || tokio::fs::create_dir_all(&self.dir).await.is_err() {
+            return;
+        }
+        let cached = CachedSearch {
+            fetched_at: helper::unix_timestamp(),
+            url: url.to_string(),
+            body: body.to_string(),
+        };
+        if let Ok(json) = serde_json::to_string(&cached) {
+            let _ = tokio::fs::write(self.path(url), json).await;
+        }
+    }
+}
+
+fn has_param(url: &str, param: &str) -> bool {
+    url.split(['?', '&']).any(|part| part.starts_with(param))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cache_honours_ttl_and_api_key() {
+        let dir = std::env::temp_dir().join(format!(
+            "rust-paper-search-cache-{}-{}",
+            std::process::id(),
+            helper::unix_timestamp()
+        ));
+        let url = "https://wallhaven.cc/api/v1/search?q=city";
+        let cache = SearchCache::new(dir.clone(), Duration::from_secs(600), None);
+        assert_eq!(cache.get(url).await, None);
+        cache.put(url, "{\"data\": []}").await;
+        assert_eq!(cache.get(url).await.as_deref(), Some("{\"data\": []}"));
+
+        let keyed = SearchCache::new(dir.clone(), Duration::from_secs(600), Some("k".into()));
+        assert_eq!(keyed.get(url).await, None);
+        let disabled = SearchCache::new(dir.clone(), Duration::ZERO, None);
+        assert_eq!(disabled.get(url).await, None);
+
+        assert!(SearchCache::is_cacheable(url));
+        assert!(!SearchCache::is_cacheable(
+            "https://wallhaven.cc/api/v1/search?q=&sorting=random"
+        ));
+        assert!(SearchCache::is_cacheable(
+            "https://wallhaven.cc/api/v1/search?seed=abc123&sorting=random"
+        ));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,115 @@
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::error::Result;
+use crate::follow::Follows;
+use crate::output::say;
+use crate::{helper, RustPaper};
+
+/// Every wallpaper followed uploaders have offered, with when it was first
+/// offered, so one removed or rejected since isn't added again
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct Seen {
+    ids: BTreeMap<String, u64>,
+}
+
+impl Seen {
+    /// Load the seen wallpapers from disk, starting empty if the file is missing
+    pub async fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        match tokio::fs::read_to_string(path.as_ref()).await {
+            Ok(contents) => {
+                serde_json::from_str(&contents).context("   Failed to parse seen wallpapers")
+            }
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Save the seen wallpapers to disk
+    pub async fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let json = serde_json::to_string_pretty(&self)
+            .context("   Failed to serialize seen wallpapers")?;
+        tokio::fs::write(path.as_ref(), json)
+            .await
+            .context("   Failed to write seen wallpapers")
+    }
+
+    /// Record that `id` was offered, returning whether it is the first time
+    pub fn offer(&mut self, id: &str, now: u64) -> bool {
+        if self.ids.contains_key(id) {
+            return false;
+        }
+        self.ids.insert(id.to_string(), now);
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+}
+
+impl RustPaper {
+    /// Print the wallpapers followed uploaders have offered, oldest first
+    pub async fn seen_list(&self) -> Result<()> {
+        let seen = Seen::load(&self.seen_file_location).await?;
+        if seen.is_empty() {
+            say!("   No wallpapers seen from followed uploaders yet.");
+            return Ok(());
+        }
+        let mut ids: Vec<(&String, &u64)> = seen.ids.iter().collect();
+        ids.sort_by_key(|(id, at)| (**at, *id));
+        for (id, at) in ids {
+            let tracked = if self.wallpapers.contains(id) {
+                ""
+            } else {
+                " (not tracked)"
+            };
+            say!(
+                "  {} - {} UTC{}",
+                id,
+                helper::format_timestamp(*at),
+                tracked
+            );
+        }
+        say!("   {} wallpaper(s) seen", seen.len());
+        Ok(())
+    }
+
+    /// Forget every wallpaper followed uploaders have offered, so the next
+    /// sync adds their current uploads again, including removed ones
+    pub async fn seen_purge(&self) -> Result<()> {
+        let mut seen = Seen::load(&self.seen_file_location).await?;
+        let mut follows = Follows::load(&self.follows_file_location).await?;
+        let forgotten = seen.len();
+        seen.ids.clear();
+        follows.forget_uploads();
+        tokio::try_join!(
+            seen.save(&self.seen_file_location),
+            follows.save(&self.follows_file_location),
+        )?;
+        say!(
+            "  ✓ Forgot {} seen wallpaper(s); the next sync offers them again",
+            forgotten
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offer_only_once() {
+        let mut seen = Seen::default();
+        assert!(seen.offer("abc123", 100));
+        assert!(!seen.offer("abc123", 200));
+        assert_eq!(seen.ids["abc123"], 100);
+        assert_eq!(seen.len(), 1);
+    }
+}
@@ -0,0 +1,358 @@
+use anyhow::{anyhow, Context};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::timeout;
+
+use crate::error::{Error, Result};
+use crate::hash::constant_time_eq;
+use crate::output::{say, say_err};
+use crate::{AddOptions, RustPaper, SyncOptions, WallpaperStatus};
+
+/// Largest request body accepted, which is plenty for a list of IDs
+const MAX_BODY: usize = 1024 * 1024;
+
+/// Largest request line and headers accepted, together
+const MAX_HEAD: u64 = 16 * 1024;
+
+/// Time a client gets to send its request, and to take the response
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Pause after a failed accept (e.g. out of file descriptors) before trying again
+const ACCEPT_RETRY: Duration = Duration::from_millis(100);
+
+/// A request waiting for the server loop, with where to send the answer
+type Pending = (Request, oneshot::Sender<(u16, Value)>);
+
+/// A parsed HTTP request
+#[derive(Debug, PartialEq, Eq)]
+struct Request {
+    method: String,
+    path: String,
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+/// Read one HTTP/1.1 request from `stream`
+async fn read_request(stream: &mut TcpStream) -> anyhow::Result<Request> {
+    let mut reader = BufReader::new(stream);
+    let mut head = (&mut reader).take(MAX_HEAD);
+    let mut line = String::new();
+    head.read_line(&mut line).await?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return Err(anyhow!("Malformed request line"));
+    };
+    let (method, path) = (method.to_string(), path.to_string());
+
+    let mut headers = HashMap::new();
+    loop {
+        line.clear();
+        if head.read_line(&mut line).await? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+    if head.limit() == 0 {
+        return Err(anyhow!("Request headers too large"));
+    }
+
+    let length: usize = match headers.get("content-length") {
+        Some(length) => length.parse().context("Invalid Content-Length")?,
+        None => 0,
+    };
+    if length > MAX_BODY {
+        return Err(anyhow!("Request body too large"));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).await?;
+    Ok(Request {
+        method,
+        path,
+        headers,
+        body,
+    })
+}
+
+/// Serialize a JSON response with `status`
+fn response(status: u16, body: &Value) -> Vec<u8> {
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        409 => "Conflict",
+        501 => "Not Implemented",
+        _ => "Internal Server Error",
+    };
+    let body = body.to_string();
+    format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    )
+    .into_bytes()
+}
+
+fn error(status: u16, message: impl std::fmt::Display) -> (u16, Value) {
+    (status, json!({ "error": message.to_string() }))
+}
+
+/// HTTP status for an error from the library
+fn error_status(err: &Error) -> u16 {
+    match err {
+        Error::InvalidInput(_) => 400,
+        Error::NotFound(_) => 404,
+        Error::Locked(_) => 409,
+        _ => 500,
+    }
+}
+
+/// Whether `request` carries `Authorization: Bearer <token>`
+fn authorized(request: &Request, token: &str) -> bool {
+    request
+        .headers
+        .get("authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|given| constant_time_eq(given.as_bytes(), token.as_bytes()))
+}
+
+/// Read one request from `stream`, pass it to the server loop through
+/// `requests` and write back the answer. Clients that are slow to send or to
+/// read are cut off after `CLIENT_TIMEOUT`
+async fn handle_connection(
+    mut stream: TcpStream,
+    token: Arc<str>,
+    requests: mpsc::Sender<Pending>,
+) {
+    let (status, body) = match timeout(CLIENT_TIMEOUT, read_request(&mut stream)).await {
+        Err(_) => error(408, "Timed out reading the request"),
+        Ok(Err(e)) => error(400, e),
+        Ok(Ok(request)) if !authorized(&request, &token) => {
+            error(401, "Missing or wrong bearer token")
+        }
+        Ok(Ok(request)) => {
+            let (reply, answer) = oneshot::channel();
+            if requests.send((request, reply)).await.is_err() {
+                return;
+            }
+            match answer.await {
+                Ok(answer) => answer,
+                Err(_) => return,
+            }
+        }
+    };
+    match timeout(CLIENT_TIMEOUT, stream.write_all(&response(status, &body))).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => say_err!("  ⚠ Failed to answer request: {}", e),
+        Err(_) => say_err!("  ⚠ Failed to answer request: timed out"),
+    }
+}
+
+/// `ids` and optional `group` from a JSON request body
+fn parse_ids(body: &[u8]) -> std::result::Result<(Vec<String>, Option<String>), String> {
+    let value: Value = serde_json::from_slice(body).map_err(|e| e.to_string())?;
+    let ids = value["ids"]
+        .as_array()
+        .ok_or("Expected {\"ids\": [...]}")?
+        .iter()
+        .filter_map(|id| id.as_str().map(str::to_string))
+        .collect();
+    Ok((ids, value["group"].as_str().map(str::to_string)))
+}
+
+/// A JSON object request body; an empty body counts as `{}`
+fn parse_object(body: &[u8]) -> std::result::Result<Value, String> {
+    if body.is_empty() {
+        return Ok(json!({}));
+    }
+    let value: Value = serde_json::from_slice(body).map_err(|e| e.to_string())?;
+    if !value.is_object() {
+        return Err("Expected a JSON object".to_string());
+    }
+    Ok(value)
+}
+
+impl RustPaper {
+    /// Serve the REST API on `listen` until interrupted. Every request must
+    /// carry `Authorization: Bearer <token>`
+    pub async fn serve(&mut self, listen: &str, token: &str) -> Result<()> {
+        let listener = TcpListener::bind(listen)
+            .await
+            .with_context(|| format!("   Failed to listen on {}", listen))?;
+        say!("   Listening on http://{}", listener.local_addr()?);
+        tokio::select! {
+            result = self.serve_on(listener, token) => result,
+            _ = tokio::signal::ctrl_c() => {
+                say!("   Shutting down");
+                Ok(())
+            }
+        }
+    }
+
+    /// Answer requests on an already bound listener. Connections are read
+    /// side by side; the requests on them are answered one at a time
+    pub async fn serve_on(&mut self, listener: TcpListener, token: &str) -> Result<()> {
+        if token.is_empty() {
+            return Err(Error::Config(
+                "serve needs a token; pass --token or set serve_token".to_string(),
+            ));
+        }
+        let token: Arc<str> = token.into();
+        let (sender, mut requests) = mpsc::channel::<Pending>(64);
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, _)) => {
+                        tokio::spawn(handle_connection(stream, token.clone(), sender.clone()));
+                    }
+                    // Such as running out of file descriptors, which passes
+                    Err(e) => {
+                        say_err!("  ⚠ Failed to accept a connection: {}", e);
+                        tokio::time::sleep(ACCEPT_RETRY).await;
+                    }
+                },
+                Some((request, reply)) = requests.recv() => {
+                    let _ = reply.send(self.respond(&request).await);
+                }
+            }
+        }
+    }
+
+    async fn respond(&mut self, request: &Request) -> (u16, Value) {
+        // Changes take turns with other rust-paper runs; reads don't have to
+        let _run_lock = if request.method == "GET" {
+            None
+        } else {
+            match self.lock_operation().await {
+                Ok(run_lock) => run_lock,
+                Err(e) => return error(error_status(&e), e),
+            }
+        };
+        let path = request.path.split('?').next().unwrap_or_default();
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        let result =
+            match (request.method.as_str(), segments.as_slice()) {
+                ("GET", ["wallpapers"]) => self.statuses().await.map(|statuses| {
+                    let wallpapers: Vec<Value> = statuses
+                        .into_iter()
+                        .map(|(id, status)| match status {
+                            WallpaperStatus::Downloaded { path } => {
+                                json!({ "id": id, "status": "downloaded", "path": path })
+                            }
+                            WallpaperStatus::NotDownloaded => {
+                                json!({ "id": id, "status": "not_downloaded" })
+                            }
+                            WallpaperStatus::GoneUpstream => json!({ "id": id, "status": "gone" }),
+                        })
+                        .collect();
+                    json!({ "wallpapers": wallpapers })
+                }),
+                ("POST", ["wallpapers"]) => {
+                    let (mut ids, group) = match parse_ids(&request.body) {
+                        Ok(parsed) => parsed,
+                        Err(e) => return error(400, e),
+                    };
+                    self.add(
+                        &mut ids,
+                        &AddOptions {
+                            group,
+                            ..Default::default()
+                        },
+                    )
+                    .await
+                    .map(|()| json!({ "wallpapers": self.wallpapers }))
+                }
+                ("DELETE", ["wallpapers", id]) => self
+                    .remove(&[id.to_string()])
+                    .await
+                    .map(|()| json!({ "wallpapers": self.wallpapers })),
+                ("POST", ["sync"]) => self
+                    .sync(None, &SyncOptions::default())
+                    .await
+                    .map(|report| json!(report)),
+                ("GET", ["status"]) => self.last_sync().await.map(|last_sync| json!(last_sync)),
+                ("POST", ["set"]) | ("POST", ["random"]) => return error(
+                    501,
+                    "rust-paper does not set the desktop wallpaper; record one with POST /current",
+                ),
+                ("POST", ["current"]) | ("POST", ["current", "random"]) => {
+                    let fields = match parse_object(&request.body) {
+                        Ok(fields) => fields,
+                        Err(e) => return error(400, e),
+                    };
+                    let monitor = fields["monitor"].as_str();
+                    let recorded = if segments.len() == 1 {
+                        let Some(id) = fields["id"].as_str() else {
+                            return error(400, "Expected {\"id\": \"...\"}");
+                        };
+                        self.set_current_id(id, monitor).await
+                    } else {
+                        self.set_random(monitor).await.map(|_| ())
+                    };
+                    match recorded {
+                        Ok(()) => self
+                            .current_wallpaper(monitor)
+                            .await
+                            .map(|current| json!(current)),
+                        Err(e) => Err(e),
+                    }
+                }
+                (_, ["wallpapers"])
+                | (_, ["wallpapers", _])
+                | (_, ["sync"])
+                | (_, ["status"])
+                | (_, ["set"])
+                | (_, ["random"])
+                | (_, ["current"])
+                | (_, ["current", "random"]) => return error(405, "Method not allowed"),
+                _ => return error(404, format!("No endpoint {}", path)),
+            };
+        match result {
+            Ok(body) => (200, body),
+            Err(e) => error(error_status(&e), e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ids() {
+        let (ids, group) = parse_ids(br#"{"ids": ["abc123"], "group": "dark"}"#).unwrap();
+        assert_eq!(ids, ["abc123".to_string()]);
+        assert_eq!(group.as_deref(), Some("dark"));
+        assert!(parse_ids(b"[]").is_err());
+    }
+
+    #[test]
+    fn test_parse_object() {
+        assert_eq!(parse_object(b"").unwrap(), json!({}));
+        assert_eq!(
+            parse_object(br#"{"monitor": "DP-1"}"#).unwrap()["monitor"],
+            "DP-1"
+        );
+        assert!(parse_object(b"[]").is_err());
+    }
+
+    #[test]
+    fn test_response_framing() {
+        let bytes = response(404, &json!({ "error": "nope" }));
+        let text = String::from_utf8(bytes).unwrap();
+        assert!(text.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(text.ends_with("\r\n\r\n{\"error\":\"nope\"}"));
+        assert!(text.contains("Content-Length: 16\r\n"));
+    }
+}
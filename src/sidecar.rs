@@ -0,0 +1,127 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use crate::{WallhavenWallpaper, WALLHAVEN_BASE};
+
+/// Metadata written as `<id>.json` next to a wallpaper so the collection describes itself
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Sidecar {
+    pub id: String,
+    /// Wallhaven page of the wallpaper
+    pub url: String,
+    /// Short link for sharing, e.g. `https://whvn.cc/<id>`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub short_url: Option<String>,
+    /// Full-size image the file was downloaded from
+    pub image_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uploader: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub purity: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<String>,
+    /// Original source given on Wallhaven, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+impl Sidecar {
+    /// Build the sidecar from the API metadata, if we have it
+    pub fn new(id: &str, image_url: &str, data: Option<&WallhavenWallpaper>) -> Self {
+        let text = |field: fn(&WallhavenWallpaper) -> &String| {
+            data.map(field).filter(|s| !s.is_empty()).cloned()
+        };
+        Sidecar {
+            id: id.to_string(),
+            url: format!("{}/{}", WALLHAVEN_BASE, id),
+            short_url: text(|d| &d.short_url),
+            image_url: image_url.to_string(),
+            uploader: data
+                .and_then(|d| d.uploader.as_ref())
+                .map(|u| u.username.clone()),
+            tags: data
+                .map(|d| d.tags.iter().map(|t| t.name.clone()).collect())
+                .unwrap_or_default(),
+            purity: text(|d| &d.purity),
+            category: text(|d| &d.category),
+            resolution: text(|d| &d.resolution),
+            source: text(|d| &d.source),
+        }
+    }
+
+    /// Sidecar of an image imported from the local disk
+    pub fn local(id: &str, source: &Path) -> Self {
+        let location = std::fs::canonicalize(source)
+            .ok()
+            .and_then(|path| url::Url::from_file_path(path).ok())
+            .map(|url| url.to_string())
+            .unwrap_or_else(|| source.to_string_lossy().to_string());
+        Sidecar {
+            id: id.to_string(),
+            url: location.clone(),
+            short_url: None,
+            image_url: location,
+            uploader: None,
+            tags: Vec::new(),
+            purity: None,
+            category: None,
+            resolution: None,
+            source: None,
+        }
+    }
+
+    /// Write the sidecar next to `image`
+    pub async fn write(&self, image: impl AsRef<Path>) -> Result<()> {
+        let path = path_for(image);
+        let json = serde_json::to_string_pretty(self).context("   Failed to serialize sidecar")?;
+        tokio::fs::write(&path, json)
+            .await
+            .with_context(|| format!("   Failed to write {}", path.display()))
+    }
+}
+
+/// Sidecar location for an image
+pub fn path_for(image: impl AsRef<Path>) -> PathBuf {
+    image.as_ref().with_extension("json")
+}
+
+/// Whether a file in the save location is a sidecar rather than an image
+pub fn is_sidecar(path: impl AsRef<Path>) -> bool {
+    path.as_ref().extension().and_then(|e| e.to_str()) == Some("json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sidecar_from_api_data() {
+        let data: WallhavenWallpaper = serde_json::from_str(
+            r#"{"purity":"sfw","category":"anime","resolution":"1920x1080","source":"",
+                "uploader":{"username":"artist"},"tags":[{"name":"city"},{"name":"night"}]}"#,
+        )
+        .unwrap();
+        let sidecar = Sidecar::new(
+            "7pmgv9",
+            "https://w.wallhaven.cc/full/7p/7pmgv9.png",
+            Some(&data),
+        );
+        assert_eq!(sidecar.url, "https://wallhaven.cc/w/7pmgv9");
+        assert_eq!(sidecar.uploader.as_deref(), Some("artist"));
+        assert_eq!(sidecar.tags, ["city", "night"]);
+        assert_eq!(sidecar.source, None);
+
+        let bare = Sidecar::new("7pmgv9", "https://w.wallhaven.cc/full/7p/7pmgv9.png", None);
+        assert!(bare.tags.is_empty() && bare.purity.is_none());
+        assert_eq!(
+            path_for("/walls/7pmgv9.png"),
+            PathBuf::from("/walls/7pmgv9.json")
+        );
+        assert!(is_sidecar("/walls/7pmgv9.json"));
+    }
+}
@@ -0,0 +1,122 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::helper;
+
+/// Name of the source backed by the manually maintained `wallpapers.lst`
+pub const MANUAL_SOURCE: &str = "manual";
+
+/// Name of the source pulling new uploads of followed Wallhaven users
+pub const FOLLOW_SOURCE: &str = "follows";
+
+/// Per-source sync settings
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct SourceConfig {
+    /// Whether the source takes part in a plain `sync` run
+    pub enabled: bool,
+    /// Minimum time between syncs of this source, e.g. "12h" or "7d" (optional)
+    pub interval: Option<String>,
+}
+
+impl Default for SourceConfig {
+    fn default() -> Self {
+        SourceConfig {
+            enabled: true,
+            interval: None,
+        }
+    }
+}
+
+/// Last sync time of every source, persisted between runs
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct SourceState {
+    last_synced: HashMap<String, u64>,
+}
+
+impl SourceState {
+    /// Load the state from disk, starting fresh if it is missing or unreadable
+    pub async fn load(path: impl AsRef<Path>) -> Self {
+        match tokio::fs::read_to_string(path.as_ref()).await {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_default(),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// Save the state to disk
+    pub async fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let json =
+            serde_json::to_string_pretty(&self).context("   Failed to serialize source state")?;
+        tokio::fs::write(path.as_ref(), json)
+            .await
+            .context("   Failed to write source state")
+    }
+
+    /// Check whether a source should run now, honouring its `enabled` flag and interval
+    pub fn is_due(&self, name: &str, config: &SourceConfig, now: u64) -> Result<bool> {
+        if !config.enabled {
+            return Ok(false);
+        }
+        let Some(interval) = config.interval.as_deref() else {
+            return Ok(true);
+        };
+        let interval = helper::parse_duration(interval)
+            .with_context(|| format!("Invalid interval for source '{}'", name))?;
+        Ok(match self.last_synced.get(name) {
+            Some(last) => now.saturating_sub(*last) >= interval.as_secs(),
+            None => true,
+        })
+    }
+
+    /// Record a successful sync of a source
+    pub fn mark_synced(&mut self, name: &str, now: u64) {
+        self.last_synced.insert(name.to_string(), now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_due_without_interval() {
+        let state = SourceState::default();
+        assert!(state
+            .is_due(MANUAL_SOURCE, &SourceConfig::default(), 100)
+            .unwrap());
+    }
+
+    #[test]
+    fn test_source_disabled() {
+        let state = SourceState::default();
+        let config = SourceConfig {
+            enabled: false,
+            interval: None,
+        };
+        assert!(!state.is_due(MANUAL_SOURCE, &config, 100).unwrap());
+    }
+
+    #[test]
+    fn test_source_interval() {
+        let mut state = SourceState::default();
+        let config = SourceConfig {
+            enabled: true,
+            interval: Some("1h".to_string()),
+        };
+        state.mark_synced(MANUAL_SOURCE, 1_000);
+        assert!(!state.is_due(MANUAL_SOURCE, &config, 1_000 + 60).unwrap());
+        assert!(state.is_due(MANUAL_SOURCE, &config, 1_000 + 3_600).unwrap());
+    }
+
+    #[test]
+    fn test_source_invalid_interval() {
+        let state = SourceState::default();
+        let config = SourceConfig {
+            enabled: true,
+            interval: Some("soon".to_string()),
+        };
+        assert!(state.is_due(MANUAL_SOURCE, &config, 100).is_err());
+    }
+}
@@ -0,0 +1,83 @@
+use std::path::Path;
+
+use crate::error::{Error, Result};
+use crate::output::say;
+use crate::{api, helper, RustPaper, SyncOptions};
+
+/// Assumed size of a wallpaper whose metadata we haven't seen yet
+const UNKNOWN_SIZE_ESTIMATE: u64 = 5 * 1024 * 1024;
+
+const MIB: u64 = 1024 * 1024;
+
+/// Sizes of the wallpapers whose size is `known`, with the average of those
+/// (or a typical size) filled in where it isn't
+fn estimate(known: &[Option<u64>]) -> Vec<u64> {
+    let sizes: Vec<u64> = known.iter().flatten().copied().collect();
+    let average = match sizes.len() {
+        0 => UNKNOWN_SIZE_ESTIMATE,
+        n => sizes.iter().sum::<u64>() / n as u64,
+    };
+    known.iter().map(|size| size.unwrap_or(average)).collect()
+}
+
+impl RustPaper {
+    /// Expected download size of each of `ids`, from search results or the
+    /// metadata cache, estimated where neither knows it
+    pub(crate) async fn expected_download_sizes(&self, ids: &[String]) -> Vec<u64> {
+        let mut known = Vec::new();
+        for id in ids {
+            let size = match self.expected_sizes.get(id) {
+                Some(&size) => Some(size),
+                None => helper::read_cached_metadata(&self.metadata_cache_location, id)
+                    .await
+                    .and_then(|data| api::WallpaperInfoResponse::parse(&data).ok())
+                    .map(|data| data.file_size.max(0) as u64),
+            };
+            known.push(size.filter(|&size| size > 0));
+        }
+        estimate(&known)
+    }
+
+    /// Refuse to start downloading `expected` bytes when they would leave less
+    /// than `min_free_space_mb` free on the save location's filesystem
+    pub(crate) fn check_free_space(&self, expected: u64, options: &SyncOptions) -> Result<()> {
+        if options.ignore_disk_space {
+            return Ok(());
+        }
+        let save_location = Path::new(&self.config.save_location);
+        let available = match fs2::available_space(save_location) {
+            Ok(available) => available,
+            Err(e) => {
+                say!(
+                    "  ⚠ Could not check free space on {}: {}",
+                    save_location.display(),
+                    e
+                );
+                return Ok(());
+            }
+        };
+        let needed = expected.saturating_add(self.config.min_free_space_mb.saturating_mul(MIB));
+        if available < needed {
+            return Err(Error::InsufficientSpace {
+                path: save_location.display().to_string(),
+                needed_mb: needed.div_ceil(MIB),
+                available_mb: available / MIB,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_fills_in_unknown_sizes() {
+        assert_eq!(
+            estimate(&[None, None]),
+            [UNKNOWN_SIZE_ESTIMATE, UNKNOWN_SIZE_ESTIMATE]
+        );
+        assert_eq!(estimate(&[Some(10), Some(30), None]), [10, 30, 20]);
+    }
+}
@@ -0,0 +1,124 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
+use tokio::fs::{File, OpenOptions};
+
+/// Folder inside a download's target folder where it is written until complete
+pub const STAGING_DIR: &str = ".staging";
+
+/// Staged files untouched for this long were left behind by a crash
+pub const STALE_AFTER: Duration = Duration::from_secs(24 * 60 * 60);
+
+static NEXT: AtomicU64 = AtomicU64::new(0);
+
+/// A download in progress. It only appears at its target once committed;
+/// dropping it uncommitted deletes it
+pub struct Staged {
+    path: PathBuf,
+    file: File,
+    committed: bool,
+}
+
+impl Staged {
+    /// Start a staged file that will become `target`, under a name no other
+    /// download (in this or another process) can use
+    pub async fn create(target: &Path) -> io::Result<Self> {
+        let dir = target
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join(STAGING_DIR);
+        tokio::fs::create_dir_all(&dir).await?;
+        let name = target.file_name().unwrap_or_default().to_string_lossy();
+        let path = dir.join(format!(
+            "{}.{}-{}.part",
+            name,
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .await?;
+        Ok(Staged {
+            path,
+            file,
+            committed: false,
+        })
+    }
+
+    pub fn file(&mut self) -> &mut File {
+        &mut self.file
+    }
+
+    /// Where the file is written until it is committed
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Flush the file to disk and move it to `target` in one step
+    pub async fn commit(mut self, target: &Path) -> io::Result<()> {
+        self.file.sync_all().await?;
+        tokio::fs::rename(&self.path, target).await?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Drop for Staged {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Delete staged files in `folder` older than `max_age`, returning how many
+pub async fn clean_stale(folder: &Path, max_age: Duration) -> usize {
+    let Ok(mut entries) = tokio::fs::read_dir(folder.join(STAGING_DIR)).await else {
+        return 0;
+    };
+    let now = SystemTime::now();
+    let mut removed = 0;
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let stale = entry
+            .metadata()
+            .await
+            .and_then(|metadata| metadata.modified())
+            .is_ok_and(|modified| now.duration_since(modified).unwrap_or_default() >= max_age);
+        if stale && tokio::fs::remove_file(entry.path()).await.is_ok() {
+            removed += 1;
+        }
+    }
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+
+    #[tokio::test]
+    async fn test_staged_files_appear_only_when_committed() {
+        let dir = std::env::temp_dir().join(format!("rust-paper-staging-{}", std::process::id()));
+        let target = dir.join("abc123.png");
+
+        let mut staged = Staged::create(&target).await.unwrap();
+        staged.file().write_all(b"image").await.unwrap();
+        assert!(!target.exists());
+        staged.commit(&target).await.unwrap();
+        assert_eq!(std::fs::read(&target).unwrap(), b"image");
+
+        let mut abandoned = Staged::create(&dir.join("def456.png")).await.unwrap();
+        abandoned.file().write_all(b"half").await.unwrap();
+        drop(abandoned);
+        assert_eq!(std::fs::read_dir(dir.join(STAGING_DIR)).unwrap().count(), 0);
+
+        std::fs::write(dir.join(STAGING_DIR).join("old.part"), "").unwrap();
+        assert_eq!(clean_stale(&dir, STALE_AFTER).await, 0);
+        assert_eq!(clean_stale(&dir, Duration::ZERO).await, 1);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
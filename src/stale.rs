@@ -0,0 +1,199 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::current::CurrentState;
+use crate::error::{Error, Result};
+use crate::group::Groups;
+use crate::output::{say, say_err};
+use crate::{build_file_map, helper, RustPaper};
+
+/// Group stale wallpapers are archived into
+pub const STALE_GROUP: &str = "stale";
+
+/// What happens to wallpapers that have gone unused for `stale_after`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StaleAction {
+    /// Only warn after a sync, pointing at `rust-paper stale`
+    #[default]
+    Flag,
+    /// Move them into the archived "stale" group, which sync only verifies
+    Archive,
+    /// Stop tracking them (their files are kept)
+    Remove,
+}
+
+/// A wallpaper that hasn't been used for a while
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaleWallpaper {
+    pub id: String,
+    /// When it was last set as the current wallpaper, if ever
+    pub last_set: Option<u64>,
+    /// When its file was downloaded (or last changed)
+    pub downloaded: u64,
+}
+
+impl StaleWallpaper {
+    /// Unix seconds of the last time it was used or, if never, arrived
+    fn last_used(&self) -> u64 {
+        self.last_set.unwrap_or(self.downloaded)
+    }
+}
+
+impl RustPaper {
+    /// Tracked wallpapers not set as the current wallpaper within `older_than`.
+    /// One that was never set counts from its download; wallpapers not
+    /// downloaded yet or already archived are left out
+    pub async fn stale_wallpapers(&self, older_than: Duration) -> Result<Vec<StaleWallpaper>> {
+        let (file_map, current, groups) = tokio::try_join!(
+            build_file_map(&self.config),
+            CurrentState::load(&self.current_file_location),
+            Groups::load(&self.groups_file_location),
+        )?;
+        let archived = groups.archived_ids();
+        let cutoff = helper::unix_timestamp().saturating_sub(older_than.as_secs());
+        let mut stale = Vec::new();
+        for id in &self.wallpapers {
+            let Some(path) = file_map.get(id) else {
+                continue;
+            };
+            if archived.contains(id) {
+                continue;
+            }
+            let wallpaper = StaleWallpaper {
+                id: id.clone(),
+                last_set: current.last_set(id),
+                downloaded: helper::modified_timestamp(path).await,
+            };
+            if wallpaper.last_used() < cutoff {
+                stale.push(wallpaper);
+            }
+        }
+        stale.sort_by_key(StaleWallpaper::last_used);
+        Ok(stale)
+    }
+
+    /// List the wallpapers unused for `older_than` (default: `stale_after`),
+    /// then flag, archive or remove them
+    pub async fn stale(&mut self, older_than: Option<&str>, action: StaleAction) -> Result<()> {
+        let window = match older_than.or(self.config.stale_after.as_deref()) {
+            Some(window) => window.to_string(),
+            None => {
+                return Err(Error::InvalidInput(
+                    "Pass --older-than (e.g. 90d) or set stale_after in the config".to_string(),
+                ))
+            }
+        };
+        let older_than =
+            helper::parse_duration(&window).map_err(|e| Error::InvalidInput(format!("{:#}", e)))?;
+        let stale = self.stale_wallpapers(older_than).await?;
+        if stale.is_empty() {
+            say!("   Every wallpaper was used in the last {}", window);
+            return Ok(());
+        }
+        for wallpaper in &stale {
+            match wallpaper.last_set {
+                Some(set_at) => say!(
+                    "  {} - last set {} UTC",
+                    wallpaper.id,
+                    helper::format_timestamp(set_at)
+                ),
+                None => say!(
+                    "  {} - never set, downloaded {} UTC",
+                    wallpaper.id,
+                    helper::format_timestamp(wallpaper.downloaded)
+                ),
+            }
+        }
+        say!(
+            "   {} wallpaper(s) not used in the last {}",
+            stale.len(),
+            window
+        );
+        let ids: Vec<String> = stale.into_iter().map(|wallpaper| wallpaper.id).collect();
+        self.apply_stale_action(&ids, action).await
+    }
+
+    async fn apply_stale_action(&mut self, ids: &[String], action: StaleAction) -> Result<()> {
+        match action {
+            StaleAction::Flag => Ok(()),
+            StaleAction::Remove => self.remove(ids).await,
+            StaleAction::Archive => {
+                archive_into_stale_group(&self.groups_file_location, ids).await?;
+                say!(
+                    "   Archived {} wallpaper(s) into the '{}' group",
+                    ids.len(),
+                    STALE_GROUP
+                );
+                Ok(())
+            }
+        }
+    }
+
+    /// With `stale_after` set, flag, archive or remove (`stale_action`) the
+    /// wallpapers that went unused for that long. Runs after a sync; failing
+    /// only warns
+    pub(crate) async fn apply_stale_policy(&mut self) {
+        let Some(window) = self.config.stale_after.clone() else {
+            return;
+        };
+        let result = async {
+            let older_than = helper::parse_duration(&window)?;
+            let ids: Vec<String> = self
+                .stale_wallpapers(older_than)
+                .await?
+                .into_iter()
+                .map(|wallpaper| wallpaper.id)
+                .collect();
+            if ids.is_empty() {
+                return anyhow::Ok(());
+            }
+            match self.config.stale_action {
+                StaleAction::Flag => say!(
+                    "  ⚠ {} wallpaper(s) not used in the last {}; review them with `rust-paper stale`",
+                    ids.len(),
+                    window
+                ),
+                action => self.apply_stale_action(&ids, action).await?,
+            }
+            Ok(())
+        }
+        .await;
+        if let Err(e) = result {
+            say_err!("  ⚠ Could not apply stale_after: {:#}", e);
+        }
+    }
+}
+
+/// Add `ids` to the archived stale group, creating it if needed
+async fn archive_into_stale_group(groups_file: &Path, ids: &[String]) -> anyhow::Result<()> {
+    let mut groups = Groups::load(groups_file).await?;
+    if groups.members(STALE_GROUP).is_none() {
+        groups.create(STALE_GROUP)?;
+    }
+    groups.set_archived(STALE_GROUP, false)?;
+    groups.add(STALE_GROUP, ids)?;
+    groups.set_archived(STALE_GROUP, true)?;
+    groups.save(groups_file).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stale_wallpapers_count_from_their_last_use() {
+        let never_set = StaleWallpaper {
+            id: "abc123".to_string(),
+            last_set: None,
+            downloaded: 10,
+        };
+        assert_eq!(never_set.last_used(), 10);
+        let set = StaleWallpaper {
+            last_set: Some(20),
+            ..never_set
+        };
+        assert_eq!(set.last_used(), 20);
+    }
+}
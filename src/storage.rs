@@ -0,0 +1,259 @@
+use anyhow::{Context, Result};
+use futures::future::BoxFuture;
+use futures::stream::{self, StreamExt};
+use futures::FutureExt;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::output::{say, say_err};
+use crate::report::SyncFailure;
+use crate::{build_file_map, RustPaper};
+
+/// A place downloaded wallpapers are mirrored to, addressed by file name
+pub trait Storage: Send + Sync {
+    /// Where the files go, for messages
+    fn describe(&self) -> String;
+
+    /// Whether a file called `name` is stored
+    fn exists<'a>(&'a self, name: &'a str) -> BoxFuture<'a, Result<bool>>;
+
+    /// Store `data` as `name`, replacing any file already there
+    fn put<'a>(&'a self, name: &'a str, data: Vec<u8>) -> BoxFuture<'a, Result<()>>;
+
+    /// Delete `name`; a file that isn't there is not an error
+    fn delete<'a>(&'a self, name: &'a str) -> BoxFuture<'a, Result<()>>;
+}
+
+/// A storage mirror in the config
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum StorageConfig {
+    /// Another folder, e.g. a mounted network share
+    Local { path: String },
+    /// A WebDAV collection (needs the `webdav` feature)
+    Webdav {
+        url: String,
+        username: Option<String>,
+        password: Option<String>,
+    },
+    /// An S3 bucket, or a bucket on an S3-compatible server (needs the `s3` feature)
+    S3 {
+        endpoint: String,
+        bucket: String,
+        #[serde(default = "default_region")]
+        region: String,
+        access_key: String,
+        secret_key: String,
+        /// Prepended to every object key, e.g. "wallpapers/"
+        #[serde(default)]
+        prefix: String,
+    },
+}
+
+fn default_region() -> String {
+    "us-east-1".to_string()
+}
+
+/// Open the backend described by `storage`
+#[cfg_attr(not(any(feature = "webdav", feature = "s3")), allow(unused_variables))]
+pub fn open(storage: &StorageConfig, config: &Config) -> crate::Result<Arc<dyn Storage>> {
+    match storage {
+        StorageConfig::Local { path } => Ok(Arc::new(LocalStorage::new(path))),
+        #[cfg(feature = "webdav")]
+        StorageConfig::Webdav {
+            url,
+            username,
+            password,
+        } => Ok(Arc::new(crate::webdav::WebDavStorage::new(
+            crate::helper::create_download_client(config)?,
+            url,
+            username.clone(),
+            password.clone(),
+        ))),
+        #[cfg(feature = "s3")]
+        StorageConfig::S3 {
+            endpoint,
+            bucket,
+            region,
+            access_key,
+            secret_key,
+            prefix,
+        } => Ok(Arc::new(crate::s3::S3Storage::new(
+            crate::helper::create_download_client(config)?,
+            endpoint,
+            bucket,
+            region,
+            access_key,
+            secret_key,
+            prefix,
+        )?)),
+        #[allow(unreachable_patterns)]
+        StorageConfig::Webdav { .. } => Err(missing_feature("webdav")),
+        #[allow(unreachable_patterns)]
+        StorageConfig::S3 { .. } => Err(missing_feature("s3")),
+    }
+}
+
+fn missing_feature(feature: &str) -> crate::Error {
+    crate::Error::Config(format!(
+        "storage_mirrors uses {0}, but rust-paper was built without the `{0}` feature",
+        feature
+    ))
+}
+
+/// Files in a folder on this machine
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        LocalStorage { root: root.into() }
+    }
+}
+
+impl Storage for LocalStorage {
+    fn describe(&self) -> String {
+        self.root.display().to_string()
+    }
+
+    fn exists<'a>(&'a self, name: &'a str) -> BoxFuture<'a, Result<bool>> {
+        async move { Ok(tokio::fs::try_exists(self.root.join(name)).await?) }.boxed()
+    }
+
+    fn put<'a>(&'a self, name: &'a str, data: Vec<u8>) -> BoxFuture<'a, Result<()>> {
+        async move {
+            tokio::fs::create_dir_all(&self.root)
+                .await
+                .with_context(|| format!("Failed to create {}", self.root.display()))?;
+            // Readers never see a half-written file
+            let partial = self.root.join(format!(".{}.partial", name));
+            tokio::fs::write(&partial, data).await?;
+            tokio::fs::rename(&partial, self.root.join(name)).await?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn delete<'a>(&'a self, name: &'a str) -> BoxFuture<'a, Result<()>> {
+        async move {
+            match tokio::fs::remove_file(self.root.join(name)).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            }
+        }
+        .boxed()
+    }
+}
+
+/// Copy `path` to `storage` as `name` unless it is already there
+async fn mirror_file(storage: &dyn Storage, name: &str, path: &Path) -> Result<bool> {
+    if storage.exists(name).await? {
+        return Ok(false);
+    }
+    let data = tokio::fs::read(path)
+        .await
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    storage.put(name, data).await?;
+    Ok(true)
+}
+
+impl RustPaper {
+    /// Copy the downloaded files of `ids` to every storage mirror that lacks them
+    pub(crate) async fn mirror_wallpapers(
+        &self,
+        ids: &[String],
+    ) -> crate::Result<Vec<SyncFailure>> {
+        if self.storage_mirrors.is_empty() {
+            return Ok(Vec::new());
+        }
+        let file_map = build_file_map(&self.config).await?;
+        let jobs: Vec<(&String, String, &PathBuf, &Arc<dyn Storage>)> = ids
+            .iter()
+            .filter_map(|id| {
+                let path = file_map.get(id)?;
+                let name = path.file_name()?.to_string_lossy().to_string();
+                Some((id, name, path))
+            })
+            .flat_map(|(id, name, path)| {
+                self.storage_mirrors
+                    .iter()
+                    .map(move |storage| (id, name.clone(), path, storage))
+            })
+            .collect();
+        let results: Vec<_> = stream::iter(jobs)
+            .map(|(id, name, path, storage)| async move {
+                let result = mirror_file(storage.as_ref(), &name, path).await;
+                (id, storage, result)
+            })
+            .buffer_unordered(self.config.max_concurrent_downloads.max(1))
+            .collect()
+            .await;
+
+        let mut failures = Vec::new();
+        for (id, storage, result) in results {
+            match result {
+                Ok(true) => say!("  ✓ Mirrored {} to {}", id, storage.describe()),
+                Ok(false) => {}
+                Err(e) => {
+                    say_err!(
+                        "  ✗ Failed to mirror {} to {}: {:#}",
+                        id,
+                        storage.describe(),
+                        e
+                    );
+                    failures.push(SyncFailure {
+                        id: id.clone(),
+                        reason: format!("mirror to {}: {:#}", storage.describe(), e),
+                    });
+                }
+            }
+        }
+        Ok(failures)
+    }
+
+    /// Delete files called `names` from every storage mirror, warning on failure
+    pub(crate) async fn unmirror(&self, names: &[String]) {
+        for storage in &self.storage_mirrors {
+            for name in names {
+                if let Err(e) = storage.delete(name).await {
+                    say_err!(
+                        "  ⚠ Failed to delete {} from {}: {:#}",
+                        name,
+                        storage.describe(),
+                        e
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_storage_config() {
+        let config: Config = serde_json::from_str(
+            r#"{"storage_mirrors": [
+                {"type": "local", "path": "/mnt/nas/wallpapers"},
+                {"type": "s3", "endpoint": "https://minio.lan:9000", "bucket": "walls",
+                 "access_key": "key", "secret_key": "secret"}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.storage_mirrors[0],
+            StorageConfig::Local {
+                path: "/mnt/nas/wallpapers".to_string()
+            }
+        );
+        assert!(matches!(
+            &config.storage_mirrors[1],
+            StorageConfig::S3 { region, prefix, .. } if region == "us-east-1" && prefix.is_empty()
+        ));
+    }
+}
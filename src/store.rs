@@ -0,0 +1,94 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+/// Where the image with `hash` lives in the store, keeping the extension of `path`
+pub fn stored_path(store: &Path, hash: &str, path: &Path) -> PathBuf {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some(extension) => store.join(format!("{}.{}", hash, extension)),
+        None => store.join(hash),
+    }
+}
+
+/// Link `link` to `target`, hard if possible and symbolic otherwise (e.g. across filesystems)
+fn link(target: &Path, link: &Path) -> std::io::Result<()> {
+    if std::fs::hard_link(target, link).is_ok() {
+        return Ok(());
+    }
+    #[cfg(unix)]
+    return std::os::unix::fs::symlink(target, link);
+    #[cfg(windows)]
+    return std::os::windows::fs::symlink_file(target, link);
+}
+
+/// Move a freshly written image into the store under its hash and link it back
+/// into place. When the store already holds the same content, the new copy is dropped
+pub async fn adopt(store: &Path, path: &Path, hash: &str) -> Result<PathBuf> {
+    tokio::fs::create_dir_all(store)
+        .await
+        .with_context(|| format!("   Failed to create store {}", store.display()))?;
+    let stored = stored_path(store, hash, path);
+    if tokio::fs::metadata(&stored).await.is_ok() {
+        tokio::fs::remove_file(path).await?;
+    } else if tokio::fs::rename(path, &stored).await.is_err() {
+        tokio::fs::copy(path, &stored).await?;
+        tokio::fs::remove_file(path).await?;
+    }
+    link(&stored, path)
+        .with_context(|| format!("   Failed to link {} into the store", path.display()))?;
+    Ok(stored)
+}
+
+/// Whether `path` is a link to the stored image with `hash`. The store name
+/// is the checksum, so this stands in for re-hashing the file
+pub fn is_linked(store: &Path, hash: &str, path: &Path) -> bool {
+    let stored = stored_path(store, hash, path);
+    match (std::fs::canonicalize(&stored), std::fs::canonicalize(path)) {
+        (Ok(stored), Ok(resolved)) if stored == resolved => true,
+        (Ok(_), Ok(_)) => same_file(&stored, path),
+        _ => false,
+    }
+}
+
+#[cfg(unix)]
+fn same_file(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (std::fs::metadata(a), std::fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn same_file(_a: &Path, _b: &Path) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_adopt_dedups_and_links() {
+        let dir = std::env::temp_dir().join(format!("rust-paper-store-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let walls = dir.join("walls");
+        let store = dir.join("store");
+        std::fs::create_dir_all(&walls).unwrap();
+        std::fs::write(walls.join("abc123.png"), "image").unwrap();
+        std::fs::write(walls.join("def456.png"), "image").unwrap();
+
+        let first = adopt(&store, &walls.join("abc123.png"), "ff00")
+            .await
+            .unwrap();
+        let second = adopt(&store, &walls.join("def456.png"), "ff00")
+            .await
+            .unwrap();
+        assert_eq!(first, store.join("ff00.png"));
+        assert_eq!(first, second);
+        assert_eq!(std::fs::read_dir(&store).unwrap().count(), 1);
+        assert_eq!(std::fs::read(walls.join("def456.png")).unwrap(), b"image");
+        assert!(is_linked(&store, "ff00", &walls.join("abc123.png")));
+        assert!(!is_linked(&store, "0000", &walls.join("abc123.png")));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,282 @@
+//! Helpers for testing code that embeds rust-paper, without network access
+//! or the real config folders. Enabled by the `test-support` feature.
+//!
+//! ```no_run
+//! # async fn example() -> rust_paper::Result<()> {
+//! use rust_paper::test_support::{fixtures, MockFetcher, TempDir};
+//! use rust_paper::{AddOptions, SyncOptions};
+//!
+//! let fetcher = MockFetcher::new()
+//!     .route("https://wallhaven.cc/api/v1/w/abc123", fixtures::info_json("abc123"))
+//!     .route("https://w.wallhaven.cc/full/abc123.png", fixtures::png());
+//! let dir = TempDir::new("my-tool")?;
+//! let mut paper = dir.rust_paper(fetcher.into()).await?;
+//! paper.add(&mut vec!["abc123".to_string()], &AddOptions::default()).await?;
+//! paper.sync(None, &SyncOptions::default()).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use futures::future::BoxFuture;
+use futures::{stream, FutureExt, StreamExt};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::fetch::{FetchResponse, HttpFetcher};
+use crate::RustPaper;
+
+/// In-memory `HttpFetcher` serving canned responses by URL prefix.
+/// Unrouted URLs answer `Error::NotFound`, and every request is recorded
+pub struct MockFetcher {
+    routes: Vec<(String, Vec<u8>)>,
+    online: bool,
+    ranges: bool,
+    requests: Mutex<Vec<String>>,
+}
+
+impl Default for MockFetcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockFetcher {
+    /// An online fetcher without routes
+    pub fn new() -> Self {
+        MockFetcher {
+            routes: Vec::new(),
+            online: true,
+            ranges: false,
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Answer URLs starting with `prefix` with `body`; earlier routes win
+    pub fn route(mut self, prefix: &str, body: impl Into<Vec<u8>>) -> Self {
+        self.routes.push((prefix.to_string(), body.into()));
+        self
+    }
+
+    /// Report Wallhaven as unreachable, so syncs run offline
+    pub fn offline(mut self) -> Self {
+        self.online = false;
+        self
+    }
+
+    /// Serve range requests, so large files are downloaded in chunks
+    pub fn with_ranges(mut self) -> Self {
+        self.ranges = true;
+        self
+    }
+
+    /// Every URL requested so far, in order. Range requests are recorded as
+    /// `<url> bytes=<start>-<end>`
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    fn lookup(&self, url: &str) -> Result<Vec<u8>> {
+        self.requests.lock().unwrap().push(url.to_string());
+        self.routes
+            .iter()
+            .find(|(prefix, _)| url.starts_with(prefix.as_str()))
+            .map(|(_, body)| body.clone())
+            .ok_or_else(|| Error::NotFound(url.to_string()))
+    }
+}
+
+impl From<MockFetcher> for Arc<dyn HttpFetcher> {
+    fn from(fetcher: MockFetcher) -> Self {
+        Arc::new(fetcher)
+    }
+}
+
+impl HttpFetcher for MockFetcher {
+    fn get_text<'a>(
+        &'a self,
+        url: &'a str,
+        _api_key: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<String>> {
+        async move {
+            String::from_utf8(self.lookup(url)?)
+                .map_err(|e| Error::InvalidInput(format!("Route for {} is not text: {}", url, e)))
+        }
+        .boxed()
+    }
+
+    fn get_stream<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<FetchResponse>> {
+        async move {
+            let body = self.lookup(url)?;
+            Ok(FetchResponse {
+                content_length: Some(body.len() as u64),
+                body: stream::iter(vec![Ok(body)]).boxed(),
+            })
+        }
+        .boxed()
+    }
+
+    fn get_range<'a>(
+        &'a self,
+        url: &'a str,
+        start: u64,
+        len: u64,
+    ) -> BoxFuture<'a, Result<Option<Vec<u8>>>> {
+        async move {
+            if !self.ranges {
+                return Ok(None);
+            }
+            let body = self.lookup(&format!("{} bytes={}-{}", url, start, start + len - 1))?;
+            let end = (start + len).min(body.len() as u64);
+            Ok(body.get(start as usize..end as usize).map(<[u8]>::to_vec))
+        }
+        .boxed()
+    }
+
+    fn is_online(&self) -> BoxFuture<'_, bool> {
+        async move { self.online }.boxed()
+    }
+}
+
+static NEXT: AtomicU64 = AtomicU64::new(0);
+
+/// A fresh folder under the system temp dir, deleted when dropped
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// Create an empty folder whose name starts with `name`
+    pub fn new(name: &str) -> Result<Self> {
+        let path = std::env::temp_dir().join(format!(
+            "rust-paper-{}-{}-{}",
+            name,
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path)?;
+        Ok(TempDir { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// A config saving wallpapers to `wallpapers/` in this folder, with an
+    /// API key set so metadata is fetched through the API
+    pub fn config(&self) -> Config {
+        Config {
+            save_location: self.path.join("wallpapers").to_string_lossy().to_string(),
+            api_key: Some("test".to_string()),
+            retry_count: 1,
+            ..Config::default()
+        }
+    }
+
+    /// A `RustPaper` using `config()`, keeping its list, lock file and state
+    /// in `config/` in this folder
+    pub async fn rust_paper(&self, fetcher: Arc<dyn HttpFetcher>) -> Result<RustPaper> {
+        self.rust_paper_with(self.config(), fetcher).await
+    }
+
+    /// Like `rust_paper`, with a config of your own
+    pub async fn rust_paper_with(
+        &self,
+        config: Config,
+        fetcher: Arc<dyn HttpFetcher>,
+    ) -> Result<RustPaper> {
+        RustPaper::builder()
+            .config(config)
+            .config_folder(self.path.join("config"))
+            .fetcher(fetcher)
+            .build()
+            .await
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Canned Wallhaven responses and images
+pub mod fixtures {
+    /// A 4x4 transparent PNG
+    pub fn png() -> Vec<u8> {
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        image::RgbaImage::new(4, 4)
+            .write_to(&mut bytes, image::ImageFormat::Png)
+            .expect("encoding a PNG in memory can't fail");
+        bytes.into_inner()
+    }
+
+    /// Wallpaper endpoint response for `id`, whose image is served at
+    /// `https://w.wallhaven.cc/full/<id>.png`
+    pub fn info_json(id: &str) -> String {
+        format!(
+            r#"{{"data":{{"id":"{id}","url":"https://wallhaven.cc/w/{id}","purity":"sfw","category":"general","dimension_x":4,"dimension_y":4,"resolution":"4x4","file_size":{size},"file_type":"image/png","path":"https://w.wallhaven.cc/full/{id}.png","tags":[]}}}}"#,
+            size = png().len()
+        )
+    }
+
+    /// A single-page search response listing `ids`
+    pub fn search_json(ids: &[&str]) -> String {
+        let size = png().len();
+        let data: Vec<String> = ids
+            .iter()
+            .map(|id| {
+                format!(
+                    r#"{{"id":"{id}","url":"https://wallhaven.cc/w/{id}","short_url":"https://whvn.cc/{id}","views":1,"favorites":1,"source":"","purity":"sfw","category":"general","dimension_x":4,"dimension_y":4,"resolution":"4x4","ratio":"1","file_size":{size},"file_type":"image/png","created_at":"2024-01-01 00:00:00","colors":[],"path":"https://w.wallhaven.cc/full/{id}.png","thumbs":{{"large":"","original":"","small":""}}}}"#
+                )
+            })
+            .collect();
+        format!(
+            r#"{{"data":[{}],"meta":{{"current_page":1,"last_page":1,"per_page":"24","total":{},"query":null,"seed":null}}}}"#,
+            data.join(","),
+            ids.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AddOptions, SyncOptions};
+
+    #[tokio::test]
+    async fn test_harness_syncs_without_network() {
+        let fetcher = Arc::new(
+            MockFetcher::new()
+                .route(
+                    "https://wallhaven.cc/api/v1/w/abc123",
+                    fixtures::info_json("abc123"),
+                )
+                .route("https://w.wallhaven.cc/full/abc123.png", fixtures::png()),
+        );
+        let dir = TempDir::new("test-support").unwrap();
+        let path = dir.path().to_path_buf();
+        {
+            let mut paper = dir.rust_paper(fetcher.clone()).await.unwrap();
+            paper
+                .add(&mut vec!["abc123".to_string()], &AddOptions::default())
+                .await
+                .unwrap();
+            let report = paper.sync(None, &SyncOptions::default()).await.unwrap();
+            assert_eq!(report.downloaded, 1);
+        }
+        assert!(path.join("wallpapers/abc123.png").exists());
+        assert!(fetcher
+            .requests()
+            .contains(&"https://w.wallhaven.cc/full/abc123.png".to_string()));
+
+        let search: crate::api::SearchResponse =
+            serde_json::from_str(&fixtures::search_json(&["abc123", "def456"])).unwrap();
+        assert_eq!(search.data.len(), 2);
+        drop(dir);
+        assert!(!path.exists());
+    }
+}
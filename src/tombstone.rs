@@ -0,0 +1,66 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Tracked wallpapers that Wallhaven no longer has, with when we noticed
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct Tombstones {
+    gone: BTreeMap<String, u64>,
+}
+
+impl Tombstones {
+    /// Load the tombstones from disk, starting empty if the file is missing
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self> {
+        match tokio::fs::read_to_string(path.as_ref()).await {
+            Ok(contents) => {
+                serde_json::from_str(&contents).context("   Failed to parse tombstones")
+            }
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Save the tombstones to disk
+    pub async fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let json =
+            serde_json::to_string_pretty(&self).context("   Failed to serialize tombstones")?;
+        tokio::fs::write(path.as_ref(), json)
+            .await
+            .context("   Failed to write tombstones")
+    }
+
+    /// Mark `id` as gone upstream, keeping the time it was first noticed
+    pub fn bury(&mut self, id: &str, now: u64) {
+        self.gone.entry(id.to_string()).or_insert(now);
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.gone.contains_key(id)
+    }
+
+    /// IDs of every wallpaper gone upstream
+    pub fn ids(&self) -> Vec<String> {
+        self.gone.keys().cloned().collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.gone.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bury_keeps_first_sighting() {
+        let mut tombstones = Tombstones::default();
+        tombstones.bury("p9pzk9", 100);
+        tombstones.bury("p9pzk9", 200);
+        assert!(tombstones.contains("p9pzk9"));
+        assert_eq!(tombstones.gone["p9pzk9"], 100);
+        assert_eq!(tombstones.ids(), ["p9pzk9".to_string()]);
+        tombstones.clear();
+        assert!(!tombstones.contains("p9pzk9"));
+    }
+}
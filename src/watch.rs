@@ -0,0 +1,255 @@
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tokio::time::{sleep, Instant};
+
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::output::{say, say_err};
+use crate::source::MANUAL_SOURCE;
+use crate::{load_wallpapers, RustPaper, SyncOptions};
+
+/// How often the list file is checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Settings that are read once when RustPaper starts (into the HTTP client,
+/// lock file, storage mirrors or thread pools), so changing them needs a restart
+const RESTART_SETTINGS: &[&str] = &[
+    "save_location",
+    "integrity",
+    "api_key",
+    "http_proxy",
+    "https_proxy",
+    "ca_bundle",
+    "connect_timeout",
+    "metadata_timeout",
+    "download_idle_timeout",
+    "max_concurrent_downloads",
+    "storage_mirrors",
+    "decode_threads",
+];
+
+/// Modification time and size of `path`, if it exists
+async fn file_stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = tokio::fs::metadata(path).await.ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// A file polled for edits. Only its metadata is polled; the contents are
+/// read when the modification time or size moves
+struct WatchedFile {
+    path: PathBuf,
+    stamp: Option<(SystemTime, u64)>,
+    seen: Option<Vec<u8>>,
+    handled: Option<Vec<u8>>,
+    changed_at: Option<Instant>,
+}
+
+impl WatchedFile {
+    async fn new(path: PathBuf) -> Self {
+        let stamp = file_stamp(&path).await;
+        let seen = tokio::fs::read(&path).await.ok();
+        WatchedFile {
+            path,
+            stamp,
+            handled: seen.clone(),
+            seen,
+            changed_at: None,
+        }
+    }
+
+    /// Whether the file changed since it was last handled and has since been
+    /// left alone for `debounce`, so the editor (or git checkout) is done
+    async fn settled_change(&mut self, debounce: Duration) -> bool {
+        let stamp = file_stamp(&self.path).await;
+        if stamp != self.stamp {
+            self.stamp = stamp;
+            let current = tokio::fs::read(&self.path).await.ok();
+            // A save that rewrote the same contents isn't a change
+            if current != self.seen {
+                self.seen = current;
+                self.changed_at = Some(Instant::now());
+                return false;
+            }
+        }
+        if self.changed_at.is_none_or(|at| at.elapsed() < debounce) {
+            return false;
+        }
+        self.changed_at = None;
+        self.seen != self.handled
+    }
+
+    /// Take the current contents as handled, including any rewrite of our own
+    async fn mark_handled(&mut self) {
+        self.stamp = file_stamp(&self.path).await;
+        self.handled = tokio::fs::read(&self.path).await.ok();
+        self.seen = self.handled.clone();
+    }
+
+    /// Report the pending change again once `debounce` has passed
+    fn retry_later(&mut self) {
+        self.changed_at = Some(Instant::now());
+    }
+}
+
+/// Top-level settings that differ between two configs
+fn changed_settings(old: &Config, new: &Config) -> Vec<String> {
+    let (Ok(Value::Object(old)), Ok(Value::Object(new))) =
+        (serde_json::to_value(old), serde_json::to_value(new))
+    else {
+        return Vec::new();
+    };
+    new.into_iter()
+        .filter(|(key, value)| old.get(key) != Some(value))
+        .map(|(key, _)| key)
+        .collect()
+}
+
+/// `current` with the `settings` taken from `new`
+fn apply_settings(
+    current: &Config,
+    new: &Config,
+    settings: &[String],
+) -> serde_json::Result<Config> {
+    let mut merged = serde_json::to_value(current)?;
+    let new = serde_json::to_value(new)?;
+    for setting in settings {
+        merged[setting] = new[setting].clone();
+    }
+    serde_json::from_value(merged)
+}
+
+impl RustPaper {
+    /// Sync the wallpapers list whenever its file changes, until interrupted.
+    /// Edits are synced once the file has been left alone for `debounce`
+    pub async fn watch(&mut self, debounce: Duration) -> Result<()> {
+        say!(
+            "   Watching {} for changes",
+            self.wallpapers_list_file_location.display()
+        );
+        tokio::select! {
+            result = self.watch_with(POLL_INTERVAL.min(debounce), debounce) => result,
+            _ = tokio::signal::ctrl_c() => {
+                say!("   Stopped watching");
+                Ok(())
+            }
+        }
+    }
+
+    /// Poll the list file every `poll`; runs until the future is dropped.
+    /// Edits to the config file are applied as they happen
+    pub async fn watch_with(&mut self, poll: Duration, debounce: Duration) -> Result<()> {
+        let mut list = WatchedFile::new(self.wallpapers_list_file_location.clone()).await;
+        let mut config_file = match &self.config_file {
+            Some(path) => Some((
+                WatchedFile::new(path.clone()).await,
+                confy::load_path::<Config>(path).ok(),
+            )),
+            None => None,
+        };
+        loop {
+            sleep(poll).await;
+            if let Some((file, loaded)) = &mut config_file {
+                if file.settled_change(debounce).await {
+                    self.reload_config(&file.path, loaded);
+                    file.mark_handled().await;
+                }
+            }
+            if !list.settled_change(debounce).await {
+                continue;
+            }
+            let path = &list.path;
+            let _run_lock = match self.lock_operation().await {
+                Ok(run_lock) => run_lock,
+                Err(Error::Locked(_)) => {
+                    say!("   Another rust-paper run is busy, syncing later");
+                    list.retry_later();
+                    continue;
+                }
+                Err(e) => {
+                    say_err!("  ✗ Sync failed: {}", e);
+                    list.mark_handled().await;
+                    continue;
+                }
+            };
+            match load_wallpapers(path).await {
+                Ok(wallpapers) => {
+                    say!("   {} changed, syncing...", path.display());
+                    self.wallpapers = wallpapers;
+                    if let Err(e) = self
+                        .sync(Some(MANUAL_SOURCE), &SyncOptions::default())
+                        .await
+                    {
+                        say_err!("  ✗ Sync failed: {}", e);
+                    }
+                }
+                Err(e) => say_err!("  ✗ Failed to read {}: {:#}", path.display(), e),
+            }
+            // The sync may rewrite the list itself (e.g. sorting it)
+            list.mark_handled().await;
+        }
+    }
+
+    /// Apply the settings that changed in the config file since `loaded`
+    /// was read, saying which took effect and which wait for a restart
+    fn reload_config(&mut self, path: &Path, loaded: &mut Option<Config>) {
+        let new: Config = match confy::load_path(path) {
+            Ok(config) => config,
+            Err(e) => {
+                say_err!("  ✗ Ignoring the changed {}: {}", path.display(), e);
+                return;
+            }
+        };
+        let previous = loaded.take().unwrap_or_else(|| self.config.clone());
+        let (restart, live): (Vec<String>, Vec<String>) = changed_settings(&previous, &new)
+            .into_iter()
+            .partition(|setting| RESTART_SETTINGS.contains(&setting.as_str()));
+        let merged = apply_settings(&self.config, &new, &live)
+            .map_err(Error::from)
+            .and_then(|config| config.validate().map(|()| config));
+        match merged {
+            Ok(config) => {
+                self.config = config;
+                if !live.is_empty() {
+                    say!("   Reloaded {}: {}", path.display(), live.join(", "));
+                }
+                if !restart.is_empty() {
+                    say!("  ⚠ Restart to apply the change to {}", restart.join(", "));
+                }
+                *loaded = Some(new);
+            }
+            Err(e) => {
+                say_err!("  ✗ Could not apply the changed {}: {}", path.display(), e);
+                *loaded = Some(previous);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changed_settings_are_applied_selectively() {
+        let current = Config {
+            save_location: "/from/the/command/line".to_string(),
+            ..Config::default()
+        };
+        let new = Config {
+            exclude_tags: vec!["anime".to_string()],
+            api_key: Some("new".to_string()),
+            ..Config::default()
+        };
+        let mut changed = changed_settings(&Config::default(), &new);
+        changed.sort();
+        assert_eq!(changed, ["api_key", "exclude_tags"]);
+        assert!(changed_settings(&new, &new.clone()).is_empty());
+
+        let live = vec!["exclude_tags".to_string()];
+        let applied = apply_settings(&current, &new, &live).unwrap();
+        assert_eq!(applied.exclude_tags, ["anime"]);
+        assert_eq!(applied.api_key, None);
+        assert_eq!(applied.save_location, "/from/the/command/line");
+    }
+}
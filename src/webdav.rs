@@ -0,0 +1,119 @@
+use anyhow::{anyhow, Result};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use reqwest::{Client, Method, RequestBuilder, StatusCode};
+
+use crate::storage::Storage;
+
+/// Files in a WebDAV collection, e.g. a share on a NAS
+pub struct WebDavStorage {
+    client: Client,
+    /// Collection URL, ending in `/`
+    url: String,
+    username: Option<String>,
+    password: Option<String>,
+}
+
+impl WebDavStorage {
+    pub fn new(
+        client: Client,
+        url: &str,
+        username: Option<String>,
+        password: Option<String>,
+    ) -> Self {
+        WebDavStorage {
+            client,
+            url: format!("{}/", url.trim_end_matches('/')),
+            username,
+            password,
+        }
+    }
+
+    fn request(&self, method: Method, url: &str) -> RequestBuilder {
+        let request = self.client.request(method, url);
+        match &self.username {
+            Some(username) => request.basic_auth(username, self.password.as_deref()),
+            None => request,
+        }
+    }
+
+    fn file_url(&self, name: &str) -> String {
+        format!("{}{}", self.url, name)
+    }
+}
+
+/// Fail unless `status` is a success
+fn check(status: StatusCode, what: &str) -> Result<()> {
+    if status.is_success() {
+        Ok(())
+    } else {
+        Err(anyhow!("{} failed with status {}", what, status.as_u16()))
+    }
+}
+
+impl Storage for WebDavStorage {
+    fn describe(&self) -> String {
+        self.url.clone()
+    }
+
+    fn exists<'a>(&'a self, name: &'a str) -> BoxFuture<'a, Result<bool>> {
+        async move {
+            let status = self
+                .request(Method::HEAD, &self.file_url(name))
+                .send()
+                .await?
+                .status();
+            if status == StatusCode::NOT_FOUND {
+                return Ok(false);
+            }
+            check(status, "HEAD")?;
+            Ok(true)
+        }
+        .boxed()
+    }
+
+    fn put<'a>(&'a self, name: &'a str, data: Vec<u8>) -> BoxFuture<'a, Result<()>> {
+        async move {
+            let url = self.file_url(name);
+            let status = self
+                .request(Method::PUT, &url)
+                .body(data.clone())
+                .send()
+                .await?
+                .status();
+            // 409 means the collection doesn't exist yet
+            if status != StatusCode::CONFLICT {
+                return check(status, "PUT");
+            }
+            let mkcol = Method::from_bytes(b"MKCOL").expect("valid method");
+            check(
+                self.request(mkcol, &self.url).send().await?.status(),
+                "MKCOL",
+            )?;
+            check(
+                self.request(Method::PUT, &url)
+                    .body(data)
+                    .send()
+                    .await?
+                    .status(),
+                "PUT",
+            )
+        }
+        .boxed()
+    }
+
+    fn delete<'a>(&'a self, name: &'a str) -> BoxFuture<'a, Result<()>> {
+        async move {
+            let status = self
+                .request(Method::DELETE, &self.file_url(name))
+                .send()
+                .await?
+                .status();
+            if status == StatusCode::NOT_FOUND {
+                return Ok(());
+            }
+            check(status, "DELETE")
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,1982 @@
+use futures::StreamExt;
+use rust_paper::test_support::fixtures::{info_json, png, search_json};
+use rust_paper::test_support::{MockFetcher, TempDir};
+use rust_paper::{
+    AddOptions, AnimatedPolicy, CleanOptions, Cli, Command, Config, DownloadQuality, Error,
+    ImportTool, RustPaper, StaleAction, StorageConfig, SyncEvent, SyncOptions, VariantLayout,
+    WallhavenClient, WallpaperStatus,
+};
+use std::sync::Arc;
+use std::time::Duration;
+
+use clap::Parser;
+
+#[tokio::test]
+async fn test_sync_downloads_from_canned_api() {
+    let dir = TempDir::new("sync").unwrap();
+    std::fs::create_dir_all(dir.path().join("config")).unwrap();
+    std::fs::write(dir.path().join("config/wallpapers.lst"), "abc123\n").unwrap();
+    let fetcher = Arc::new(
+        MockFetcher::new()
+            .route("https://wallhaven.cc/api/v1/w/abc123", info_json("abc123"))
+            .route("https://w.wallhaven.cc/full/abc123.png", png()),
+    );
+
+    let mut rust_paper = dir.rust_paper(fetcher.clone()).await.unwrap();
+    rust_paper
+        .sync(None, &SyncOptions::default())
+        .await
+        .unwrap();
+
+    assert!(dir.path().join("wallpapers/abc123.png").exists());
+    let lock = std::fs::read_to_string(dir.path().join("config/wallpaper.lock")).unwrap();
+    assert!(lock.contains("abc123"));
+    assert_eq!(fetcher.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn test_sync_falls_back_to_mirror() {
+    let dir = TempDir::new("mirror").unwrap();
+    std::fs::create_dir_all(dir.path().join("config")).unwrap();
+    std::fs::write(dir.path().join("config/wallpapers.lst"), "abc123\n").unwrap();
+    let fetcher = Arc::new(
+        MockFetcher::new()
+            .route("https://wallhaven.cc/api/v1/w/abc123", info_json("abc123"))
+            .route("https://mirror.example/full/abc123.png", png()),
+    );
+    let config = Config {
+        download_mirrors: vec!["https://mirror.example".to_string()],
+        ..dir.config()
+    };
+    let mut rust_paper = dir.rust_paper_with(config, fetcher.clone()).await.unwrap();
+
+    let report = rust_paper
+        .sync(None, &SyncOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(report.downloaded, 1);
+    assert!(dir.path().join("wallpapers/abc123.png").exists());
+    assert!(fetcher
+        .requests()
+        .contains(&"https://w.wallhaven.cc/full/abc123.png".to_string()));
+}
+
+#[tokio::test]
+async fn test_sync_keeps_original_bytes_and_rejects_corrupt_images() {
+    let mut corrupt = png();
+    corrupt.truncate(corrupt.len() / 2);
+    for (body, validate_decode, downloaded) in [
+        (png(), true, true),
+        (corrupt.clone(), true, false),
+        (corrupt.clone(), false, true),
+    ] {
+        let dir = TempDir::new("validate-decode").unwrap();
+        std::fs::create_dir_all(dir.path().join("config")).unwrap();
+        std::fs::write(dir.path().join("config/wallpapers.lst"), "abc123\n").unwrap();
+        let fetcher = Arc::new(
+            MockFetcher::new()
+                .route("https://wallhaven.cc/api/v1/w/abc123", info_json("abc123"))
+                .route("https://w.wallhaven.cc/full/abc123.png", body.clone()),
+        );
+        let config = Config {
+            validate_decode,
+            ..dir.config()
+        };
+        let mut rust_paper = dir.rust_paper_with(config, fetcher).await.unwrap();
+
+        let report = rust_paper
+            .sync(None, &SyncOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(report.downloaded, usize::from(downloaded));
+        let saved = std::fs::read(dir.path().join("wallpapers/abc123.png")).ok();
+        assert_eq!(saved, downloaded.then_some(body));
+    }
+}
+
+#[tokio::test]
+async fn test_large_downloads_use_parallel_ranges() {
+    let mut body = png();
+    body.resize(3 * 1024 * 1024, 7);
+    for ranges in [true, false] {
+        let dir = TempDir::new("chunked").unwrap();
+        std::fs::create_dir_all(dir.path().join("config")).unwrap();
+        std::fs::write(dir.path().join("config/wallpapers.lst"), "abc123\n").unwrap();
+        let fetcher = if ranges {
+            MockFetcher::new().with_ranges()
+        } else {
+            MockFetcher::new()
+        };
+        let fetcher = Arc::new(
+            fetcher
+                .route("https://wallhaven.cc/api/v1/w/abc123", info_json("abc123"))
+                .route("https://w.wallhaven.cc/full/abc123.png", body.clone()),
+        );
+        let config = Config {
+            validate_decode: false,
+            chunked_download_min_mb: 1,
+            download_chunks: 3,
+            ..dir.config()
+        };
+        let mut rust_paper = dir.rust_paper_with(config, fetcher.clone()).await.unwrap();
+
+        assert_eq!(
+            rust_paper
+                .sync(None, &SyncOptions::default())
+                .await
+                .unwrap()
+                .downloaded,
+            1
+        );
+        assert_eq!(
+            std::fs::read(dir.path().join("wallpapers/abc123.png")).unwrap(),
+            body
+        );
+        let range_requests = fetcher
+            .requests()
+            .iter()
+            .filter(|url| url.contains(" bytes="))
+            .count();
+        assert_eq!(range_requests, if ranges { 2 } else { 0 });
+    }
+}
+
+#[tokio::test]
+async fn test_sync_applies_animated_policy() {
+    let mut gif = Vec::new();
+    {
+        let mut encoder = image::codecs::gif::GifEncoder::new(&mut gif);
+        for red in [0, 255] {
+            let frame = image::RgbaImage::from_pixel(4, 4, image::Rgba([red, 0, 0, 255]));
+            encoder.encode_frame(image::Frame::new(frame)).unwrap();
+        }
+    }
+    for (policy, expected) in [
+        (AnimatedPolicy::Keep, Some("abc123.gif")),
+        (AnimatedPolicy::Still, Some("abc123.png")),
+        (AnimatedPolicy::Skip, None),
+    ] {
+        let dir = TempDir::new(&format!("animated-{:?}", policy)).unwrap();
+        std::fs::create_dir_all(dir.path().join("config")).unwrap();
+        std::fs::write(dir.path().join("config/wallpapers.lst"), "abc123\n").unwrap();
+        let fetcher = Arc::new(
+            MockFetcher::new()
+                .route("https://wallhaven.cc/api/v1/w/abc123", info_json("abc123"))
+                .route("https://w.wallhaven.cc/full/abc123.png", gif.clone()),
+        );
+        let config = Config {
+            animated: policy,
+            ..dir.config()
+        };
+        let mut rust_paper = dir.rust_paper_with(config, fetcher).await.unwrap();
+
+        let report = rust_paper
+            .sync(None, &SyncOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(report.failed, 0);
+        assert_eq!(report.downloaded, usize::from(expected.is_some()));
+        assert_eq!(report.skipped_animated, usize::from(expected.is_none()));
+        let files: Vec<String> = std::fs::read_dir(dir.path().join("wallpapers"))
+            .map(|entries| {
+                entries
+                    .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+                    .filter(|name| name != ".staging")
+                    .collect()
+            })
+            .unwrap_or_default();
+        assert_eq!(
+            files,
+            expected.map(str::to_string).into_iter().collect::<Vec<_>>()
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_sync_into_content_store() {
+    let dir = TempDir::new("store").unwrap();
+    std::fs::create_dir_all(dir.path().join("config")).unwrap();
+    std::fs::write(dir.path().join("config/wallpapers.lst"), "abc123\n").unwrap();
+    let fetcher = Arc::new(
+        MockFetcher::new()
+            .route("https://wallhaven.cc/api/v1/w/abc123", info_json("abc123"))
+            .route("https://w.wallhaven.cc/full/abc123.png", png()),
+    );
+    let config = Config {
+        save_location: dir.path().join("wallpapers").to_string_lossy().to_string(),
+        store_location: Some(dir.path().join("store").to_string_lossy().to_string()),
+        api_key: Some("test".to_string()),
+        retry_count: 1,
+        ..Config::default()
+    };
+    let mut rust_paper = dir.rust_paper_with(config, fetcher.clone()).await.unwrap();
+
+    rust_paper
+        .sync(None, &SyncOptions::default())
+        .await
+        .unwrap();
+    let stored: Vec<_> = std::fs::read_dir(dir.path().join("store"))
+        .unwrap()
+        .collect();
+    assert_eq!(stored.len(), 1);
+    assert_eq!(
+        std::fs::read(dir.path().join("wallpapers/abc123.png")).unwrap(),
+        png()
+    );
+
+    // The link into the store counts as verified, nothing is fetched again
+    let requests = fetcher.requests().len();
+    let report = rust_paper
+        .sync(None, &SyncOptions::default())
+        .await
+        .unwrap();
+    assert_eq!((report.downloaded, report.integrity_failures), (0, 0));
+    assert_eq!(fetcher.requests().len(), requests);
+}
+
+#[tokio::test]
+async fn test_sync_refuses_to_fill_the_disk() {
+    let dir = TempDir::new("space").unwrap();
+    std::fs::create_dir_all(dir.path().join("config")).unwrap();
+    std::fs::write(dir.path().join("config/wallpapers.lst"), "abc123\n").unwrap();
+    let fetcher = Arc::new(
+        MockFetcher::new()
+            .route("https://wallhaven.cc/api/v1/w/abc123", info_json("abc123"))
+            .route("https://w.wallhaven.cc/full/abc123.png", png()),
+    );
+    let config = Config {
+        // A petabyte, more than any test machine has free
+        min_free_space_mb: 1_000_000_000,
+        ..dir.config()
+    };
+    let mut rust_paper = dir.rust_paper_with(config, fetcher.clone()).await.unwrap();
+
+    assert!(matches!(
+        rust_paper.sync(None, &SyncOptions::default()).await,
+        Err(Error::InsufficientSpace { .. })
+    ));
+    // Only the metadata was fetched, which gave the size
+    assert_eq!(
+        fetcher.requests(),
+        ["https://wallhaven.cc/api/v1/w/abc123".to_string()]
+    );
+
+    let options = SyncOptions {
+        ignore_disk_space: true,
+        ..Default::default()
+    };
+    assert_eq!(rust_paper.sync(None, &options).await.unwrap().downloaded, 1);
+}
+
+#[tokio::test]
+async fn test_sync_rejects_downloads_failing_checks() {
+    let dir = TempDir::new("download-checks").unwrap();
+    std::fs::create_dir_all(dir.path().join("config")).unwrap();
+    std::fs::write(dir.path().join("config/wallpapers.lst"), "abc123\n").unwrap();
+    let fetcher = Arc::new(
+        MockFetcher::new()
+            .route("https://wallhaven.cc/api/v1/w/abc123", info_json("abc123"))
+            .route("https://w.wallhaven.cc/full/abc123.png", png()),
+    );
+    let mut config = Config { ..dir.config() };
+    // The fixture is fully transparent, which reads as black
+    config.download_checks.max_black_fraction = Some(0.9);
+    let mut rust_paper = dir.rust_paper_with(config, fetcher).await.unwrap();
+
+    let report = rust_paper
+        .sync(None, &SyncOptions::default())
+        .await
+        .unwrap();
+    assert_eq!((report.downloaded, report.rejected), (0, 1));
+    assert!(!report.has_failures());
+    assert!(!dir.path().join("wallpapers/abc123.png").exists());
+    let audit = std::fs::read_to_string(&rust_paper.audit_log_location).unwrap();
+    assert!(audit.contains("\"rejected\""));
+}
+
+#[tokio::test]
+async fn test_sync_with_events_reports_progress() {
+    let dir = TempDir::new("sync-events").unwrap();
+    std::fs::create_dir_all(dir.path().join("config")).unwrap();
+    std::fs::write(dir.path().join("config/wallpapers.lst"), "abc123\nmissin\n").unwrap();
+    let fetcher = Arc::new(
+        MockFetcher::new()
+            .route("https://wallhaven.cc/api/v1/w/abc123", info_json("abc123"))
+            .route("https://w.wallhaven.cc/full/abc123.png", png()),
+    );
+
+    let mut rust_paper = dir.rust_paper(fetcher).await.unwrap();
+    let events: Vec<SyncEvent> = rust_paper
+        .sync_with_events(Some("manual"), &SyncOptions::default())
+        .collect()
+        .await;
+
+    assert!(matches!(&events[0], SyncEvent::Started { source } if source == "manual"));
+    assert!(events
+        .iter()
+        .any(|e| matches!(e, SyncEvent::Downloaded { id, .. } if id == "abc123")));
+    assert!(events
+        .iter()
+        .any(|e| matches!(e, SyncEvent::DownloadFailed { id, .. } if id == "missin")));
+    assert!(matches!(
+        events.last(),
+        Some(SyncEvent::Completed(Ok(report))) if report.downloaded == 1 && report.failed == 1
+    ));
+}
+
+#[tokio::test]
+async fn test_sync_skips_downloads_when_unreachable() {
+    let dir = TempDir::new("sync-offline").unwrap();
+    std::fs::create_dir_all(dir.path().join("config")).unwrap();
+    std::fs::write(dir.path().join("config/wallpapers.lst"), "abc123\n").unwrap();
+    let fetcher = Arc::new(MockFetcher::new().offline());
+
+    let mut rust_paper = dir.rust_paper(fetcher.clone()).await.unwrap();
+    rust_paper
+        .sync(None, &SyncOptions::default())
+        .await
+        .unwrap();
+
+    assert!(!dir.path().join("wallpapers/abc123.png").exists());
+    assert!(fetcher.requests().is_empty());
+}
+
+#[tokio::test]
+async fn test_dry_run_fetches_metadata_only() {
+    let dir = TempDir::new("dry-run").unwrap();
+    std::fs::create_dir_all(dir.path().join("config")).unwrap();
+    std::fs::write(dir.path().join("config/wallpapers.lst"), "abc123\n").unwrap();
+    let ten_mb = r#"{"data":{"id":"abc123","resolution":"4x4","file_size":10485760,"path":"https://w.wallhaven.cc/full/abc123.png"}}"#;
+    let fetcher = Arc::new(
+        MockFetcher::new()
+            .route("https://wallhaven.cc/api/v1/w/abc123", ten_mb)
+            .route("https://w.wallhaven.cc/full/abc123.png", png()),
+    );
+    let image_requests = || {
+        fetcher
+            .requests()
+            .iter()
+            .filter(|url| url.starts_with("https://w.wallhaven.cc"))
+            .count()
+    };
+
+    let mut paper = dir.rust_paper(fetcher.clone()).await.unwrap();
+    let options = SyncOptions {
+        dry_run: true,
+        ..Default::default()
+    };
+    let report = paper.sync(None, &options).await.unwrap();
+    assert_eq!(report.downloaded, 0);
+    assert_eq!(fetcher.requests().len(), 1);
+    assert!(paper.last_sync().await.unwrap().is_none());
+    drop(paper);
+
+    // Filters see the metadata, so a wallpaper over the limit is never downloaded
+    let config = Config {
+        max_file_size_mb: Some(5),
+        ..dir.config()
+    };
+    let mut paper = dir.rust_paper_with(config, fetcher.clone()).await.unwrap();
+    let report = paper.sync(None, &SyncOptions::default()).await.unwrap();
+    assert_eq!(report.skipped_large, 1);
+    assert_eq!(image_requests(), 0);
+    assert!(!dir.path().join("wallpapers/abc123.png").exists());
+}
+
+#[tokio::test]
+async fn test_info_falls_back_to_cache() {
+    let dir = TempDir::new("info").unwrap();
+    let online = Arc::new(
+        MockFetcher::new()
+            .offline()
+            .route("https://wallhaven.cc/api/v1/w/abc123", info_json("abc123")),
+    );
+    dir.rust_paper(online)
+        .await
+        .unwrap()
+        .info("abc123")
+        .await
+        .unwrap();
+
+    let mut offline = dir
+        .rust_paper(Arc::new(MockFetcher::new().offline()))
+        .await
+        .unwrap();
+    offline.info("abc123").await.unwrap();
+    offline.offline = true;
+    offline.info("abc123").await.unwrap();
+    assert!(matches!(
+        offline.info("zzz999").await,
+        Err(Error::NotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_diff_compares_downloads_metadata_and_files() {
+    let dir = TempDir::new("diff").unwrap();
+    std::fs::create_dir_all(dir.path().join("wallpapers")).unwrap();
+    std::fs::write(dir.path().join("wallpapers/abc123.png"), png()).unwrap();
+    std::fs::write(dir.path().join("copy.png"), png()).unwrap();
+    let fetcher = Arc::new(
+        MockFetcher::new()
+            .offline()
+            .route("https://wallhaven.cc/api/v1/w/abc123", info_json("abc123"))
+            .route("https://wallhaven.cc/api/v1/w/def456", info_json("def456")),
+    );
+    let mut paper = dir.rust_paper(fetcher).await.unwrap();
+    let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+
+    paper.diff(&ids(&["abc123", "def456"]), &[]).await.unwrap();
+    paper
+        .diff(&ids(&["abc123"]), &[dir.path().join("copy.png")])
+        .await
+        .unwrap();
+    assert!(matches!(
+        paper.diff(&ids(&["abc123"]), &[]).await,
+        Err(Error::InvalidInput(_))
+    ));
+    paper.offline = true;
+    assert!(matches!(
+        paper.diff(&ids(&["abc123", "zzz999"]), &[]).await,
+        Err(Error::NotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_current_wallpaper_is_recorded_per_monitor() {
+    let dir = TempDir::new("current").unwrap();
+    let fetcher = Arc::new(
+        MockFetcher::new()
+            .route("https://wallhaven.cc/api/v1/w/abc123", info_json("abc123"))
+            .route("https://w.wallhaven.cc/full/abc123.png", png()),
+    );
+    let mut paper = dir.rust_paper(fetcher).await.unwrap();
+    assert!(matches!(paper.current_id().await, Err(Error::NotFound(_))));
+    paper
+        .add(
+            &mut vec!["abc123".to_string(), "def456".to_string()],
+            &AddOptions::default(),
+        )
+        .await
+        .unwrap();
+    paper.sync(None, &SyncOptions::default()).await.unwrap();
+
+    let file = dir.path().join("wallpapers/abc123.png");
+    paper
+        .set_current(file.to_str().unwrap(), None)
+        .await
+        .unwrap();
+    paper.set_current("def456", Some("DP-1")).await.unwrap();
+    assert!(paper.set_current("zzz999", None).await.is_err());
+
+    let all = paper
+        .current_wallpaper(Some("HDMI-1"))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(all.id, "abc123");
+    assert_eq!(all.path, Some(file.to_string_lossy().to_string()));
+    let dp1 = paper
+        .current_wallpaper(Some("DP-1"))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!((dp1.id.as_str(), dp1.path), ("def456", None));
+    paper.current(true).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_stale_wallpapers_are_archived() {
+    let dir = TempDir::new("stale").unwrap();
+    let fetcher = Arc::new(
+        MockFetcher::new()
+            .route("https://wallhaven.cc/api/v1/w/abc123", info_json("abc123"))
+            .route("https://wallhaven.cc/api/v1/w/def456", info_json("def456"))
+            .route("https://w.wallhaven.cc/full/abc123.png", png())
+            .route("https://w.wallhaven.cc/full/def456.png", png()),
+    );
+    let mut paper = dir.rust_paper(fetcher).await.unwrap();
+    paper
+        .add(
+            &mut vec!["abc123".to_string(), "def456".to_string()],
+            &AddOptions::default(),
+        )
+        .await
+        .unwrap();
+    paper.sync(None, &SyncOptions::default()).await.unwrap();
+    let long_ago = std::time::SystemTime::now() - Duration::from_secs(100 * 24 * 60 * 60);
+    for id in ["abc123", "def456"] {
+        std::fs::File::options()
+            .write(true)
+            .open(dir.path().join(format!("wallpapers/{}.png", id)))
+            .unwrap()
+            .set_modified(long_ago)
+            .unwrap();
+    }
+    // Downloaded long ago, but shown today
+    paper.set_current("abc123", None).await.unwrap();
+
+    let stale = paper
+        .stale_wallpapers(Duration::from_secs(90 * 24 * 60 * 60))
+        .await
+        .unwrap();
+    assert_eq!(stale.len(), 1);
+    assert_eq!((stale[0].id.as_str(), stale[0].last_set), ("def456", None));
+
+    paper
+        .stale(Some("90d"), StaleAction::Archive)
+        .await
+        .unwrap();
+    let groups = std::fs::read_to_string(dir.path().join("config/groups.json")).unwrap();
+    assert!(groups.contains("def456") && !groups.contains("abc123"));
+    assert!(paper
+        .stale_wallpapers(Duration::from_secs(90 * 24 * 60 * 60))
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn test_search_download() {
+    let dir = TempDir::new("search").unwrap();
+    let search = search_json(&["abc123"]);
+    let fetcher = Arc::new(
+        MockFetcher::new()
+            .offline()
+            .route("https://wallhaven.cc/api/v1/search", search)
+            .route("https://w.wallhaven.cc/full/abc123.png", png()),
+    );
+    let cli = Cli::parse_from(["rust-paper", "search", "-d", "-q", "mountains"]);
+
+    let rust_paper = dir.rust_paper(fetcher).await.unwrap();
+    let mut client = WallhavenClient::from_rust_paper(cli.command, rust_paper);
+    client.execute().await.unwrap();
+
+    assert!(dir.path().join("wallpapers/abc123.png").exists());
+}
+
+#[tokio::test]
+async fn test_search_preset_fills_in_options() {
+    let dir = TempDir::new("preset").unwrap();
+    let fetcher = Arc::new(MockFetcher::new().offline().route(
+        "https://wallhaven.cc/api/v1/search",
+        search_json(&["abc123"]),
+    ));
+    let cli = Cli::parse_from([
+        "rust-paper",
+        "search",
+        "--preset",
+        "cyberpunk",
+        "--sorting",
+        "RANDOM",
+    ]);
+
+    let rust_paper = dir.rust_paper(fetcher.clone()).await.unwrap();
+    let mut client = WallhavenClient::from_rust_paper(cli.command, rust_paper);
+    client.execute().await.unwrap();
+
+    assert_eq!(
+        fetcher.requests(),
+        ["https://wallhaven.cc/api/v1/search?q=cyberpunk&sorting=random&topRange=1y"]
+    );
+}
+
+#[tokio::test]
+async fn test_repeated_search_is_served_from_cache() {
+    let dir = TempDir::new("search-cache").unwrap();
+    let fetcher = Arc::new(MockFetcher::new().offline().route(
+        "https://wallhaven.cc/api/v1/search",
+        search_json(&["abc123"]),
+    ));
+    let paper = dir.rust_paper(fetcher.clone()).await.unwrap();
+    let search = |extra: &[&str]| {
+        let mut args = vec!["rust-paper", "search", "-q", "mountains"];
+        args.extend_from_slice(extra);
+        Cli::parse_from(args).command
+    };
+
+    let mut client = WallhavenClient::from_rust_paper(search(&[]), paper);
+    let first = client.execute().await.unwrap();
+    drop(client);
+    let paper = dir.rust_paper(fetcher.clone()).await.unwrap();
+    let mut client = WallhavenClient::from_rust_paper(search(&[]), paper);
+    assert_eq!(client.execute().await.unwrap(), first);
+    assert_eq!(fetcher.requests().len(), 1);
+    drop(client);
+
+    let paper = dir.rust_paper(fetcher.clone()).await.unwrap();
+    let mut client = WallhavenClient::from_rust_paper(search(&["--no-cache"]), paper);
+    client.execute().await.unwrap();
+    assert_eq!(fetcher.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn test_toplist_add() {
+    let dir = TempDir::new("toplist").unwrap();
+    let fetcher = Arc::new(MockFetcher::new().offline().route(
+        "https://wallhaven.cc/api/v1/search",
+        search_json(&["abc123"]),
+    ));
+    let cli = Cli::parse_from(["rust-paper", "toplist", "--range", "1w", "-n", "1", "--add"]);
+
+    let rust_paper = dir.rust_paper(fetcher.clone()).await.unwrap();
+    let mut client = WallhavenClient::from_rust_paper(cli.command, rust_paper);
+    client.execute().await.unwrap();
+
+    assert_eq!(
+        fetcher.requests(),
+        ["https://wallhaven.cc/api/v1/search?sorting=toplist&topRange=1w&page=1"]
+    );
+    let list = std::fs::read_to_string(dir.path().join("config/wallpapers.lst")).unwrap();
+    assert!(list.contains("abc123"));
+}
+
+#[tokio::test]
+async fn test_similar_skips_tracked_wallpapers() {
+    let dir = TempDir::new("similar").unwrap();
+    std::fs::create_dir_all(dir.path().join("config")).unwrap();
+    std::fs::write(dir.path().join("config/wallpapers.lst"), "abc123\n").unwrap();
+    let fetcher = Arc::new(MockFetcher::new().offline().route(
+        "https://wallhaven.cc/api/v1/search",
+        search_json(&["abc123"]),
+    ));
+    let cli = Cli::parse_from([
+        "rust-paper",
+        "similar",
+        "https://wallhaven.cc/w/xyz789",
+        "--add",
+    ]);
+
+    let rust_paper = dir.rust_paper(fetcher.clone()).await.unwrap();
+    let mut client = WallhavenClient::from_rust_paper(cli.command, rust_paper);
+    client.execute().await.unwrap();
+
+    assert_eq!(
+        fetcher.requests(),
+        ["https://wallhaven.cc/api/v1/search?q=like:xyz789&page=1"]
+    );
+    let list = std::fs::read_to_string(dir.path().join("config/wallpapers.lst")).unwrap();
+    assert_eq!(list.trim(), "abc123");
+}
+
+#[tokio::test]
+async fn test_sync_pulls_followed_uploads() {
+    let dir = TempDir::new("follow").unwrap();
+    let fetcher = Arc::new(
+        MockFetcher::new()
+            .route(
+                "https://wallhaven.cc/api/v1/search?q=@artist",
+                search_json(&["abc123"]),
+            )
+            .route("https://wallhaven.cc/api/v1/w/abc123", info_json("abc123"))
+            .route("https://w.wallhaven.cc/full/abc123.png", png()),
+    );
+
+    let mut rust_paper = dir.rust_paper(fetcher).await.unwrap();
+    rust_paper.follow("artist").await.unwrap();
+    rust_paper
+        .sync(Some("follows"), &SyncOptions::default())
+        .await
+        .unwrap();
+
+    assert_eq!(rust_paper.wallpapers, ["abc123".to_string()]);
+    assert!(dir.path().join("wallpapers/abc123.png").exists());
+}
+
+#[tokio::test]
+async fn test_removed_follow_uploads_are_not_added_again() {
+    let dir = TempDir::new("follow-seen").unwrap();
+    let fetcher = Arc::new(
+        MockFetcher::new()
+            .route(
+                "https://wallhaven.cc/api/v1/search?q=@artist",
+                search_json(&["abc123"]),
+            )
+            .route("https://wallhaven.cc/api/v1/w/abc123", info_json("abc123"))
+            .route("https://w.wallhaven.cc/full/abc123.png", png()),
+    );
+
+    let mut paper = dir.rust_paper(fetcher).await.unwrap();
+    paper.follow("artist").await.unwrap();
+    paper
+        .sync(Some("follows"), &SyncOptions::default())
+        .await
+        .unwrap();
+    paper.remove(&["abc123".to_string()]).await.unwrap();
+    // Unfollowing drops the uploads seen from the artist, but not seen.json
+    paper.unfollow("artist").await.unwrap();
+    paper.follow("artist").await.unwrap();
+    paper
+        .sync(Some("follows"), &SyncOptions::default())
+        .await
+        .unwrap();
+    assert!(paper.wallpapers.is_empty());
+
+    paper.seen_purge().await.unwrap();
+    paper
+        .sync(Some("follows"), &SyncOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(paper.wallpapers, ["abc123".to_string()]);
+}
+
+#[tokio::test]
+async fn test_followed_uploads_with_excluded_tags_are_skipped() {
+    let dir = TempDir::new("follow-exclude").unwrap();
+    let tagged = info_json("abc123").replace(r#""tags":[]"#, r#""tags":[{"id":1,"name":"Anime"}]"#);
+    let fetcher = Arc::new(
+        MockFetcher::new()
+            .route(
+                "https://wallhaven.cc/api/v1/search?q=@artist",
+                search_json(&["abc123"]),
+            )
+            .route("https://wallhaven.cc/api/v1/w/abc123", tagged),
+    );
+
+    let mut paper = dir.rust_paper(fetcher.clone()).await.unwrap();
+    paper.config.exclude_tags = vec!["anime".to_string()];
+    paper.follow("artist").await.unwrap();
+    paper
+        .sync(Some("follows"), &SyncOptions::default())
+        .await
+        .unwrap();
+
+    assert!(paper.wallpapers.is_empty());
+    assert!(fetcher
+        .requests()
+        .iter()
+        .any(|url| url.starts_with("https://wallhaven.cc/api/v1/search?q=@artist -anime&")));
+    assert!(!dir.path().join("wallpapers/abc123.png").exists());
+}
+
+#[tokio::test]
+async fn test_sync_follows_walks_pages() {
+    let dir = TempDir::new("follow-pages").unwrap();
+    let page = |id: &str, current: u32| {
+        search_json(&["abc123"]).replace("abc123", id).replace(
+            r#""current_page":1,"last_page":1"#,
+            &format!(r#""current_page":{},"last_page":3"#, current),
+        )
+    };
+    let search = "https://wallhaven.cc/api/v1/search?q=@artist&sorting=date_added&order=desc";
+    let fetcher = Arc::new(
+        MockFetcher::new()
+            .route(&format!("{}&page=1", search), page("abc123", 1))
+            .route(&format!("{}&page=2", search), page("def456", 2))
+            .route("https://wallhaven.cc/api/v1/w/abc123", info_json("abc123"))
+            .route("https://wallhaven.cc/api/v1/w/def456", info_json("def456"))
+            .route("https://w.wallhaven.cc/full/abc123.png", png())
+            .route("https://w.wallhaven.cc/full/def456.png", png()),
+    );
+
+    let mut rust_paper = dir.rust_paper(fetcher.clone()).await.unwrap();
+    rust_paper.config.follow.max_pages = Some(2);
+    rust_paper.follow("artist").await.unwrap();
+    rust_paper
+        .sync(Some("follows"), &SyncOptions::default())
+        .await
+        .unwrap();
+
+    assert_eq!(
+        rust_paper.wallpapers,
+        ["abc123".to_string(), "def456".to_string()]
+    );
+    assert!(!fetcher.requests().iter().any(|url| url.ends_with("page=3")));
+}
+
+#[tokio::test]
+async fn test_repair_rebuilds_corrupted_lock_file() {
+    let dir = TempDir::new("repair").unwrap();
+    std::fs::create_dir_all(dir.path().join("config")).unwrap();
+    std::fs::create_dir_all(dir.path().join("wallpapers")).unwrap();
+    std::fs::write(dir.path().join("config/wallpapers.lst"), "abc123\n").unwrap();
+    std::fs::write(dir.path().join("wallpapers/abc123.png"), png()).unwrap();
+    std::fs::write(dir.path().join("config/wallpaper.lock"), "{ not json").unwrap();
+
+    let rust_paper = dir
+        .rust_paper(Arc::new(MockFetcher::new().offline()))
+        .await
+        .unwrap();
+    rust_paper.repair().await.unwrap();
+
+    let lock = std::fs::read_to_string(dir.path().join("config/wallpaper.lock")).unwrap();
+    assert!(lock.contains("abc123"));
+    assert!(lock.contains("blake3"));
+}
+
+#[tokio::test]
+async fn test_add_refuses_disallowed_purity() {
+    let dir = TempDir::new("guard").unwrap();
+    let nsfw = r#"{"data":{"id":"nsf123","purity":"nsfw","category":"people","path":"https://w.wallhaven.cc/full/nsf123.png"}}"#;
+    let sfw = r#"{"data":{"id":"sfw123","purity":"sfw","category":"general","path":"https://w.wallhaven.cc/full/sfw123.png"}}"#;
+    let fetcher = Arc::new(
+        MockFetcher::new()
+            .offline()
+            .route("https://wallhaven.cc/api/v1/w/nsf123", nsfw)
+            .route("https://wallhaven.cc/api/v1/w/sfw123", sfw),
+    );
+    let config = Config {
+        save_location: dir.path().join("wallpapers").to_string_lossy().to_string(),
+        allowed_purity: Some(vec!["sfw".to_string()]),
+        retry_count: 1,
+        ..Config::default()
+    };
+    let mut rust_paper = dir.rust_paper_with(config, fetcher).await.unwrap();
+
+    let mut ids = vec!["nsf123".to_string(), "sfw123".to_string()];
+    rust_paper
+        .add(&mut ids, &AddOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(rust_paper.wallpapers, ["sfw123".to_string()]);
+
+    let options = AddOptions {
+        force: true,
+        ..Default::default()
+    };
+    rust_paper.add(&mut ids, &options).await.unwrap();
+    assert_eq!(rust_paper.wallpapers.len(), 2);
+}
+
+#[tokio::test]
+async fn test_add_verify_rejects_missing_ids() {
+    let dir = TempDir::new("verify").unwrap();
+    let fetcher = Arc::new(
+        MockFetcher::new()
+            .offline()
+            .route("https://wallhaven.cc/api/v1/w/abc123", info_json("abc123")),
+    );
+    let mut rust_paper = dir.rust_paper(fetcher).await.unwrap();
+    let options = AddOptions {
+        verify: true,
+        ..Default::default()
+    };
+
+    let mut ids = vec!["abc123".to_string(), "typo99".to_string()];
+    rust_paper.add(&mut ids, &options).await.unwrap();
+    assert_eq!(rust_paper.wallpapers, ["abc123".to_string()]);
+}
+
+#[tokio::test]
+async fn test_add_local_imports_images() {
+    let dir = TempDir::new("import").unwrap();
+    let old_walls = dir.path().join("old-walls");
+    std::fs::create_dir_all(&old_walls).unwrap();
+    std::fs::write(old_walls.join("sunset.png"), png()).unwrap();
+    std::fs::write(old_walls.join("notes.txt"), "not an image").unwrap();
+    let fetcher = Arc::new(MockFetcher::new().offline());
+
+    let mut rust_paper = dir.rust_paper(fetcher.clone()).await.unwrap();
+    rust_paper
+        .import_local(&old_walls, false, None)
+        .await
+        .unwrap();
+
+    assert_eq!(rust_paper.wallpapers.len(), 1);
+    let id = rust_paper.wallpapers[0].clone();
+    assert!(id.starts_with("local-"));
+    assert!(dir.path().join(format!("wallpapers/{}.png", id)).exists());
+    assert!(old_walls.join("sunset.png").exists());
+
+    // Importing again is a no-op, and sync leaves the local image alone
+    rust_paper
+        .import_local(&old_walls, false, None)
+        .await
+        .unwrap();
+    assert_eq!(rust_paper.wallpapers.len(), 1);
+    rust_paper
+        .sync(None, &SyncOptions::default())
+        .await
+        .unwrap();
+    assert!(fetcher.requests().is_empty());
+}
+
+#[tokio::test]
+async fn test_import_from_variety_keeps_downloaded_images() {
+    let dir = TempDir::new("migrate").unwrap();
+    let variety = dir.path().join("variety");
+    std::fs::create_dir_all(variety.join("Downloaded/wallhaven_city")).unwrap();
+    std::fs::write(
+        variety.join("Downloaded/wallhaven_city/wallhaven-abc123.png"),
+        png(),
+    )
+    .unwrap();
+    std::fs::write(
+        variety.join("history.txt"),
+        "/home/me/Pictures/beach.jpg\nhttps://wallhaven.cc/w/def456\n",
+    )
+    .unwrap();
+    let fetcher = Arc::new(MockFetcher::new().offline());
+
+    let mut rust_paper = dir.rust_paper(fetcher.clone()).await.unwrap();
+    rust_paper
+        .import_from(ImportTool::Variety, Some(&variety), None)
+        .await
+        .unwrap();
+
+    assert_eq!(rust_paper.wallpapers, ["abc123", "def456"]);
+    assert!(dir.path().join("wallpapers/abc123.png").exists());
+    assert!(!dir.path().join("wallpapers/def456.png").exists());
+    assert!(fetcher.requests().is_empty());
+}
+
+#[tokio::test]
+async fn test_sync_tombstones_wallpapers_gone_upstream() {
+    let dir = TempDir::new("tombstones").unwrap();
+    std::fs::create_dir_all(dir.path().join("config")).unwrap();
+    std::fs::write(dir.path().join("config/wallpapers.lst"), "abc123\ngone00\n").unwrap();
+    let fetcher = Arc::new(
+        MockFetcher::new()
+            .route("https://wallhaven.cc/api/v1/w/abc123", info_json("abc123"))
+            .route("https://w.wallhaven.cc/full/abc123.png", png()),
+    );
+
+    let mut rust_paper = dir.rust_paper(fetcher.clone()).await.unwrap();
+    let report = rust_paper
+        .sync(None, &SyncOptions::default())
+        .await
+        .unwrap();
+    assert_eq!((report.downloaded, report.failed), (1, 1));
+    assert!(report.has_failures());
+    assert!(
+        std::fs::read_to_string(dir.path().join("config/tombstones.json"))
+            .unwrap()
+            .contains("gone00")
+    );
+
+    // The next sync doesn't ask for it again
+    let requests = fetcher.requests().len();
+    assert!(!rust_paper
+        .sync(Some("manual"), &SyncOptions::default())
+        .await
+        .unwrap()
+        .has_failures());
+    assert_eq!(fetcher.requests().len(), requests);
+
+    let statuses = rust_paper.statuses().await.unwrap();
+    assert!(matches!(statuses[0].1, WallpaperStatus::Downloaded { .. }));
+    assert_eq!(
+        statuses[1],
+        ("gone00".to_string(), WallpaperStatus::GoneUpstream)
+    );
+
+    rust_paper
+        .clean(&CleanOptions {
+            tombstones: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(rust_paper.wallpapers, ["abc123".to_string()]);
+}
+
+#[tokio::test]
+async fn test_clean_leaves_non_images_and_subfolders_alone() {
+    let dir = TempDir::new("clean").unwrap();
+    std::fs::create_dir_all(dir.path().join("config")).unwrap();
+    std::fs::create_dir_all(dir.path().join("wallpapers/lockscreen")).unwrap();
+    std::fs::write(dir.path().join("config/wallpapers.lst"), "abc123\n").unwrap();
+    // No extension, but PNG contents
+    std::fs::write(dir.path().join("wallpapers/abc123"), png()).unwrap();
+    std::fs::write(dir.path().join("wallpapers/orphan.png"), png()).unwrap();
+    std::fs::write(dir.path().join("wallpapers/notes.txt"), "keep me").unwrap();
+    std::fs::write(dir.path().join("wallpapers/lockscreen/mine.png"), png()).unwrap();
+
+    let mut rust_paper = dir
+        .rust_paper(Arc::new(MockFetcher::new().offline()))
+        .await
+        .unwrap();
+    let statuses = rust_paper.statuses().await.unwrap();
+    assert!(matches!(statuses[0].1, WallpaperStatus::Downloaded { .. }));
+
+    rust_paper.clean(&CleanOptions::default()).await.unwrap();
+    assert!(!dir.path().join("wallpapers/orphan.png").exists());
+    assert!(dir.path().join("wallpapers/abc123").exists());
+    assert!(dir.path().join("wallpapers/notes.txt").exists());
+    assert!(dir.path().join("wallpapers/lockscreen/mine.png").exists());
+
+    // Subfolders only take part when asked to
+    rust_paper.config.scan_subfolders = true;
+    rust_paper.clean(&CleanOptions::default()).await.unwrap();
+    assert!(!dir.path().join("wallpapers/lockscreen/mine.png").exists());
+}
+
+#[tokio::test]
+async fn test_delete_removes_tracking_and_files() {
+    let dir = TempDir::new("delete").unwrap();
+    std::fs::create_dir_all(dir.path().join("config")).unwrap();
+    std::fs::create_dir_all(dir.path().join("wallpapers")).unwrap();
+    std::fs::write(dir.path().join("config/wallpapers.lst"), "abc123\ndef456\n").unwrap();
+    std::fs::write(dir.path().join("wallpapers/abc123.png"), png()).unwrap();
+    std::fs::write(dir.path().join("wallpapers/abc123.json"), "{}").unwrap();
+    std::fs::write(dir.path().join("wallpapers/def456.png"), png()).unwrap();
+    std::fs::write(dir.path().join("wallpapers/zzz999.png"), png()).unwrap();
+
+    let mut paper = dir
+        .rust_paper(Arc::new(MockFetcher::new().offline()))
+        .await
+        .unwrap();
+    // Not tracked, so its file is left alone
+    paper.delete(&["zzz999".to_string()], false).await.unwrap();
+    assert!(dir.path().join("wallpapers/zzz999.png").exists());
+    paper.delete(&["abc123".to_string()], false).await.unwrap();
+    assert_eq!(paper.wallpapers, ["def456".to_string()]);
+    assert!(!dir.path().join("wallpapers/abc123.png").exists());
+    assert!(!dir.path().join("wallpapers/abc123.json").exists());
+    let log = std::fs::read_to_string(dir.path().join("config/audit.jsonl")).unwrap();
+    assert!(log.contains(r#""event":"delete","id":"abc123""#));
+
+    paper.delete(&["def456".to_string()], true).await.unwrap();
+    assert!(paper.wallpapers.is_empty());
+    assert!(dir.path().join("wallpapers/def456.png").exists());
+}
+
+#[tokio::test]
+async fn test_clean_filters_by_age_and_pattern() {
+    let dir = TempDir::new("clean-filters").unwrap();
+    std::fs::create_dir_all(dir.path().join("config")).unwrap();
+    std::fs::create_dir_all(dir.path().join("wallpapers")).unwrap();
+    std::fs::write(dir.path().join("config/wallpapers.lst"), "").unwrap();
+    for name in ["old.png", "old.jpg", "new.png"] {
+        std::fs::write(dir.path().join("wallpapers").join(name), png()).unwrap();
+    }
+    let month_ago = std::time::SystemTime::now() - Duration::from_secs(31 * 24 * 60 * 60);
+    for name in ["old.png", "old.jpg"] {
+        std::fs::File::options()
+            .write(true)
+            .open(dir.path().join("wallpapers").join(name))
+            .unwrap()
+            .set_modified(month_ago)
+            .unwrap();
+    }
+
+    let mut rust_paper = dir
+        .rust_paper(Arc::new(MockFetcher::new().offline()))
+        .await
+        .unwrap();
+    rust_paper
+        .clean(&CleanOptions {
+            older_than: Some(Duration::from_secs(30 * 24 * 60 * 60)),
+            pattern: Some("*.png".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert!(!dir.path().join("wallpapers/old.png").exists());
+    assert!(dir.path().join("wallpapers/old.jpg").exists());
+    assert!(dir.path().join("wallpapers/new.png").exists());
+
+    let cli = Cli::parse_from(["rust-paper", "clean", "--older-than", "30d"]);
+    assert!(matches!(
+        cli.command,
+        Command::Clean {
+            older_than: Some(d),
+            ..
+        } if d == Duration::from_secs(30 * 24 * 60 * 60)
+    ));
+    assert!(Cli::try_parse_from(["rust-paper", "clean", "--older-than", "30"]).is_err());
+}
+
+#[tokio::test]
+async fn test_variants_per_id_are_regenerated_and_cleaned() {
+    let dir = TempDir::new("variants").unwrap();
+    std::fs::create_dir_all(dir.path().join("config")).unwrap();
+    std::fs::create_dir_all(dir.path().join("wallpapers/variants/gone00")).unwrap();
+    std::fs::write(dir.path().join("config/wallpapers.lst"), "abc123\n").unwrap();
+    std::fs::write(dir.path().join("wallpapers/abc123.png"), png()).unwrap();
+    std::fs::write(
+        dir.path().join("wallpapers/variants/gone00/lockscreen.png"),
+        png(),
+    )
+    .unwrap();
+    let config = Config {
+        save_location: dir.path().join("wallpapers").to_string_lossy().to_string(),
+        integrity: false,
+        scan_subfolders: true,
+        variant_layout: VariantLayout::ById,
+        lockscreen: Some(Default::default()),
+        ..Config::default()
+    };
+    let mut rust_paper = dir
+        .rust_paper_with(config, Arc::new(MockFetcher::new().offline()))
+        .await
+        .unwrap();
+
+    // The variant folder never counts as downloaded originals
+    assert_eq!(rust_paper.statuses().await.unwrap().len(), 1);
+    rust_paper
+        .sync(None, &SyncOptions::default())
+        .await
+        .unwrap();
+    assert!(dir
+        .path()
+        .join("wallpapers/variants/abc123/lockscreen.png")
+        .exists());
+
+    rust_paper.clean(&CleanOptions::default()).await.unwrap();
+    assert!(dir.path().join("wallpapers/abc123.png").exists());
+    assert!(dir
+        .path()
+        .join("wallpapers/variants/abc123/lockscreen.png")
+        .exists());
+    assert!(!dir.path().join("wallpapers/variants/gone00").exists());
+}
+
+#[tokio::test]
+async fn test_serve_api() {
+    let dir = TempDir::new("serve").unwrap();
+    std::fs::create_dir_all(dir.path().join("config")).unwrap();
+    std::fs::write(dir.path().join("config/wallpapers.lst"), "").unwrap();
+    let fetcher = MockFetcher::new()
+        .offline()
+        .route("https://wallhaven.cc/api/v1/w/abc123", info_json("abc123"));
+    let mut rust_paper = dir.rust_paper(Arc::new(fetcher)).await.unwrap();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let client = async {
+        let client = reqwest::Client::new();
+        let anonymous = client.get(format!("{}/wallpapers", base)).send();
+        assert_eq!(anonymous.await.unwrap().status(), 401);
+
+        let added: serde_json::Value = client
+            .post(format!("{}/wallpapers", base))
+            .bearer_auth("secret")
+            .body(r#"{"ids": ["abc123"]}"#)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(added["wallpapers"], serde_json::json!(["abc123"]));
+
+        let listed: serde_json::Value = client
+            .get(format!("{}/wallpapers", base))
+            .bearer_auth("secret")
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(listed["wallpapers"][0]["status"], "not_downloaded");
+
+        let set = client
+            .post(format!("{}/set", base))
+            .bearer_auth("secret")
+            .body(r#"{"id": "abc123"}"#)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(set.status(), 501);
+        let random = client
+            .post(format!("{}/current/random", base))
+            .bearer_auth("secret")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(random.status(), 404);
+
+        std::fs::create_dir_all(dir.path().join("wallpapers")).unwrap();
+        std::fs::write(dir.path().join("wallpapers/abc123.png"), png()).unwrap();
+        // Only IDs are taken over HTTP, never a path on the server
+        let by_path = client
+            .post(format!("{}/current", base))
+            .bearer_auth("secret")
+            .json(&serde_json::json!({ "id": dir.path().join("wallpapers/abc123.png") }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(by_path.status(), 400);
+        let set: serde_json::Value = client
+            .post(format!("{}/current", base))
+            .bearer_auth("secret")
+            .body(r#"{"id": "abc123", "monitor": "DP-1"}"#)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(set["id"], "abc123");
+        let random: serde_json::Value = client
+            .post(format!("{}/current/random", base))
+            .bearer_auth("secret")
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(random["id"], "abc123");
+
+        let removed = client
+            .delete(format!("{}/wallpapers/abc123", base))
+            .bearer_auth("secret")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(removed.status(), 200);
+    };
+    tokio::select! {
+        result = rust_paper.serve_on(listener, "secret") => panic!("server stopped: {:?}", result.err()),
+        () = client => {}
+    }
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("config/wallpapers.lst")).unwrap(),
+        ""
+    );
+}
+
+#[tokio::test]
+async fn test_serve_is_not_held_up_by_slow_or_oversized_requests() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let dir = TempDir::new("serve-slow").unwrap();
+    let mut rust_paper = dir
+        .rust_paper(Arc::new(MockFetcher::new().offline()))
+        .await
+        .unwrap();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let client = async {
+        // Connected but silent
+        let _idle = tokio::net::TcpStream::connect(addr).await.unwrap();
+
+        let listed = tokio::time::timeout(
+            Duration::from_secs(5),
+            reqwest::Client::new()
+                .get(format!("http://{}/wallpapers", addr))
+                .bearer_auth("secret")
+                .send(),
+        )
+        .await
+        .expect("answered while another client is idle")
+        .unwrap();
+        assert_eq!(listed.status(), 200);
+
+        let mut flood = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let header = format!("X-Filler: {}\r\n", "a".repeat(1024));
+        let request = format!("GET /wallpapers HTTP/1.1\r\n{}\r\n", header.repeat(32));
+        flood.write_all(request.as_bytes()).await.unwrap();
+        // The server stops reading, so its close may reset the connection
+        // before the 400 arrives
+        let mut answer = String::new();
+        match flood.read_to_string(&mut answer).await {
+            Ok(_) => assert!(answer.starts_with("HTTP/1.1 400 "), "{}", answer),
+            Err(e) => assert_eq!(e.kind(), std::io::ErrorKind::ConnectionReset),
+        }
+    };
+    tokio::select! {
+        result = rust_paper.serve_on(listener, "secret") => panic!("server stopped: {:?}", result.err()),
+        () = client => {}
+    }
+}
+
+#[tokio::test]
+async fn test_serve_only_locks_while_changing() {
+    let dir = TempDir::new("serve-lock").unwrap();
+    let fetcher = Arc::new(MockFetcher::new().offline());
+    let mut server = RustPaper::builder()
+        .config(dir.config())
+        .config_folder(dir.path().join("config"))
+        .fetcher(fetcher.clone())
+        .lock_per_operation(true)
+        .build()
+        .await
+        .unwrap();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let client = async {
+        // Other commands still run while the server is up
+        let mut other = dir.rust_paper(fetcher.clone()).await.unwrap();
+        other
+            .add(&mut vec!["abc123".to_string()], &AddOptions::default())
+            .await
+            .unwrap();
+
+        let client = reqwest::Client::new();
+        let busy = client
+            .post(format!("{}/sync", base))
+            .bearer_auth("secret")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(busy.status(), 409);
+
+        drop(other);
+        let added: serde_json::Value = client
+            .post(format!("{}/wallpapers", base))
+            .bearer_auth("secret")
+            .body(r#"{"ids": ["def456"]}"#)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        // The other run's change was picked up before this one
+        assert_eq!(added["wallpapers"], serde_json::json!(["abc123", "def456"]));
+    };
+    tokio::select! {
+        result = server.serve_on(listener, "secret") => panic!("server stopped: {:?}", result.err()),
+        () = client => {}
+    }
+}
+
+#[tokio::test]
+async fn test_audit_purity_moves_disallowed_wallpapers() {
+    let dir = TempDir::new("audit-purity").unwrap();
+    std::fs::create_dir_all(dir.path().join("config/cache")).unwrap();
+    std::fs::create_dir_all(dir.path().join("wallpapers")).unwrap();
+    std::fs::write(dir.path().join("config/wallpapers.lst"), "abc123\ndef456\n").unwrap();
+    for (id, purity) in [("abc123", "sfw"), ("def456", "sketchy")] {
+        std::fs::write(dir.path().join(format!("wallpapers/{}.png", id)), png()).unwrap();
+        std::fs::write(
+            dir.path().join(format!("config/cache/{}.json", id)),
+            format!(r#"{{"data":{{"id":"{id}","purity":"{purity}","category":"general"}}}}"#),
+        )
+        .unwrap();
+    }
+    let config = Config {
+        save_location: dir.path().join("wallpapers").to_string_lossy().to_string(),
+        allowed_purity: Some(vec!["sfw".to_string()]),
+        ..Config::default()
+    };
+    let mut rust_paper = dir
+        .rust_paper_with(config, Arc::new(MockFetcher::new().offline()))
+        .await
+        .unwrap();
+
+    let violations = rust_paper.purity_violations(false).await.unwrap();
+    assert_eq!(violations.len(), 1);
+    assert_eq!(violations[0].id, "def456");
+
+    rust_paper
+        .audit_purity(false, Some(&dir.path().join("quarantine")))
+        .await
+        .unwrap();
+    assert!(dir.path().join("quarantine/def456.png").exists());
+    assert!(!dir.path().join("wallpapers/def456.png").exists());
+    assert!(dir.path().join("wallpapers/abc123.png").exists());
+    assert_eq!(rust_paper.wallpapers, ["abc123".to_string()]);
+}
+
+#[tokio::test]
+async fn test_backup_round_trip() {
+    let dir = TempDir::new("backup").unwrap();
+    std::fs::create_dir_all(dir.path().join("config/cache")).unwrap();
+    std::fs::write(dir.path().join("config/wallpapers.lst"), "abc123\n").unwrap();
+    std::fs::write(dir.path().join("config/groups.json"), r#"{"groups":{}}"#).unwrap();
+    std::fs::write(
+        dir.path().join("config/cache/abc123.json"),
+        info_json("abc123"),
+    )
+    .unwrap();
+
+    let mut rust_paper = dir
+        .rust_paper(Arc::new(MockFetcher::new().offline()))
+        .await
+        .unwrap();
+    let backup = dir.path().join("state.backup");
+    rust_paper.backup_create(&backup).await.unwrap();
+
+    std::fs::remove_file(dir.path().join("config/wallpapers.lst")).unwrap();
+    std::fs::remove_dir_all(dir.path().join("config/cache")).unwrap();
+    rust_paper.wallpapers.clear();
+    rust_paper.backup_restore(&backup).await.unwrap();
+
+    assert_eq!(rust_paper.wallpapers, ["abc123".to_string()]);
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("config/cache/abc123.json")).unwrap(),
+        info_json("abc123")
+    );
+}
+
+#[tokio::test]
+async fn test_sync_records_last_sync_summary() {
+    let dir = TempDir::new("last-sync").unwrap();
+    std::fs::create_dir_all(dir.path().join("config")).unwrap();
+    std::fs::write(dir.path().join("config/wallpapers.lst"), "abc123\nbroken\n").unwrap();
+    let fetcher = Arc::new(
+        MockFetcher::new()
+            .route("https://wallhaven.cc/api/v1/w/abc123", info_json("abc123"))
+            .route("https://wallhaven.cc/api/v1/w/broken", "not json")
+            .route("https://w.wallhaven.cc/full/abc123.png", png()),
+    );
+
+    let mut rust_paper = dir.rust_paper(fetcher).await.unwrap();
+    assert!(rust_paper.last_sync().await.unwrap().is_none());
+    rust_paper
+        .sync(None, &SyncOptions::default())
+        .await
+        .unwrap();
+
+    let last_sync = rust_paper.last_sync().await.unwrap().unwrap();
+    assert_eq!(last_sync.scope, "all");
+    assert!(last_sync.has_failures());
+    let report = last_sync.report.unwrap();
+    assert_eq!(report.downloaded, 1);
+    assert_eq!(report.bytes_downloaded, png().len() as u64);
+    assert_eq!(report.failures.len(), 1);
+    assert_eq!(report.failures[0].id, "broken");
+    assert!(dir.path().join("config/last_sync.json").exists());
+}
+
+#[tokio::test]
+async fn test_sync_ids_only_touches_the_given_wallpapers() {
+    let dir = TempDir::new("sync-ids").unwrap();
+    std::fs::create_dir_all(dir.path().join("config")).unwrap();
+    std::fs::write(dir.path().join("config/wallpapers.lst"), "abc123\ndef456\n").unwrap();
+    let fetcher = Arc::new(
+        MockFetcher::new()
+            .route("https://wallhaven.cc/api/v1/w/abc123", info_json("abc123"))
+            .route("https://w.wallhaven.cc/full/abc123.png", png())
+            .route("https://wallhaven.cc/api/v1/w/def456", info_json("def456"))
+            .route("https://w.wallhaven.cc/full/def456.png", png()),
+    );
+    let paper = dir.rust_paper(fetcher.clone()).await.unwrap();
+
+    let report = paper
+        .sync_ids(
+            &["https://wallhaven.cc/w/def456".to_string()],
+            &SyncOptions::default(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(report.downloaded, 1);
+    assert_eq!(report.downloads.len(), 1);
+    assert_eq!(report.downloads[0].id, "def456");
+    assert_eq!(report.downloads[0].bytes, png().len() as u64);
+    assert!(dir.path().join("wallpapers/def456.png").exists());
+    assert!(!dir.path().join("wallpapers/abc123.png").exists());
+    assert!(fetcher.requests().iter().all(|url| !url.contains("abc123")));
+
+    assert!(matches!(
+        paper
+            .sync_ids(&["zzz999".to_string()], &SyncOptions::default())
+            .await,
+        Err(Error::InvalidInput(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_sync_only_verifies_archived_groups() {
+    let dir = TempDir::new("archive").unwrap();
+    std::fs::create_dir_all(dir.path().join("config")).unwrap();
+    std::fs::write(dir.path().join("config/wallpapers.lst"), "abc123\ndef456\n").unwrap();
+    let fetcher = Arc::new(
+        MockFetcher::new()
+            .route("https://wallhaven.cc/api/v1/w/abc123", info_json("abc123"))
+            .route("https://w.wallhaven.cc/full/abc123.png", png())
+            .route("https://wallhaven.cc/api/v1/w/def456", info_json("def456"))
+            .route("https://w.wallhaven.cc/full/def456.png", png()),
+    );
+    let mut rust_paper = dir.rust_paper(fetcher.clone()).await.unwrap();
+    rust_paper
+        .sync(None, &SyncOptions::default())
+        .await
+        .unwrap();
+    rust_paper.create_group("done").await.unwrap();
+    let options = AddOptions {
+        group: Some("done".to_string()),
+        ..Default::default()
+    };
+    rust_paper
+        .add(
+            &mut vec!["abc123".to_string(), "def456".to_string()],
+            &options,
+        )
+        .await
+        .unwrap();
+    rust_paper.set_group_archived("done", true).await.unwrap();
+    assert!(rust_paper
+        .add(&mut vec!["abc123".to_string()], &options)
+        .await
+        .is_err());
+
+    std::fs::remove_file(dir.path().join("wallpapers/abc123.png")).unwrap();
+    std::fs::write(dir.path().join("wallpapers/def456.png"), b"tampered").unwrap();
+    let requests = fetcher.requests().len();
+    let report = rust_paper
+        .sync_group("done", &SyncOptions::default())
+        .await
+        .unwrap();
+
+    assert_eq!(fetcher.requests().len(), requests);
+    assert!(!dir.path().join("wallpapers/abc123.png").exists());
+    assert_eq!(
+        std::fs::read(dir.path().join("wallpapers/def456.png")).unwrap(),
+        b"tampered"
+    );
+    let mut drifted: Vec<&str> = report.archive_drift.iter().map(|d| d.id.as_str()).collect();
+    drifted.sort_unstable();
+    assert_eq!(drifted, ["abc123", "def456"]);
+    assert!(report.has_failures());
+
+    rust_paper.set_group_archived("done", false).await.unwrap();
+    let report = rust_paper
+        .sync_group("done", &SyncOptions::default())
+        .await
+        .unwrap();
+    assert!(report.archive_drift.is_empty());
+    assert!(dir.path().join("wallpapers/abc123.png").exists());
+}
+
+#[tokio::test]
+async fn test_clean_keeps_files_the_lock_file_tracks() {
+    let dir = TempDir::new("clean-lock").unwrap();
+    std::fs::create_dir_all(dir.path().join("config")).unwrap();
+    std::fs::write(dir.path().join("config/wallpapers.lst"), "abc123\n").unwrap();
+    let fetcher = Arc::new(
+        MockFetcher::new()
+            .route("https://wallhaven.cc/api/v1/w/abc123", info_json("abc123"))
+            .route("https://w.wallhaven.cc/full/abc123.png", png()),
+    );
+    let mut rust_paper = dir.rust_paper(fetcher).await.unwrap();
+    rust_paper
+        .sync(None, &SyncOptions::default())
+        .await
+        .unwrap();
+
+    // A renamed copy of a tracked wallpaper waits for repair; the others are orphans
+    std::fs::rename(
+        dir.path().join("wallpapers/abc123.png"),
+        dir.path().join("wallpapers/renamed.png"),
+    )
+    .unwrap();
+    let mut other = image::RgbaImage::new(8, 8);
+    other.put_pixel(0, 0, image::Rgba([255, 0, 0, 255]));
+    other
+        .save(dir.path().join("wallpapers/orphan1.png"))
+        .unwrap();
+    other
+        .save(dir.path().join("wallpapers/orphan2.png"))
+        .unwrap();
+
+    rust_paper.clean(&CleanOptions::default()).await.unwrap();
+    assert!(dir.path().join("wallpapers/renamed.png").exists());
+    assert!(!dir.path().join("wallpapers/orphan1.png").exists());
+    assert!(!dir.path().join("wallpapers/orphan2.png").exists());
+    let audit = std::fs::read_to_string(dir.path().join("config/audit.jsonl")).unwrap();
+    assert!(audit.contains("orphan1") && audit.contains("orphan2"));
+    assert!(!audit.contains("renamed"));
+}
+
+#[tokio::test]
+async fn test_credit_uses_cached_uploader() {
+    let dir = TempDir::new("credit").unwrap();
+    std::fs::create_dir_all(dir.path().join("config/cache")).unwrap();
+    std::fs::write(dir.path().join("config/wallpapers.lst"), "abc123\n").unwrap();
+    std::fs::write(
+        dir.path().join("config/cache/abc123.json"),
+        r#"{"data":{"id":"abc123","short_url":"https://whvn.cc/abc123","source":"https://example.com/art","uploader":{"username":"artist"}}}"#,
+    )
+    .unwrap();
+
+    let mut rust_paper = dir
+        .rust_paper(Arc::new(MockFetcher::new().offline()))
+        .await
+        .unwrap();
+    rust_paper.offline = true;
+    assert_eq!(
+        rust_paper.attribution("abc123").await.unwrap(),
+        "Wallpaper by artist on Wallhaven: https://whvn.cc/abc123\nOriginal source: https://example.com/art"
+    );
+    assert!(matches!(
+        rust_paper.attribution("current").await,
+        Err(Error::InvalidInput(_))
+    ));
+    assert!(matches!(
+        rust_paper.attribution("zzz999").await,
+        Err(Error::NotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_watch_syncs_when_the_list_changes() {
+    let dir = TempDir::new("watch").unwrap();
+    std::fs::create_dir_all(dir.path().join("config")).unwrap();
+    std::fs::write(dir.path().join("config/wallpapers.lst"), "").unwrap();
+    let fetcher = Arc::new(
+        MockFetcher::new()
+            .route("https://wallhaven.cc/api/v1/w/abc123", info_json("abc123"))
+            .route("https://w.wallhaven.cc/full/abc123.png", png()),
+    );
+    let mut rust_paper = dir.rust_paper(fetcher).await.unwrap();
+
+    let downloaded = dir.path().join("wallpapers/abc123.png");
+    tokio::select! {
+        result = rust_paper.watch_with(Duration::from_millis(10), Duration::from_millis(50)) => {
+            panic!("watch stopped: {:?}", result)
+        }
+        _ = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            std::fs::write(dir.path().join("config/wallpapers.lst"), "abc123\n").unwrap();
+            while !downloaded.exists() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        } => {}
+    }
+    assert_eq!(rust_paper.wallpapers, ["abc123".to_string()]);
+}
+
+#[tokio::test]
+async fn test_watch_keeps_the_config_when_a_reload_is_invalid() {
+    let dir = TempDir::new("watch-config").unwrap();
+    std::fs::create_dir_all(dir.path().join("config")).unwrap();
+    std::fs::write(dir.path().join("config/wallpapers.lst"), "").unwrap();
+    let config_file = dir.path().join("config.toml");
+    std::fs::write(&config_file, "retry_count = 1\n").unwrap();
+    let fetcher = Arc::new(
+        MockFetcher::new()
+            .route("https://wallhaven.cc/api/v1/w/abc123", info_json("abc123"))
+            .route("https://w.wallhaven.cc/full/abc123.png", png()),
+    );
+    let mut rust_paper = dir.rust_paper(fetcher).await.unwrap();
+    rust_paper.config_file = Some(config_file.clone());
+
+    let downloaded = dir.path().join("wallpapers/abc123.png");
+    tokio::select! {
+        result = rust_paper.watch_with(Duration::from_millis(10), Duration::from_millis(50)) => {
+            panic!("watch stopped: {:?}", result)
+        }
+        _ = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            std::fs::write(&config_file, "retry_count = 0\n").unwrap();
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            std::fs::write(dir.path().join("config/wallpapers.lst"), "abc123\n").unwrap();
+            while !downloaded.exists() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        } => {}
+    }
+    assert_eq!(rust_paper.config.retry_count, 1);
+}
+
+#[tokio::test]
+async fn test_list_file_keeps_comments_and_annotations() {
+    let dir = TempDir::new("list-format").unwrap();
+    std::fs::create_dir_all(dir.path().join("config")).unwrap();
+    let list = "# Favourites\nabc123 tags=dark  # skyline\n\n# Anime\nfgh456 group=anime\n";
+    std::fs::write(dir.path().join("config/wallpapers.lst"), list).unwrap();
+
+    let mut rust_paper = dir
+        .rust_paper(Arc::new(MockFetcher::new().offline()))
+        .await
+        .unwrap();
+    assert_eq!(rust_paper.wallpapers.len(), 2);
+    rust_paper
+        .add(&mut vec!["zzz999".to_string()], &AddOptions::default())
+        .await
+        .unwrap();
+    rust_paper.remove(&["fgh456".to_string()]).await.unwrap();
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("config/wallpapers.lst")).unwrap(),
+        "# Favourites\nabc123 tags=dark  # skyline\n\n# Anime\nzzz999\n"
+    );
+
+    // Nothing changed, so the file is left alone
+    let modified = std::fs::metadata(dir.path().join("config/wallpapers.lst"))
+        .unwrap()
+        .modified()
+        .unwrap();
+    std::thread::sleep(Duration::from_millis(20));
+    rust_paper
+        .add(&mut vec!["abc123".to_string()], &AddOptions::default())
+        .await
+        .unwrap();
+    let after = std::fs::metadata(dir.path().join("config/wallpapers.lst"))
+        .unwrap()
+        .modified()
+        .unwrap();
+    assert_eq!(modified, after);
+}
+
+#[tokio::test]
+async fn test_pinned_hash_rejects_replaced_upstream_file() {
+    let dir = TempDir::new("pin-hash").unwrap();
+    std::fs::create_dir_all(dir.path().join("config")).unwrap();
+    std::fs::write(dir.path().join("config/wallpapers.lst"), "").unwrap();
+    let fetcher = Arc::new(
+        MockFetcher::new()
+            .route("https://wallhaven.cc/api/v1/w/abc123", info_json("abc123"))
+            .route("https://w.wallhaven.cc/full/abc123.png", png()),
+    );
+    let mut paper = dir.rust_paper(fetcher).await.unwrap();
+    let options = AddOptions {
+        pin_hash: true,
+        ..Default::default()
+    };
+    paper
+        .add(&mut vec!["abc123".to_string()], &options)
+        .await
+        .unwrap();
+    assert!(dir.path().join("config/pins.json").exists());
+    drop(paper);
+
+    let mut replaced = std::io::Cursor::new(Vec::new());
+    image::RgbaImage::from_pixel(4, 4, image::Rgba([255, 0, 0, 255]))
+        .write_to(&mut replaced, image::ImageFormat::Png)
+        .unwrap();
+    let fetcher = Arc::new(
+        MockFetcher::new()
+            .route("https://wallhaven.cc/api/v1/w/abc123", info_json("abc123"))
+            .route(
+                "https://w.wallhaven.cc/full/abc123.png",
+                replaced.into_inner(),
+            ),
+    );
+    let mut paper = dir.rust_paper(fetcher).await.unwrap();
+    let report = paper.sync(None, &SyncOptions::default()).await.unwrap();
+    assert_eq!(report.downloaded, 0);
+    assert_eq!(report.failed, 1);
+    assert!(!dir.path().join("wallpapers/abc123.png").exists());
+}
+
+#[tokio::test]
+async fn test_sync_downloads_configured_thumbnail_size() {
+    let dir = TempDir::new("download-quality").unwrap();
+    std::fs::create_dir_all(dir.path().join("config")).unwrap();
+    std::fs::write(dir.path().join("config/wallpapers.lst"), "abc123\n").unwrap();
+    let fetcher = Arc::new(
+        MockFetcher::new()
+            .route("https://wallhaven.cc/api/v1/w/abc123", info_json("abc123"))
+            .route("https://th.wallhaven.cc/lg/ab/abc123.jpg", png()),
+    );
+    let config = Config {
+        download_quality: DownloadQuality::Large,
+        ..dir.config()
+    };
+    let mut rust_paper = dir.rust_paper_with(config, fetcher.clone()).await.unwrap();
+
+    let report = rust_paper
+        .sync(None, &SyncOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(report.downloaded, 1);
+    assert!(!fetcher.requests().iter().any(|url| url.contains("/full/")));
+    assert!(std::fs::read_dir(dir.path().join("wallpapers"))
+        .unwrap()
+        .any(|entry| entry
+            .unwrap()
+            .file_name()
+            .to_string_lossy()
+            .starts_with("abc123")));
+}
+
+#[tokio::test]
+async fn test_pinned_wallpapers_download_as_thumbnails() {
+    let dir = TempDir::new("pin-thumbnail").unwrap();
+    std::fs::create_dir_all(dir.path().join("config")).unwrap();
+    std::fs::write(dir.path().join("config/wallpapers.lst"), "").unwrap();
+    let mut thumbnail = std::io::Cursor::new(Vec::new());
+    image::RgbaImage::from_pixel(2, 2, image::Rgba([0, 0, 255, 255]))
+        .write_to(&mut thumbnail, image::ImageFormat::Png)
+        .unwrap();
+    let fetcher = Arc::new(
+        MockFetcher::new()
+            .route("https://wallhaven.cc/api/v1/w/abc123", info_json("abc123"))
+            .route("https://w.wallhaven.cc/full/abc123.png", png())
+            .route(
+                "https://th.wallhaven.cc/lg/ab/abc123.jpg",
+                thumbnail.into_inner(),
+            ),
+    );
+    let config = Config {
+        download_quality: DownloadQuality::Large,
+        ..dir.config()
+    };
+    let mut rust_paper = dir.rust_paper_with(config, fetcher).await.unwrap();
+    let options = AddOptions {
+        pin_hash: true,
+        ..Default::default()
+    };
+    rust_paper
+        .add(&mut vec!["abc123".to_string()], &options)
+        .await
+        .unwrap();
+
+    // The pin covers the original, so the thumbnail isn't held against it
+    let report = rust_paper
+        .sync(None, &SyncOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(report.downloaded, 1);
+    assert_eq!(report.failed, 0);
+}
+
+#[tokio::test]
+async fn test_integrity_failure_quarantines_the_corrupt_file() {
+    let dir = TempDir::new("integrity-quarantine").unwrap();
+    std::fs::create_dir_all(dir.path().join("config")).unwrap();
+    std::fs::write(dir.path().join("config/wallpapers.lst"), "abc123\n").unwrap();
+    let fetcher = Arc::new(
+        MockFetcher::new()
+            .route("https://wallhaven.cc/api/v1/w/abc123", info_json("abc123"))
+            .route("https://w.wallhaven.cc/full/abc123.png", png()),
+    );
+    let mut rust_paper = dir.rust_paper(fetcher).await.unwrap();
+    rust_paper
+        .sync(None, &SyncOptions::default())
+        .await
+        .unwrap();
+    std::fs::write(dir.path().join("wallpapers/abc123.png"), b"bit rot").unwrap();
+
+    let report = rust_paper
+        .sync(None, &SyncOptions::default())
+        .await
+        .unwrap();
+    assert_eq!((report.downloaded, report.integrity_failures), (1, 1));
+    assert_eq!(
+        std::fs::read(dir.path().join("wallpapers/abc123.png")).unwrap(),
+        png()
+    );
+    let quarantined = rust_paper.quarantined().await.unwrap();
+    assert_eq!(quarantined.len(), 1);
+    assert_eq!(std::fs::read(&quarantined[0].path).unwrap(), b"bit rot");
+    let audit = std::fs::read_to_string(dir.path().join("config/audit.jsonl")).unwrap();
+    assert!(audit.contains("quarantined"));
+
+    rust_paper.quarantine_purge().await.unwrap();
+    assert!(rust_paper.quarantined().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_sync_mirrors_downloads_to_storage() {
+    let dir = TempDir::new("storage-mirror").unwrap();
+    std::fs::create_dir_all(dir.path().join("config")).unwrap();
+    std::fs::write(dir.path().join("config/wallpapers.lst"), "abc123\n").unwrap();
+    let fetcher = Arc::new(
+        MockFetcher::new()
+            .route("https://wallhaven.cc/api/v1/w/abc123", info_json("abc123"))
+            .route("https://w.wallhaven.cc/full/abc123.png", png()),
+    );
+    let mirror = dir.path().join("nas");
+    let config = Config {
+        storage_mirrors: vec![StorageConfig::Local {
+            path: mirror.to_string_lossy().to_string(),
+        }],
+        ..dir.config()
+    };
+    let mut rust_paper = dir.rust_paper_with(config, fetcher).await.unwrap();
+
+    let report = rust_paper
+        .sync(None, &SyncOptions::default())
+        .await
+        .unwrap();
+    assert!(!report.has_failures());
+    assert_eq!(std::fs::read(mirror.join("abc123.png")).unwrap(), png());
+
+    // Files the mirror lost are copied again on the next sync, but not in
+    // a dry run
+    std::fs::remove_file(mirror.join("abc123.png")).unwrap();
+    let dry_run = SyncOptions {
+        dry_run: true,
+        ..Default::default()
+    };
+    let ids = ["abc123".to_string()];
+    rust_paper.sync_ids(&ids, &dry_run).await.unwrap();
+    assert!(!mirror.join("abc123.png").exists());
+    rust_paper
+        .sync_ids(&ids, &SyncOptions::default())
+        .await
+        .unwrap();
+    assert!(mirror.join("abc123.png").exists());
+}
+
+#[tokio::test]
+async fn test_pull_applies_another_machines_manifest() {
+    let machine = |name: &str, list: &str| {
+        let dir = TempDir::new(name).unwrap();
+        std::fs::create_dir_all(dir.path().join("config")).unwrap();
+        std::fs::write(dir.path().join("config/wallpapers.lst"), list).unwrap();
+        dir
+    };
+    async fn open(dir: &TempDir) -> RustPaper {
+        let mut fetcher = MockFetcher::new();
+        for id in ["abc123", "def456", "ghi789"] {
+            fetcher = fetcher
+                .route(
+                    &format!("https://wallhaven.cc/api/v1/w/{id}"),
+                    info_json(id),
+                )
+                .route(&format!("https://w.wallhaven.cc/full/{id}.png"), png());
+        }
+        let config = Config {
+            manifest_key: Some("shared".to_string()),
+            ..dir.config()
+        };
+        dir.rust_paper_with(config, Arc::new(fetcher))
+            .await
+            .unwrap()
+    }
+
+    let laptop = machine("pull-laptop", "abc123\ndef456\n");
+    let mut rust_paper = open(&laptop).await;
+    rust_paper
+        .sync(None, &SyncOptions::default())
+        .await
+        .unwrap();
+    rust_paper.remove(&["def456".to_string()]).await.unwrap();
+    rust_paper
+        .add(&mut vec!["ghi789".to_string()], &AddOptions::default())
+        .await
+        .unwrap();
+    rust_paper
+        .sync(None, &SyncOptions::default())
+        .await
+        .unwrap();
+    drop(rust_paper);
+    let manifest = laptop.path().join("config/manifest.json");
+
+    let desktop = machine("pull-desktop", "def456\n");
+    let mut rust_paper = open(&desktop).await;
+    rust_paper.pull(&manifest.to_string_lossy()).await.unwrap();
+    assert_eq!(rust_paper.wallpapers, ["abc123", "ghi789"]);
+
+    // A tampered manifest is refused
+    let text = std::fs::read_to_string(&manifest)
+        .unwrap()
+        .replace("ghi789", "zzz999");
+    std::fs::write(&manifest, text).unwrap();
+    assert!(matches!(
+        rust_paper.pull(&manifest.to_string_lossy()).await,
+        Err(Error::IntegrityMismatch { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_blocked_wallpapers_are_not_added_again() {
+    let dir = TempDir::new("block").unwrap();
+    let mut paper = dir
+        .rust_paper(Arc::new(MockFetcher::new().offline()))
+        .await
+        .unwrap();
+    paper
+        .add(
+            &mut vec!["abc123".to_string(), "def456".to_string()],
+            &AddOptions::default(),
+        )
+        .await
+        .unwrap();
+
+    paper
+        .block(&["https://wallhaven.cc/w/abc123".to_string()])
+        .await
+        .unwrap();
+    assert_eq!(paper.wallpapers, ["def456"]);
+
+    paper
+        .add(&mut vec!["abc123".to_string()], &AddOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(paper.wallpapers, ["def456"]);
+
+    paper.unblock(&["abc123".to_string()]).await.unwrap();
+    paper
+        .add(&mut vec!["abc123".to_string()], &AddOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(paper.wallpapers, ["abc123", "def456"]);
+}